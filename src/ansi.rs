@@ -0,0 +1,96 @@
+//! Minimal ANSI escape code helpers used to highlight plans printed to a terminal
+
+use lazy_regex::regex;
+
+/// Escape code that resets all text attributes
+const RESET: &str = "\x1b[0m";
+
+/// Foreground color of highlighted text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+impl Color {
+    /// SGR parameter of the color
+    fn code(self) -> &'static str {
+        match self {
+            Self::Red => "31",
+            Self::Green => "32",
+            Self::Yellow => "33",
+        }
+    }
+}
+
+/// Wrap the `text` with the escape codes that color the text using the `color`
+pub fn paint(text: &str, color: Color) -> String {
+    format!("\x1b[{}m{text}{RESET}", color.code())
+}
+
+/// Wrap the `text` with the escape codes that make the text bold
+pub fn bold(text: &str) -> String {
+    format!("\x1b[1m{text}{RESET}")
+}
+
+/// Remove all escape codes written by this module from the `text`
+pub fn strip(text: &str) -> String {
+    regex!(r"\x1b\[[0-9;]*m").replace_all(text, "").into_owned()
+}
+
+/// Choice of when output is colored
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color output only when STDOUT is a terminal
+    #[default]
+    Auto,
+    /// Always color output
+    Always,
+    /// Never color output
+    Never,
+}
+
+impl ColorChoice {
+    /// True if output should be colored, where `is_terminal` is true when the output is written
+    /// to a terminal
+    pub fn is_enabled(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Auto => is_terminal,
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bold, paint, strip, Color, ColorChoice};
+
+    #[rstest::rstest]
+    #[case(ColorChoice::Auto, true, true)]
+    #[case(ColorChoice::Auto, false, false)]
+    #[case(ColorChoice::Always, false, true)]
+    #[case(ColorChoice::Never, true, false)]
+    fn is_enabled_should_only_color_terminals_when_auto(
+        #[case] choice: ColorChoice,
+        #[case] is_terminal: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, choice.is_enabled(is_terminal));
+    }
+
+    #[test]
+    fn strip_should_remove_escape_codes() {
+        let text = format!(
+            "{}\n{}",
+            bold("-- TABLE public.test"),
+            paint("CREATE TABLE public.test ();", Color::Green)
+        );
+
+        assert_eq!(
+            "-- TABLE public.test\nCREATE TABLE public.test ();",
+            strip(&text)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Rendering of planned migrations into the file structures consumed by other migration tools
+
+use std::path::{Component, Path, PathBuf};
+
+use sqlx::types::chrono::{DateTime, Utc};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use crate::object::MigrationPlan;
+use crate::{PgDiffError, VERSION};
+
+/// Name given to every migration emitted, suffixed or prefixed by the migration's timestamp
+pub const MIGRATION_NAME: &str = "pg_diff";
+/// Name of the sqitch plan file within the migrations directory
+const SQITCH_PLAN_FILE: &str = "sqitch.plan";
+/// Comment written at the start of every revert migration
+const REVERT_WARNING: &str = "-- Best-effort revert generated by pg-diff-rs. Data removed by the \
+                              migration is not restored so review before use.\n";
+
+/// Output format of a planned migration
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum EmitFormat {
+    /// Migration script is printed to STDOUT
+    #[default]
+    Plain,
+    /// Single versioned migration file (e.g. `V20240101120000__pg_diff.sql`)
+    Flyway,
+    /// Paired up and down migration files (e.g. `20240101120000_pg_diff.up.sql` and
+    /// `20240101120000_pg_diff.down.sql`)
+    GolangMigrate,
+    /// Deploy, revert and verify scripts of a new change that is appended to `sqitch.plan`
+    Sqitch,
+}
+
+/// Single file of an emitted migration
+#[derive(Debug, PartialEq)]
+struct MigrationFile {
+    /// Path of the file relative to the migrations directory
+    path: PathBuf,
+    /// Contents of the file
+    contents: String,
+}
+
+impl MigrationFile {
+    fn new<P: Into<PathBuf>>(path: P, contents: String) -> Self {
+        Self {
+            path: path.into(),
+            contents,
+        }
+    }
+}
+
+impl EmitFormat {
+    /// True if the format includes a revert (i.e. down) migration that must be planned
+    pub fn requires_revert(&self) -> bool {
+        matches!(self, Self::GolangMigrate | Self::Sqitch)
+    }
+
+    /// Files of the migration `plan` created at the `timestamp`. Paths are relative to the
+    /// migrations directory. The sqitch plan entry is not included since it's appended to an
+    /// existing file (see [sqitch_plan_entry]).
+    fn migration_files(
+        &self,
+        plan: &MigrationPlan,
+        timestamp: &DateTime<Utc>,
+    ) -> Vec<MigrationFile> {
+        let version = timestamp.format("%Y%m%d%H%M%S");
+        let revert = format!("{REVERT_WARNING}{}", plan.revert);
+        match self {
+            Self::Plain => vec![],
+            Self::Flyway => vec![MigrationFile::new(
+                format!("V{version}__{MIGRATION_NAME}.sql"),
+                plan.deploy.clone(),
+            )],
+            Self::GolangMigrate => vec![
+                MigrationFile::new(
+                    format!("{version}_{MIGRATION_NAME}.up.sql"),
+                    plan.deploy.clone(),
+                ),
+                MigrationFile::new(format!("{version}_{MIGRATION_NAME}.down.sql"), revert),
+            ],
+            Self::Sqitch => {
+                let file_name = format!("{MIGRATION_NAME}_{version}.sql");
+                vec![
+                    MigrationFile::new(
+                        Path::new("deploy").join(&file_name),
+                        format!("-- Deploy {MIGRATION_NAME}_{version}\n{}", plan.deploy),
+                    ),
+                    MigrationFile::new(
+                        Path::new("revert").join(&file_name),
+                        format!("-- Revert {MIGRATION_NAME}_{version}\n{revert}"),
+                    ),
+                    MigrationFile::new(
+                        Path::new("verify").join(&file_name),
+                        format!(
+                            "-- Verify {MIGRATION_NAME}_{version}\n\nBEGIN;\n\n-- XXX Add \
+                             verifications here.\n\nROLLBACK;\n"
+                        ),
+                    ),
+                ]
+            },
+        }
+    }
+
+    /// Write the migration `plan` to the `migrations_dir` using this format. Existing migration
+    /// files are never overwritten. Returns the paths of the files written.
+    ///
+    /// ## Errors
+    /// If any of the migration files already exist or cannot be written
+    pub async fn write_migration(
+        &self,
+        migrations_dir: &Path,
+        plan: &MigrationPlan,
+    ) -> Result<Vec<PathBuf>, PgDiffError> {
+        let timestamp = Utc::now();
+        let mut paths =
+            write_migration_files(migrations_dir, self.migration_files(plan, &timestamp)).await?;
+        if *self == Self::Sqitch {
+            paths.push(append_sqitch_plan_entry(migrations_dir, &timestamp).await?);
+        }
+        Ok(paths)
+    }
+}
+
+/// Write the `files` of a migration to the `migrations_dir`, creating any missing directories.
+/// Existing files are never overwritten. Returns the paths of the files written.
+async fn write_migration_files(
+    migrations_dir: &Path,
+    files: Vec<MigrationFile>,
+) -> Result<Vec<PathBuf>, PgDiffError> {
+    let mut paths = vec![];
+    for file in files {
+        let path = migrations_dir.join(file.path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+        output.write_all(file.contents.as_bytes()).await?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Files of the migration `plan` created at the `timestamp` within a folder named after the
+/// timestamp and `label` (e.g. `20240101120000_add_users/up.sql` and
+/// `20240101120000_add_users/down.sql`). Paths are relative to the migrations directory.
+fn migration_folder_files(
+    plan: &MigrationPlan,
+    timestamp: &DateTime<Utc>,
+    label: &str,
+) -> Vec<MigrationFile> {
+    let folder = PathBuf::from(format!("{}_{label}", timestamp.format("%Y%m%d%H%M%S")));
+    vec![
+        MigrationFile::new(folder.join("up.sql"), plan.deploy.clone()),
+        MigrationFile::new(
+            folder.join("down.sql"),
+            format!("{REVERT_WARNING}{}", plan.revert),
+        ),
+    ]
+}
+
+/// Write the migration `plan` as `up.sql` and `down.sql` files within a new folder of the
+/// `migrations_dir` named after the current timestamp and the `label`. The down file contains the
+/// best-effort revert of the plan. Returns the paths of the files written.
+///
+/// ## Errors
+/// If the `label` is not a single path component, the migration files already exist or cannot be
+/// written
+pub async fn write_migration_folder(
+    migrations_dir: &Path,
+    label: &str,
+    plan: &MigrationPlan,
+) -> Result<Vec<PathBuf>, PgDiffError> {
+    let mut components = Path::new(label).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(PgDiffError::General(format!(
+            "Migration label `{label}` must be a single path component"
+        )));
+    }
+    write_migration_files(
+        migrations_dir,
+        migration_folder_files(plan, &Utc::now(), label),
+    )
+    .await
+}
+
+/// Line of the sqitch plan file that adds the change created at the `timestamp`. The planner is
+/// taken from the `SQITCH_FULLNAME` and `SQITCH_EMAIL` environment variables (as sqitch does)
+/// when set.
+fn sqitch_plan_entry(timestamp: &DateTime<Utc>) -> String {
+    let planner_name = std::env::var("SQITCH_FULLNAME").unwrap_or_else(|_| "pg-diff-rs".into());
+    let planner_email =
+        std::env::var("SQITCH_EMAIL").unwrap_or_else(|_| "pg-diff-rs@localhost".into());
+    format!(
+        "{MIGRATION_NAME}_{} {} {planner_name} <{planner_email}> # Generated by pg-diff-rs \
+         {VERSION}\n",
+        timestamp.format("%Y%m%d%H%M%S"),
+        timestamp.format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+/// Append the plan entry of the change created at the `timestamp` to the sqitch plan file within
+/// the `migrations_dir`. If the plan file does not exist, a new plan is created for a project
+/// named after the `migrations_dir`.
+async fn append_sqitch_plan_entry(
+    migrations_dir: &Path,
+    timestamp: &DateTime<Utc>,
+) -> Result<PathBuf, PgDiffError> {
+    let path = migrations_dir.join(SQITCH_PLAN_FILE);
+    let mut plan_file = if tokio::fs::try_exists(&path).await? {
+        OpenOptions::new().append(true).open(&path).await?
+    } else {
+        let project = migrations_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| MIGRATION_NAME.into());
+        let mut plan_file = File::create(&path).await?;
+        plan_file
+            .write_all(format!("%syntax-version=1.0.0\n%project={project}\n\n").as_bytes())
+            .await?;
+        plan_file
+    };
+    plan_file
+        .write_all(sqitch_plan_entry(timestamp).as_bytes())
+        .await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use sqlx::types::chrono::{DateTime, TimeZone, Utc};
+
+    use crate::object::MigrationPlan;
+
+    use uuid::Uuid;
+
+    use super::{
+        migration_folder_files, sqitch_plan_entry, write_migration_folder, EmitFormat,
+        MigrationFile, REVERT_WARNING,
+    };
+
+    fn timestamp() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()
+    }
+
+    fn create_plan() -> MigrationPlan {
+        MigrationPlan {
+            deploy: "CREATE SCHEMA test_schema;\n".into(),
+            revert: "DROP SCHEMA test_schema;\n".into(),
+            statement_count: 1,
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(EmitFormat::Plain, vec![])]
+    #[case(EmitFormat::Flyway, vec!["V20240102030405__pg_diff.sql"])]
+    #[case(
+        EmitFormat::GolangMigrate,
+        vec!["20240102030405_pg_diff.up.sql", "20240102030405_pg_diff.down.sql"],
+    )]
+    #[case(
+        EmitFormat::Sqitch,
+        vec![
+            "deploy/pg_diff_20240102030405.sql",
+            "revert/pg_diff_20240102030405.sql",
+            "verify/pg_diff_20240102030405.sql",
+        ],
+    )]
+    fn migration_files_should_use_format_file_names(
+        #[case] format: EmitFormat,
+        #[case] expected_paths: Vec<&str>,
+    ) {
+        let files = format.migration_files(&create_plan(), &timestamp());
+
+        let paths: Vec<PathBuf> = files.into_iter().map(|f| f.path).collect();
+        let expected_paths: Vec<PathBuf> = expected_paths.into_iter().map(PathBuf::from).collect();
+        assert_eq!(expected_paths, paths);
+    }
+
+    #[test]
+    fn migration_files_should_write_revert_to_down_migration() {
+        let plan = create_plan();
+
+        let files = EmitFormat::GolangMigrate.migration_files(&plan, &timestamp());
+
+        assert_eq!(plan.deploy, files[0].contents);
+        assert_eq!(
+            format!("{REVERT_WARNING}{}", plan.revert),
+            files[1].contents
+        );
+    }
+
+    #[test]
+    fn migration_folder_files_should_write_up_and_down_files_within_label_folder() {
+        let plan = create_plan();
+
+        let files = migration_folder_files(&plan, &timestamp(), "add_test_schema");
+
+        assert_eq!(
+            vec![
+                MigrationFile::new("20240102030405_add_test_schema/up.sql", plan.deploy.clone()),
+                MigrationFile::new(
+                    "20240102030405_add_test_schema/down.sql",
+                    format!("{REVERT_WARNING}{}", plan.revert)
+                ),
+            ],
+            files
+        );
+    }
+
+    #[tokio::test]
+    async fn write_migration_folder_should_write_up_and_down_files() {
+        let plan = create_plan();
+        let migrations_dir =
+            std::env::temp_dir().join(format!("pg_diff_rs_migrations_{}", Uuid::new_v4().simple()));
+
+        let result = write_migration_folder(&migrations_dir, "add_test_schema", &plan).await;
+        let contents = match &result {
+            Ok(paths) => {
+                let mut contents = vec![];
+                for path in paths {
+                    contents.push(tokio::fs::read_to_string(path).await.unwrap());
+                }
+                contents
+            },
+            Err(_) => vec![],
+        };
+        tokio::fs::remove_dir_all(&migrations_dir).await.ok();
+
+        let paths = result.unwrap();
+        let file_names: Vec<String> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(vec!["up.sql", "down.sql"], file_names);
+        let folder = paths[0].parent().unwrap();
+        assert_eq!(Some(folder), paths[1].parent());
+        assert_eq!(Some(migrations_dir.as_path()), folder.parent());
+        let folder_name = folder.file_name().unwrap().to_string_lossy();
+        let (version, label) = folder_name.split_once('_').unwrap();
+        assert_eq!(14, version.len());
+        assert!(version.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!("add_test_schema", label);
+        assert_eq!(
+            vec![plan.deploy, format!("{REVERT_WARNING}{}", plan.revert)],
+            contents
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("")]
+    #[case("../outside")]
+    #[case("nested/label")]
+    #[tokio::test]
+    async fn write_migration_folder_should_reject_labels_that_are_not_a_single_component(
+        #[case] label: &str,
+    ) {
+        let result = write_migration_folder(&std::env::temp_dir(), label, &create_plan()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn sqitch_plan_entry_should_include_change_name_and_timestamp() {
+        let entry = sqitch_plan_entry(&timestamp());
+
+        assert!(entry.starts_with("pg_diff_20240102030405 2024-01-02T03:04:05Z "));
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+use sqlx::{query_as, PgPool};
+
+use crate::PgDiffError;
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all access methods found within the current database that are not built-in or owned by
+/// an extension
+pub async fn get_access_methods(pool: &PgPool) -> Result<Vec<AccessMethod>, PgDiffError> {
+    let access_methods_query = include_str!("./../../queries/access_methods.pgsql");
+    let access_methods =
+        match fetch_with_retry(|| query_as(access_methods_query).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load access methods");
+                return Err(error.into());
+            },
+        };
+    Ok(access_methods)
+}
+
+/// Postgresql index or table access method object (i.e. `CREATE ACCESS METHOD`)
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct AccessMethod {
+    /// Full name of the access method (never includes a schema name since access methods reside
+    /// outside a schema)
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Type of the access method. Either `INDEX` or `TABLE`.
+    pub(crate) access_method_type: String,
+    /// Full name of the function that handles the access method
+    #[sqlx(json)]
+    pub(crate) handler: SchemaQualifiedName,
+    /// Dependencies of the access method. This is only ever populated with the handler function
+    /// when the function is not built-in.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl SqlObject for AccessMethod {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "ACCESS METHOD"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "CREATE ACCESS METHOD {} TYPE {} HANDLER {};",
+            self.name, self.access_method_type, self.handler
+        )?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    /// Access methods cannot be altered so any change drops and recreates the access method
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self != new {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP ACCESS METHOD {}{};",
+            context.if_exists(),
+            self.name
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::AccessMethod;
+
+    const NAME: &str = "test_access_method";
+    const SCHEMA: &str = "test_schema";
+
+    fn create_access_method(access_method_type: &str, handler: &str) -> AccessMethod {
+        AccessMethod {
+            name: SchemaQualifiedName::new("", NAME),
+            access_method_type: access_method_type.into(),
+            handler: SchemaQualifiedName::new(SCHEMA, handler),
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, handler)],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_access_method("INDEX", "test_index_handler"),
+        include_str!("../../test-files/sql/access-method-create-case1.pgsql"),
+    )]
+    #[case(
+        create_access_method("TABLE", "test_table_handler"),
+        include_str!("../../test-files/sql/access-method-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_access_method_statement(
+        #[case] access_method: AccessMethod,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        access_method.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_drop_and_recreate_when_handler_changes() {
+        let old = create_access_method("INDEX", "test_index_handler");
+        let new = create_access_method("INDEX", "other_index_handler");
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/access-method-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_access_method_statement() {
+        let access_method = create_access_method("INDEX", "test_index_handler");
+        let mut writable = String::new();
+
+        access_method.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/access-method-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
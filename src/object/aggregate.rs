@@ -0,0 +1,247 @@
+use std::fmt::Write;
+
+use sqlx::{query_as, PgPool};
+
+use crate::PgDiffError;
+
+use super::function::FunctionParallel;
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all aggregate functions found within the specified schemas
+pub async fn get_aggregates(
+    pool: &PgPool,
+    schemas: &[&str],
+) -> Result<Vec<Aggregate>, PgDiffError> {
+    let aggregates_query = include_str!("./../../queries/aggregates.pgsql");
+    let aggregates =
+        match fetch_with_retry(|| query_as(aggregates_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load aggregates");
+                return Err(error.into());
+            },
+        };
+    Ok(aggregates)
+}
+
+/// Postgresql aggregate function object. Only normal aggregates are supported (i.e. not ordered-set
+/// or hypothetical-set aggregates).
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct Aggregate {
+    /// Full name of the aggregate
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Declaration block for the aggregate arguments as returned from
+    /// `pg_catalog.pg_get_function_arguments`. Empty if the aggregate accepts `*`
+    pub(crate) arguments: String,
+    /// Full name of the state transition function
+    #[sqlx(json)]
+    pub(crate) transition_function: SchemaQualifiedName,
+    /// Data type of the aggregate's state value
+    pub(crate) state_type: String,
+    /// Full name of the final function, if any
+    pub(crate) final_function: Option<SchemaQualifiedName>,
+    /// Initial value of the aggregate state as a string literal, if any
+    pub(crate) initial_condition: Option<String>,
+    /// Aggregate parallelism option
+    pub(crate) parallel: FunctionParallel,
+    /// Dependencies of the aggregate. This includes the transition and final functions
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl Aggregate {
+    /// Argument list of the aggregate as required in `CREATE` and `DROP` statements
+    fn argument_list(&self) -> &str {
+        if self.arguments.is_empty() {
+            "*"
+        } else {
+            &self.arguments
+        }
+    }
+
+    /// Write the `CREATE` statement to the writable object, optionally using `CREATE OR REPLACE`
+    fn create_statement<W>(&self, w: &mut W, or_replace: bool) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "CREATE {}AGGREGATE {} ({}) (\n    SFUNC = {},\n    STYPE = {}",
+            if or_replace { "OR REPLACE " } else { "" },
+            self.name,
+            self.argument_list(),
+            self.transition_function,
+            self.state_type,
+        )?;
+        if let Some(final_function) = &self.final_function {
+            write!(w, ",\n    FINALFUNC = {final_function}")?;
+        }
+        if let Some(initial_condition) = &self.initial_condition {
+            write!(
+                w,
+                ",\n    INITCOND = '{}'",
+                initial_condition.replace('\'', "''")
+            )?;
+        }
+        let parallel = self.parallel.as_ref().trim_start_matches("PARALLEL ");
+        writeln!(w, ",\n    PARALLEL = {parallel}\n);")?;
+        Ok(())
+    }
+}
+
+impl SqlObject for Aggregate {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "AGGREGATE"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, false)
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, true)
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self != new {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP AGGREGATE {}{} ({});",
+            context.if_exists(),
+            self.name,
+            self.argument_list()
+        )?;
+        Ok(())
+    }
+
+    /// Override the alter prefix to include the required argument list to distinguish between
+    /// aggregate overloads when altering.
+    fn write_alter_prefix<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "ALTER AGGREGATE {} ({})",
+            self.name,
+            self.argument_list()
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::function::FunctionParallel;
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::Aggregate;
+
+    const SCHEMA: &str = "test_schema";
+    const AGGREGATE_NAME: &str = "test_aggregate";
+
+    fn create_aggregate(
+        final_function: Option<&str>,
+        initial_condition: Option<&str>,
+        parallel: FunctionParallel,
+    ) -> Aggregate {
+        Aggregate {
+            name: SchemaQualifiedName::new(SCHEMA, AGGREGATE_NAME),
+            arguments: "numeric".into(),
+            transition_function: SchemaQualifiedName::new(SCHEMA, "test_transition"),
+            state_type: "numeric[]".into(),
+            final_function: final_function.map(|f| SchemaQualifiedName::new(SCHEMA, f)),
+            initial_condition: initial_condition.map(String::from),
+            parallel,
+            dependencies: vec![],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_aggregate(None, None, FunctionParallel::Unsafe),
+        include_str!("../../test-files/sql/aggregate-create-case1.pgsql"),
+    )]
+    #[case(
+        create_aggregate(Some("test_final"), Some("{0,0}"), FunctionParallel::Safe),
+        include_str!("../../test-files/sql/aggregate-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_aggregate_statement(
+        #[case] aggregate: Aggregate,
+        #[case] expected_statement: &str,
+    ) {
+        let mut writeable = String::new();
+
+        aggregate.create_statements(&mut writeable).unwrap();
+
+        assert_eq!(expected_statement.trim(), writeable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_drop_and_recreate_when_aggregate_changes() {
+        let old = create_aggregate(None, None, FunctionParallel::Unsafe);
+        let new = create_aggregate(Some("test_final"), Some("{0,0}"), FunctionParallel::Safe);
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/aggregate-alter.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_do_nothing_when_aggregates_are_equal() {
+        let old = create_aggregate(Some("test_final"), None, FunctionParallel::Safe);
+        let new = create_aggregate(Some("test_final"), None, FunctionParallel::Safe);
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert!(writeable.is_empty());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_aggregate_statement() {
+        let aggregate = create_aggregate(None, None, FunctionParallel::Unsafe);
+        let mut writeable = String::new();
+
+        aggregate.drop_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/aggregate-drop.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+}
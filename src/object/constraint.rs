@@ -4,7 +4,10 @@ use serde::Deserialize;
 use sqlx::postgres::types::Oid;
 use sqlx::{query_as, PgPool};
 
-use crate::object::{IndexParameters, SchemaQualifiedName, SqlObject};
+use crate::object::{
+    expressions_match, fetch_with_retry, Index, IndexParameters, RenderContext,
+    SchemaQualifiedName, SqlObject, TableSpace,
+};
 use crate::{write_join, PgDiffError};
 
 /// Fetch all constraints within the current database for the specified tables (by OID)
@@ -13,22 +16,20 @@ pub async fn get_constraints(
     tables: &[Oid],
 ) -> Result<Vec<Constraint>, PgDiffError> {
     let constraints_query = include_str!("./../../queries/constraints.pgsql");
-    let constraints = match query_as(constraints_query)
-        .bind(tables)
-        .fetch_all(pool)
-        .await
-    {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load constraints");
-            return Err(error.into());
-        },
-    };
+    let constraints =
+        match fetch_with_retry(|| query_as(constraints_query).bind(tables).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load constraints");
+                return Err(error.into());
+            },
+        };
     Ok(constraints)
 }
 
 /// Struct representing a SQL constraint object
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Constraint {
     /// OID of the owning table
     pub(crate) table_oid: Oid,
@@ -46,6 +47,16 @@ pub struct Constraint {
     /// Constraint firing timing
     #[sqlx(json)]
     pub(crate) timing: ConstraintTiming,
+    /// False if the constraint was added as `NOT VALID` and has not been validated yet. Only check
+    /// and foreign key constraints can be not validated.
+    pub(crate) is_validated: bool,
+    /// False if the constraint is `NOT ENFORCED`. Only check and foreign key constraints can be
+    /// not enforced (Postgresql 18+) so this is always true for older servers.
+    pub(crate) is_enforced: bool,
+    /// True if the index backing the constraint is the table's replica identity. Only primary key
+    /// and unique constraints can be used as a replica identity. The `REPLICA IDENTITY USING INDEX`
+    /// statement is written with the constraint since the index must exist beforehand.
+    pub(crate) is_replica_identity: bool,
     /// Dependencies of the constraint
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
@@ -57,25 +68,118 @@ impl PartialEq for Constraint {
         self.owner_table_name == other.owner_table_name
             && self.name == other.name
             && self.schema_qualified_name == other.schema_qualified_name
-            && self.constraint_type == other.constraint_type
+            && self.constraint_type.matches(&other.constraint_type)
             && self.timing == other.timing
+            && self.is_validated == other.is_validated
+            && self.is_enforced == other.is_enforced
+            && self.is_replica_identity == other.is_replica_identity
     }
 }
 
-impl SqlObject for Constraint {
-    fn name(&self) -> &SchemaQualifiedName {
-        &self.schema_qualified_name
+impl Constraint {
+    /// True if migrating to the `new` constraint drops and adds the constraint again rather than
+    /// altering the constraint (see [SqlObject::alter_statements])
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        let is_timing_or_enforcement_changed =
+            self.timing != new.timing || self.is_enforced != new.is_enforced;
+        !self.constraint_type.matches(&new.constraint_type)
+            || (is_timing_or_enforcement_changed && !new.is_foreign_key())
     }
 
-    fn object_type_name(&self) -> &str {
-        "CONSTRAINT"
+    /// True if the constraint can be added as `NOT VALID` (i.e. an enforced check or foreign key
+    /// constraint) and the constraint is expected to be validated
+    pub(crate) fn can_be_added_not_valid(&self) -> bool {
+        self.is_validated
+            && self.is_enforced
+            && matches!(
+                self.constraint_type,
+                ConstraintType::Check { .. } | ConstraintType::ForeignKey { .. }
+            )
     }
 
-    fn dependencies(&self) -> &[SchemaQualifiedName] {
-        &self.dependencies
+    /// True if the constraint is a foreign key. Only foreign key constraints can have their timing
+    /// or enforcement altered without recreating the constraint.
+    pub(crate) fn is_foreign_key(&self) -> bool {
+        matches!(self.constraint_type, ConstraintType::ForeignKey { .. })
     }
 
-    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+    /// Tablespace of the index backing the constraint. Only primary key and unique constraints
+    /// are backed by an index so all other constraints return [None].
+    pub(crate) fn tablespace(&self) -> Option<&TableSpace> {
+        match &self.constraint_type {
+            ConstraintType::PrimaryKey {
+                index_parameters, ..
+            }
+            | ConstraintType::Unique {
+                index_parameters, ..
+            } => index_parameters.tablespace.as_ref(),
+            ConstraintType::Check { .. } | ConstraintType::ForeignKey { .. } => None,
+        }
+    }
+
+    /// True if the constraint can be defined within the `CREATE TABLE` statement of the owner
+    /// table rather than added by a separate `ALTER TABLE` statement. Only enforced and validated
+    /// primary key, unique and single-column check constraints that are not the table's replica
+    /// identity can be inlined. Constraints that depend on objects other than the owner table are
+    /// never inlined since those objects might be created after the table.
+    pub(crate) fn can_be_inlined(&self) -> bool {
+        let is_inline_type = match &self.constraint_type {
+            ConstraintType::Check { columns, .. } => columns.len() == 1,
+            ConstraintType::PrimaryKey { .. } | ConstraintType::Unique { .. } => true,
+            ConstraintType::ForeignKey { .. } => false,
+        };
+        is_inline_type
+            && self.is_enforced
+            && self.is_validated
+            && !self.is_replica_identity
+            && self
+                .dependencies
+                .iter()
+                .all(|dependency| dependency == &self.owner_table_name)
+    }
+
+    /// True if the constraint can be added using the existing `index` (i.e. `ADD CONSTRAINT ...
+    /// USING INDEX`) rather than building a new index. Only primary key and unique constraints can
+    /// use an index. The index must be a unique btree index of the owner table whose key columns
+    /// are exactly the constraint's columns without any column options, predicate or different
+    /// included columns.
+    pub(crate) fn can_use_index(&self, index: &Index) -> bool {
+        let (columns, are_nulls_distinct, index_parameters) = match &self.constraint_type {
+            ConstraintType::PrimaryKey {
+                columns,
+                index_parameters,
+            } => (columns, true, index_parameters),
+            ConstraintType::Unique {
+                columns,
+                are_nulls_distinct,
+                index_parameters,
+            } => (columns, *are_nulls_distinct, index_parameters),
+            ConstraintType::Check { .. } | ConstraintType::ForeignKey { .. } => return false,
+        };
+        index.is_unique
+            && index.owner_table_name == self.owner_table_name
+            && index.access_method == "btree"
+            && index.predicate.is_none()
+            && &index.columns == columns
+            && index.key_columns.len() == columns.len()
+            && index.key_columns.iter().all(|column| {
+                column.collation.is_none()
+                    && column.operator_class.is_none()
+                    && !column.is_descending
+                    && column.nulls_order.is_none()
+            })
+            && index.parameters.include == index_parameters.include
+            && index.definition_statement.contains("NULLS NOT DISTINCT") != are_nulls_distinct
+    }
+
+    /// Write the constraint's definition (i.e. `CONSTRAINT name ...` including the timing) to
+    /// the writable object. The `separator` is written between the constraint name and the
+    /// constraint variant details.
+    fn write_definition<W>(&self, w: &mut W, separator: &str) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(w, "CONSTRAINT {}{separator}", self.name)?;
         match &self.constraint_type {
             ConstraintType::Check {
                 expression,
@@ -83,9 +187,7 @@ impl SqlObject for Constraint {
                 ..
             } => write!(
                 w,
-                "ALTER TABLE {} ADD CONSTRAINT {}\nCHECK({}){} ",
-                self.owner_table_name,
-                self.name,
+                "CHECK({}){} ",
                 expression.trim(),
                 if *is_inheritable { "" } else { " NO INHERIT" }
             )?,
@@ -94,12 +196,16 @@ impl SqlObject for Constraint {
                 are_nulls_distinct,
                 index_parameters,
             } => {
+                // The default `NULLS DISTINCT` is never written since the clause is a syntax error
+                // before Postgresql 15. `NULLS NOT DISTINCT` can only be found on 15+ servers.
                 write!(
                     w,
-                    "ALTER TABLE {} ADD CONSTRAINT {}\nUNIQUE NULLS{} DISTINCT (",
-                    self.owner_table_name,
-                    self.name,
-                    if *are_nulls_distinct { "" } else { " NOT" },
+                    "UNIQUE {}(",
+                    if *are_nulls_distinct {
+                        ""
+                    } else {
+                        "NULLS NOT DISTINCT "
+                    },
                 )?;
                 write_join!(w, columns, ",");
                 write!(w, "){index_parameters} ")?;
@@ -108,11 +214,7 @@ impl SqlObject for Constraint {
                 columns,
                 index_parameters,
             } => {
-                write!(
-                    w,
-                    "ALTER TABLE {} ADD CONSTRAINT {}\nPRIMARY KEY (",
-                    self.owner_table_name, self.name,
-                )?;
+                w.write_str("PRIMARY KEY (")?;
                 write_join!(w, columns, ",");
                 write!(w, "){index_parameters} ")?;
             },
@@ -124,11 +226,7 @@ impl SqlObject for Constraint {
                 on_delete,
                 on_update,
             } => {
-                write!(
-                    w,
-                    "ALTER TABLE {} ADD CONSTRAINT {}\nFOREIGN KEY (",
-                    self.owner_table_name, self.name,
-                )?;
+                w.write_str("FOREIGN KEY (")?;
                 write_join!(w, columns, ",");
                 write!(w, ") REFERENCES {ref_table}(")?;
                 write_join!(w, ref_columns, ",");
@@ -139,14 +237,196 @@ impl SqlObject for Constraint {
                 )?;
             },
         };
-        writeln!(w, "{};", self.timing)?;
+        write!(w, "{}", self.timing)?;
+        Ok(())
+    }
+
+    /// Write the constraint as a table constraint within the `CREATE TABLE` statement of the
+    /// owner table. Only valid for constraints that [Constraint::can_be_inlined].
+    pub(crate) fn write_inline_definition<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        self.write_definition(w, " ")
+    }
+
+    /// Write the `ADD CONSTRAINT` statement to the writable object. If `not_valid` is true, the
+    /// constraint is added as `NOT VALID` so existing rows are not checked. Constraints that are
+    /// not enforced are never validated so `not_valid` is ignored for those constraints.
+    fn add_constraint_statement<W>(&self, w: &mut W, not_valid: bool) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(w, "ALTER TABLE {} ADD ", self.owner_table_name)?;
+        self.write_definition(w, "\n")?;
+        if !self.is_enforced {
+            w.write_str(" NOT ENFORCED")?;
+        } else if not_valid {
+            w.write_str(" NOT VALID")?;
+        }
+        writeln!(w, ";")?;
+        if self.is_replica_identity {
+            self.replica_identity_statement(w)?;
+        }
+        Ok(())
+    }
+
+    /// Write the `ADD CONSTRAINT ... USING INDEX` statement that adds this constraint using the
+    /// existing `index` so the index is not built again. The server renames the index to the
+    /// constraint's name. Only valid when [Constraint::can_use_index] is true for the `index`.
+    pub(crate) fn create_statements_using_index<W>(
+        &self,
+        index: &Index,
+        w: &mut W,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(
+            w,
+            "ALTER TABLE {} ADD CONSTRAINT {} {} USING INDEX {} {};",
+            self.owner_table_name,
+            self.name,
+            match self.constraint_type {
+                ConstraintType::PrimaryKey { .. } => "PRIMARY KEY",
+                _ => "UNIQUE",
+            },
+            index.schema_qualified_name.local_name,
+            self.timing
+        )?;
+        if self.is_replica_identity {
+            self.replica_identity_statement(w)?;
+        }
         Ok(())
     }
 
+    /// Write the [Constraint::create_statements_using_index] statements. When `idempotent` is
+    /// true, the statements are only executed if the constraint does not exist (see
+    /// [Constraint::write_missing_constraint_guard]).
+    pub(crate) fn create_statements_using_index_with<W>(
+        &self,
+        index: &Index,
+        idempotent: bool,
+        w: &mut W,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        if idempotent {
+            let mut statements = String::new();
+            self.create_statements_using_index(index, &mut statements)?;
+            self.write_missing_constraint_guard(w, &statements)
+        } else {
+            self.create_statements_using_index(index, w)
+        }
+    }
+
+    /// Write the statement setting the constraint's index as the owner table's replica identity
+    fn replica_identity_statement<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(
+            w,
+            "ALTER TABLE {} REPLICA IDENTITY USING INDEX {};",
+            self.owner_table_name, self.name
+        )?;
+        Ok(())
+    }
+
+    /// Write the `ADD CONSTRAINT` statement as `NOT VALID` regardless of the constraint's
+    /// validation state. Must be followed by [Constraint::validate_statements] to validate the
+    /// constraint.
+    pub(crate) fn create_statements_not_valid<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        self.add_constraint_statement(w, true)
+    }
+
+    /// Write the `statements` provided within an anonymous `DO` block that only executes the
+    /// statements when this constraint does not exist within `pg_constraint`. Postgresql has no
+    /// `ADD CONSTRAINT IF NOT EXISTS` and adding a duplicate primary key does not raise a
+    /// duplicate object error so the catalog is checked rather than catching errors.
+    pub(crate) fn write_missing_constraint_guard<W>(
+        &self,
+        w: &mut W,
+        statements: &str,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        w.write_str("DO $do$\nBEGIN\n    IF NOT EXISTS (\n        SELECT NULL\n")?;
+        w.write_str("        FROM pg_catalog.pg_constraint\n        WHERE\n")?;
+        writeln!(
+            w,
+            "            conrelid = '{}'::REGCLASS",
+            self.owner_table_name.to_string().replace('\'', "''")
+        )?;
+        writeln!(
+            w,
+            "            AND conname = '{}'\n    ) THEN",
+            self.name.replace('\'', "''")
+        )?;
+        writeln!(w, "{}", statements.trim_end())?;
+        w.write_str("    END IF;\nEND;\n$do$;\n")?;
+        Ok(())
+    }
+
+    /// Write the `VALIDATE CONSTRAINT` statement to the writable object
+    pub(crate) fn validate_statements<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(
+            w,
+            "ALTER TABLE {} VALIDATE CONSTRAINT {};",
+            self.owner_table_name, self.name
+        )?;
+        Ok(())
+    }
+}
+
+impl SqlObject for Constraint {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.schema_qualified_name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "CONSTRAINT"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.add_constraint_statement(w, !self.is_validated)
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        let mut statements = String::new();
+        self.create_statements(&mut statements)?;
+        self.write_missing_constraint_guard(w, &statements)
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        if self.constraint_type != new.constraint_type {
-            self.drop_statements(w)?;
-            new.create_statements(w)?;
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    /// Alter the constraint in place when possible. Changes to the constraint type, or changes to
+    /// the timing/enforcement of non-foreign key constraints, require the constraint to be
+    /// recreated since Postgresql only supports `ALTER CONSTRAINT` for foreign keys (e.g. a unique
+    /// constraint that becomes `DEFERRABLE` is dropped and added again).
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
             return Ok(());
         }
 
@@ -158,14 +438,42 @@ impl SqlObject for Constraint {
             )?;
         }
 
+        if self.is_enforced != new.is_enforced {
+            writeln!(
+                w,
+                "ALTER TABLE {} ALTER CONSTRAINT {} {}ENFORCED;",
+                self.owner_table_name,
+                self.name,
+                if new.is_enforced { "" } else { "NOT " }
+            )?;
+        }
+
+        // A validated constraint cannot be marked as not valid again so only validation is
+        // handled. Enforcing a constraint also validates the constraint.
+        if self.is_enforced && new.is_enforced && !self.is_validated && new.is_validated {
+            new.validate_statements(w)?;
+        }
+
+        // Removing the replica identity is handled by the owner table since the table's identity
+        // is set to something else
+        if !self.is_replica_identity && new.is_replica_identity {
+            new.replica_identity_statement(w)?;
+        }
+
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
         writeln!(
             w,
-            "ALTER TABLE {} DROP CONSTRAINT {};",
-            self.owner_table_name, self.name
+            "ALTER TABLE {} DROP CONSTRAINT {}{};",
+            self.owner_table_name,
+            context.if_exists(),
+            self.name
         )?;
         Ok(())
     }
@@ -221,6 +529,43 @@ pub enum ConstraintType {
     },
 }
 
+impl ConstraintType {
+    /// True if this constraint type is equal to the `other` constraint type. Check expressions
+    /// are compared as expressions rather than text (see [expressions_match]).
+    fn matches(&self, other: &ConstraintType) -> bool {
+        match (self, other) {
+            (
+                Self::Check {
+                    columns,
+                    expression,
+                    is_inheritable,
+                },
+                Self::Check {
+                    columns: other_columns,
+                    expression: other_expression,
+                    is_inheritable: other_is_inheritable,
+                },
+            ) => {
+                columns == other_columns
+                    && is_inheritable == other_is_inheritable
+                    && expressions_match(expression, other_expression)
+            },
+            _ => self == other,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Default for ConstraintType {
+    fn default() -> Self {
+        Self::Check {
+            columns: vec![],
+            expression: String::new(),
+            is_inheritable: true,
+        }
+    }
+}
+
 /// Constraint timing as deferrable or not deferrable
 #[derive(Debug, Default, Deserialize, PartialEq)]
 #[serde(tag = "type")]
@@ -325,7 +670,7 @@ impl Display for ForeignKeyAction {
 mod test {
     use sqlx::postgres::types::Oid;
 
-    use crate::object::{IndexParameters, SchemaQualifiedName, SqlObject};
+    use crate::object::{IndexParameters, RenderContext, SchemaQualifiedName, SqlObject};
 
     use super::{Constraint, ConstraintTiming, ConstraintType, ForeignKeyAction, ForeignKeyMatch};
     static SCHEMA: &str = "test_schema";
@@ -351,10 +696,30 @@ mod test {
             )),
             constraint_type,
             timing,
+            is_validated: true,
+            is_enforced: true,
+            is_replica_identity: false,
             dependencies: vec![],
         }
     }
 
+    fn create_check_constraint(is_validated: bool) -> Constraint {
+        Constraint {
+            is_validated,
+            ..create_constraint(
+                SCHEMA,
+                TABLE,
+                NAME,
+                ConstraintType::Check {
+                    columns: vec![TEST_COL.into()],
+                    expression: "test_col = 'test'".into(),
+                    is_inheritable: true,
+                },
+                ConstraintTiming::NotDeferrable,
+            )
+        }
+    }
+
     #[rstest::rstest]
     #[case(
         create_constraint(
@@ -516,9 +881,98 @@ mod test {
     }
 
     #[test]
-    fn alter_statements_should_add_alter_table_alter_constraint_when_changed_timing() {
+    fn create_statements_should_add_not_valid_when_constraint_is_not_validated() {
+        let constraint = create_check_constraint(false);
+        let mut writable = String::new();
+
+        constraint.create_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/constraint-create-not-valid.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        true,
+        include_str!("../../test-files/sql/constraint-alter-validate.pgsql")
+    )]
+    #[case(true, false, "")]
+    fn alter_statements_should_validate_constraint_when_new_constraint_is_validated(
+        #[case] old_is_validated: bool,
+        #[case] new_is_validated: bool,
+        #[case] statement: &str,
+    ) {
+        let old_constraint = create_check_constraint(old_is_validated);
+        let new_constraint = create_check_constraint(new_is_validated);
+        let mut writable = String::new();
+
+        old_constraint
+            .alter_statements(&new_constraint, &mut writable)
+            .unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    fn create_foreign_key_constraint(timing: ConstraintTiming, is_enforced: bool) -> Constraint {
+        Constraint {
+            is_enforced,
+            is_validated: is_enforced,
+            ..create_constraint(
+                SCHEMA,
+                TABLE,
+                NAME,
+                ConstraintType::ForeignKey {
+                    columns: vec![TEST_COL.into()],
+                    ref_table: SchemaQualifiedName::new(SCHEMA, REF_TABLE),
+                    ref_columns: vec![TEST_COL.into()],
+                    match_type: ForeignKeyMatch::Simple,
+                    on_delete: ForeignKeyAction::NoAction,
+                    on_update: ForeignKeyAction::NoAction,
+                },
+                timing,
+            )
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        ConstraintTiming::NotDeferrable,
+        ConstraintTiming::Deferrable { is_immediate: false },
+        include_str!("../../test-files/sql/constraint-alter-changed-timing-case1.pgsql"),
+    )]
+    #[case(
+        ConstraintTiming::Deferrable { is_immediate: false },
+        ConstraintTiming::Deferrable { is_immediate: true },
+        include_str!("../../test-files/sql/constraint-alter-changed-timing-case2.pgsql"),
+    )]
+    #[case(
+        ConstraintTiming::Deferrable { is_immediate: true },
+        ConstraintTiming::NotDeferrable,
+        include_str!("../../test-files/sql/constraint-alter-changed-timing-case3.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_table_alter_constraint_when_foreign_key_timing_changed(
+        #[case] old_timing: ConstraintTiming,
+        #[case] new_timing: ConstraintTiming,
+        #[case] statement: &str,
+    ) {
+        let constraint_before = create_foreign_key_constraint(old_timing, true);
+        let constraint_after = create_foreign_key_constraint(new_timing, true);
+        let mut writable = String::new();
+
+        constraint_before
+            .alter_statements(&constraint_after, &mut writable)
+            .unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_recreate_constraint_when_unique_timing_changed() {
         let constraint_type = ConstraintType::Unique {
-            columns: vec![],
+            columns: vec![TEST_COL.into()],
             are_nulls_distinct: true,
             index_parameters: IndexParameters {
                 include: None,
@@ -540,7 +994,148 @@ mod test {
             constraint_type,
             ConstraintTiming::Deferrable { is_immediate: true },
         );
-        let statement = include_str!("../../test-files/sql/constraint-alter-changed-timing.pgsql");
+        let statement =
+            include_str!("../../test-files/sql/constraint-alter-changed-timing-recreate.pgsql");
+        let mut writable = String::new();
+
+        constraint_before
+            .alter_statements(&constraint_after, &mut writable)
+            .unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_with_should_guard_recreated_constraint_when_idempotent() {
+        let constraint_type = ConstraintType::Unique {
+            columns: vec![TEST_COL.into()],
+            are_nulls_distinct: true,
+            index_parameters: IndexParameters {
+                include: None,
+                with: None,
+                tablespace: None,
+            },
+        };
+        let constraint_before = create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            constraint_type.clone(),
+            ConstraintTiming::NotDeferrable,
+        );
+        let constraint_after = create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            constraint_type,
+            ConstraintTiming::Deferrable { is_immediate: true },
+        );
+        let context = RenderContext {
+            idempotent: true,
+            ..Default::default()
+        };
+        let mut writable = String::new();
+
+        constraint_before
+            .alter_statements_with(&constraint_after, &context, &mut writable)
+            .unwrap();
+
+        assert!(writable.starts_with(
+            "ALTER TABLE test_schema.test_table DROP CONSTRAINT IF EXISTS test_constraint;\n"
+        ));
+        assert!(writable.contains("FROM pg_catalog.pg_constraint"));
+        assert!(writable.contains("UNIQUE (test_col) DEFERRABLE INITIALLY IMMEDIATE;"));
+    }
+
+    #[test]
+    fn create_statements_should_add_not_enforced_constraint() {
+        let constraint = create_foreign_key_constraint(ConstraintTiming::NotDeferrable, false);
+        let mut writable = String::new();
+
+        constraint.create_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/constraint-create-not-enforced.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    fn create_replica_identity_constraint(is_replica_identity: bool) -> Constraint {
+        Constraint {
+            is_replica_identity,
+            ..create_constraint(
+                SCHEMA,
+                TABLE,
+                NAME,
+                ConstraintType::PrimaryKey {
+                    columns: vec![TEST_COL.into()],
+                    index_parameters: IndexParameters {
+                        include: None,
+                        with: None,
+                        tablespace: None,
+                    },
+                },
+                ConstraintTiming::NotDeferrable,
+            )
+        }
+    }
+
+    #[test]
+    fn create_statements_should_set_replica_identity_when_constraint_is_replica_identity() {
+        let constraint = create_replica_identity_constraint(true);
+        let mut writable = String::new();
+
+        constraint.create_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/constraint-create-replica-identity.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        true,
+        include_str!("../../test-files/sql/constraint-alter-replica-identity.pgsql"),
+    )]
+    #[case(true, false, "")]
+    fn alter_statements_should_set_replica_identity_when_constraint_becomes_replica_identity(
+        #[case] old_is_replica_identity: bool,
+        #[case] new_is_replica_identity: bool,
+        #[case] statement: &str,
+    ) {
+        let constraint_before = create_replica_identity_constraint(old_is_replica_identity);
+        let constraint_after = create_replica_identity_constraint(new_is_replica_identity);
+        let mut writable = String::new();
+
+        constraint_before
+            .alter_statements(&constraint_after, &mut writable)
+            .unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        true,
+        false,
+        include_str!("../../test-files/sql/constraint-alter-enforcement-case1.pgsql"),
+    )]
+    #[case(
+        false,
+        true,
+        include_str!("../../test-files/sql/constraint-alter-enforcement-case2.pgsql"),
+    )]
+    fn alter_statements_should_alter_enforcement_when_foreign_key_enforcement_changed(
+        #[case] old_is_enforced: bool,
+        #[case] new_is_enforced: bool,
+        #[case] statement: &str,
+    ) {
+        let constraint_before =
+            create_foreign_key_constraint(ConstraintTiming::NotDeferrable, old_is_enforced);
+        let constraint_after =
+            create_foreign_key_constraint(ConstraintTiming::NotDeferrable, new_is_enforced);
         let mut writable = String::new();
 
         constraint_before
@@ -618,4 +1213,137 @@ mod test {
 
         assert_eq!(statement.trim(), writable.trim());
     }
+
+    #[test]
+    fn create_statements_idempotent_should_check_pg_constraint_within_do_block() {
+        let constraint = create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            ConstraintType::Check {
+                columns: vec![TEST_COL.into()],
+                expression: "test_col = 'test'".into(),
+                is_inheritable: false,
+            },
+            ConstraintTiming::NotDeferrable,
+        );
+        let mut writable = String::new();
+
+        constraint
+            .create_statements_idempotent(&mut writable)
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/constraint-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_alter_table_drop_constraint_statement() {
+        let constraint = create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            ConstraintType::default(),
+            ConstraintTiming::NotDeferrable,
+        );
+        let mut writable = String::new();
+
+        constraint.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/constraint-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    fn create_primary_key() -> Constraint {
+        create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            ConstraintType::PrimaryKey {
+                columns: vec![TEST_COL.into(), TEST_COL2.into()],
+                index_parameters: IndexParameters::default(),
+            },
+            ConstraintTiming::NotDeferrable,
+        )
+    }
+
+    #[rstest::rstest]
+    #[case(create_check_constraint(true), true)]
+    #[case(create_check_constraint(false), false)]
+    #[case(create_primary_key(), true)]
+    #[case(
+        Constraint {
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            ..create_primary_key()
+        },
+        true
+    )]
+    #[case(
+        Constraint {
+            is_replica_identity: true,
+            ..create_primary_key()
+        },
+        false
+    )]
+    #[case(
+        Constraint {
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, REF_TABLE)],
+            ..create_check_constraint(true)
+        },
+        false
+    )]
+    #[case(
+        create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            ConstraintType::Check {
+                columns: vec![TEST_COL.into(), TEST_COL2.into()],
+                expression: "test_col > test_col2".into(),
+                is_inheritable: true,
+            },
+            ConstraintTiming::NotDeferrable,
+        ),
+        false
+    )]
+    #[case(
+        create_constraint(
+            SCHEMA,
+            TABLE,
+            NAME,
+            ConstraintType::ForeignKey {
+                columns: vec![TEST_COL.into()],
+                ref_table: SchemaQualifiedName::new(SCHEMA, REF_TABLE),
+                ref_columns: vec![TEST_COL.into()],
+                match_type: ForeignKeyMatch::Simple,
+                on_delete: ForeignKeyAction::NoAction,
+                on_update: ForeignKeyAction::NoAction,
+            },
+            ConstraintTiming::NotDeferrable,
+        ),
+        false
+    )]
+    fn can_be_inlined_should_only_allow_self_contained_constraints(
+        #[case] constraint: Constraint,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, constraint.can_be_inlined());
+    }
+
+    #[test]
+    fn write_inline_definition_should_write_table_constraint() {
+        let constraint = create_primary_key();
+        let mut writable = String::new();
+
+        constraint.write_inline_definition(&mut writable).unwrap();
+
+        assert_eq!(
+            "CONSTRAINT test_constraint PRIMARY KEY (test_col,test_col2) NOT DEFERRABLE",
+            writable
+        );
+    }
 }
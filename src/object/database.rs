@@ -1,1526 +1,9861 @@
-use std::collections::{HashSet, VecDeque};
-use std::fmt::{Display, Formatter};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter, Write};
+use std::future::Future;
+use std::path::{Path, PathBuf};
 
 use async_walkdir::WalkDir;
 use futures::stream::StreamExt;
-use pg_query::protobuf::{node::Node, ConstrType, RangeVar};
-use serde::Deserialize;
+use lazy_regex::{regex, Captures, Regex};
+use pg_query::protobuf::{a_const, node::Node, ConstrType, ObjectType, RangeVar, RoleSpecType};
+use serde::{Deserialize, Serialize};
 use sqlx::postgres::types::Oid;
 use sqlx::postgres::PgDatabaseError;
+use sqlx::types::chrono::Utc;
 use sqlx::types::Uuid;
 use sqlx::{query_as, query_scalar, Error, PgPool};
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+use crate::ansi::{self, Color};
+use crate::object::trigger::TriggerEnabled;
 use crate::object::{
-    find_index, get_constraints, get_extensions, get_functions, get_indexes, get_policies,
-    get_schemas, get_sequences, get_tables, get_triggers, get_udts, get_views, is_verbose,
-    plpgsql::parse_plpgsql_function, Constraint, Extension, Function, Index, Policy, Schema,
-    SchemaQualifiedName, Sequence, SqlObject, SqlObjectEnum, Table, Trigger, Udt, View,
-    BUILT_IN_FUNCTIONS, BUILT_IN_NAMES,
+    fetch_with_retry, get_access_methods, get_aggregates, get_constraints, get_default_privileges,
+    get_event_triggers, get_extensions, get_functions, get_indexes, get_operator_classes,
+    get_operators, get_policies, get_publications, get_roles, get_schemas, get_sequences,
+    get_statistics, get_tables, get_text_search_configurations, get_text_search_dictionaries,
+    get_triggers, get_udts, get_views, is_built_in_name, is_emit_roles, is_excluded_object,
+    is_fail_on_unsupported, is_include_roles, is_verbose, is_warn_function_search_path,
+    load_built_in_names, matches_any_pattern, parse_search_path, plpgsql::parse_plpgsql_function,
+    scrape_pool, AccessMethod, Aggregate, Constraint, DefaultPrivileges, EventTrigger, Extension,
+    Function, Index, KeyValuePairs, Operator, OperatorClass, Policy, Publication, RenderContext,
+    Role, Schema, SchemaQualifiedName, Sequence, SqlObject, SqlObjectEnum, Statistics, Table,
+    TablespaceStub, TextSearchConfiguration, TextSearchDictionary, Trigger, Udt, View,
+    TEXT_SEARCH_FUNCTIONS,
 };
-use crate::PgDiffError;
+use crate::{write_join, PgDiffError, Timings, VERSION};
 
-/// Main object of the application that contains metadata about the targeted database and the source
-/// control SQL files provided.
-pub struct DatabaseMigration {
-    pool: PgPool,
-    database: Database,
-    source_control_database: SourceControlDatabase,
+/// Options that change the statements generated when planning a migration
+#[derive(Debug, Clone)]
+pub struct MigrationOptions {
+    /// Drop schemas removed from the source control files using `DROP SCHEMA ... CASCADE` rather
+    /// than dropping each contained object
+    pub cascade_schema_drop: bool,
+    /// Add new check and foreign key constraints as `NOT VALID` and validate them at the end of
+    /// the migration. This avoids holding long locks while existing rows are checked.
+    pub add_constraints_not_valid: bool,
+    /// Start a non-empty plan with a comment block noting the tool version, generation timestamp,
+    /// target database and the number of creates, alters and drops within the plan. The plan also
+    /// ends with a comment block summarizing the changes per object type, the destructive steps
+    /// and the total number of statements.
+    pub include_header: bool,
+    /// Start a non-empty plan with `SET` statements that make the session safe for the migration
+    /// (i.e. empty `search_path`, bounded `lock_timeout`, no `statement_timeout` and no function
+    /// body checks) and end the plan by resetting those settings
+    pub session_preamble: bool,
+    /// Value of the `lock_timeout` set by the session preamble (e.g. `10s`)
+    pub lock_timeout: String,
+    /// Wrap a non-empty plan within a single transaction. Statements that cannot run within a
+    /// transaction block are moved to separate sections before or after the transaction (see
+    /// [TransactionSections]). The session preamble (if any) is placed outside the transaction so
+    /// the settings also apply to those sections.
+    pub transactional: bool,
+    /// Selection of the drop statements included within the plan
+    pub drop_mode: DropMode,
+    /// Drop roles of the cluster that are not created within the source control files. Roles
+    /// are often shared with other applications so a comment noting each skipped role drop is
+    /// written instead unless this is true.
+    pub drop_roles: bool,
+    /// End a non-empty plan with a maintenance section, separate from the migration statements,
+    /// that analyzes each table touched by the plan and notes indexes that should be rebuilt
+    pub post_migration_maintenance: bool,
+    /// Disable the triggers of every table altered by a non-empty plan (along with setting the
+    /// session's `session_replication_role` to `replica`) before the plan's migration statements
+    /// and enable the triggers again after those statements
+    pub disable_triggers: bool,
+    /// Highlight each step of the plan for display within a terminal. A step's statements are
+    /// colored by the [StepKind] and preceded by a bold comment naming the step's object. The
+    /// plan then contains ANSI escape codes that must be removed (see [ansi::strip]) before the
+    /// plan is executed.
+    pub highlight: bool,
+    /// Set `NOT NULL` on existing columns by adding and validating a `NOT VALID` check constraint
+    /// first so the `SET NOT NULL` statement skips the full table scan. Only used when the target
+    /// server supports skipping the scan (Postgresql 12+).
+    pub safe_not_null: bool,
+    /// Set existing `NULL` values of columns becoming `NOT NULL` to the column's default before
+    /// setting `NOT NULL` so the statement does not fail on populated tables. Without this
+    /// option, a comment warns that setting `NOT NULL` may fail.
+    pub backfill_nulls: bool,
+    /// Generate guarded statements (e.g. `IF NOT EXISTS` and `IF EXISTS`) so the plan can be
+    /// applied to a database that is already partially migrated (see [RenderContext])
+    pub idempotent: bool,
+    /// Detect schemas that were renamed (see [SchemaRename::detect]) and rename those schemas
+    /// rather than dropping the old schema and creating the new schema along with all contained
+    /// objects. Only used when all drops are included within the plan.
+    pub detect_renames: bool,
+    /// Handling of alterations that cannot be migrated automatically (e.g. changing a column's
+    /// data type)
+    pub on_unsupported: OnUnsupported,
+    /// Fail rather than returning a plan that contains manual changes (see
+    /// [OnUnsupported::Comment])
+    pub fail_on_manual: bool,
+    /// Glob patterns of object names managed externally. Existing objects that match (along
+    /// with objects owned by a matching table or view) are never altered or dropped.
+    pub ignored_objects: Vec<String>,
+    /// Allow the plan to drop schemas. Dropping a schema drops every object within the schema so
+    /// planning a schema drop fails unless this is true (see [PgDiffError::DestructiveSchemaDrop]).
+    pub allow_destructive: bool,
 }
 
-impl DatabaseMigration {
-    /// Create a new [DatabaseMigration] using the connection `pool` provided to scrape metadata
-    /// from the target database and the `source_control_directory` to collect source control SQL
-    /// files for generating the desired new state of the target database.
-    ///
-    /// ## Errors
-    /// if database scraping fails (see [Database::from_connection]) or source control file
-    /// analyzing fails (see [SourceControlDatabase::from_directory]).
-    pub async fn new<P>(pool: PgPool, source_control_directory: P) -> Result<Self, PgDiffError>
-    where
-        P: AsRef<Path>,
-    {
-        let database = Database::from_connection(&pool).await?;
-        let source_control_database =
-            SourceControlDatabase::from_directory(source_control_directory).await?;
-        Ok(Self {
-            pool,
-            database,
-            source_control_database,
-        })
+/// Options for creating and populating the temp databases used while planning a migration. All
+/// options default to a randomly named database created using the server's default template and
+/// tablespace.
+#[derive(Debug, Default, Clone)]
+pub struct TempDatabaseOptions {
+    /// Name of the temp database. The name is case sensitive and the database must not already
+    /// exist.
+    pub name: Option<String>,
+    /// Template database copied to create the temp database (e.g. `template0`)
+    pub template: Option<String>,
+    /// Tablespace that the temp database is created within
+    pub tablespace: Option<String>,
+    /// Length in bytes above which a source control statement applied to the temp database is
+    /// reported as oversized when verbose. Oversized statements are still applied.
+    pub max_statement_length: Option<usize>,
+    /// Existing empty database that the source control statements are applied to rather than a
+    /// created temp database. Plans cannot be verified when a staging database is used.
+    pub staging: Option<StagingDatabase>,
+}
+
+/// Existing database used to stage the source control statements when the current user cannot
+/// create a temp database
+#[derive(Debug, Clone)]
+pub struct StagingDatabase {
+    /// Connection pool of the staging database
+    pub pool: PgPool,
+    /// Cleanup of the staging database after the source control statements are staged
+    pub cleanup: StagingCleanup,
+}
+
+/// Options for cleaning up the staging database once the source control statements have been
+/// applied to the staging database and scraped
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum StagingCleanup {
+    /// Leave the staged objects within the staging database
+    #[default]
+    None,
+    /// Drop every object created within the staging database so the database can be reused
+    DropObjects,
+}
+
+/// Options for handling objects that must be dropped when planning a migration
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum DropMode {
+    /// Include drop statements along with all other statements
+    #[default]
+    Include,
+    /// Skip drop statements for an additive-only plan. A comment noting each skipped drop is
+    /// written in place of the statement.
+    Exclude,
+    /// Only include drop statements (e.g. for teardown workflows)
+    Only,
+    /// Skip create statements. Used to revert an additive-only plan since the objects whose drops
+    /// were skipped still exist.
+    ExcludeCreates,
+    /// Only include create statements. Used to revert a drops-only plan by creating the dropped
+    /// objects again.
+    OnlyCreates,
+}
+
+impl DropMode {
+    /// True if the statements of the compare `result` should be included within the plan
+    fn includes(&self, result: &DbCompareResult) -> bool {
+        let is_drop = matches!(
+            result,
+            DbCompareResult::Drop(_) | DbCompareResult::DropSchemaCascade(_)
+        );
+        let is_create = matches!(result, DbCompareResult::Create(_));
+        match self {
+            Self::Include => true,
+            Self::Exclude => !is_drop,
+            Self::Only => is_drop,
+            Self::ExcludeCreates => !is_create,
+            Self::OnlyCreates => is_create,
+        }
     }
 
-    /// Plan the steps required to migrate the target database to the state described in the source
-    /// control files.
-    ///
-    /// This applies the source control statements to a temp database, scrapes that temp database
-    /// for metadata and compares the temp database to the current state of the target database to
-    /// find the steps required for migration.
-    ///
-    /// ## Errors
-    /// See [SourceControlDatabase::apply_to_temp_database]
-    /// See [SourceControlDatabase::scrape_temp_database]
-    pub async fn plan_migration(&mut self) -> Result<String, PgDiffError> {
-        self.create_temp_database().await?;
-        let db_options = (*self.pool.connect_options())
-            .clone()
-            .database(&self.source_control_database.temp_db_name);
-        let temp_db_pool = PgPool::connect_with(db_options).await?;
-        self.source_control_database
-            .apply_to_temp_database(&temp_db_pool)
-            .await?;
-        let source_control_temp_database = Database::from_connection(&temp_db_pool).await?;
-        let migration_script = self
-            .database
-            .compare_to_other_database(&source_control_temp_database)?;
-        Ok(migration_script)
+    /// True if alterations (including database settings) are included within the plan
+    fn includes_alters(&self) -> bool {
+        !matches!(self, Self::Only | Self::OnlyCreates)
     }
 
-    async fn create_temp_database(&self) -> Result<(), PgDiffError> {
-        let query = include_str!("./../../queries/check_create_db_role.pgsql");
-        let can_create_database: bool = query_scalar(query).fetch_one(&self.pool).await?;
-        if !can_create_database {
-            return Err("Current user does not have permission to create a temp database for migration staging".into());
+    /// Drop mode of the revert of a plan using this drop mode. The revert compares the databases
+    /// in the opposite direction so the drops of the plan are the creates of the revert.
+    fn revert(self) -> Self {
+        match self {
+            Self::Include => Self::Include,
+            Self::Exclude => Self::ExcludeCreates,
+            Self::Only => Self::OnlyCreates,
+            Self::ExcludeCreates => Self::Exclude,
+            Self::OnlyCreates => Self::Only,
         }
+    }
+}
 
-        let db_options = DatabaseOptions::from_connection(&self.pool).await?;
-        let create_database = format!(
-            "CREATE DATABASE {}{};",
-            self.source_control_database.temp_db_name, db_options
-        );
-        sqlx::query(&create_database).execute(&self.pool).await?;
-        if is_verbose() {
-            println!(
-                "Created temp database: {}",
-                self.source_control_database.temp_db_name
-            );
+/// Options for handling alterations that cannot be migrated automatically when planning a
+/// migration
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OnUnsupported {
+    /// Fail the plan with the reason the alteration cannot be migrated
+    #[default]
+    Error,
+    /// Write a comment noting the manual change required for each alteration that cannot be
+    /// migrated, keep the statements of the supported alterations and continue planning the
+    /// remaining objects
+    Comment,
+}
+
+impl OnUnsupported {
+    /// Handle the `result` of writing the alteration statements of an `object_type`. Returns a
+    /// comment noting the manual change required for each alteration that cannot be migrated, or
+    /// [None] if every alteration is supported. The statements already written for the supported
+    /// alterations are kept. Other errors are always returned.
+    fn handle(
+        self,
+        result: Result<(), PgDiffError>,
+        object_type: &str,
+    ) -> Result<Option<String>, PgDiffError> {
+        let (object_name, reasons) = match result {
+            Ok(()) => return Ok(None),
+            Err(PgDiffError::InvalidMigration {
+                object_name,
+                reason,
+            }) if self == Self::Comment => (object_name, vec![reason]),
+            Err(PgDiffError::InvalidMigrations {
+                object_name,
+                reasons,
+            }) if self == Self::Comment => (object_name, reasons),
+            Err(error) => return Err(error),
+        };
+        let mut sql = String::new();
+        for reason in reasons {
+            writeln!(
+                sql,
+                "-- MANUAL CHANGE REQUIRED: {} {object_name} {reason}",
+                object_type.to_lowercase()
+            )?;
+        }
+        Ok(Some(sql))
+    }
+}
+
+impl MigrationOptions {
+    /// Write the statements required before the plan's migration statements. Statements of the
+    /// plan that must run before the transaction are written between the session preamble and
+    /// the start of the transaction.
+    fn write_preamble<W: Write>(
+        &self,
+        w: &mut W,
+        sections: &TransactionSections,
+    ) -> Result<(), PgDiffError> {
+        if self.session_preamble {
+            w.write_str("SET search_path = '';\n")?;
+            writeln!(
+                w,
+                "SET lock_timeout = '{}';",
+                self.lock_timeout.replace('\'', "''")
+            )?;
+            w.write_str("SET statement_timeout = 0;\n")?;
+            w.write_str("SET check_function_bodies = off;\n")?;
+        }
+        if self.transactional {
+            sections.write_before(w)?;
+            w.write_str("BEGIN;\n")?;
+        }
+        Ok(())
+    }
+
+    /// Write the statements required after the plan's migration statements. Statements of the
+    /// plan that must run after the transaction are written between the end of the transaction
+    /// and the session settings reset.
+    fn write_postamble<W: Write>(
+        &self,
+        w: &mut W,
+        sections: &TransactionSections,
+    ) -> Result<(), PgDiffError> {
+        if self.transactional {
+            w.write_str("COMMIT;\n")?;
+            sections.write_after(w)?;
+        }
+        if self.session_preamble {
+            w.write_str("RESET check_function_bodies;\n")?;
+            w.write_str("RESET statement_timeout;\n")?;
+            w.write_str("RESET lock_timeout;\n")?;
+            w.write_str("RESET search_path;\n")?;
         }
         Ok(())
     }
+
+    /// Print a warning to STDERR if `safe_not_null` is requested but the server hosting the
+    /// `database` cannot skip the table scan of `SET NOT NULL` (see [Database::migration_steps]).
+    /// Called once per plan rather than per comparison since every comparison of a plan targets
+    /// the same server.
+    fn warn_unsupported_safe_not_null(&self, database: &Database) {
+        if self.safe_not_null && !database.settings.can_skip_not_null_scan() {
+            eprintln!(
+                "Warning: Server cannot skip the table scan of SET NOT NULL. Columns are set as \
+                 NOT NULL directly"
+            );
+        }
+    }
 }
 
-impl Drop for DatabaseMigration {
-    fn drop(&mut self) {
-        let db_name = self.source_control_database.temp_db_name.clone();
-        let pool = self.pool.clone();
-        let fut = async move {
-            if let Err(error) = sqlx::query(&format!(
-                "DROP DATABASE IF EXISTS {} WITH (FORCE);",
-                db_name
-            ))
-            .execute(&pool)
-            .await
-            {
-                println!("Error dropping temp database: {error}");
-            }
-        };
-        // It's okay to block on this future here since the database migration will signify the end
-        // of the application's lifetime
-        futures::executor::block_on(fut);
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            cascade_schema_drop: true,
+            add_constraints_not_valid: false,
+            include_header: false,
+            session_preamble: false,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT.into(),
+            transactional: false,
+            drop_mode: DropMode::default(),
+            drop_roles: false,
+            post_migration_maintenance: false,
+            disable_triggers: false,
+            highlight: false,
+            safe_not_null: false,
+            backfill_nulls: false,
+            idempotent: false,
+            detect_renames: false,
+            on_unsupported: OnUnsupported::default(),
+            fail_on_manual: false,
+            ignored_objects: vec![],
+            allow_destructive: false,
+        }
     }
 }
 
-struct NodeIter<'n> {
-    root: &'n pg_query::NodeEnum,
-    current_node: &'n pg_query::NodeEnum,
-    queued_elements: VecDeque<SchemaQualifiedName>,
-    queued_nodes: VecDeque<&'n pg_query::NodeEnum>,
+/// Planned migration of a target database along with the best-effort revert of the migration
+#[derive(Debug, Default)]
+pub struct MigrationPlan {
+    /// Statements that migrate the target database to the source control state
+    pub deploy: String,
+    /// Statements that migrate the target database from the source control state back to its
+    /// current state
+    pub revert: String,
+    /// Number of statements within the deploy that must be applied to migrate the target
+    /// database, including manual changes. Comments of skipped drops are not counted.
+    pub statement_count: usize,
 }
 
-impl<'n> NodeIter<'n> {
-    fn new(node: &'n pg_query::NodeEnum) -> Self {
-        let mut iter = Self {
-            root: node,
-            current_node: node,
-            queued_elements: VecDeque::new(),
-            queued_nodes: VecDeque::new(),
-        };
-        iter.extract_objects_from_current_node();
-        iter
+impl MigrationPlan {
+    /// True if the deploy has no statements to apply (i.e. the target database does not need to
+    /// be migrated). A plan that only comments on skipped drops does not require a migration.
+    pub fn is_empty(&self) -> bool {
+        self.statement_count == 0
     }
+}
 
-    fn queue_nodes(&mut self, nodes: &'n [pg_query::protobuf::Node]) {
-        nodes
-            .iter()
-            .filter_map(|c| c.node.as_ref())
-            .for_each(|n| self.queued_nodes.push_back(n));
-    }
+/// Default `lock_timeout` set by the session preamble of a migration plan
+pub const DEFAULT_LOCK_TIMEOUT: &str = "10s";
 
-    fn queue_node(&mut self, node: &'n Option<Box<pg_query::protobuf::Node>>) {
-        if let Some(node) = node.as_deref().and_then(|e| e.node.as_ref()) {
-            self.queued_nodes.push_back(node)
+/// Single step of a migration plan. Each step contains all statements generated for a single
+/// object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStep {
+    /// Type of operation performed by the step
+    pub kind: StepKind,
+    /// Full name of the object migrated by the step
+    pub object: SchemaQualifiedName,
+    /// Object type name as found within SQL statements (e.g. `TABLE`)
+    pub object_type: String,
+    /// Statements executed by the step. Skipped drops only contain a comment.
+    pub sql: String,
+    /// True if the step can remove data or drops an object (i.e. the step drops an object, drops
+    /// or retypes table columns or drops the object to create it again)
+    pub is_destructive: bool,
+    /// Table that the step's object belongs to along with the step's position within all steps of
+    /// that table. [None] if the object is not a table or owned by a table.
+    pub table_step: Option<TableStep>,
+    /// Dependencies of the migrated object. For drops, these are the dependencies of the existing
+    /// object.
+    pub dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl MigrationStep {
+    /// Create a new step of the `kind` for the object of the compare `result`
+    fn new(kind: StepKind, result: &DbCompareResult, sql: String, is_destructive: bool) -> Self {
+        let (object, object_type) = result.object_name_and_type();
+        Self {
+            kind,
+            object: object.clone(),
+            object_type: object_type.to_string(),
+            sql,
+            is_destructive,
+            table_step: result.table_step(),
+            dependencies: result.dependencies().to_vec(),
         }
     }
 
-    fn queue_names(&mut self, name_nodes: &[pg_query::protobuf::Node]) {
-        if let Some(name) = extract_names(name_nodes) {
-            self.queued_elements.push_back(name);
+    /// Write the step's statements colored by the step's kind below a bold header comment naming
+    /// the step's object
+    fn write_highlighted<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "{}",
+            ansi::bold(&format!("-- {} {}", self.object_type, self.object))
+        )?;
+        let sql = self.sql.trim_end();
+        match self.kind.color() {
+            Some(color) => writeln!(w, "{}", ansi::paint(sql, color))?,
+            None => writeln!(w, "{sql}")?,
         }
+        Ok(())
     }
 
-    fn queue_relation(&mut self, relation: &Option<RangeVar>) {
-        let Some(range_var) = relation else {
-            return;
+    /// Step setting the replica identity of a created or altered table to an index (see
+    /// [Table::replica_identity_index]). The step depends on the index so the index is created
+    /// first. [None] if the compare `result` is not a table with a new index replica identity or
+    /// the identity is set by one of the `constraints` (see [Constraint::is_replica_identity]).
+    fn replica_identity(
+        result: &DbCompareResult,
+        constraints: &[Constraint],
+    ) -> Result<Option<Self>, PgDiffError> {
+        let (old, new) = match result {
+            DbCompareResult::Create(SqlObjectEnum::Table(new)) => (None, new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Table(old),
+                new: SqlObjectEnum::Table(new),
+            } => (Some(old), new),
+            _ => return Ok(None),
         };
-        let name = SchemaQualifiedName::new(&range_var.schemaname, &range_var.relname);
-        self.queued_elements.push_back(name);
+        let Some(index) = new.replica_identity_index() else {
+            return Ok(None);
+        };
+        if old.is_some_and(|old| old.replica_identity == new.replica_identity)
+            || constraints
+                .iter()
+                .any(|c| c.is_replica_identity && c.owner_table_name == new.name)
+        {
+            return Ok(None);
+        }
+        let mut sql = String::new();
+        new.write_replica_identity(&mut sql, &new.replica_identity)?;
+        Ok(Some(Self {
+            kind: StepKind::Alter,
+            object: new.name.clone(),
+            object_type: new.object_type_name().to_string(),
+            sql,
+            is_destructive: false,
+            table_step: Some(TableStep {
+                table: new.name.clone(),
+                order: TableStepOrder::ConstraintOrIndex,
+            }),
+            dependencies: vec![index.clone()],
+        }))
     }
 
-    fn parse_inline_sql_code(&mut self, code: &str) {
-        match pg_query::parse(code) {
-            Ok(result) => {
-                for table in result.tables() {
-                    self.queued_elements.push_back(table.into());
-                }
-                for function in result.functions() {
-                    self.queued_elements.push_back(function.into());
-                }
-            },
-            Err(error) => {
-                if is_verbose() {
-                    println!(
-                        "Skipping SQL code block since the source text could not be parsed. {error}\n{}",
-                        code
-                    )
-                }
+    /// True if the step applies to an event trigger
+    fn is_event_trigger(&self) -> bool {
+        self.object_type == "EVENT TRIGGER"
+    }
+
+    /// True if the step applies to default privileges
+    fn is_default_privileges(&self) -> bool {
+        self.object_type == "DEFAULT PRIVILEGES"
+    }
+
+    /// True if this step applies to an object within a schema and the `other` step sets the
+    /// default privileges of that schema (or of every schema). Default privileges only apply to
+    /// objects created after the privileges are set so the object must wait for the privileges.
+    /// Schemas and roles never wait since default privileges depend on them.
+    fn follows_default_privileges(&self, other: &Self) -> bool {
+        other.is_default_privileges()
+            && !self.is_default_privileges()
+            && !self.object.schema_name.is_empty()
+            && !self.object.local_name.is_empty()
+            && (other.object.schema_name.is_empty()
+                || other.object.schema_name == self.object.schema_name)
+    }
+
+    /// True if the step drops (or would drop) an object owned by a table
+    fn is_owned_drop(&self) -> bool {
+        matches!(
+            self.table_step,
+            Some(TableStep {
+                order: TableStepOrder::DropForeignKey | TableStepOrder::DropOwned,
+                ..
+            })
+        )
+    }
+
+    /// True if the step drops an object
+    fn is_drop(&self) -> bool {
+        matches!(self.kind, StepKind::Drop | StepKind::SkippedDrop)
+    }
+
+    /// True if this step must be executed after the `other` step. Creates and alters wait for
+    /// the steps of their dependencies (and the default privileges of their schema, see
+    /// [MigrationStep::follows_default_privileges]) while drops wait for the drops of their
+    /// dependents. Steps of the same table are executed in [TableStepOrder].
+    fn must_follow(&self, other: &Self) -> bool {
+        if let (Some(table_step), Some(other_table_step)) = (&self.table_step, &other.table_step) {
+            if table_step.table == other_table_step.table
+                && other_table_step.order < table_step.order
+            {
+                return true;
+            }
+        }
+        match (self.is_drop(), other.is_drop()) {
+            (false, false) => {
+                self.dependencies.contains(&other.object) || self.follows_default_privileges(other)
             },
+            (true, true) => other.dependencies.contains(&self.object),
+            _ => false,
         }
     }
+}
 
-    fn parse_inline_plpgsql_code(&mut self, code: &str) {
-        let functions = match parse_plpgsql_function(code) {
-            Ok(functions) => functions,
-            Err(error) => {
-                if is_verbose() {
-                    println!("Skipping plpg/sql code block since the source text could not be parsed. {error}\n")
-                }
-                return;
-            },
+/// Table that a [MigrationStep] belongs to and the position of the step within the table's steps
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStep {
+    /// Full name of the table
+    pub table: SchemaQualifiedName,
+    /// Position of the step relative to the other steps of the table
+    pub order: TableStepOrder,
+}
+
+/// Order of the steps that belong to a single table. Steps are executed in the order of the
+/// variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TableStepOrder {
+    /// Drop of a foreign key. Foreign keys are dropped first since they can depend on the unique
+    /// constraints and indexes of other tables.
+    DropForeignKey,
+    /// Drop of a constraint, index, trigger or policy
+    DropOwned,
+    /// Create, alter or drop of the table itself. Columns are altered or dropped before new
+    /// columns are added.
+    Table,
+    /// Create or alter of a constraint or index
+    ConstraintOrIndex,
+    /// Create or alter of a trigger or policy
+    TriggerOrPolicy,
+}
+
+/// Reorder the migration `steps` so the steps of each table are grouped together and executed in
+/// [TableStepOrder].
+///
+/// Drops of table owned objects are moved to the start of the plan (foreign keys first) so no
+/// constraint, index, trigger or policy can block the alteration of a column it references. The
+/// remaining steps are sorted topologically (Kahn's algorithm) where each step waits for every
+/// other step it must follow (see [MigrationStep::must_follow]). When multiple steps are ready,
+/// the steps of the table scheduled last are preferred, then the steps found first within the
+/// original order.
+///
+/// ## Errors
+/// If the remaining steps must follow each other in a cycle. The error names the steps of the
+/// cycle.
+fn order_steps(steps: Vec<MigrationStep>) -> Result<Vec<MigrationStep>, PgDiffError> {
+    let (mut ordered, remaining): (Vec<MigrationStep>, Vec<MigrationStep>) =
+        steps.into_iter().partition(|s| s.is_owned_drop());
+    ordered.sort_by_key(|s| s.table_step.as_ref().map(|t| t.order));
+    // Indexes of the steps that must follow each step
+    let mut followers: Vec<Vec<usize>> = vec![vec![]; remaining.len()];
+    // Number of unscheduled steps that each unscheduled step must follow
+    let mut in_degrees: BTreeMap<usize, usize> = (0..remaining.len()).map(|i| (i, 0)).collect();
+    for (index, step) in remaining.iter().enumerate() {
+        for (other_index, other) in remaining.iter().enumerate() {
+            if index != other_index && step.must_follow(other) {
+                followers[other_index].push(index);
+                *in_degrees.entry(index).or_default() += 1;
+            }
+        }
+    }
+    let mut last_table = ordered
+        .last()
+        .and_then(|s| s.table_step.as_ref())
+        .map(|t| &t.table);
+    let mut schedule = Vec::with_capacity(remaining.len());
+    while !in_degrees.is_empty() {
+        let mut ready = in_degrees
+            .iter()
+            .filter(|(_, in_degree)| **in_degree == 0)
+            .map(|(index, _)| *index);
+        let same_table = ready.clone().find(|index| {
+            last_table.is_some()
+                && remaining[*index].table_step.as_ref().map(|t| &t.table) == last_table
+        });
+        let Some(index) = same_table.or_else(|| ready.next()) else {
+            return Err(PgDiffError::StepCycle {
+                steps: find_step_cycle(&remaining, &followers, &in_degrees),
+            });
         };
-        for function in functions {
-            match function.get_objects() {
-                Ok(objects) => {
-                    self.queued_elements.append(&mut VecDeque::from(objects));
-                },
-                Err(error) => {
-                    if is_verbose() {
-                        println!("Skipping plpg/sql code block since the source text could not be parsed for objects. {error}")
-                    }
+        in_degrees.remove(&index);
+        for follower in &followers[index] {
+            if let Some(in_degree) = in_degrees.get_mut(follower) {
+                *in_degree -= 1;
+            }
+        }
+        last_table = remaining[index].table_step.as_ref().map(|t| &t.table);
+        schedule.push(index);
+    }
+    let mut remaining: Vec<Option<MigrationStep>> = remaining.into_iter().map(Some).collect();
+    ordered.extend(
+        schedule
+            .into_iter()
+            .filter_map(|index| remaining[index].take()),
+    );
+    Ok(ordered)
+}
+
+/// Find a cycle within the unscheduled steps of [order_steps]. Every unscheduled step (the keys
+/// of `in_degrees`) must follow another unscheduled step so walking backward from any step
+/// eventually reaches a step already visited. Returns the steps of the cycle in the order they
+/// must follow each other.
+fn find_step_cycle(
+    steps: &[MigrationStep],
+    followers: &[Vec<usize>],
+    in_degrees: &BTreeMap<usize, usize>,
+) -> Vec<String> {
+    let mut path: Vec<usize> = vec![];
+    let mut current = in_degrees.keys().next().copied();
+    while let Some(index) = current {
+        if let Some(position) = path.iter().position(|i| *i == index) {
+            return path[position..]
+                .iter()
+                .rev()
+                .map(|i| format!("{} {}", steps[*i].object_type, steps[*i].object))
+                .collect();
+        }
+        path.push(index);
+        current = in_degrees
+            .keys()
+            .find(|other| followers[**other].contains(&index))
+            .copied();
+    }
+    path.iter()
+        .map(|i| format!("{} {}", steps[*i].object_type, steps[*i].object))
+        .collect()
+}
+
+/// Type of operation performed by a [MigrationStep]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepKind {
+    /// Object is created
+    Create,
+    /// Object is altered in place or recreated
+    Alter,
+    /// Object is dropped
+    Drop,
+    /// Constraint added as `NOT VALID` is validated
+    Validate,
+    /// Drop that was skipped because of the [DropMode]. Only a comment noting the drop is included.
+    SkippedDrop,
+    /// Alteration that cannot be migrated automatically. Only a comment noting the manual change
+    /// required is included (see [OnUnsupported::Comment]).
+    Manual,
+}
+
+impl StepKind {
+    /// Color of highlighted steps of this kind. Skipped drops are not colored since the step only
+    /// contains a comment.
+    fn color(self) -> Option<Color> {
+        match self {
+            Self::Create => Some(Color::Green),
+            Self::Alter | Self::Validate => Some(Color::Yellow),
+            Self::Drop | Self::Manual => Some(Color::Red),
+            Self::SkippedDrop => None,
+        }
+    }
+}
+
+/// Number of objects created, altered and dropped by a migration plan
+#[derive(Debug, Default, PartialEq)]
+struct ChangeCounts {
+    creates: usize,
+    alters: usize,
+    drops: usize,
+}
+
+impl ChangeCounts {
+    /// Count a step of the `kind` provided. Validations, skipped drops and manual changes are not
+    /// counted.
+    fn add(&mut self, kind: StepKind) {
+        match kind {
+            StepKind::Create => self.creates += 1,
+            StepKind::Alter => self.alters += 1,
+            StepKind::Drop => self.drops += 1,
+            StepKind::Validate | StepKind::SkippedDrop | StepKind::Manual => {},
+        }
+    }
+
+    /// Write the non-zero counts as readable phrases (e.g. `2 tables created, 1 table altered`)
+    /// for objects of the `object_type`
+    fn write_phrases<W>(&self, w: &mut W, object_type: &str, first: &mut bool) -> std::fmt::Result
+    where
+        W: Write,
+    {
+        for (count, action) in [
+            (self.creates, "created"),
+            (self.alters, "altered"),
+            (self.drops, "dropped"),
+        ] {
+            if count == 0 {
+                continue;
+            }
+            if !*first {
+                w.write_str(", ")?;
+            }
+            *first = false;
+            write!(
+                w,
+                "{count} {} {action}",
+                object_type_label(object_type, count)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Lowercase label of the `object_type` (e.g. `TABLE`), pluralized unless the `count` is 1
+fn object_type_label(object_type: &str, count: usize) -> String {
+    let label = object_type.to_lowercase();
+    if count == 1 || label.ends_with("statistics") {
+        return label;
+    }
+    if let Some(stem) = label.strip_suffix('y') {
+        return format!("{stem}ies");
+    }
+    if label.ends_with('x') || label.ends_with("ss") || label.ends_with("ch") {
+        return format!("{label}es");
+    }
+    format!("{label}s")
+}
+
+impl Display for ChangeCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Creates: {}, Alters: {}, Drops: {}",
+            self.creates, self.alters, self.drops
+        )
+    }
+}
+
+/// Number of objects created, altered and dropped by a migration plan, in total and per object
+/// type. Only objects that contribute statements to the plan are counted.
+#[derive(Debug, Default)]
+struct PlanSummary {
+    total: ChangeCounts,
+    /// Object type name (e.g. `TABLE`) and the changes to objects of that type, in the order the
+    /// object types first appear within the plan
+    object_types: Vec<(String, ChangeCounts)>,
+    /// Description of each step that can remove data (e.g. `DROP TABLE public.old_table`), in
+    /// plan order
+    destructive_steps: Vec<String>,
+    /// Description of each alteration that must be made manually (e.g. `TABLE public.users`), in
+    /// plan order
+    manual_steps: Vec<String>,
+    /// Number of SQL statements executed by the plan's steps
+    statement_count: usize,
+}
+
+impl PlanSummary {
+    /// Count the creates, alters, drops and statements within the migration `steps` and collect
+    /// the steps that are destructive or must be made manually
+    fn from_steps(steps: &[MigrationStep]) -> Self {
+        let mut summary = Self::default();
+        for step in steps {
+            if step.kind == StepKind::SkippedDrop {
+                continue;
+            }
+            if step.kind == StepKind::Manual {
+                summary
+                    .manual_steps
+                    .push(format!("{} {}", step.object_type, step.object));
+                continue;
+            }
+            summary.statement_count += count_statements(&step.sql);
+            if step.kind == StepKind::Validate {
+                continue;
+            }
+            if step.is_destructive {
+                let action = if step.kind == StepKind::Drop {
+                    "DROP"
+                } else {
+                    "ALTER"
+                };
+                summary
+                    .destructive_steps
+                    .push(format!("{action} {} {}", step.object_type, step.object));
+            }
+            summary.total.add(step.kind);
+            match summary
+                .object_types
+                .iter_mut()
+                .find(|(object_type, _)| *object_type == step.object_type)
+            {
+                Some((_, counts)) => counts.add(step.kind),
+                None => {
+                    let mut counts = ChangeCounts::default();
+                    counts.add(step.kind);
+                    summary
+                        .object_types
+                        .push((step.object_type.clone(), counts));
                 },
             }
         }
+        summary
     }
 
-    fn extract_objects_from_current_node(&mut self) -> bool {
-        match &self.current_node {
-            Node::TableFunc(table_func) => {
-                self.queue_nodes(&table_func.coltypes);
-                self.queue_node(&table_func.rowexpr)
-            },
-            Node::CaseExpr(expr) => {
-                self.queue_nodes(&expr.args);
-                self.queue_node(&expr.defresult);
-            },
-            Node::CaseWhen(when) => {
-                self.queue_node(&when.expr);
-                self.queue_node(&when.result);
-            },
-            Node::TypeName(type_name) => {
-                self.queue_nodes(&type_name.names);
-            },
-            Node::AExpr(expr) => {
-                self.queue_node(&expr.lexpr);
-                self.queue_node(&expr.lexpr);
-            },
-            Node::FuncCall(func_call) => {
-                self.queue_nodes(&func_call.args);
-                self.queue_names(&func_call.funcname);
-            },
-            Node::ColumnDef(column) => {
-                if let Some(name) = &column.type_name {
-                    self.queue_names(&name.names);
-                }
-            },
-            Node::AlterTableStmt(alter_table) => {
-                self.queue_relation(&alter_table.relation);
-                self.queue_nodes(&alter_table.cmds);
-            },
-            Node::AlterTableCmd(alter_command) => {
-                self.queue_node(&alter_command.def);
-            },
-            Node::CreateStmt(create_table) => {
-                self.queue_nodes(&create_table.constraints);
-                self.queue_nodes(&create_table.table_elts);
-            },
-            Node::Constraint(constraint) => match constraint.contype() {
-                ConstrType::ConstrCheck => self.queue_node(&constraint.raw_expr),
-                ConstrType::ConstrForeign => self.queue_relation(&constraint.pktable),
-                _ => {},
-            },
-            Node::CreatePolicyStmt(create_policy) => {
-                self.queue_relation(&create_policy.table);
-                self.queue_node(&create_policy.qual);
-                self.queue_node(&create_policy.with_check);
-            },
-            Node::AlterPolicyStmt(alter_policy) => {
-                self.queue_relation(&alter_policy.table);
-                self.queue_node(&alter_policy.qual);
-                self.queue_node(&alter_policy.with_check);
-            },
-            Node::CreateTrigStmt(create_trigger) => {
-                self.queue_relation(&create_trigger.relation);
-                self.queue_names(&create_trigger.funcname);
-            },
-            Node::IndexStmt(index_statement) => {
-                self.queue_relation(&index_statement.relation);
-            },
-            Node::CreateFunctionStmt(create_function) => {
-                self.queue_node(&create_function.sql_body);
-                if let Some(type_name) = &create_function.return_type {
-                    self.queue_names(&type_name.names);
-                }
-                self.queue_nodes(&create_function.parameters);
-                let def_elements = create_function
-                    .options
-                    .iter()
-                    .filter_map(|n| n.node.as_ref())
-                    .filter_map(|n| {
-                        if let Node::DefElem(def_element) = n {
-                            Some(def_element)
-                        } else {
-                            None
-                        }
-                    });
-                if let Some(language) = def_elements
-                    .clone()
-                    .filter(|def| def.defname == "language")
-                    .filter_map(|def| def.arg.as_ref().and_then(|a| a.node.as_ref()))
-                    .filter_map(|n| {
-                        if let Node::String(language) = n {
-                            Some(language)
-                        } else {
-                            None
-                        }
-                    })
-                    .next()
-                {
-                    match language.sval.as_str() {
-                        "plpgsql" => match self.current_node.deparse() {
-                            Ok(function_def) => self.parse_inline_plpgsql_code(&function_def),
-                            Err(error) => {
-                                if is_verbose() {
-                                    println!("Could not deparse plpg/sql function. {error}");
-                                }
-                            },
-                        },
-                        "sql" => {
-                            if let Some(inline_code) = def_elements
-                                .clone()
-                                .filter(|def| def.defname == "as")
-                                .filter_map(|def| def.arg.as_ref().and_then(|a| a.node.as_ref()))
-                                .filter_map(|n| {
-                                    if let Node::List(list) = n {
-                                        Some(list.items.iter())
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .flatten()
-                                .filter_map(|n| n.node.as_ref())
-                                .filter_map(|n| {
-                                    if let Node::String(inline_code) = n {
-                                        Some(inline_code)
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .next()
-                            {
-                                self.parse_inline_sql_code(&inline_code.sval)
-                            }
-                        },
-                        _ => {
-                            if is_verbose() {
-                                println!(
-                                    "Unknown language '{}' for function. Could not parse.",
-                                    language.sval
-                                )
-                            }
-                        },
-                    }
-                };
-            },
-            Node::FunctionParameter(function_parameter) => {
-                if let Some(type_name) = &function_parameter.arg_type {
-                    self.queue_names(&type_name.names);
-                }
-            },
-            Node::AlterFunctionStmt(alter_function) => {
-                self.queue_nodes(&alter_function.actions);
-            },
-            Node::InlineCodeBlock(inline_code_block) => match inline_code_block.lang_oid {
-                14 => self.parse_inline_sql_code(&inline_code_block.source_text),
-                13545 => self.parse_inline_plpgsql_code(&inline_code_block.source_text),
-                _ => {
-                    if is_verbose() {
-                        println!(
-                            "Skipping code block since the language is not supported. Lang ID = {}",
-                            inline_code_block.lang_oid
-                        )
-                    }
-                },
-            },
-            Node::AlterTypeStmt(alter_type) => {
-                self.queue_nodes(&alter_type.options);
-            },
-            Node::CompositeTypeStmt(composite_type) => {
-                self.queue_nodes(&composite_type.coldeflist);
-            },
-            Node::CreateDomainStmt(create_domain) => {
-                if let Some(name) = &create_domain.type_name {
-                    self.queue_names(&name.names);
-                }
-                self.queue_nodes(&create_domain.constraints);
-            },
-            Node::ViewStmt(view) => {
-                if let Some(query) = view.query.as_ref().and_then(|q| q.node.as_ref()) {
-                    match query.deparse() {
-                        Ok(query_text) => self.parse_inline_sql_code(&query_text),
-                        Err(error) => {
-                            if is_verbose() {
-                                println!("Error trying to deparse view query. {error}")
-                            }
-                        },
-                    }
-                }
-            },
-            _ => return false,
-        };
-        self.move_to_next_node();
-        true
+    /// Write the plan's header comment block for a migration of the `database_name`
+    fn write_header<W>(&self, w: &mut W, database_name: &str) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+        writeln!(w, "-- Generated by pg-diff-rs {VERSION}")?;
+        writeln!(w, "-- Timestamp: {timestamp}")?;
+        writeln!(w, "-- Database: {database_name}")?;
+        writeln!(w, "-- {}\n", self.total)?;
+        Ok(())
     }
 
-    fn move_to_next_node(&mut self) {
-        if let Some(node) = self.queued_nodes.pop_front() {
-            self.current_node = node;
-        } else {
-            self.current_node = self.root;
+    /// Write the plan's closing comment block that describes the changes made by the plan, the
+    /// destructive steps, the manual changes required (if any) and the total number of statements
+    fn write_footer<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(w, "\n-- Summary")?;
+        w.write_str("-- ")?;
+        let mut first = true;
+        for (object_type, counts) in &self.object_types {
+            counts.write_phrases(w, object_type, &mut first)?;
         }
-    }
-
-    fn get_next(&mut self) -> Option<SchemaQualifiedName> {
-        if let Some(name) = self.queued_elements.pop_front() {
-            return Some(name);
+        if first {
+            w.write_str("No objects changed")?;
         }
-        if self.current_node == self.root {
-            return None;
+        writeln!(w)?;
+        if self.destructive_steps.is_empty() {
+            writeln!(w, "-- Destructive operations: none")?;
+        } else {
+            writeln!(w, "-- Destructive operations:")?;
+            for step in &self.destructive_steps {
+                writeln!(w, "--     {step}")?;
+            }
         }
-        if self.extract_objects_from_current_node() {
-            return self.get_next();
+        if !self.manual_steps.is_empty() {
+            writeln!(w, "-- Manual changes required:")?;
+            for step in &self.manual_steps {
+                writeln!(w, "--     {step}")?;
+            }
         }
-        self.move_to_next_node();
-        self.get_next()
+        writeln!(w, "-- Total statements: {}", self.statement_count)?;
+        Ok(())
     }
 }
 
-impl<'n> Iterator for NodeIter<'n> {
-    type Item = SchemaQualifiedName;
+/// Number of statements within the `sql` of a migration step. Comments are not counted. If the
+/// SQL cannot be split, the step is counted as a single statement.
+fn count_statements(sql: &str) -> usize {
+    pg_query::split_with_parser(sql)
+        .map(|statements| {
+            statements
+                .iter()
+                .filter(|statement| !statement.trim().is_empty())
+                .count()
+        })
+        .unwrap_or(1)
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.get_next()
+impl Display for PlanSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Plan Summary")?;
+        for (object_type, counts) in &self.object_types {
+            writeln!(f, "    {object_type}: {counts}")?;
+        }
+        write!(f, "    Total: {}", self.total)?;
+        if !self.manual_steps.is_empty() {
+            write!(
+                f,
+                "\n    Manual changes required: {}",
+                self.manual_steps.len()
+            )?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-struct DdlStatement {
-    statement: String,
-    object: SchemaQualifiedName,
-    dependencies: Vec<SchemaQualifiedName>,
+/// Number of objects of each type found within a [Database]
+#[derive(Debug, Default, PartialEq)]
+pub struct DatabaseSummary {
+    /// Object type name (e.g. `TABLE`) and the number of objects of that type, in the order the
+    /// object types are first found within the database
+    object_counts: Vec<(String, usize)>,
 }
 
-impl DdlStatement {
-    fn has_dependencies_met(&self, completed_dependencies: &HashSet<SchemaQualifiedName>) -> bool {
-        self.dependencies
-            .iter()
-            .all(|d| completed_dependencies.contains(d))
-    }
-
-    fn depends_on(&self, object: &SchemaQualifiedName) -> bool {
-        self.dependencies.contains(object)
+impl Display for DatabaseSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.object_counts.is_empty() {
+            return f.write_str("Found no objects");
+        }
+        write_join!(
+            f,
+            "Found ",
+            self.object_counts.iter(),
+            |w, (object_type, count)| write!(w, "{object_type}: {count}"),
+            ", ",
+            ""
+        );
+        Ok(())
     }
 }
 
-struct StatementIter {
-    statements: Vec<DdlStatement>,
-    completed_objects: HashSet<SchemaQualifiedName>,
-    failed_statements: Vec<DdlStatement>,
-    initial_failed_count: usize,
-    failed_statement_index: usize,
+/// Tables whose triggers are disabled while the statements of a migration plan are executed
+#[derive(Debug, Default)]
+struct DisabledTriggers<'s> {
+    /// Tables altered by the plan. Tables created or dropped by the plan are not included since
+    /// they do not exist before or after the plan's statements.
+    tables: Vec<&'s SchemaQualifiedName>,
+    /// Enabled triggers of the altered tables that are not migrated by the plan. Each trigger is
+    /// disabled by name and enabled again with the trigger's previous firing state so triggers
+    /// that were already disabled stay disabled.
+    triggers: Vec<&'s Trigger>,
 }
 
-impl StatementIter {
-    fn new(statements: &[DdlStatement]) -> Self {
-        Self {
-            statements: statements.to_vec(),
-            completed_objects: HashSet::new(),
-            failed_statements: vec![],
-            initial_failed_count: 0,
-            failed_statement_index: 0,
+impl<'s> DisabledTriggers<'s> {
+    /// Find the tables altered by the migration `steps` along with the existing `triggers` of
+    /// those tables to disable. A table is altered if the table itself or any object owned by the
+    /// table is migrated without the table being created or dropped. Triggers that are disabled
+    /// or migrated by the plan are never disabled.
+    fn from_steps(steps: &'s [MigrationStep], triggers: &'s [Trigger]) -> Self {
+        let mut tables: Vec<&SchemaQualifiedName> = vec![];
+        for table_step in steps.iter().filter_map(|step| step.table_step.as_ref()) {
+            let is_created_or_dropped = steps.iter().any(|step| {
+                step.object_type == "TABLE"
+                    && step.object == table_step.table
+                    && matches!(step.kind, StepKind::Create | StepKind::Drop)
+            });
+            if !is_created_or_dropped && !tables.contains(&&table_step.table) {
+                tables.push(&table_step.table);
+            }
         }
+        let triggers = triggers
+            .iter()
+            .filter(|trigger| {
+                trigger.enabled != TriggerEnabled::Disabled
+                    && tables.contains(&&trigger.owner_object_name)
+                    && !steps
+                        .iter()
+                        .any(|step| step.object == trigger.schema_qualified_name)
+            })
+            .collect();
+        Self { tables, triggers }
     }
 
-    fn add_back_failed_statement(&mut self, statement: DdlStatement) {
-        self.completed_objects.remove(&statement.object);
-        self.failed_statements.push(statement);
+    /// Write the statements that disable triggers before the plan's migration statements along
+    /// with a comment noting the safety implications
+    fn write_disable<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        if self.tables.is_empty() {
+            return Ok(());
+        }
+        w.write_str(
+            "-- Triggers are disabled for the tables altered by this migration and the internal \
+             triggers\n-- that enforce foreign keys do not fire while session_replication_role \
+             is replica. Rows\n-- changed while the triggers are disabled are neither checked \
+             against foreign keys nor\n-- passed to triggers. Requires superuser privileges.\n",
+        )?;
+        w.write_str("SET session_replication_role = replica;\n")?;
+        for trigger in &self.triggers {
+            writeln!(
+                w,
+                "ALTER TABLE {} DISABLE TRIGGER {};",
+                trigger.owner_object_name, trigger.name
+            )?;
+        }
+        Ok(())
     }
 
-    fn has_remaining(&self) -> bool {
-        !self.statements.is_empty() || !self.failed_statements.is_empty()
+    /// Write the statements that enable the disabled triggers again (restoring each trigger's
+    /// previous firing state) after the plan's migration statements
+    fn write_enable<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        if self.tables.is_empty() {
+            return Ok(());
+        }
+        for trigger in &self.triggers {
+            writeln!(
+                w,
+                "ALTER TABLE {} {} TRIGGER {};",
+                trigger.owner_object_name,
+                trigger.enabled.as_ref(),
+                trigger.name
+            )?;
+        }
+        w.write_str("RESET session_replication_role;\n")?;
+        Ok(())
     }
+}
 
-    fn take_remaining(&mut self) -> Vec<DdlStatement> {
-        let mut result = vec![];
-        result.append(&mut self.statements);
-        result.append(&mut self.failed_statements);
-        result
+/// Position of a statement that cannot run within a transaction block relative to the transaction
+/// of a transactional plan
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransactionSection {
+    /// Statement is executed before the transaction since statements within the transaction may
+    /// require the statement's changes
+    Before,
+    /// Statement is executed after the transaction since the statement may require changes made
+    /// within the transaction
+    After,
+}
+
+/// Find the section that the SQL `statement` must be moved to within a transactional plan along
+/// with the reason the statement cannot run within the transaction. [None] if the statement can
+/// run within a transaction block (or cannot be parsed).
+fn non_transactional_section(statement: &str) -> Option<(TransactionSection, &'static str)> {
+    let result = pg_query::parse(statement).ok()?;
+    let node = result
+        .protobuf
+        .stmts
+        .first()
+        .and_then(|s| s.stmt.as_ref())
+        .and_then(|n| n.node.as_ref())?;
+    match node {
+        Node::AlterEnumStmt(alter_enum) if alter_enum.old_val.is_empty() => Some((
+            TransactionSection::Before,
+            "New enum values cannot be used within the transaction that adds them (or added \
+             within a transaction block before Postgresql 12)",
+        )),
+        Node::CreatedbStmt(_) => Some((
+            TransactionSection::Before,
+            "CREATE DATABASE cannot run within a transaction block",
+        )),
+        Node::IndexStmt(index) if index.concurrent => Some((
+            TransactionSection::After,
+            "CREATE INDEX CONCURRENTLY cannot run within a transaction block",
+        )),
+        Node::DropStmt(drop_statement) if drop_statement.concurrent => Some((
+            TransactionSection::After,
+            "DROP INDEX CONCURRENTLY cannot run within a transaction block",
+        )),
+        Node::ReindexStmt(reindex) if has_option(&reindex.params, "concurrently") => Some((
+            TransactionSection::After,
+            "REINDEX CONCURRENTLY cannot run within a transaction block",
+        )),
+        Node::AlterTableStmt(alter_table) if alter_table.cmds.iter().any(is_concurrent_detach) => {
+            Some((
+                TransactionSection::After,
+                "DETACH PARTITION CONCURRENTLY cannot run within a transaction block",
+            ))
+        },
+        Node::VacuumStmt(vacuum) if vacuum.is_vacuumcmd => Some((
+            TransactionSection::After,
+            "VACUUM cannot run within a transaction block",
+        )),
+        Node::AlterSystemStmt(_) => Some((
+            TransactionSection::After,
+            "ALTER SYSTEM cannot run within a transaction block",
+        )),
+        Node::DropdbStmt(_) => Some((
+            TransactionSection::After,
+            "DROP DATABASE cannot run within a transaction block",
+        )),
+        _ => None,
     }
 }
 
-impl Iterator for StatementIter {
-    type Item = DdlStatement;
+/// True if the option `name` is found within the utility statement `options`
+fn has_option(options: &[pg_query::protobuf::Node], name: &str) -> bool {
+    options
+        .iter()
+        .any(|option| matches!(&option.node, Some(Node::DefElem(d)) if d.defname == name))
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.statements.is_empty() && self.failed_statements.is_empty() {
-            return None;
-        }
+/// True if the `ALTER TABLE` `command` detaches a partition concurrently
+fn is_concurrent_detach(command: &pg_query::protobuf::Node) -> bool {
+    let Some(Node::AlterTableCmd(command)) = &command.node else {
+        return false;
+    };
+    matches!(
+        command.def.as_deref().and_then(|d| d.node.as_ref()),
+        Some(Node::PartitionCmd(partition)) if partition.concurrent
+    )
+}
 
-        if !self.statements.is_empty() {
-            if let Some(index) = find_index(&self.statements, |s| {
-                s.has_dependencies_met(&self.completed_objects)
-            }) {
-                let statement = self.statements.remove(index);
-                self.completed_objects.insert(statement.object.clone());
-                return Some(statement);
+/// Statements of a transactional migration plan that cannot run within a transaction block. The
+/// statements are removed from their steps and executed before or after the plan's transaction.
+#[derive(Debug, Default)]
+struct TransactionSections {
+    /// Statements executed before the transaction along with the reason each statement was moved
+    before: Vec<(String, &'static str)>,
+    /// Statements executed after the transaction along with the reason each statement was moved
+    after: Vec<(String, &'static str)>,
+}
+
+impl TransactionSections {
+    /// Move every statement of the `steps` that cannot run within a transaction block into the
+    /// section required by the statement (see [non_transactional_section]). The SQL of steps
+    /// without such statements is left untouched.
+    fn extract(steps: &mut [MigrationStep]) -> Self {
+        let mut sections = Self::default();
+        for step in steps.iter_mut() {
+            let Ok(statements) = pg_query::split_with_parser(&step.sql) else {
+                continue;
+            };
+            let classified: Vec<(&str, Option<(TransactionSection, &'static str)>)> = statements
+                .iter()
+                .map(|statement| (statement.trim(), non_transactional_section(statement)))
+                .filter(|(statement, _)| !statement.is_empty())
+                .collect();
+            if classified.iter().all(|(_, section)| section.is_none()) {
+                continue;
             }
-            if let Some(index) = find_index(&self.statements, |s| {
-                self.statements
-                    .iter()
-                    .all(|other| !s.depends_on(&other.object))
-            }) {
-                let statement = self.statements.remove(index);
-                self.completed_objects.insert(statement.object.clone());
-                return Some(statement);
+            let mut sql = String::new();
+            for (statement, section) in classified {
+                let statement = format!("{statement};\n");
+                match section {
+                    Some((TransactionSection::Before, reason)) => {
+                        sections.before.push((statement, reason))
+                    },
+                    Some((TransactionSection::After, reason)) => {
+                        sections.after.push((statement, reason))
+                    },
+                    None => sql.push_str(&statement),
+                }
             }
-            return Some(self.statements.remove(0));
+            step.sql = sql;
         }
+        sections
+    }
 
-        if self.initial_failed_count == 0 {
-            self.initial_failed_count = self.failed_statements.len();
-            return Some(self.failed_statements.remove(self.failed_statement_index));
-        }
+    /// Write the statements executed before the transaction, if any, with a comment noting why
+    /// each statement is outside the transaction
+    fn write_before<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        Self::write_section(w, "before", &self.before)
+    }
 
-        self.failed_statement_index += 1;
-        self.failed_statement_index = self
-            .failed_statement_index
-            .clamp(0, self.failed_statements.len() - 1);
-        if self.failed_statement_index == 0 {
-            if self.initial_failed_count == self.failed_statements.len() {
-                return None;
-            }
-            self.initial_failed_count = self.failed_statements.len();
+    /// Write the statements executed after the transaction, if any, with a comment noting why
+    /// each statement is outside the transaction
+    fn write_after<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        Self::write_section(w, "after", &self.after)
+    }
+
+    fn write_section<W: Write>(
+        w: &mut W,
+        position: &str,
+        statements: &[(String, &'static str)],
+    ) -> Result<(), PgDiffError> {
+        if statements.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            w,
+            "-- Statements that cannot run within the transaction are executed {position} the \
+             transaction"
+        )?;
+        for (statement, reason) in statements {
+            writeln!(w, "-- {reason}")?;
+            w.write_str(statement)?;
         }
-        Some(self.failed_statements.remove(self.failed_statement_index))
+        Ok(())
     }
 }
 
+/// Schema of the target database that was renamed to a schema of the source control database
 #[derive(Debug)]
-pub struct SourceControlDatabase {
-    temp_db_name: String,
-    statements: Vec<DdlStatement>,
+struct SchemaRename<'d> {
+    /// Schema within the target database
+    old: &'d Schema,
+    /// Schema within the source control database
+    new: &'d Schema,
 }
 
-impl SourceControlDatabase {
-    fn new() -> Self {
-        Self {
-            temp_db_name: format!(
-                "pg_diff_rs_{}",
-                Uuid::new_v4().to_string().replace("-", "_")
+impl<'d> SchemaRename<'d> {
+    /// Find the schemas of the `old` database renamed to a schema of the `new` database. A schema
+    /// that only exists in the `old` database is renamed when a schema that only exists in the
+    /// `new` database contains the same objects once the old schema name is replaced (see
+    /// [schema_contents]). Schemas that match more than one other schema are never renamed since
+    /// the rename is ambiguous.
+    ///
+    /// ## Errors
+    /// If the create statements of a schema's objects cannot be written
+    fn detect(old: &'d Database, new: &'d Database) -> Result<Vec<Self>, PgDiffError> {
+        let dropped: Vec<&Schema> = old
+            .schemas
+            .iter()
+            .filter(|schema| !new.schemas.iter().any(|s| s.name == schema.name))
+            .collect();
+        let created: Vec<&Schema> = new
+            .schemas
+            .iter()
+            .filter(|schema| !old.schemas.iter().any(|s| s.name == schema.name))
+            .collect();
+        let mut candidates = vec![];
+        for new_schema in &created {
+            let new_contents = schema_contents(new, &new_schema.name.schema_name, None)?;
+            for old_schema in &dropped {
+                let old_contents = schema_contents(
+                    old,
+                    &old_schema.name.schema_name,
+                    Some(&new_schema.name.schema_name),
+                )?;
+                if old_contents == new_contents {
+                    candidates.push(Self {
+                        old: old_schema,
+                        new: new_schema,
+                    });
+                }
+            }
+        }
+        let renames = candidates
+            .iter()
+            .filter(|rename| {
+                candidates
+                    .iter()
+                    .filter(|r| r.old.name == rename.old.name || r.new.name == rename.new.name)
+                    .count()
+                    == 1
+            })
+            .map(|rename| Self {
+                old: rename.old,
+                new: rename.new,
+            })
+            .collect();
+        Ok(renames)
+    }
+
+    /// True if the compare `result` is replaced by this rename (i.e. drops of the old schema and
+    /// its objects or creates of the new schema and its objects)
+    fn replaces(&self, result: &DbCompareResult) -> bool {
+        match result {
+            DbCompareResult::Create(object) => {
+                object.name().schema_name == self.new.name.schema_name
+            },
+            DbCompareResult::Drop(object) => object.name().schema_name == self.old.name.schema_name,
+            DbCompareResult::DropSchemaCascade(schema) => schema.name == self.old.name,
+            DbCompareResult::Alter { .. } => false,
+        }
+    }
+
+    /// Migration step that renames the old schema and sets the owner of the new schema if the
+    /// owner changed
+    fn step(&self) -> Result<MigrationStep, PgDiffError> {
+        let mut sql = String::new();
+        writeln!(
+            sql,
+            "ALTER SCHEMA {} RENAME TO {};",
+            self.old.name, self.new.name
+        )?;
+        if self.old.owner != self.new.owner {
+            writeln!(
+                sql,
+                "ALTER SCHEMA {} OWNER TO {};",
+                self.new.name, self.new.owner
+            )?;
+        }
+        Ok(MigrationStep {
+            kind: StepKind::Alter,
+            object: self.new.name.clone(),
+            object_type: self.new.object_type_name().to_string(),
+            sql,
+            is_destructive: false,
+            table_step: None,
+            dependencies: vec![],
+        })
+    }
+}
+
+/// Sorted create statements of every object within the `schema_name` of the `database`
+/// (excluding the schema itself). If `renamed_to` is provided, qualified references to the
+/// schema within the statements are replaced by the new schema name so the contents can be
+/// compared to the contents of the renamed schema.
+///
+/// ## Errors
+/// If the create statements of an object cannot be written
+fn schema_contents(
+    database: &Database,
+    schema_name: &str,
+    renamed_to: Option<&str>,
+) -> Result<Vec<String>, PgDiffError> {
+    let reference_regex = Regex::new(&format!(
+        r#"(^|[^\w"$.]){}\."#,
+        lazy_regex::regex::escape(schema_name)
+    ))
+    .map_err(|error| PgDiffError::General(error.to_string()))?;
+    let mut contents = vec![];
+    for object in database.objects() {
+        if matches!(object, SqlObjectEnum::Schema(_)) || object.name().schema_name != schema_name {
+            continue;
+        }
+        let mut statements = String::new();
+        object.create_statements(&mut statements)?;
+        if let Some(new_name) = renamed_to {
+            statements = reference_regex
+                .replace_all(&statements, |captures: &Captures| {
+                    format!("{}{new_name}.", &captures[1])
+                })
+                .into_owned();
+        }
+        contents.push(statements);
+    }
+    contents.sort();
+    Ok(contents)
+}
+
+/// Tables and indexes touched by a migration plan that require maintenance once the migration has
+/// completed. Only objects that contribute statements to the plan are included.
+#[derive(Debug, Default)]
+struct PostMigrationMaintenance<'d> {
+    /// Tables created or altered (including their constraints and indexes) by the plan
+    tables: Vec<&'d SchemaQualifiedName>,
+    /// Indexes whose storage parameters were altered in place so the index must be rebuilt for
+    /// the parameters to apply to the existing index
+    indexes: Vec<&'d SchemaQualifiedName>,
+}
+
+impl<'d> PostMigrationMaintenance<'d> {
+    /// Find the table touched by the compare `result` and the index that requires a rebuild
+    /// because of the `result`, if any
+    fn targets(
+        result: &DbCompareResult<'d>,
+    ) -> (
+        Option<&'d SchemaQualifiedName>,
+        Option<&'d SchemaQualifiedName>,
+    ) {
+        match *result {
+            DbCompareResult::Create(SqlObjectEnum::Table(table))
+            | DbCompareResult::Alter {
+                new: SqlObjectEnum::Table(table),
+                ..
+            } => (Some(&table.name), None),
+            DbCompareResult::Create(SqlObjectEnum::Constraint(constraint))
+            | DbCompareResult::Alter {
+                new: SqlObjectEnum::Constraint(constraint),
+                ..
+            } => (Some(&constraint.owner_table_name), None),
+            // Extended statistics are only built when the table is analyzed
+            DbCompareResult::Create(SqlObjectEnum::Statistics(statistics))
+            | DbCompareResult::Alter {
+                new: SqlObjectEnum::Statistics(statistics),
+                ..
+            } => (Some(&statistics.owner_table_name), None),
+            DbCompareResult::Create(SqlObjectEnum::Index(index)) => {
+                (Some(&index.owner_table_name), None)
+            },
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Index(old),
+                new: SqlObjectEnum::Index(new),
+            } => (
+                Some(&new.owner_table_name),
+                (!old.requires_recreation(new)).then_some(&new.schema_qualified_name),
             ),
-            statements: vec![],
+            _ => (None, None),
+        }
+    }
+
+    /// Record the `table` and `index` returned from [PostMigrationMaintenance::targets]
+    fn add(
+        &mut self,
+        (table, index): (
+            Option<&'d SchemaQualifiedName>,
+            Option<&'d SchemaQualifiedName>,
+        ),
+    ) {
+        if let Some(table) = table.filter(|t| !self.tables.contains(t)) {
+            self.tables.push(table);
+        }
+        if let Some(index) = index.filter(|i| !self.indexes.contains(i)) {
+            self.indexes.push(index);
+        }
+    }
+
+    /// Write the maintenance section. Nothing is written if no maintenance is required.
+    ///
+    /// `REINDEX ... CONCURRENTLY` cannot run within a transaction block so rebuilds are only
+    /// noted as comments to be run separately.
+    fn write<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        if self.tables.is_empty() && self.indexes.is_empty() {
+            return Ok(());
+        }
+        w.write_str("\n-- Post-migration maintenance (run after the migration has completed)\n")?;
+        for table in &self.tables {
+            writeln!(w, "ANALYZE {table};")?;
+        }
+        for index in &self.indexes {
+            writeln!(w, "-- REINDEX INDEX CONCURRENTLY {index};")?;
         }
+        Ok(())
     }
+}
+
+/// Location of the source control SQL that describes the desired state of the target database
+#[derive(Debug, Clone, Copy)]
+pub enum SourceFiles<'p> {
+    /// Directory of SQL files (see [SourceControlDatabase::from_directory])
+    Directory(&'p Path),
+    /// Single file produced by `pg_dump --schema-only` (see
+    /// [SourceControlDatabase::from_dump_file])
+    DumpFile(&'p Path),
+}
+
+/// Main object of the application that contains metadata about the targeted database and the source
+/// control SQL files provided.
+pub struct DatabaseMigration {
+    pool: PgPool,
+    database: Database,
+    source_control_database: SourceControlDatabase,
+    /// Desired state of the target database as scraped from the temp database. Populated once a
+    /// migration has been planned so the plan can be verified (see
+    /// [DatabaseMigration::verify_plan]).
+    source_control_temp_database: Option<Database>,
+    /// Options used when creating the temp databases
+    temp_database_options: TempDatabaseOptions,
+    /// Temp databases created by this migration. Only these databases are dropped when the
+    /// migration is dropped.
+    created_databases: Vec<String>,
+}
 
-    pub async fn from_directory<P>(files_path: P) -> Result<Self, PgDiffError>
+impl DatabaseMigration {
+    /// Create a new [DatabaseMigration] using the connection `pool` provided to scrape metadata
+    /// from the target database and the `source_control_directory` to collect source control SQL
+    /// files for generating the desired new state of the target database. Each phase is recorded
+    /// within the supplied `timings`. When `strict` is true, unreadable source files fail the
+    /// analysis rather than being skipped. Temp databases are created using the
+    /// `temp_database_options`.
+    ///
+    /// ## Errors
+    /// See [DatabaseMigration::from_source]
+    pub async fn new<P>(
+        pool: PgPool,
+        source_control_directory: P,
+        strict: bool,
+        temp_database_options: TempDatabaseOptions,
+        timings: &mut Timings,
+    ) -> Result<Self, PgDiffError>
     where
         P: AsRef<Path>,
     {
-        println!("Analyzing code within source control directory");
-        let mut builder = SourceControlDatabase::new();
-        let mut entries = WalkDir::new(files_path).map(|entry| entry.map(|e| e.path()));
-        while let Some(result) = entries.next().await {
-            let path = result?;
-            if path.is_dir() {
-                continue;
+        Self::from_source(
+            pool,
+            SourceFiles::Directory(source_control_directory.as_ref()),
+            strict,
+            temp_database_options,
+            timings,
+        )
+        .await
+    }
+
+    /// Create a new [DatabaseMigration] the same as [DatabaseMigration::new] but collecting the
+    /// source control SQL from the specified `source`. `strict` only applies to directories since
+    /// a dump file is always analyzed strictly.
+    ///
+    /// ## Errors
+    /// if database scraping fails (see [Database::from_connection]), source control file
+    /// analyzing fails (see [SourceControlDatabase::from_directory] and
+    /// [SourceControlDatabase::from_dump_file]) or a temp database name supplied already exists.
+    pub async fn from_source(
+        pool: PgPool,
+        source: SourceFiles<'_>,
+        strict: bool,
+        temp_database_options: TempDatabaseOptions,
+        timings: &mut Timings,
+    ) -> Result<Self, PgDiffError> {
+        let database = timings
+            .time("Target database scrape", Database::from_connection(&pool))
+            .await?;
+        let mut source_control_database = match source {
+            SourceFiles::Directory(path) => {
+                timings
+                    .time(
+                        "Source file analysis",
+                        SourceControlDatabase::from_directory(path, strict),
+                    )
+                    .await?
+            },
+            SourceFiles::DumpFile(path) => {
+                timings
+                    .time(
+                        "Source file analysis",
+                        SourceControlDatabase::from_dump_file(path),
+                    )
+                    .await?
+            },
+        };
+        if let Some(staging) = &temp_database_options.staging {
+            let staging_database = timings
+                .time(
+                    "Staging database check",
+                    Database::from_connection(&staging.pool),
+                )
+                .await?;
+            let objects = staging_database.object_descriptions();
+            if !objects.is_empty() {
+                return Err(PgDiffError::StagingDatabaseNotEmpty {
+                    name: staging_database.settings.name,
+                    objects,
+                });
             }
-            let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else {
-                if is_verbose() {
-                    println!("Skipping {:?}", path);
-                }
-                continue;
-            };
-            if !file_name.ends_with(".pgsql") && !file_name.ends_with(".sql") {
-                if is_verbose() {
-                    println!("Skipping {:?}", file_name);
+            source_control_database.temp_db_name = staging_database.settings.name;
+        } else if let Some(temp_db_name) = &temp_database_options.name {
+            // Reject existing databases before the migration is created rather than failing
+            // once source control files are applied
+            let verify_db_name = verify_db_name_for(temp_db_name);
+            for db_name in [temp_db_name, &verify_db_name] {
+                if database_exists(&pool, db_name).await? {
+                    return Err(PgDiffError::TempDatabaseExists {
+                        name: db_name.clone(),
+                    });
                 }
-                continue;
             }
-            builder.append_source_file(path).await?;
+            source_control_database.temp_db_name = temp_db_name.clone();
         }
-        println!("Done!");
-        Ok(builder)
+        source_control_database.max_statement_length = temp_database_options.max_statement_length;
+        Ok(Self {
+            pool,
+            database,
+            source_control_database,
+            source_control_temp_database: None,
+            temp_database_options,
+            created_databases: vec![],
+        })
     }
 
-    /// Read source file and find all queries, the main DDL object of each query and the
-    /// dependencies for each DDL query.
+    /// Plan the steps required to migrate the target database to the state described in the source
+    /// control files.
     ///
-    /// The steps are as follows:
-    /// 1. Read the entire source control file into a string buffer.
-    /// 2. Split the source file statements into 1 or more queries.
-    /// 3. Parse each query extracting:
-    ///     * Root node of the query for further analyzing
-    ///     * Main object created/altered by the query (found from the root node)
-    ///     * All dependencies of the query (found by expanding [NodeIter])
+    /// This applies the source control statements to a temp database, scrapes that temp database
+    /// for metadata and compares the temp database to the current state of the target database to
+    /// find the steps required for migration. Each phase is recorded within the supplied
+    /// `timings`.
+    ///
+    /// The statements generated can be adjusted using the migration `options`.
     ///
     /// ## Errors
-    /// If an IO error occurs trying to read the file path or an error occurs attempting to read the
-    /// AST returned from query parsing. Querying parsing can fail for various reasons, but it
-    /// should only fail if the SQL code is not syntactically valid.
-    async fn append_source_file<P>(&mut self, path: P) -> Result<(), PgDiffError>
+    /// See [SourceControlDatabase::apply_to_temp_database]
+    /// See [SourceControlDatabase::scrape_temp_database]
+    pub async fn plan_migration(
+        &mut self,
+        timings: &mut Timings,
+        options: MigrationOptions,
+    ) -> Result<MigrationPlan, PgDiffError> {
+        let source_control_temp_database = self.build_source_control_database(timings).await?;
+        options.warn_unsupported_safe_not_null(&self.database);
+        let plan = timings
+            .time("Comparison", async {
+                self.database
+                    .plan_to_other_database(&source_control_temp_database, options)
+            })
+            .await?;
+        self.source_control_temp_database = Some(source_control_temp_database);
+        Ok(plan)
+    }
+
+    /// Plan the steps required to migrate the target database to the state described in the source
+    /// control files along with a best-effort revert of those steps.
+    ///
+    /// The revert is found by comparing the source control state to the current state of the
+    /// target database (i.e. the same comparison as [DatabaseMigration::plan_migration] with the
+    /// old and new databases swapped). The revert only undoes the statements included within the
+    /// migration (e.g. objects whose drops were skipped are not created again, see
+    /// [DropMode::revert]). Data removed by the migration can never be restored by the revert.
+    ///
+    /// ## Errors
+    /// See [DatabaseMigration::plan_migration]
+    pub async fn plan_migration_with_revert(
+        &mut self,
+        timings: &mut Timings,
+        options: MigrationOptions,
+    ) -> Result<MigrationPlan, PgDiffError> {
+        let mut source_control_temp_database = self.build_source_control_database(timings).await?;
+        // Database level statements of the revert must target the actual database rather than
+        // the temp database
+        source_control_temp_database.settings.name = self.database.settings.name.clone();
+        options.warn_unsupported_safe_not_null(&self.database);
+        let plan = timings
+            .time("Comparison", async {
+                self.database
+                    .plan_to_other_database(&source_control_temp_database, options.clone())
+            })
+            .await?;
+        // The revert drops the schemas created by the plan
+        let revert_options = MigrationOptions {
+            drop_mode: options.drop_mode.revert(),
+            allow_destructive: true,
+            ..options
+        };
+        let revert = timings
+            .time("Revert comparison", async {
+                source_control_temp_database
+                    .compare_to_other_database(&self.database, revert_options)
+            })
+            .await?;
+        self.source_control_temp_database = Some(source_control_temp_database);
+        Ok(MigrationPlan { revert, ..plan })
+    }
+
+    /// Verify that the planned migration `plan` converges to the state described in the source
+    /// control files. Must be called after planning the migration (see
+    /// [DatabaseMigration::plan_migration]).
+    ///
+    /// A second temp database is created and the target database's scripted state (see
+    /// [Database::script_out]) is applied to it the same way source control files are applied.
+    /// The `plan` is then executed against that database, which is scraped and compared to the
+    /// desired state using the same `options` as the plan. Each phase is recorded within the
+    /// supplied `timings`.
+    ///
+    /// Database settings are retargeted to the verification database and role statements are
+    /// skipped since roles exist at the cluster level (see [verification_statement]). Role changes
+    /// are therefore never verified.
+    ///
+    /// ## Errors
+    /// - No migration has been planned or a staging database is used
+    /// - The verification database cannot be created or the target database's state cannot be
+    ///   applied to it (see [SourceControlDatabase::apply_to_temp_database])
+    /// - A statement of the `plan` fails ([PgDiffError::PlanVerificationFailed])
+    /// - Differences remain after applying the `plan` ([PgDiffError::PlanDoesNotConverge])
+    pub async fn verify_plan(
+        &mut self,
+        timings: &mut Timings,
+        plan: &str,
+        options: MigrationOptions,
+    ) -> Result<(), PgDiffError> {
+        if self.temp_database_options.staging.is_some() {
+            return Err("Plans cannot be verified when a staging database is used".into());
+        }
+        let Some(desired_database) = &self.source_control_temp_database else {
+            return Err("A migration must be planned before the plan can be verified".into());
+        };
+        let verify_db_name = self.verify_db_name();
+        timings
+            .time(
+                "Verification database creation",
+                create_temp_database(&self.pool, &verify_db_name, &self.temp_database_options),
+            )
+            .await?;
+        self.created_databases.push(verify_db_name.clone());
+        let verify_db_pool = connect_to_database(&self.pool, &verify_db_name).await?;
+        let result = async {
+            timings
+                .time(
+                    "Verification restore",
+                    self.restore_target_database(&verify_db_pool, &verify_db_name),
+                )
+                .await?;
+            timings
+                .time(
+                    "Verification plan application",
+                    apply_plan(&verify_db_pool, plan, &verify_db_name),
+                )
+                .await?;
+            let migrated_database = timings
+                .time(
+                    "Verification scrape",
+                    Database::from_connection(&verify_db_pool),
+                )
+                .await?;
+            Ok::<_, PgDiffError>(migrated_database)
+        }
+        .await;
+        // Close before the verification database is dropped (see `connect_to_database`)
+        verify_db_pool.close().await;
+        let mut migrated_database = result?;
+        migrated_database
+            .settings
+            .name
+            .clone_from(&desired_database.settings.name);
+        migrated_database.roles.clone_from(&desired_database.roles);
+        let (steps, _) = timings
+            .time("Verification comparison", async {
+                migrated_database.migration_steps(desired_database, &options)
+            })
+            .await?;
+        let mut residual_objects: Vec<String> = vec![];
+        for step in steps.iter().filter(|s| s.kind != StepKind::SkippedDrop) {
+            let object = format!("{} {}", step.object_type, step.object);
+            if !residual_objects.contains(&object) {
+                residual_objects.push(object);
+            }
+        }
+        if !residual_objects.is_empty() {
+            return Err(PgDiffError::PlanDoesNotConverge { residual_objects });
+        }
+        Ok(())
+    }
+
+    /// Name of the temp database used to verify a planned migration
+    fn verify_db_name(&self) -> String {
+        verify_db_name_for(&self.source_control_database.temp_db_name)
+    }
+
+    /// Restore the current state of the target database into the database `db_name` (connected
+    /// to by the `pool`). The target database is scripted into a temp directory and the scripted
+    /// files are applied the same way as source control files.
+    async fn restore_target_database(
+        &self,
+        pool: &PgPool,
+        db_name: &str,
+    ) -> Result<(), PgDiffError> {
+        let script_path = std::env::temp_dir().join(db_name);
+        let result = async {
+            self.database
+                .script_out(&script_path, ScriptOptions::default())
+                .await?;
+            let mut scripted_database =
+                SourceControlDatabase::from_directory(&script_path, false).await?;
+            scripted_database.temp_db_name = db_name.to_string();
+            scripted_database.apply_to_temp_database(pool).await
+        }
+        .await;
+        if tokio::fs::try_exists(&script_path).await? {
+            tokio::fs::remove_dir_all(&script_path).await?;
+        }
+        result
+    }
+
+    /// Create the temp database (or use the staging database if supplied), apply the source
+    /// control statements to the temp database and scrape the temp database to get the desired
+    /// state of the target database. The staging database is cleaned up afterward (even if
+    /// staging fails) when requested, restoring the database settings found before staging. When
+    /// staging fails, a cleanup failure is printed and the staging error is returned.
+    async fn build_source_control_database(
+        &mut self,
+        timings: &mut Timings,
+    ) -> Result<Database, PgDiffError> {
+        let Some(staging) = self.temp_database_options.staging.clone() else {
+            return build_temp_database(
+                &self.pool,
+                &mut self.source_control_database,
+                &self.temp_database_options,
+                &mut self.created_databases,
+                timings,
+            )
+            .await;
+        };
+        if staging.cleanup == StagingCleanup::None {
+            return self
+                .source_control_database
+                .apply_and_scrape(&staging.pool, timings)
+                .await;
+        }
+        let original_settings = DatabaseSettings::from_connection(&staging.pool).await?;
+        let result = self
+            .source_control_database
+            .apply_and_scrape(&staging.pool, timings)
+            .await;
+        let cleanup_result = timings
+            .time(
+                "Staging database cleanup",
+                drop_staged_objects(&staging.pool, original_settings.settings),
+            )
+            .await;
+        match (result, cleanup_result) {
+            (Ok(database), Ok(())) => Ok(database),
+            (Ok(_), Err(cleanup_error)) => Err(cleanup_error),
+            (Err(error), Ok(())) => Err(error),
+            (Err(error), Err(cleanup_error)) => {
+                eprintln!("Error cleaning up staging database: {cleanup_error}");
+                Err(error)
+            },
+        }
+    }
+}
+
+/// Migration between two directories of source control files (e.g. the scripted state of a
+/// production database and the current source files). Neither directory needs to correspond to
+/// the connected database since the connection is only used to create the temp databases that
+/// each directory is applied to.
+pub struct FilesMigration {
+    pool: PgPool,
+    /// Source control files describing the current state
+    old_source_control_database: SourceControlDatabase,
+    /// Source control files describing the desired state
+    new_source_control_database: SourceControlDatabase,
+    /// Temp databases created by this migration. Only these databases are dropped when the
+    /// migration is dropped.
+    created_databases: Vec<String>,
+}
+
+impl FilesMigration {
+    /// Create a new [FilesMigration] from the source control files within the `old_directory`
+    /// and `new_directory`. Temp databases are created using the connection `pool`. Each phase is
+    /// recorded within the supplied `timings`. When `strict` is true, unreadable source files fail
+    /// the analysis rather than being skipped.
+    ///
+    /// ## Errors
+    /// If source control file analyzing fails (see [SourceControlDatabase::from_directory])
+    pub async fn new<P>(
+        pool: PgPool,
+        old_directory: P,
+        new_directory: P,
+        strict: bool,
+        timings: &mut Timings,
+    ) -> Result<Self, PgDiffError>
     where
         P: AsRef<Path>,
     {
-        let mut file = File::open(path.as_ref()).await?;
-        let mut str = String::new();
-        file.read_to_string(&mut str).await?;
-        let Some(file_name) = path.as_ref().file_stem().and_then(|f| f.to_str()) else {
-            return Err(PgDiffError::General(format!(
-                "Could not extract a file name from {:?}",
-                path.as_ref()
-            )));
-        };
-        let queries = pg_query::split_with_parser(&str).map_err(|error| PgDiffError::PgQuery {
-            object_name: file_name.into(),
-            error,
-        })?;
-        for query in queries {
-            let result = pg_query::parse(query).map_err(|error| PgDiffError::PgQuery {
-                object_name: file_name.into(),
-                error,
-            })?;
-            let root_node = result
-                .protobuf
-                .stmts
-                .first()
-                .and_then(|s| s.stmt.as_ref())
-                .and_then(|n| n.node.as_ref());
-            let root_node = *extract_option(
-                &path,
-                &root_node,
-                format!(
-                    "Could not get first node of statement: {:?}",
-                    result.protobuf
-                ),
-            )?;
-            let parent_object = match root_node {
-                Node::AlterTableStmt(alter_table) => {
-                    let relation = extract_option(
-                        &path,
-                        &alter_table.relation,
-                        "Could not extract a table name from from an ALTER TABLE statement".into(),
-                    )?;
-                    let constraint_names = alter_table
-                        .cmds
-                        .iter()
-                        .filter_map(|n| n.node.as_ref())
-                        .filter_map(|n| match n {
-                            Node::Constraint(constraint) => Some(constraint.conname.as_str()),
-                            _ => None,
-                        })
-                        .collect::<Vec<&str>>()
-                        .join(",");
+        let old_source_control_database = timings
+            .time(
+                "Old source file analysis",
+                SourceControlDatabase::from_directory(old_directory, strict),
+            )
+            .await?;
+        let new_source_control_database = timings
+            .time(
+                "New source file analysis",
+                SourceControlDatabase::from_directory(new_directory, strict),
+            )
+            .await?;
+        Ok(Self {
+            pool,
+            old_source_control_database,
+            new_source_control_database,
+            created_databases: vec![],
+        })
+    }
 
-                    SchemaQualifiedName::new(
-                        &relation.schemaname,
-                        &format!("{}.({})", relation.relname, constraint_names),
-                    )
-                },
-                Node::CreateSchemaStmt(create_schema) => {
-                    SchemaQualifiedName::new(&create_schema.schemaname, "")
-                },
-                Node::CompositeTypeStmt(composite_type) => {
-                    let composite = extract_option(
-                        &path,
-                        &composite_type.typevar,
-                        "Could not extract a table name from from an CREATE POLICY statement"
-                            .into(),
-                    )?;
-                    SchemaQualifiedName::new(&composite.schemaname, &composite.relname)
-                },
-                Node::CreateExtensionStmt(create_extension) => {
-                    SchemaQualifiedName::new("", &create_extension.extname)
-                },
-                Node::CreatePolicyStmt(create_policy) => {
-                    let relation = extract_option(
-                        &path,
-                        &create_policy.table,
-                        "Could not extract a table name from from an CREATE POLICY statement"
-                            .into(),
-                    )?;
-                    SchemaQualifiedName::new(
-                        &relation.schemaname,
-                        &format!("{}.{}", relation.relname, create_policy.policy_name),
-                    )
-                },
-                Node::CreateTrigStmt(create_trigger) => {
-                    let relation = extract_option(
-                        &path,
-                        &create_trigger.relation,
-                        "Could not extract a table name from from an CREATE TRIGGER statement"
-                            .into(),
-                    )?;
-                    SchemaQualifiedName::new(
-                        &relation.schemaname,
-                        &format!("{}.{}", relation.relname, create_trigger.trigname),
-                    )
-                },
-                Node::CreateSeqStmt(create_sequence) => {
-                    let sequence = extract_option(
-                        &path,
-                        &create_sequence.sequence,
-                        "Could not extract a table name from from an CREATE SEQUENCE statement"
-                            .into(),
-                    )?;
-                    SchemaQualifiedName::new(&sequence.schemaname, &sequence.relname)
-                },
-                Node::CreateFunctionStmt(create_function) => {
-                    extract_names(&create_function.funcname).ok_or(PgDiffError::FileQueryParse {
-                        path: path.as_ref().into(),
-                        message: "Could not extract function name".into(),
-                    })?
-                },
-                Node::CreateEnumStmt(create_enum) => {
-                    extract_names(&create_enum.type_name).ok_or(PgDiffError::FileQueryParse {
-                        path: path.as_ref().into(),
-                        message: "Could not extract enum type name".into(),
-                    })?
-                },
-                Node::CreateDomainStmt(create_domain) => extract_names(&create_domain.domainname)
-                    .ok_or(PgDiffError::FileQueryParse {
-                    path: path.as_ref().into(),
-                    message: "Could not extract domain type name".into(),
-                })?,
-                Node::CreateRangeStmt(create_range) => extract_names(&create_range.type_name)
-                    .ok_or(PgDiffError::FileQueryParse {
-                        path: path.as_ref().into(),
-                        message: "Could not extract range type name".into(),
-                    })?,
-                Node::CreateStmt(create_table) => {
-                    let relation = extract_option(
-                        &path,
-                        &create_table.relation,
-                        "Could not extract a table name from from an CREATE TABLE statement".into(),
-                    )?;
-                    SchemaQualifiedName::new(&relation.schemaname, &relation.relname)
-                },
-                Node::ViewStmt(create_view) => {
-                    let relation = extract_option(
-                        &path,
-                        &create_view.view,
-                        "Could not extract a view name from from an CREATE VIEW statement".into(),
-                    )?;
-                    SchemaQualifiedName::new(&relation.schemaname, &relation.relname)
-                },
-                Node::IndexStmt(create_index) => {
-                    let relation = extract_option(
-                        &path,
-                        &create_index.relation,
-                        "Could not extract a view name from from an CREATE VIEW statement".into(),
-                    )?;
-                    SchemaQualifiedName::new(&relation.schemaname, &create_index.idxname)
-                },
-                _ => {
-                    return Err(PgDiffError::FileQueryParse {
-                        path: path.as_ref().into(),
-                        message: format!(
-                            "First node of statement is not recognized: {:?}",
-                            root_node
-                        ),
-                    });
-                },
-            };
-            let statement = DdlStatement {
-                statement: query.to_string(),
-                object: parent_object,
-                dependencies: NodeIter::new(root_node).collect(),
-            };
-            self.statements.push(statement);
-        }
-
-        Ok(())
-    }
-
-    /// Apply statements collected from SQL source control files and apply them to the database
-    /// targeted by the supplied `pool`.
+    /// Plan the steps required to migrate a database from the state described in the old source
+    /// control files to the state described in the new source control files.
     ///
-    /// This creates a [StatementIter] referencing the `statements` collected. The order of
-    /// statements follows all objects whose dependencies have already been met (or no dependencies
-    /// exist). As objects are created, the collection of dependencies created will be updated and
-    /// statements that were previously not able to created, are then released for execution. If
-    /// an error occurs during query execution, the statement is put into a special queue of failed
-    /// statements that will be handled later. For failed statements, the error message is also
-    /// checked to see if the missing dependency is specified and if found, the dependency is added
-    /// to the object's list of dependencies before pushing to error statement queue.
-    ///
-    /// After iteration completes, the iterator object is checked to see if any statements remain
-    /// which would indicate some of the statements could not be executed successfully (i.e. a
-    /// circular dependency was found or the application could not derive the order of the
-    /// statements to create the database state).
-    ///
-    /// For more details of iteration, see [StatementIter].
+    /// Each directory is applied to its own temp database, both temp databases are scraped and
+    /// the scraped states are compared. Database settings are written against the database named
+    /// within the old source control files (falling back to the connected database). The
+    /// statements generated can be adjusted using the migration `options`.
     ///
     /// ## Errors
-    /// - Executing the statement query returns an error that cannot be parsed into a
-    ///     [PgDatabaseError]
-    /// - After iterating over the ordered statements, the iterator still has remaining statements.
-    ///     This would indicate that an infinite loop was detected and the application cannot
-    ///     continue
-    pub async fn apply_to_temp_database(&mut self, pool: &PgPool) -> Result<(), PgDiffError> {
-        println!("Applying source control DDL statements to temp database");
-        println!("Temp Database Name: {}", self.temp_db_name);
-        println!("Total statements: {}", self.statements.len());
+    /// See [SourceControlDatabase::apply_to_temp_database]
+    /// See [Database::from_connection]
+    pub async fn plan_migration(
+        &mut self,
+        timings: &mut Timings,
+        options: MigrationOptions,
+    ) -> Result<String, PgDiffError> {
+        let temp_database_options = TempDatabaseOptions::default();
+        let mut old_database = build_temp_database(
+            &self.pool,
+            &mut self.old_source_control_database,
+            &temp_database_options,
+            &mut self.created_databases,
+            timings,
+        )
+        .await?;
+        let mut new_database = build_temp_database(
+            &self.pool,
+            &mut self.new_source_control_database,
+            &temp_database_options,
+            &mut self.created_databases,
+            timings,
+        )
+        .await?;
+        let database_name = self.database_name();
+        old_database.settings.name.clone_from(&database_name);
+        new_database.settings.name = database_name;
+        options.warn_unsupported_safe_not_null(&old_database);
+        timings
+            .time("Comparison", async {
+                old_database.compare_to_other_database(&new_database, options)
+            })
+            .await
+    }
 
-        let mut iter = StatementIter::new(&self.statements);
-        let mut i = 0;
-        while let Some(statement) = iter.next() {
-            if let Err(error) = sqlx::query(&statement.statement).execute(pool).await {
-                let Error::Database(db_error) = &error else {
-                    return Err(error.into());
-                };
-                let Some(pg_error) = db_error.try_downcast_ref::<PgDatabaseError>() else {
-                    return Err(error.into());
-                };
-                let Some(item) = self.statements.iter_mut().find(|s| **s == statement) else {
-                    iter.add_back_failed_statement(statement);
-                    continue;
-                };
-                let message = pg_error.message();
-                if message.ends_with(" does not exist") {
-                    let name: String = message
-                        .chars()
-                        .skip_while(|c| *c == '"')
-                        .take_while(|c| *c == '"')
-                        .collect();
-                    let dependency = SchemaQualifiedName::from(name.trim_matches('"'));
-                    item.dependencies.push(dependency);
-                }
-                iter.add_back_failed_statement(item.clone());
-                continue;
-            }
-            i += 1;
-            if is_verbose() {
-                println!("Statement {}/{}\n", i, self.statements.len());
-            }
-        }
-        if iter.has_remaining() {
-            let remaining_statements = iter
-                .take_remaining()
-                .into_iter()
-                .map(|s| s.statement)
-                .collect();
-            return Err(PgDiffError::SourceControlScript {
-                remaining_statements,
-            });
-        }
-        println!("Done!");
-        Ok(())
+    /// Name of the database that the planned migration targets. This is the database named
+    /// within the first `ALTER DATABASE` statement of the old source control files or the
+    /// connected database if the old files do not set any database settings.
+    fn database_name(&self) -> String {
+        self.old_source_control_database
+            .database_settings
+            .first()
+            .map(|alter_database| alter_database.dbname.clone())
+            .or_else(|| self.pool.connect_options().get_database().map(String::from))
+            .unwrap_or_default()
     }
 }
 
-/// Extract the schema qualified name(s) from the list of `name_nodes` supplied. This assumes that
-/// each list item node is a node containing a [Node::String].
-///
-/// Returns a [SchemaQualifiedName] if a name can be extracted. Returns [None] when:
-/// - the schema name is `pg_catalog`
-/// - the name has no schema + the local name is in [BUILT_IN_NAMES] or [BUILT_IN_FUNCTIONS]
-/// - there are no nodes in the list
-///
-/// See [extract_string].
-fn extract_names(name_nodes: &[pg_query::protobuf::Node]) -> Option<SchemaQualifiedName> {
-    match name_nodes {
-        [schema_name, local_name] => {
-            let schema_name = extract_string(schema_name)?;
-            if schema_name == "pg_catalog" {
-                return None;
-            }
-            let local_name = extract_string(local_name)?;
-            Some(SchemaQualifiedName::new(schema_name, local_name))
-        },
-        [local_name] => {
-            let local_name = extract_string(local_name)?;
-            if BUILT_IN_NAMES.contains(&local_name.as_str())
-                || BUILT_IN_FUNCTIONS.contains(&local_name.as_str())
-            {
-                return None;
-            }
-            Some(SchemaQualifiedName::from(local_name))
-        },
-        _ => None,
+impl Drop for FilesMigration {
+    fn drop(&mut self) {
+        drop_temp_databases(&self.pool, std::mem::take(&mut self.created_databases));
+        let mut role_names = std::mem::take(&mut self.old_source_control_database.created_roles);
+        role_names.append(&mut self.new_source_control_database.created_roles);
+        drop_temp_roles(&self.pool, role_names);
     }
 }
 
-/// Extract the string contained within the `node`. Returns [None] if the `node` does not point to
-/// anything or the inner node is not [Node::String]. Otherwise, the inner string is returned.
-fn extract_string(node: &pg_query::protobuf::Node) -> Option<&String> {
-    match &node.node {
-        Some(pg_query::NodeEnum::String(pg_query::protobuf::String { sval, .. })) => Some(sval),
-        _ => None,
-    }
+/// Create the temp database of the `source_control_database` (using the connection `pool` and
+/// the `temp_database_options`), apply the source control statements to the temp database and
+/// scrape the temp database to get the state described by the source control files. The temp
+/// database is added to the `created_databases` once created.
+async fn build_temp_database(
+    pool: &PgPool,
+    source_control_database: &mut SourceControlDatabase,
+    temp_database_options: &TempDatabaseOptions,
+    created_databases: &mut Vec<String>,
+    timings: &mut Timings,
+) -> Result<Database, PgDiffError> {
+    timings
+        .time(
+            "Temp database creation",
+            create_temp_database(
+                pool,
+                &source_control_database.temp_db_name,
+                temp_database_options,
+            ),
+        )
+        .await?;
+    created_databases.push(source_control_database.temp_db_name.clone());
+    let temp_db_pool = connect_to_database(pool, &source_control_database.temp_db_name).await?;
+    let result = source_control_database
+        .apply_and_scrape(&temp_db_pool, timings)
+        .await;
+    // Close before the temp database is dropped (see `connect_to_database`)
+    temp_db_pool.close().await;
+    result
 }
 
-/// Extract a reference to the value within the `option` if it's [Some]. If the value is [None],
-/// return a [PgDiffError::FileQueryParse] with the `path` and `message`.
-fn extract_option<P, I>(path: P, option: &Option<I>, message: String) -> Result<&I, PgDiffError>
-where
-    P: AsRef<Path>,
-{
-    option.as_ref().ok_or(PgDiffError::FileQueryParse {
-        path: path.as_ref().into(),
-        message,
-    })
+/// Name of the temp database used to verify a planned migration when the source control
+/// statements are applied to the temp database `temp_db_name`
+fn verify_db_name_for(temp_db_name: &str) -> String {
+    format!("{temp_db_name}_verify")
 }
 
-/// Contains the LOCAL_PROVIDER database option as well as the other allowed options based upon the
-/// specific provider type.
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
-enum LocalProvider {
-    #[serde(rename = "c")]
-    Libc {
-        lc_collate: String,
-        lc_ctype: String,
-    },
-    #[serde(rename = "i")]
-    Icu {
-        icu_locale: String,
-        icu_rules: Option<String>,
-    },
+/// Drop every object found within the staging database (connected to by the `pool`) by planning
+/// the migration of the staging database to an empty copy of itself. The database settings of the
+/// staging database are reset to the `original_settings` found before staging.
+async fn drop_staged_objects(
+    pool: &PgPool,
+    original_settings: Vec<String>,
+) -> Result<(), PgDiffError> {
+    let staged_database = Database::from_connection(pool).await?;
+    let mut empty_database = staged_database.empty_copy();
+    empty_database.settings.settings = original_settings;
+    let plan = staged_database.compare_to_other_database(
+        &empty_database,
+        MigrationOptions {
+            allow_destructive: true,
+            ..Default::default()
+        },
+    )?;
+    if !plan.is_empty() {
+        sqlx::raw_sql(&plan).execute(pool).await?;
+    }
+    Ok(())
 }
 
-impl Display for LocalProvider {
-    /// Write the LOCAL_PROVIDER option as well as the other options allowed with the specific
-    /// [LocalProvider]
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            LocalProvider::Libc {
-                lc_collate,
-                lc_ctype,
-            } => {
-                write!(
-                    f,
-                    "\n    LOCALE_PROVIDER 'libc'\n    LC_COLLATE '{}'\n    LC_CTYPE '{}'",
-                    lc_collate, lc_ctype
-                )
-            },
-            LocalProvider::Icu {
-                icu_locale,
-                icu_rules,
-            } => {
-                write!(
-                    f,
-                    "\n    LOCALE_PROVIDER 'icu'\n    ICU_LOCALE '{}'",
-                    icu_locale
-                )?;
-                if let Some(icu_rules) = icu_rules {
-                    write!(f, "\n    ICU_RULES '{}'", icu_rules)?;
-                }
-                Ok(())
-            },
-        }
+/// Create the temp database `db_name` using the same options as the database of the `pool` along
+/// with the template and tablespace of the `temp_database_options`
+async fn create_temp_database(
+    pool: &PgPool,
+    db_name: &str,
+    temp_database_options: &TempDatabaseOptions,
+) -> Result<(), PgDiffError> {
+    let query = include_str!("./../../queries/check_create_db_role.pgsql");
+    let can_create_database: bool = query_scalar(query).fetch_one(pool).await?;
+    if !can_create_database {
+        return Err(PgDiffError::InsufficientPrivilege {
+            action: "create a temp database for migration staging".into(),
+            required_role: "CREATEDB".into(),
+        });
+    }
+
+    let mut db_options = DatabaseOptions::from_connection(pool).await?;
+    db_options
+        .template
+        .clone_from(&temp_database_options.template);
+    db_options
+        .tablespace
+        .clone_from(&temp_database_options.tablespace);
+    let create_database = format!("CREATE DATABASE {}{db_options};", quote_identifier(db_name));
+    sqlx::query(&create_database).execute(pool).await?;
+    if is_verbose() {
+        println!("Created temp database: {db_name}");
     }
+    Ok(())
 }
 
-/// Database options that can be supplied when executing a `CREATE DATABASE` command
-#[derive(Debug, sqlx::FromRow)]
-struct DatabaseOptions {
-    encoding: String,
-    locale: Option<String>,
-    #[sqlx(json)]
-    locale_provider: LocalProvider,
-    collation_version: String,
+/// Quote the `identifier` so that it is used exactly as supplied rather than being folded to
+/// lowercase
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
 }
 
-impl Display for DatabaseOptions {
-    /// Write the `CREATE DATABASE` options specified by this struct
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            " WITH\n    ENCODING '{}'\n    COLLATION_VERSION '{}'",
-            self.encoding, self.collation_version
-        )?;
-        if let Some(locale) = &self.locale {
-            write!(f, "\n    LOCALE '{}'", locale)?;
+/// Connect to the database `db_name` using the same connection and pool options as the `pool`.
+///
+/// The returned pool should be closed before the database is dropped so the drop does not have to
+/// terminate the pool's sessions.
+async fn connect_to_database(pool: &PgPool, db_name: &str) -> Result<PgPool, PgDiffError> {
+    let db_options = (*pool.connect_options()).clone().database(db_name);
+    let db_pool = pool.options().clone().connect_with(db_options).await?;
+    Ok(db_pool)
+}
+
+/// Analyze the source file `path` the same way as source control files are analyzed when
+/// planning a migration (see [SourceControlDatabase::append_source_file]) and describe the object
+/// and dependencies found for each statement. No database connection is required so this is
+/// used for debugging the analysis of a single file.
+///
+/// ## Errors
+/// See [SourceControlDatabase::append_source_file]. The file is never skipped so files that would
+/// be skipped are errors.
+pub async fn dump_source_file<P>(path: P) -> Result<String, PgDiffError>
+where
+    P: AsRef<Path>,
+{
+    let mut builder = SourceControlDatabase::new();
+    builder.append_source_file(path, true).await?;
+    let mut result = String::new();
+    builder.describe_analysis(&mut result)?;
+    Ok(result)
+}
+
+/// Drop each temp database of the `db_names` (if it exists) using the `pool`. Errors are printed
+/// rather than returned since this is only called when a migration is dropped.
+fn drop_temp_databases(pool: &PgPool, db_names: Vec<String>) {
+    let pool = pool.clone();
+    let fut = async move {
+        for db_name in db_names {
+            if let Err(error) = sqlx::query(&format!(
+                "DROP DATABASE IF EXISTS {} WITH (FORCE);",
+                quote_identifier(&db_name)
+            ))
+            .execute(&pool)
+            .await
+            {
+                println!("Error dropping temp database: {error}");
+            }
         }
-        write!(f, "{}", self.locale_provider)
-    }
+    };
+    // It's okay to block on this future here since dropping a migration signifies the end of the
+    // application's lifetime
+    futures::executor::block_on(fut);
 }
 
-impl DatabaseOptions {
-    /// Capture the pool's current database's options
-    async fn from_connection(pool: &PgPool) -> Result<Self, PgDiffError> {
-        let query = include_str!("./../../queries/database.pgsql");
-        let db_options = query_as(query).fetch_one(pool).await?;
-        Ok(db_options)
-    }
+/// Drop each temp role of the `role_names` (if it exists) using the `pool`. Roles cannot be
+/// dropped while objects of any database depend on them so this must be called after the temp
+/// databases are dropped. Errors are printed rather than returned since this is only called when a
+/// migration is dropped.
+fn drop_temp_roles(pool: &PgPool, role_names: Vec<String>) {
+    let pool = pool.clone();
+    let fut = async move {
+        for role_name in role_names {
+            if let Err(error) = sqlx::query(&format!("DROP ROLE IF EXISTS {role_name};"))
+                .execute(&pool)
+                .await
+            {
+                println!("Error dropping temp role: {error}");
+            }
+        }
+    };
+    // It's okay to block on this future here since dropping a migration signifies the end of the
+    // application's lifetime
+    futures::executor::block_on(fut);
 }
 
-/// Struct representing all database objects that can be found within a target database. This
-/// ignores objects that are directly owned by extensions and does not include the public schema
-/// which is already present within a database.
-#[derive(Debug)]
-pub struct Database {
-    pub(crate) schemas: Vec<Schema>,
-    pub(crate) udts: Vec<Udt>,
-    pub(crate) tables: Vec<Table>,
-    pub(crate) policies: Vec<Policy>,
-    pub(crate) constraints: Vec<Constraint>,
-    pub(crate) indexes: Vec<Index>,
-    pub(crate) triggers: Vec<Trigger>,
-    pub(crate) sequences: Vec<Sequence>,
-    pub(crate) functions: Vec<Function>,
-    pub(crate) views: Vec<View>,
-    pub(crate) extensions: Vec<Extension>,
+/// True if the database `db_name` exists within the cluster of the `pool`
+async fn database_exists(pool: &PgPool, db_name: &str) -> Result<bool, PgDiffError> {
+    let query = include_str!("./../../queries/check_database_exists.pgsql");
+    let exists = query_scalar(query).bind(db_name).fetch_one(pool).await?;
+    Ok(exists)
 }
 
-impl Database {
-    /// Create a new [Database] from the database targeted by the supplied `pool`.
-    ///
-    /// Collect all available metadata about the database form the `pg_catalog` tables/views as well
-    /// as attempting to analyze non-compiled functions (i.e. dynamic sql and pl/pgsql functions)
-    /// to figured out dependencies. Function analysis is not guaranteed to work so errors are
-    /// written to STDOUT if the verbose flag is active.
-    ///
-    /// ## Errors
-    /// - Errors from the SQL queries executed to fetch metadata
-    /// - SQL query parsing if a function is a dynamic SQL query but the query is invalid
-    /// - A function is not SQL or pl/pgsql (other languages are not supported)
-    pub async fn from_connection(pool: &PgPool) -> Result<Self, PgDiffError> {
-        println!(
-            "Scraping database {} for metadata",
-            pool.connect_options().get_database().unwrap_or_default()
+impl Drop for DatabaseMigration {
+    fn drop(&mut self) {
+        drop_temp_databases(&self.pool, std::mem::take(&mut self.created_databases));
+        drop_temp_roles(
+            &self.pool,
+            std::mem::take(&mut self.source_control_database.created_roles),
         );
-        let mut schemas = get_schemas(pool).await?;
-        let schema_names: Vec<&str> = schemas
-            .iter()
-            .map(|s| s.name.schema_name.as_str())
-            .collect();
-        let udts = get_udts(pool, &schema_names).await?;
-        let tables = get_tables(pool, &schema_names).await?;
-        let mut table_oids: Vec<Oid> = tables.iter().map(|t| t.oid).collect();
-        let policies = get_policies(pool, &table_oids).await?;
-        let constraints = get_constraints(pool, &table_oids).await?;
-        let indexes = get_indexes(pool, &table_oids).await?;
-        let sequences = get_sequences(pool, &schema_names).await?;
-        let functions = get_functions(pool, &schema_names).await?;
-        let views = get_views(pool, &schema_names).await?;
-        let mut object_oids: Vec<Oid> = views.iter().map(|v| v.oid).collect();
-        object_oids.append(&mut table_oids);
-        let triggers = get_triggers(pool, &object_oids).await?;
-        if let Some(index) = find_index(&schemas, |schema| schema.name.schema_name == "public") {
-            schemas.remove(index);
-        }
-        let mut database = Database {
-            schemas,
-            udts,
-            tables,
-            policies,
-            constraints,
-            indexes,
-            triggers,
-            sequences,
-            functions,
-            views,
-            extensions: get_extensions(pool).await?,
+    }
+}
+
+/// Index access methods that are built into Postgresql. Indexes using any other access method
+/// depend on the access method.
+const BUILT_IN_INDEX_ACCESS_METHODS: &[&str] =
+    &["", "btree", "hash", "gist", "spgist", "gin", "brin"];
+
+struct NodeIter<'n> {
+    root: &'n pg_query::NodeEnum,
+    current_node: &'n pg_query::NodeEnum,
+    queued_elements: VecDeque<SchemaQualifiedName>,
+    queued_nodes: VecDeque<&'n pg_query::NodeEnum>,
+    /// True if skipped analysis is recorded as an error rather than logged
+    fail_on_unsupported: bool,
+    /// Reason of the first analysis skipped when `fail_on_unsupported` is true
+    unsupported_reason: Option<String>,
+}
+
+impl<'n> NodeIter<'n> {
+    fn new(node: &'n pg_query::NodeEnum, fail_on_unsupported: bool) -> Self {
+        let mut iter = Self {
+            root: node,
+            current_node: node,
+            queued_elements: VecDeque::new(),
+            queued_nodes: VecDeque::new(),
+            fail_on_unsupported,
+            unsupported_reason: None,
         };
-        for function in database.functions.iter_mut() {
-            function.extract_more_dependencies(pool).await?;
-        }
-        println!("Done!");
-        Ok(database)
+        iter.extract_objects_from_current_node();
+        iter
     }
 
-    /// Use the metadata scraped from the database to create SQL source control files in the
-    /// `output_path` provided.
-    ///
-    /// This creates files in subdirectories:
-    /// - schema, 1 per schema
-    /// - extension, 1 per extension
-    /// - composite, 1 per composite UDT
-    /// - enum, 1 per enum UDT
-    /// - table, 1 per table with all constraints, indexes, triggers and policies owned by the table
-    ///     included in this file
-    /// - view, 1 per view
-    /// - sequence, 1 per sequence
-    /// - function, 1 per function
-    /// - procedure, 1 per procedure
+    /// Collect all dependencies found within the `node`.
     ///
     /// ## Errors
-    /// - General format errors when attempting to write the statements to a string buffer
-    /// - General IO errors when writing the string buffer to the file
-    ///
-    /// See [write_create_statements_to_file]
-    /// See [append_create_statements_to_owner_table_file]
-    pub async fn script_out<P>(&self, output_path: P) -> Result<(), PgDiffError>
-    where
-        P: AsRef<Path>,
-    {
-        for schema in &self.schemas {
-            write_create_statements_to_file(schema, &output_path).await?;
-        }
-        for extension in &self.extensions {
-            write_create_statements_to_file(extension, &output_path).await?;
+    /// If `fail_on_unsupported` is true and part of the node could not be analyzed. The error
+    /// contains the reason of the first analysis skipped.
+    fn dependencies(
+        node: &'n pg_query::NodeEnum,
+        fail_on_unsupported: bool,
+    ) -> Result<Vec<SchemaQualifiedName>, String> {
+        let mut iter = Self::new(node, fail_on_unsupported);
+        let dependencies = iter.by_ref().collect();
+        match iter.unsupported_reason {
+            Some(reason) => Err(reason),
+            None => Ok(dependencies),
         }
-        for udt in &self.udts {
-            write_create_statements_to_file(udt, &output_path).await?;
+    }
+
+    /// Skip the analysis of part of the node for the `reason` provided. The reason is recorded if
+    /// the iterator fails on unsupported analysis, otherwise the reason is logged when verbose.
+    fn skip_unsupported(&mut self, reason: String) {
+        if self.fail_on_unsupported {
+            self.unsupported_reason.get_or_insert(reason);
+        } else if is_verbose() {
+            println!("{reason}");
         }
-        for table in &self.tables {
-            write_create_statements_to_file(table, &output_path).await?;
-            for constraint in self.constraints.iter().filter(|c| c.table_oid == table.oid) {
-                append_create_statements_to_owner_table_file(
-                    constraint,
-                    &constraint.owner_table_name,
-                    &output_path,
-                )
-                .await?
-            }
-            for index in self.indexes.iter().filter(|i| i.table_oid == table.oid) {
-                append_create_statements_to_owner_table_file(
-                    index,
-                    &index.owner_table_name,
-                    &output_path,
-                )
-                .await?
-            }
-            for trigger in self.triggers.iter().filter(|t| t.owner_oid == table.oid) {
-                append_create_statements_to_owner_table_file(
-                    trigger,
-                    &trigger.owner_object_name,
-                    &output_path,
-                )
-                .await?
-            }
-            for policy in self.policies.iter().filter(|c| c.table_oid == table.oid) {
-                append_create_statements_to_owner_table_file(
-                    policy,
-                    &policy.owner_table_name,
-                    &output_path,
-                )
-                .await?
-            }
+    }
+
+    fn queue_nodes(&mut self, nodes: &'n [pg_query::protobuf::Node]) {
+        nodes
+            .iter()
+            .filter_map(|c| c.node.as_ref())
+            .for_each(|n| self.queued_nodes.push_back(n));
+    }
+
+    fn queue_node(&mut self, node: &'n Option<Box<pg_query::protobuf::Node>>) {
+        if let Some(node) = node.as_deref().and_then(|e| e.node.as_ref()) {
+            self.queued_nodes.push_back(node)
         }
-        for view in &self.views {
-            write_create_statements_to_file(view, &output_path).await?;
+    }
+
+    fn queue_names(&mut self, name_nodes: &[pg_query::protobuf::Node]) {
+        if let Some(name) = extract_names(name_nodes) {
+            self.queued_elements.push_back(name);
         }
-        for sequence in &self.sequences {
-            if let Some(owner_table) = &sequence.owner {
-                append_create_statements_to_owner_table_file(
-                    sequence,
-                    &owner_table.table_name,
-                    &output_path,
-                )
-                .await?;
-            } else {
-                write_create_statements_to_file(sequence, &output_path).await?;
-            }
+    }
+
+    /// Queue the text search configuration referenced by the `node` if the node is a string
+    /// constant. Used for text search functions where the configuration is supplied as a literal.
+    fn queue_text_search_configuration(&mut self, node: &pg_query::protobuf::Node) {
+        if let Some(Node::AConst(pg_query::protobuf::AConst {
+            val: Some(a_const::Val::Sval(configuration)),
+            ..
+        })) = &node.node
+        {
+            self.queued_elements
+                .push_back(SchemaQualifiedName::from(&configuration.sval));
         }
-        for function in &self.functions {
-            write_create_statements_to_file(function, &output_path).await?;
+    }
+
+    fn queue_relation(&mut self, relation: &Option<RangeVar>) {
+        let Some(range_var) = relation else {
+            return;
+        };
+        let name = SchemaQualifiedName::new(&range_var.schemaname, &range_var.relname);
+        self.queued_elements.push_back(name);
+    }
+
+    fn parse_inline_sql_code(&mut self, code: &str) {
+        match pg_query::parse(code) {
+            Ok(result) => {
+                for table in result.tables() {
+                    self.queued_elements.push_back(table.into());
+                }
+                for function in result.functions() {
+                    self.queued_elements.push_back(function.into());
+                }
+            },
+            Err(error) => self.skip_unsupported(format!(
+                "Skipping SQL code block since the source text could not be parsed. {error}\n{code}"
+            )),
         }
-        Ok(())
     }
 
-    /// Compare this database to another database. Assumes the other database is the desired state
-    /// of the database and this object is the current state that needs to be migrated.
-    fn compare_to_other_database(&self, other: &Self) -> Result<String, PgDiffError> {
-        println!("Comparing source control database to actual database");
-        let mut result = String::new();
-        for obj in DbCompare::new(self, other) {
-            match obj {
-                DbCompareResult::Create(new) => new.create_statements(&mut result)?,
-                DbCompareResult::Alter { old, new } => {
-                    old.alter_statements(&new, &mut result)?;
+    fn parse_inline_plpgsql_code(&mut self, code: &str) {
+        let functions = match parse_plpgsql_function(code) {
+            Ok(functions) => functions,
+            Err(error) => {
+                self.skip_unsupported(format!(
+                    "Skipping plpg/sql code block since the source text could not be parsed. \
+                     {error}\n"
+                ));
+                return;
+            },
+        };
+        for function in functions {
+            match function.get_objects() {
+                Ok(objects) => {
+                    self.queued_elements.append(&mut VecDeque::from(objects));
                 },
-                DbCompareResult::Drop(old) => old.drop_statements(&mut result)?,
+                Err(error) => self.skip_unsupported(format!(
+                    "Skipping plpg/sql code block since the source text could not be parsed for \
+                     objects. {error}"
+                )),
             }
         }
-        println!("Done!");
-        Ok(result)
     }
-}
 
-struct DbIter<'d> {
-    database: &'d Database,
-    completed_objects: Vec<&'d SchemaQualifiedName>,
-    completed_schemas: usize,
-    completed_extensions: usize,
-    completed_udt: usize,
-    completed_tables: usize,
-    completed_constraints: usize,
-    completed_indexes: usize,
-    completed_triggers: usize,
-    completed_policies: usize,
-    completed_views: usize,
-    completed_sequences: usize,
-    completed_functions: usize,
-}
+    fn extract_objects_from_current_node(&mut self) -> bool {
+        match &self.current_node {
+            Node::TableFunc(table_func) => {
+                self.queue_nodes(&table_func.coltypes);
+                self.queue_node(&table_func.rowexpr)
+            },
+            Node::CaseExpr(expr) => {
+                self.queue_nodes(&expr.args);
+                self.queue_node(&expr.defresult);
+            },
+            Node::CaseWhen(when) => {
+                self.queue_node(&when.expr);
+                self.queue_node(&when.result);
+            },
+            Node::TypeName(type_name) => {
+                self.queue_nodes(&type_name.names);
+            },
+            Node::AExpr(expr) => {
+                self.queue_node(&expr.lexpr);
+                self.queue_node(&expr.lexpr);
+            },
+            Node::FuncCall(func_call) => {
+                self.queue_nodes(&func_call.args);
+                self.queue_names(&func_call.funcname);
+                let is_text_search_function = func_call
+                    .funcname
+                    .last()
+                    .and_then(extract_string)
+                    .is_some_and(|name| TEXT_SEARCH_FUNCTIONS.contains(&name.as_str()));
+                if let [configuration, _] = func_call.args.as_slice() {
+                    if is_text_search_function {
+                        self.queue_text_search_configuration(configuration);
+                    }
+                }
+            },
+            Node::TypeCast(type_cast) => {
+                let is_text_search_cast = type_cast
+                    .type_name
+                    .as_ref()
+                    .and_then(|t| t.names.last())
+                    .and_then(extract_string)
+                    .is_some_and(|name| name == "regconfig" || name == "regdictionary");
+                match type_cast.arg.as_deref() {
+                    Some(arg) if is_text_search_cast => self.queue_text_search_configuration(arg),
+                    _ => self.queue_node(&type_cast.arg),
+                }
+            },
+            Node::ColumnDef(column) => {
+                if let Some(name) = &column.type_name {
+                    self.queue_names(&name.names);
+                }
+                if let Some(collate_clause) = &column.coll_clause {
+                    self.queue_names(&collate_clause.collname);
+                }
+                self.queue_nodes(&column.constraints);
+            },
+            Node::AlterTableStmt(alter_table) => {
+                self.queue_relation(&alter_table.relation);
+                // Constraints added using an existing index depend on the index, which is always
+                // within the table's schema
+                let schema_name = alter_table
+                    .relation
+                    .as_ref()
+                    .map(|relation| relation.schemaname.as_str())
+                    .unwrap_or_default();
+                for command in &alter_table.cmds {
+                    let Some(Node::AlterTableCmd(alter_command)) = &command.node else {
+                        continue;
+                    };
+                    if let Some(Node::Constraint(constraint)) =
+                        alter_command.def.as_ref().and_then(|d| d.node.as_ref())
+                    {
+                        if !constraint.indexname.is_empty() {
+                            self.queued_elements.push_back(SchemaQualifiedName::new(
+                                schema_name,
+                                &constraint.indexname,
+                            ));
+                        }
+                    }
+                }
+                self.queue_nodes(&alter_table.cmds);
+            },
+            Node::AlterTableCmd(alter_command) => {
+                self.queue_node(&alter_command.def);
+            },
+            Node::CreateStmt(create_table) => {
+                self.queue_nodes(&create_table.constraints);
+                self.queue_nodes(&create_table.table_elts);
+            },
+            Node::Constraint(constraint) => match constraint.contype() {
+                ConstrType::ConstrCheck => self.queue_node(&constraint.raw_expr),
+                ConstrType::ConstrForeign => self.queue_relation(&constraint.pktable),
+                _ => {},
+            },
+            Node::CreatePolicyStmt(create_policy) => {
+                self.queue_relation(&create_policy.table);
+                self.queue_node(&create_policy.qual);
+                self.queue_node(&create_policy.with_check);
+            },
+            Node::AlterPolicyStmt(alter_policy) => {
+                self.queue_relation(&alter_policy.table);
+                self.queue_node(&alter_policy.qual);
+                self.queue_node(&alter_policy.with_check);
+            },
+            Node::CreateTrigStmt(create_trigger) => {
+                self.queue_relation(&create_trigger.relation);
+                self.queue_names(&create_trigger.funcname);
+            },
+            Node::IndexStmt(index_statement) => {
+                self.queue_relation(&index_statement.relation);
+                let access_method = index_statement.access_method.as_str();
+                if !BUILT_IN_INDEX_ACCESS_METHODS.contains(&access_method) {
+                    self.queued_elements
+                        .push_back(SchemaQualifiedName::new("", access_method));
+                }
+                self.queue_nodes(&index_statement.index_params);
+                self.queue_nodes(&index_statement.index_including_params);
+                self.queue_node(&index_statement.where_clause);
+            },
+            Node::CreateStatsStmt(create_statistics) => {
+                for relation in &create_statistics.relations {
+                    if let Some(Node::RangeVar(range_var)) = &relation.node {
+                        self.queued_elements.push_back(SchemaQualifiedName::new(
+                            &range_var.schemaname,
+                            &range_var.relname,
+                        ));
+                    }
+                }
+                self.queue_nodes(&create_statistics.exprs);
+            },
+            Node::StatsElem(statistics_element) => self.queue_node(&statistics_element.expr),
+            Node::CreatePublicationStmt(create_publication) => {
+                self.queue_nodes(&create_publication.pubobjects);
+            },
+            Node::AlterDefaultPrivilegesStmt(alter_default_privileges) => {
+                for schema_name in
+                    default_privileges_option_values(&alter_default_privileges.options, "schemas")
+                {
+                    self.queued_elements
+                        .push_back(SchemaQualifiedName::new(&schema_name, ""));
+                }
+            },
+            Node::PublicationObjSpec(publication_object) => match &publication_object.pubtable {
+                Some(publication_table) => self.queue_relation(&publication_table.relation),
+                None if !publication_object.name.is_empty() => self
+                    .queued_elements
+                    .push_back(SchemaQualifiedName::new(&publication_object.name, "")),
+                None => {},
+            },
+            Node::IndexElem(index_element) => {
+                self.queue_node(&index_element.expr);
+                self.queue_names(&index_element.opclass);
+            },
+            Node::DefineStmt(define_statement)
+                if matches!(
+                    define_statement.kind(),
+                    ObjectType::ObjectAggregate | ObjectType::ObjectOperator
+                ) =>
+            {
+                for argument in &define_statement.args {
+                    if let Some(Node::List(parameters)) = &argument.node {
+                        self.queue_nodes(&parameters.items);
+                    }
+                }
+                for definition in &define_statement.definition {
+                    let Some(Node::DefElem(def_element)) = &definition.node else {
+                        continue;
+                    };
+                    // Support functions, the state type and operand types are specified as type
+                    // names
+                    if let Some(Node::TypeName(type_name)) =
+                        def_element.arg.as_deref().and_then(|a| a.node.as_ref())
+                    {
+                        self.queue_names(&type_name.names);
+                    }
+                }
+            },
+            Node::DefineStmt(define_statement) => {
+                for definition in &define_statement.definition {
+                    if let Some(Node::DefElem(def_element)) = &definition.node {
+                        self.queue_node(&def_element.arg);
+                    }
+                }
+            },
+            Node::CreateAmStmt(create_access_method) => {
+                self.queue_names(&create_access_method.handler_name);
+            },
+            Node::CreateEventTrigStmt(create_event_trigger) => {
+                self.queue_names(&create_event_trigger.funcname);
+            },
+            Node::CreateOpClassStmt(create_operator_class) => {
+                if let Some(type_name) = &create_operator_class.datatype {
+                    self.queue_names(&type_name.names);
+                }
+                for item in &create_operator_class.items {
+                    let Some(Node::CreateOpClassItem(item)) = &item.node else {
+                        continue;
+                    };
+                    // Operators and support functions are specified with their argument types
+                    if let Some(object) = &item.name {
+                        self.queue_names(&object.objname);
+                        self.queue_nodes(&object.objargs);
+                    }
+                    if let Some(type_name) = &item.storedtype {
+                        self.queue_names(&type_name.names);
+                    }
+                }
+            },
+            Node::AlterTsconfigurationStmt(alter_configuration) => {
+                self.queue_names(&alter_configuration.cfgname);
+                for dictionary in &alter_configuration.dicts {
+                    if let Some(Node::List(names)) = &dictionary.node {
+                        self.queue_names(&names.items);
+                    }
+                }
+            },
+            Node::CreateFunctionStmt(create_function) => {
+                self.queue_node(&create_function.sql_body);
+                if let Some(type_name) = &create_function.return_type {
+                    self.queue_names(&type_name.names);
+                }
+                self.queue_nodes(&create_function.parameters);
+                let def_elements = create_function
+                    .options
+                    .iter()
+                    .filter_map(|n| n.node.as_ref())
+                    .filter_map(|n| {
+                        if let Node::DefElem(def_element) = n {
+                            Some(def_element)
+                        } else {
+                            None
+                        }
+                    });
+                if let Some(language) = def_elements
+                    .clone()
+                    .filter(|def| def.defname == "language")
+                    .filter_map(|def| def.arg.as_ref().and_then(|a| a.node.as_ref()))
+                    .filter_map(|n| {
+                        if let Node::String(language) = n {
+                            Some(language)
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+                {
+                    match language.sval.as_str() {
+                        "plpgsql" => match self.current_node.deparse() {
+                            Ok(function_def) => self.parse_inline_plpgsql_code(&function_def),
+                            Err(error) => self.skip_unsupported(format!(
+                                "Could not deparse plpg/sql function. {error}"
+                            )),
+                        },
+                        "sql" => {
+                            if let Some(inline_code) = def_elements
+                                .clone()
+                                .filter(|def| def.defname == "as")
+                                .filter_map(|def| def.arg.as_ref().and_then(|a| a.node.as_ref()))
+                                .filter_map(|n| {
+                                    if let Node::List(list) = n {
+                                        Some(list.items.iter())
+                                    } else {
+                                        None
+                                    }
+                                })
+                                .flatten()
+                                .filter_map(|n| n.node.as_ref())
+                                .filter_map(|n| {
+                                    if let Node::String(inline_code) = n {
+                                        Some(inline_code)
+                                    } else {
+                                        None
+                                    }
+                                })
+                                .next()
+                            {
+                                self.parse_inline_sql_code(&inline_code.sval)
+                            }
+                        },
+                        _ => self.skip_unsupported(format!(
+                            "Unknown language '{}' for function. Could not parse.",
+                            language.sval
+                        )),
+                    }
+                };
+            },
+            Node::FunctionParameter(function_parameter) => {
+                if let Some(type_name) = &function_parameter.arg_type {
+                    self.queue_names(&type_name.names);
+                }
+            },
+            Node::AlterFunctionStmt(alter_function) => {
+                self.queue_nodes(&alter_function.actions);
+            },
+            Node::InlineCodeBlock(inline_code_block) => match inline_code_block.lang_oid {
+                14 => self.parse_inline_sql_code(&inline_code_block.source_text),
+                13545 => self.parse_inline_plpgsql_code(&inline_code_block.source_text),
+                _ => self.skip_unsupported(format!(
+                    "Skipping code block since the language is not supported. Lang ID = {}",
+                    inline_code_block.lang_oid
+                )),
+            },
+            Node::AlterTypeStmt(alter_type) => {
+                self.queue_nodes(&alter_type.options);
+            },
+            Node::CompositeTypeStmt(composite_type) => {
+                self.queue_nodes(&composite_type.coldeflist);
+            },
+            Node::CreateDomainStmt(create_domain) => {
+                if let Some(name) = &create_domain.type_name {
+                    self.queue_names(&name.names);
+                }
+                self.queue_nodes(&create_domain.constraints);
+            },
+            Node::ViewStmt(view) => {
+                if let Some(query) = view.query.as_ref().and_then(|q| q.node.as_ref()) {
+                    match query.deparse() {
+                        Ok(query_text) => self.parse_inline_sql_code(&query_text),
+                        Err(error) => self.skip_unsupported(format!(
+                            "Error trying to deparse view query. {error}"
+                        )),
+                    }
+                }
+            },
+            _ => return false,
+        };
+        self.move_to_next_node();
+        true
+    }
+
+    fn move_to_next_node(&mut self) {
+        if let Some(node) = self.queued_nodes.pop_front() {
+            self.current_node = node;
+        } else {
+            self.current_node = self.root;
+        }
+    }
+
+    fn get_next(&mut self) -> Option<SchemaQualifiedName> {
+        if let Some(name) = self.queued_elements.pop_front() {
+            return Some(name);
+        }
+        if self.current_node == self.root {
+            return None;
+        }
+        if self.extract_objects_from_current_node() {
+            return self.get_next();
+        }
+        self.move_to_next_node();
+        self.get_next()
+    }
+}
+
+impl<'n> Iterator for NodeIter<'n> {
+    type Item = SchemaQualifiedName;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct DdlStatement {
+    /// Identifier of the statement that is unique within the [SourceControlDatabase]. The same
+    /// statement text can appear more than once (e.g. boilerplate repeated across files) so
+    /// statements are always matched using this identifier.
+    id: usize,
+    statement: String,
+    object: SchemaQualifiedName,
+    dependencies: Vec<SchemaQualifiedName>,
+    /// Objects removed by a `DROP ... IF EXISTS` statement. Empty for all other statements.
+    dropped_objects: Vec<SchemaQualifiedName>,
+    /// True if the statement creates or alters an event trigger. Event triggers fire for the DDL
+    /// executed after them (e.g. a `ddl_command_start` guard can block every later statement) so
+    /// these statements are always applied after all other statements.
+    is_event_trigger: bool,
+}
+
+impl DdlStatement {
+    /// True if the statement is a `DROP ... IF EXISTS` statement
+    fn is_drop(&self) -> bool {
+        !self.dropped_objects.is_empty()
+    }
+
+    fn has_dependencies_met(&self, completed_dependencies: &HashSet<SchemaQualifiedName>) -> bool {
+        self.dependencies
+            .iter()
+            .all(|d| completed_dependencies.contains(d))
+    }
+
+    fn depends_on(&self, object: &SchemaQualifiedName) -> bool {
+        self.dependencies.contains(object)
+    }
+}
+
+/// Apply the `sorted_statements` (see [sort_statements]) using the `execute` function. Returns
+/// true if every statement was applied in the sorted order.
+///
+/// For well-ordered source files, every statement succeeds in the sorted order. If a statement
+/// fails (i.e. a dependency was not declared), the statement and all statements after it are
+/// applied adaptively instead (see [apply_adaptively]).
+///
+/// ## Errors
+/// See [apply_adaptively]
+async fn apply_statements<F, R, E, H>(
+    sorted_statements: Vec<DdlStatement>,
+    mut execute: F,
+    handle_failure: H,
+) -> Result<bool, PgDiffError>
+where
+    F: FnMut(&DdlStatement) -> R,
+    R: Future<Output = Result<(), E>>,
+    H: FnMut(DdlStatement, E) -> Result<DdlStatement, PgDiffError>,
+{
+    let total = sorted_statements.len();
+    let mut completed_objects = HashSet::new();
+    let mut applied = 0;
+    for statement in &sorted_statements {
+        if execute(statement).await.is_err() {
+            break;
+        }
+        if !statement.is_drop() {
+            completed_objects.insert(statement.object.clone());
+        }
+        applied += 1;
+        if is_verbose() {
+            println!("Statement {applied}/{total}\n");
+        }
+    }
+    if applied == total {
+        return Ok(true);
+    }
+    if is_verbose() {
+        println!(
+            "Statement for {} failed in dependency order. Applying the remaining statements \
+             adaptively",
+            sorted_statements[applied].object
+        );
+    }
+    let iter =
+        StatementIter::with_completed_objects(&sorted_statements[applied..], completed_objects);
+    apply_adaptively(iter, execute, handle_failure, applied, total).await?;
+    Ok(false)
+}
+
+/// Apply the statements of the [StatementIter] using the `execute` function. `applied` is the
+/// number of statements already applied out of the `total`.
+///
+/// The order of statements follows all objects whose dependencies have already been met (or
+/// no dependencies exist). As objects are created, the collection of dependencies created will
+/// be updated and statements that were previously not able to created, are then released for
+/// execution. If an error occurs during execution, the statement returned by `handle_failure`
+/// (e.g. with the missing dependency added) is put into a special queue of failed statements that
+/// will be handled later.
+///
+/// After iteration completes, the iterator object is checked to see if any statements remain
+/// which would indicate some of the statements could not be executed successfully (i.e. a
+/// circular dependency was found or the application could not derive the order of the
+/// statements to create the database state).
+///
+/// For more details of iteration, see [StatementIter].
+///
+/// ## Errors
+/// - `handle_failure` returns an error
+/// - After iterating over the ordered statements, the iterator still has remaining statements.
+///   This would indicate that an infinite loop was detected and the application cannot
+///   continue
+async fn apply_adaptively<F, R, E, H>(
+    mut iter: StatementIter,
+    mut execute: F,
+    mut handle_failure: H,
+    mut applied: usize,
+    total: usize,
+) -> Result<(), PgDiffError>
+where
+    F: FnMut(&DdlStatement) -> R,
+    R: Future<Output = Result<(), E>>,
+    H: FnMut(DdlStatement, E) -> Result<DdlStatement, PgDiffError>,
+{
+    while let Some(statement) = iter.next() {
+        if let Err(error) = execute(&statement).await {
+            let failed_statement = handle_failure(statement, error)?;
+            iter.add_back_failed_statement(failed_statement);
+            continue;
+        }
+        applied += 1;
+        if is_verbose() {
+            println!("Statement {applied}/{total}\n");
+        }
+    }
+    iter.check_completed()
+}
+
+/// Sort the `statements` so that each statement comes after the statements that create the
+/// objects it declares as dependencies. Dependencies that are not created by any statement (e.g.
+/// built-in types) are ignored. Statements that are ready at the same time keep their original
+/// order and statements within a dependency cycle are placed last in their original order.
+///
+/// Statements of an object always come after any `DROP ... IF EXISTS` statement of that object
+/// so re-runnable scripts (i.e. drop and create pairs) never drop the object they create.
+///
+/// Event trigger statements are placed after all other statements, keeping their original order
+/// (see [DdlStatement::is_event_trigger]).
+fn sort_statements(statements: &[DdlStatement]) -> Vec<DdlStatement> {
+    let mut sorted: Vec<DdlStatement> = Vec::with_capacity(statements.len());
+    let (event_triggers, mut remaining): (Vec<&DdlStatement>, Vec<&DdlStatement>) =
+        statements.iter().partition(|s| s.is_event_trigger);
+    while !remaining.is_empty() {
+        let pending_objects: HashSet<&SchemaQualifiedName> =
+            remaining.iter().map(|s| &s.object).collect();
+        let pending_drops: HashSet<&SchemaQualifiedName> = remaining
+            .iter()
+            .flat_map(|s| s.dropped_objects.iter())
+            .collect();
+        let (ready, blocked): (Vec<&DdlStatement>, Vec<&DdlStatement>) =
+            remaining.into_iter().partition(|statement| {
+                (statement.is_drop() || !pending_drops.contains(&statement.object))
+                    && statement
+                        .dependencies
+                        .iter()
+                        .all(|d| *d == statement.object || !pending_objects.contains(d))
+            });
+        if ready.is_empty() {
+            sorted.extend(blocked.into_iter().cloned());
+            break;
+        }
+        sorted.extend(ready.into_iter().cloned());
+        remaining = blocked;
+    }
+    sorted.extend(event_triggers.into_iter().cloned());
+    sorted
+}
+
+struct StatementIter {
+    statements: Vec<DdlStatement>,
+    /// Statements whose dependencies were all met when the remaining statements were last
+    /// searched. These are yielded (in their original order) before searching again so each
+    /// search drains every ready statement rather than a single statement.
+    ready_statements: VecDeque<DdlStatement>,
+    /// Event trigger statements that are only yielded once every other statement has been
+    /// executed successfully (see [DdlStatement::is_event_trigger])
+    event_trigger_statements: VecDeque<DdlStatement>,
+    completed_objects: HashSet<SchemaQualifiedName>,
+    failed_statements: Vec<DdlStatement>,
+    /// Number of failed statements at the start of the current retry pass
+    failed_pass_length: usize,
+    /// Number of failed statements still to be yielded within the current retry pass
+    failed_pass_remaining: usize,
+}
+
+impl StatementIter {
+    #[cfg(test)]
+    fn new(statements: &[DdlStatement]) -> Self {
+        Self::with_completed_objects(statements, HashSet::new())
+    }
+
+    /// Create a new iterator over the `statements` where the `completed_objects` have already
+    /// been created
+    fn with_completed_objects(
+        statements: &[DdlStatement],
+        completed_objects: HashSet<SchemaQualifiedName>,
+    ) -> Self {
+        let (event_trigger_statements, statements) =
+            statements.iter().cloned().partition(|s| s.is_event_trigger);
+        Self {
+            statements,
+            ready_statements: VecDeque::new(),
+            event_trigger_statements,
+            completed_objects,
+            failed_statements: vec![],
+            failed_pass_length: 0,
+            failed_pass_remaining: 0,
+        }
+    }
+
+    fn add_back_failed_statement(&mut self, statement: DdlStatement) {
+        if !statement.is_drop() {
+            self.completed_objects.remove(&statement.object);
+        }
+        self.failed_statements.push(statement);
+    }
+
+    /// Mark the object of the `statement` as completed before yielding the statement. Drop
+    /// statements never complete an object.
+    fn complete_statement(&mut self, statement: DdlStatement) -> DdlStatement {
+        if !statement.is_drop() {
+            self.completed_objects.insert(statement.object.clone());
+        }
+        statement
+    }
+
+    /// Move every remaining statement that has its dependencies met (and whose object is not
+    /// dropped by another remaining statement) to the ready statements, keeping the statements'
+    /// order
+    fn drain_ready_statements(&mut self) {
+        let dropped_objects: HashSet<&SchemaQualifiedName> = self
+            .statements
+            .iter()
+            .flat_map(|s| s.dropped_objects.iter())
+            .collect();
+        let is_ready: Vec<bool> = self
+            .statements
+            .iter()
+            .map(|s| {
+                s.has_dependencies_met(&self.completed_objects)
+                    && (s.is_drop() || !dropped_objects.contains(&s.object))
+            })
+            .collect();
+        if !is_ready.contains(&true) {
+            return;
+        }
+        let mut blocked = Vec::with_capacity(self.statements.len());
+        for (statement, is_ready) in self.statements.drain(..).zip(is_ready) {
+            if is_ready {
+                self.ready_statements.push_back(statement);
+            } else {
+                blocked.push(statement);
+            }
+        }
+        self.statements = blocked;
+    }
+
+    fn has_remaining(&self) -> bool {
+        !self.statements.is_empty()
+            || !self.ready_statements.is_empty()
+            || !self.failed_statements.is_empty()
+            || !self.event_trigger_statements.is_empty()
+    }
+
+    fn take_remaining(&mut self) -> Vec<DdlStatement> {
+        let mut result: Vec<DdlStatement> = self.ready_statements.drain(..).collect();
+        result.append(&mut self.statements);
+        result.append(&mut self.failed_statements);
+        result.extend(self.event_trigger_statements.drain(..));
+        result
+    }
+
+    /// Check that all statements have been executed successfully after iteration has finished.
+    ///
+    /// ## Errors
+    /// If any statements remain, a [PgDiffError::SourceControlScript] containing the remaining
+    /// statements is returned
+    fn check_completed(&mut self) -> Result<(), PgDiffError> {
+        if !self.has_remaining() {
+            return Ok(());
+        }
+        let remaining_statements = self
+            .take_remaining()
+            .into_iter()
+            .map(|s| s.statement)
+            .collect();
+        Err(PgDiffError::SourceControlScript {
+            remaining_statements,
+        })
+    }
+}
+
+impl Iterator for StatementIter {
+    type Item = DdlStatement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.has_remaining() {
+            return None;
+        }
+
+        if self.ready_statements.is_empty() && !self.statements.is_empty() {
+            self.drain_ready_statements();
+        }
+        if let Some(statement) = self.ready_statements.pop_front() {
+            return Some(self.complete_statement(statement));
+        }
+
+        if !self.statements.is_empty() {
+            let index = self
+                .statements
+                .iter()
+                .position(|s| {
+                    self.statements
+                        .iter()
+                        .all(|other| !s.depends_on(&other.object))
+                })
+                .unwrap_or_default();
+            let statement = self.statements.remove(index);
+            return Some(self.complete_statement(statement));
+        }
+
+        if self.failed_statements.is_empty() {
+            let statement = self.event_trigger_statements.pop_front()?;
+            return Some(self.complete_statement(statement));
+        }
+
+        // Failed statements are retried in passes. Statements that fail again are added to the
+        // back of the queue so each pass yields the statements from the front. If a full pass
+        // completes without any statement succeeding, no further progress can be made.
+        if self.failed_pass_remaining == 0 {
+            if self.failed_pass_length == self.failed_statements.len() {
+                return None;
+            }
+            self.failed_pass_length = self.failed_statements.len();
+            self.failed_pass_remaining = self.failed_pass_length;
+        }
+        self.failed_pass_remaining -= 1;
+        Some(self.failed_statements.remove(0))
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceControlDatabase {
+    temp_db_name: String,
+    statements: Vec<DdlStatement>,
+    /// `ALTER DATABASE ... SET` statements found within the source control files. These are
+    /// applied to the temp database after all other statements, regardless of the database name
+    /// specified in the source file.
+    database_settings: Vec<pg_query::protobuf::AlterDatabaseSetStmt>,
+    /// Roles created within the source control files. Roles exist at the cluster level so these
+    /// statements are never applied to the temp database. Instead, the roles are compared directly
+    /// when roles are included (see [is_include_roles]).
+    roles: Vec<Role>,
+    /// Roles of the source control files created within the cluster so statements referencing
+    /// those roles can be applied to the temp database (see
+    /// [SourceControlDatabase::create_missing_roles])
+    created_roles: Vec<String>,
+    /// Source control files that were skipped during analysis along with the reason each file
+    /// was skipped
+    skipped_files: Vec<(PathBuf, String)>,
+    /// Length in bytes above which a statement is reported as oversized when applied to the temp
+    /// database (see [SourceControlDatabase::oversized_statement_warning])
+    max_statement_length: Option<usize>,
+    /// True if the source control SQL is a single `pg_dump` file so the session configuration of
+    /// the dump preamble is skipped (see [is_session_statement])
+    is_dump_file: bool,
+}
+
+impl SourceControlDatabase {
+    fn new() -> Self {
+        Self {
+            temp_db_name: format!(
+                "pg_diff_rs_{}",
+                Uuid::new_v4().to_string().replace("-", "_")
+            ),
+            statements: vec![],
+            database_settings: vec![],
+            roles: vec![],
+            created_roles: vec![],
+            skipped_files: vec![],
+            max_statement_length: None,
+            is_dump_file: false,
+        }
+    }
+
+    /// Analyze all SQL files (`.sql` or `.pgsql`) found within `files_path`.
+    ///
+    /// Files that are not valid UTF-8 or do not contain any statements are skipped and reported
+    /// once all files have been analyzed. When `strict` is true, these files fail the analysis
+    /// instead.
+    ///
+    /// ## Errors
+    /// If the directory cannot be walked or a source file cannot be analyzed (see
+    /// [SourceControlDatabase::append_source_file]).
+    pub async fn from_directory<P>(files_path: P, strict: bool) -> Result<Self, PgDiffError>
+    where
+        P: AsRef<Path>,
+    {
+        println!("Analyzing code within source control directory");
+        let mut builder = SourceControlDatabase::new();
+        let mut entries = WalkDir::new(files_path).map(|entry| entry.map(|e| e.path()));
+        while let Some(result) = entries.next().await {
+            let path = result?;
+            if path.is_dir() {
+                continue;
+            }
+            let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else {
+                if is_verbose() {
+                    println!("Skipping {:?}", path);
+                }
+                continue;
+            };
+            if !file_name.ends_with(".pgsql") && !file_name.ends_with(".sql") {
+                if is_verbose() {
+                    println!("Skipping {:?}", file_name);
+                }
+                continue;
+            }
+            builder.append_source_file(path, strict).await?;
+        }
+        if !builder.skipped_files.is_empty() {
+            println!("Skipped {} source file(s):", builder.skipped_files.len());
+            for (path, reason) in &builder.skipped_files {
+                println!("    {:?}: {reason}", path);
+            }
+        }
+        println!("Done!");
+        Ok(builder)
+    }
+
+    /// Analyze the single SQL file at `dump_path` produced by `pg_dump --schema-only`. The file is
+    /// analyzed the same as each file found by [SourceControlDatabase::from_directory] except the
+    /// file is never skipped since an empty analysis would plan dropping every object and the
+    /// session configuration of the dump preamble is skipped.
+    ///
+    /// ## Errors
+    /// If the dump file cannot be read or analyzed (see
+    /// [SourceControlDatabase::append_source_file]).
+    pub async fn from_dump_file<P>(dump_path: P) -> Result<Self, PgDiffError>
+    where
+        P: AsRef<Path>,
+    {
+        println!("Analyzing code within pg_dump file");
+        let mut builder = SourceControlDatabase::new();
+        builder.is_dump_file = true;
+        builder.append_source_file(dump_path, true).await?;
+        println!("Done!");
+        Ok(builder)
+    }
+
+    /// Record the source file `path` as skipped for the specified `reason`. If `strict` is true,
+    /// the file is not skipped and an error is returned instead.
+    fn skip_source_file(
+        &mut self,
+        path: &Path,
+        reason: String,
+        strict: bool,
+    ) -> Result<(), PgDiffError> {
+        if strict {
+            return Err(PgDiffError::FileQueryParse {
+                path: path.to_path_buf(),
+                message: reason,
+            });
+        }
+        if is_verbose() {
+            println!("Warning: Skipping {:?}. {reason}", path);
+        }
+        self.skipped_files.push((path.to_path_buf(), reason));
+        Ok(())
+    }
+
+    /// Read source file and find all queries, the main DDL object of each query and the
+    /// dependencies for each DDL query.
+    ///
+    /// The steps are as follows:
+    /// 1. Read the entire source control file into a string buffer. If the file is not valid
+    ///    UTF-8 or contains no statements, the file is skipped (see
+    ///    [SourceControlDatabase::skip_source_file]).
+    /// 2. Split the source file statements into 1 or more queries.
+    /// 3. Parse each query extracting:
+    ///     * Root node of the query for further analyzing
+    ///     * Main object created/altered by the query (found from the root node)
+    ///     * All dependencies of the query (found by expanding [NodeIter])
+    ///
+    /// When analyzing a `pg_dump` file, queries that only configure the session (i.e. the dump
+    /// preamble) are skipped (see [is_session_statement]).
+    ///
+    /// `DROP ... IF EXISTS` statements (used by re-runnable scripts) are kept as statements of the
+    /// first object dropped. These never fail against the empty temp database and are always
+    /// applied before the statements of the objects they drop (see [sort_statements]).
+    ///
+    /// ## Errors
+    /// If an IO error occurs trying to read the file path or an error occurs attempting to read the
+    /// AST returned from query parsing. Querying parsing can fail for various reasons, but it
+    /// should only fail if the SQL code is not syntactically valid.
+    async fn append_source_file<P>(&mut self, path: P, strict: bool) -> Result<(), PgDiffError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path.as_ref()).await?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).await?;
+        let str = match String::from_utf8(bytes) {
+            Ok(inner) => inner,
+            Err(error) => {
+                return self.skip_source_file(
+                    path.as_ref(),
+                    format!("File is not valid UTF-8. {error}"),
+                    strict,
+                );
+            },
+        };
+        let Some(file_name) = path.as_ref().file_stem().and_then(|f| f.to_str()) else {
+            return Err(PgDiffError::General(format!(
+                "Could not extract a file name from {:?}",
+                path.as_ref()
+            )));
+        };
+        let queries = pg_query::split_with_parser(&str).map_err(|error| PgDiffError::PgQuery {
+            object_name: file_name.into(),
+            error,
+        })?;
+        if queries.iter().all(|query| query.trim().is_empty()) {
+            return self.skip_source_file(
+                path.as_ref(),
+                "File does not contain any statements".into(),
+                strict,
+            );
+        }
+        for query in queries {
+            let result = pg_query::parse(query).map_err(|error| PgDiffError::PgQuery {
+                object_name: file_name.into(),
+                error,
+            })?;
+            let root_node = result
+                .protobuf
+                .stmts
+                .first()
+                .and_then(|s| s.stmt.as_ref())
+                .and_then(|n| n.node.as_ref());
+            let root_node = *extract_option(
+                &path,
+                &root_node,
+                format!(
+                    "Could not get first node of statement: {:?}",
+                    result.protobuf
+                ),
+            )?;
+            if self.is_dump_file && is_session_statement(root_node, query) {
+                continue;
+            }
+            if let Node::AlterDatabaseSetStmt(alter_database) = root_node {
+                self.database_settings.push(alter_database.clone());
+                continue;
+            }
+            if let Node::CreateRoleStmt(create_role) = root_node {
+                self.roles.push(Role::from_create_statement(create_role));
+                continue;
+            }
+            // Tablespace stubs are scripted for replaying against a fresh cluster but the location
+            // is a placeholder so the stubs are never applied
+            if let Node::CreateTableSpaceStmt(_) = root_node {
+                continue;
+            }
+            if let Node::DropStmt(drop_statement) = root_node {
+                let dropped_objects = dropped_object_names(drop_statement).map_err(|message| {
+                    PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message,
+                    }
+                })?;
+                let Some(object) = dropped_objects.first().cloned() else {
+                    return Err(PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message: "DROP statement does not drop any objects".into(),
+                    });
+                };
+                self.statements.push(DdlStatement {
+                    id: self.statements.len(),
+                    statement: query.to_string(),
+                    object,
+                    dependencies: vec![],
+                    dropped_objects,
+                    is_event_trigger: false,
+                });
+                continue;
+            }
+            let parent_object = match root_node {
+                Node::AlterTableStmt(alter_table) => {
+                    let relation = extract_option(
+                        &path,
+                        &alter_table.relation,
+                        "Could not extract a table name from from an ALTER TABLE statement".into(),
+                    )?;
+                    let constraint_names = alter_table
+                        .cmds
+                        .iter()
+                        .filter_map(|n| n.node.as_ref())
+                        .filter_map(|n| match n {
+                            Node::Constraint(constraint) => Some(constraint.conname.as_str()),
+                            _ => None,
+                        })
+                        .collect::<Vec<&str>>()
+                        .join(",");
+
+                    SchemaQualifiedName::new(
+                        &relation.schemaname,
+                        &format!("{}.({})", relation.relname, constraint_names),
+                    )
+                },
+                Node::CreateSchemaStmt(create_schema) => {
+                    SchemaQualifiedName::new(&create_schema.schemaname, "")
+                },
+                Node::CompositeTypeStmt(composite_type) => {
+                    let composite = extract_option(
+                        &path,
+                        &composite_type.typevar,
+                        "Could not extract a table name from from an CREATE POLICY statement"
+                            .into(),
+                    )?;
+                    SchemaQualifiedName::new(&composite.schemaname, &composite.relname)
+                },
+                Node::DefineStmt(define_statement)
+                    if matches!(
+                        define_statement.kind(),
+                        ObjectType::ObjectTsdictionary | ObjectType::ObjectTsconfiguration
+                    ) =>
+                {
+                    extract_names(&define_statement.defnames).ok_or(
+                        PgDiffError::FileQueryParse {
+                            path: path.as_ref().into(),
+                            message: "Could not extract text search object name".into(),
+                        },
+                    )?
+                },
+                Node::DefineStmt(define_statement)
+                    if define_statement.kind() == ObjectType::ObjectAggregate =>
+                {
+                    extract_names(&define_statement.defnames).ok_or(
+                        PgDiffError::FileQueryParse {
+                            path: path.as_ref().into(),
+                            message: "Could not extract aggregate name".into(),
+                        },
+                    )?
+                },
+                Node::DefineStmt(define_statement)
+                    if define_statement.kind() == ObjectType::ObjectOperator =>
+                {
+                    extract_names(&define_statement.defnames).ok_or(
+                        PgDiffError::FileQueryParse {
+                            path: path.as_ref().into(),
+                            message: "Could not extract operator name".into(),
+                        },
+                    )?
+                },
+                Node::CreateOpClassStmt(create_operator_class) => extract_names(
+                    &create_operator_class.opclassname,
+                )
+                .ok_or(PgDiffError::FileQueryParse {
+                    path: path.as_ref().into(),
+                    message: "Could not extract operator class name".into(),
+                })?,
+                Node::AlterTsconfigurationStmt(alter_configuration) => extract_names(
+                    &alter_configuration.cfgname,
+                )
+                .ok_or(PgDiffError::FileQueryParse {
+                    path: path.as_ref().into(),
+                    message: "Could not extract text search configuration name".into(),
+                })?,
+                Node::CreateExtensionStmt(create_extension) => {
+                    SchemaQualifiedName::new("", &create_extension.extname)
+                },
+                Node::CreateAmStmt(create_access_method) => {
+                    SchemaQualifiedName::new("", &create_access_method.amname)
+                },
+                Node::CreateEventTrigStmt(create_event_trigger) => {
+                    SchemaQualifiedName::new("", &create_event_trigger.trigname)
+                },
+                Node::AlterEventTrigStmt(alter_event_trigger) => {
+                    SchemaQualifiedName::new("", &alter_event_trigger.trigname)
+                },
+                Node::CreatePublicationStmt(create_publication) => {
+                    SchemaQualifiedName::new("", &create_publication.pubname)
+                },
+                Node::AlterDefaultPrivilegesStmt(alter_default_privileges) => {
+                    default_privileges_name(alter_default_privileges)
+                },
+                Node::CreatePolicyStmt(create_policy) => {
+                    let relation = extract_option(
+                        &path,
+                        &create_policy.table,
+                        "Could not extract a table name from from an CREATE POLICY statement"
+                            .into(),
+                    )?;
+                    SchemaQualifiedName::new(
+                        &relation.schemaname,
+                        &format!("{}.{}", relation.relname, create_policy.policy_name),
+                    )
+                },
+                Node::CreateTrigStmt(create_trigger) => {
+                    let relation = extract_option(
+                        &path,
+                        &create_trigger.relation,
+                        "Could not extract a table name from from an CREATE TRIGGER statement"
+                            .into(),
+                    )?;
+                    SchemaQualifiedName::new(
+                        &relation.schemaname,
+                        &format!("{}.{}", relation.relname, create_trigger.trigname),
+                    )
+                },
+                Node::CreateSeqStmt(create_sequence) => {
+                    let sequence = extract_option(
+                        &path,
+                        &create_sequence.sequence,
+                        "Could not extract a table name from from an CREATE SEQUENCE statement"
+                            .into(),
+                    )?;
+                    SchemaQualifiedName::new(&sequence.schemaname, &sequence.relname)
+                },
+                Node::CreateFunctionStmt(create_function) => {
+                    extract_names(&create_function.funcname).ok_or(PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message: "Could not extract function name".into(),
+                    })?
+                },
+                Node::CreateEnumStmt(create_enum) => {
+                    extract_names(&create_enum.type_name).ok_or(PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message: "Could not extract enum type name".into(),
+                    })?
+                },
+                Node::CreateDomainStmt(create_domain) => extract_names(&create_domain.domainname)
+                    .ok_or(PgDiffError::FileQueryParse {
+                    path: path.as_ref().into(),
+                    message: "Could not extract domain type name".into(),
+                })?,
+                Node::CreateRangeStmt(create_range) => extract_names(&create_range.type_name)
+                    .ok_or(PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message: "Could not extract range type name".into(),
+                    })?,
+                Node::CreateStmt(create_table) => {
+                    let relation = extract_option(
+                        &path,
+                        &create_table.relation,
+                        "Could not extract a table name from from an CREATE TABLE statement".into(),
+                    )?;
+                    SchemaQualifiedName::new(&relation.schemaname, &relation.relname)
+                },
+                Node::ViewStmt(create_view) => {
+                    let relation = extract_option(
+                        &path,
+                        &create_view.view,
+                        "Could not extract a view name from from an CREATE VIEW statement".into(),
+                    )?;
+                    SchemaQualifiedName::new(&relation.schemaname, &relation.relname)
+                },
+                Node::IndexStmt(create_index) => {
+                    let relation = extract_option(
+                        &path,
+                        &create_index.relation,
+                        "Could not extract a view name from from an CREATE VIEW statement".into(),
+                    )?;
+                    SchemaQualifiedName::new(&relation.schemaname, &create_index.idxname)
+                },
+                Node::CreateStatsStmt(create_statistics) => extract_names(
+                    &create_statistics.defnames,
+                )
+                .ok_or(PgDiffError::FileQueryParse {
+                    path: path.as_ref().into(),
+                    message: "Could not extract statistics name".into(),
+                })?,
+                _ => {
+                    return Err(PgDiffError::FileQueryParse {
+                        path: path.as_ref().into(),
+                        message: format!(
+                            "First node of statement is not recognized: {:?}",
+                            root_node
+                        ),
+                    });
+                },
+            };
+            let dependencies = NodeIter::dependencies(root_node, is_fail_on_unsupported())
+                .map_err(|reason| PgDiffError::UnsupportedAnalysis {
+                    object_name: parent_object.to_string(),
+                    reason,
+                })?;
+            let statement = DdlStatement {
+                id: self.statements.len(),
+                statement: query.to_string(),
+                object: parent_object,
+                dependencies,
+                dropped_objects: vec![],
+                is_event_trigger: matches!(
+                    root_node,
+                    Node::CreateEventTrigStmt(_) | Node::AlterEventTrigStmt(_)
+                ),
+            };
+            self.statements.push(statement);
+        }
+
+        Ok(())
+    }
+
+    /// Describe the analysis of each statement collected so far. Each statement is followed by the
+    /// parent object found for the statement and the dependencies found by [NodeIter] (or the
+    /// objects dropped for a `DROP ... IF EXISTS` statement). Roles and database settings, which
+    /// are not analyzed as statements, are listed after the statements.
+    fn describe_analysis<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        for (i, statement) in self.statements.iter().enumerate() {
+            writeln!(w, "-- Statement {}", i + 1)?;
+            writeln!(w, "{}", statement.statement.trim())?;
+            writeln!(w, "Object: {}", statement.object)?;
+            if statement.is_drop() {
+                w.write_str("Drops: ")?;
+                write_join!(w, statement.dropped_objects.iter(), ", ");
+                writeln!(w)?;
+                continue;
+            }
+            w.write_str("Dependencies: ")?;
+            if statement.dependencies.is_empty() {
+                w.write_str("(none)")?;
+            } else {
+                write_join!(w, statement.dependencies.iter(), ", ");
+            }
+            writeln!(w)?;
+        }
+        for role in &self.roles {
+            writeln!(w, "Role: {}", role.name)?;
+        }
+        if !self.database_settings.is_empty() {
+            writeln!(w, "Database settings: {}", self.database_settings.len())?;
+        }
+        Ok(())
+    }
+
+    /// Apply statements collected from SQL source control files and apply them to the database
+    /// targeted by the supplied `pool`.
+    ///
+    /// Statements are applied in dependency order, falling back to adaptive application when a
+    /// statement fails (see [apply_statements]). A failed statement is recorded (see
+    /// [SourceControlDatabase::record_failed_statement]) before being retried.
+    ///
+    /// When roles are included, roles of the source control files missing from the cluster are
+    /// created first (see [SourceControlDatabase::create_missing_roles]). After all statements
+    /// are applied, the `ALTER DATABASE ... SET` statements are applied to the temp database.
+    ///
+    /// ## Errors
+    /// - Executing the statement query returns an error that cannot be parsed into a
+    ///   [PgDatabaseError]
+    /// - See [SourceControlDatabase::record_failed_statement] and [apply_statements]
+    pub async fn apply_to_temp_database(&mut self, pool: &PgPool) -> Result<(), PgDiffError> {
+        println!("Applying source control DDL statements to temp database");
+        println!("Temp Database Name: {}", self.temp_db_name);
+        println!("Total statements: {}", self.statements.len());
+
+        if is_include_roles() {
+            self.create_missing_roles(pool).await?;
+        }
+
+        if is_verbose() {
+            for warning in self
+                .statements
+                .iter()
+                .filter_map(|s| self.oversized_statement_warning(s))
+            {
+                println!("{warning}");
+            }
+        }
+        let sorted_statements = sort_statements(&self.statements);
+        apply_statements(
+            sorted_statements,
+            |statement| {
+                let pool = pool.clone();
+                let statement = statement.statement.clone();
+                async move { sqlx::query(&statement).execute(&pool).await.map(|_| ()) }
+            },
+            |statement, error: Error| {
+                let Error::Database(db_error) = &error else {
+                    return Err(error.into());
+                };
+                let Some(pg_error) = db_error.try_downcast_ref::<PgDatabaseError>() else {
+                    return Err(error.into());
+                };
+                self.record_failed_statement(statement, pg_error.message())
+            },
+        )
+        .await?;
+        for alter_database in &self.database_settings {
+            let statement = retarget_database_setting(alter_database, &self.temp_db_name)?;
+            sqlx::query(&statement).execute(pool).await?;
+        }
+        println!("Done!");
+        Ok(())
+    }
+
+    /// Create each role of the source control files that does not exist within the cluster of
+    /// the `pool` so statements referencing those roles (e.g. grants or policies) can be applied
+    /// to the temp database. Roles are created without login and recorded so they can be dropped
+    /// once the temp databases are dropped (see [drop_temp_roles]).
+    async fn create_missing_roles(&mut self, pool: &PgPool) -> Result<(), PgDiffError> {
+        let query = include_str!("./../../queries/check_role_exists.pgsql");
+        for role in &self.roles {
+            let exists: bool = query_scalar(query)
+                .bind(&role.name.local_name)
+                .fetch_one(pool)
+                .await?;
+            if exists {
+                continue;
+            }
+            sqlx::query(&format!("CREATE ROLE {} NOLOGIN;", role.name))
+                .execute(pool)
+                .await?;
+            self.created_roles.push(role.name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Record the failure of the `statement` with the error `message`, returning the statement to
+    /// retry. If the message notes a missing object, the object is added to the dependencies of
+    /// the source control statement with the same id (see [DdlStatement::id]).
+    ///
+    /// ## Errors
+    /// If the message notes a missing role. Only roles of the source control files are created
+    /// while planning (see [SourceControlDatabase::create_missing_roles]) so the statement can
+    /// never succeed.
+    fn record_failed_statement(
+        &mut self,
+        statement: DdlStatement,
+        message: &str,
+    ) -> Result<DdlStatement, PgDiffError> {
+        let Some(item) = self.statements.iter_mut().find(|s| s.id == statement.id) else {
+            return Ok(statement);
+        };
+        if let Some(role_name) = missing_role_name(message) {
+            return Err(PgDiffError::MissingRole {
+                object_name: statement.object,
+                role_name: role_name.to_string(),
+            });
+        }
+        if let Some(object_name) = missing_object_name(message) {
+            item.dependencies
+                .push(SchemaQualifiedName::from(object_name));
+        }
+        Ok(item.clone())
+    }
+
+    /// Apply the source control statements to the temp database (connected to by the `pool`) and
+    /// scrape the temp database to get the state described by the source control files. Each
+    /// phase is recorded within the supplied `timings`.
+    ///
+    /// ## Errors
+    /// See [SourceControlDatabase::apply_to_temp_database] and [Database::from_connection]
+    async fn apply_and_scrape(
+        &mut self,
+        pool: &PgPool,
+        timings: &mut Timings,
+    ) -> Result<Database, PgDiffError> {
+        timings
+            .time(
+                "Temp database statement application",
+                self.apply_to_temp_database(pool),
+            )
+            .await?;
+        timings.set_statement_count(self.statements.len());
+        let mut temp_database = timings
+            .time("Temp database scrape", Database::from_connection(pool))
+            .await?;
+        if is_include_roles() {
+            // The temp database shares the target's cluster so the scraped roles are replaced by
+            // the roles found within the source control files
+            let mut roles = self.roles.clone();
+            Role::resolve_dependencies(&mut roles);
+            temp_database.roles = roles;
+            DefaultPrivileges::resolve_dependencies(
+                &mut temp_database.default_privileges,
+                &temp_database.roles,
+            );
+        }
+        Ok(temp_database)
+    }
+
+    /// Warning noting the object and size of the `statement` if the statement is longer than the
+    /// maximum statement length. Returns [None] if the statement is within the limit or no limit
+    /// is set.
+    ///
+    /// Very large statements (e.g. seeded reference data or huge enums) slow down parsing and
+    /// application so this helps find pathological source files.
+    fn oversized_statement_warning(&self, statement: &DdlStatement) -> Option<String> {
+        let max_statement_length = self.max_statement_length?;
+        let length = statement.statement.len();
+        if length <= max_statement_length {
+            return None;
+        }
+        Some(format!(
+            "Warning: Statement for {} is {length} bytes which exceeds the maximum statement \
+             length of {max_statement_length} bytes",
+            statement.object
+        ))
+    }
+}
+
+/// Extract the role name from a database error `message` raised when a statement references a
+/// role that does not exist. Returns [None] if the message is not a missing role error.
+///
+/// Only roles of the source control files are created while planning so a missing role cannot be
+/// resolved by retrying the statement.
+fn missing_role_name(message: &str) -> Option<&str> {
+    regex!(r#"^role "(.+)" does not exist$"#)
+        .captures(message)
+        .and_then(|captures| captures.get(1))
+        .map(|role_name| role_name.as_str())
+}
+
+/// Extract the object name from a database error `message` raised when a statement references an
+/// object (e.g. a relation or type) that does not exist. Returns [None] if the message is not a
+/// missing object error or does not quote the object's name.
+fn missing_object_name(message: &str) -> Option<&str> {
+    regex!(r#"^[a-z ]+ "([^"]+)" does not exist$"#)
+        .captures(message)
+        .and_then(|captures| captures.get(1))
+        .map(|object_name| object_name.as_str())
+}
+
+/// Execute each statement of the migration `plan` against the database `db_name` (connected to by
+/// the `pool`). All statements are executed using a single connection so session settings and
+/// transaction blocks within the plan behave as they would when the plan is run.
+///
+/// See [verification_statement] for the statements that are retargeted or skipped.
+///
+/// ## Errors
+/// - The plan cannot be split into statements or a statement cannot be parsed
+/// - A statement fails to execute ([PgDiffError::PlanVerificationFailed])
+async fn apply_plan(pool: &PgPool, plan: &str, db_name: &str) -> Result<(), PgDiffError> {
+    let map_error = |error| PgDiffError::PgQuery {
+        object_name: db_name.into(),
+        error,
+    };
+    let mut connection = pool.acquire().await?;
+    for statement in pg_query::split_with_parser(plan).map_err(map_error)? {
+        let Some(statement) = verification_statement(statement, db_name)? else {
+            continue;
+        };
+        if let Err(error) = sqlx::raw_sql(&statement).execute(&mut *connection).await {
+            return Err(PgDiffError::PlanVerificationFailed { statement, error });
+        }
+    }
+    Ok(())
+}
+
+/// Statement executed against the verification database `db_name` for the planned `statement`.
+/// `ALTER DATABASE ... SET` statements are retargeted to `db_name`.
+///
+/// Returns [None] for role statements (including granting or revoking role membership) since
+/// roles exist at the cluster level. Executing these would change the roles of the live cluster
+/// so role changes are never verified.
+///
+/// ## Errors
+/// If the statement cannot be parsed or retargeted
+fn verification_statement(statement: &str, db_name: &str) -> Result<Option<String>, PgDiffError> {
+    let result = pg_query::parse(statement).map_err(|error| PgDiffError::PgQuery {
+        object_name: db_name.into(),
+        error,
+    })?;
+    let root_node = result
+        .protobuf
+        .stmts
+        .first()
+        .and_then(|s| s.stmt.as_ref())
+        .and_then(|n| n.node.as_ref());
+    let statement = match root_node {
+        None
+        | Some(Node::CreateRoleStmt(_))
+        | Some(Node::AlterRoleStmt(_))
+        | Some(Node::AlterRoleSetStmt(_))
+        | Some(Node::DropRoleStmt(_))
+        | Some(Node::GrantRoleStmt(_)) => return Ok(None),
+        Some(Node::AlterDatabaseSetStmt(alter_database)) => {
+            retarget_database_setting(alter_database, db_name)?
+        },
+        Some(_) => statement.trim().to_string(),
+    };
+    Ok(Some(statement))
+}
+
+/// Create the `ALTER DATABASE ... SET` statement described by `alter_database` but targeting the
+/// database `db_name` rather than the database specified within the source file.
+///
+/// ## Errors
+/// If the statement cannot be deparsed back into SQL
+fn retarget_database_setting(
+    alter_database: &pg_query::protobuf::AlterDatabaseSetStmt,
+    db_name: &str,
+) -> Result<String, PgDiffError> {
+    let mut alter_database = alter_database.clone();
+    alter_database.dbname = db_name.to_string();
+    Node::AlterDatabaseSetStmt(alter_database)
+        .deparse()
+        .map_err(|error| PgDiffError::PgQuery {
+            object_name: SchemaQualifiedName::new("", db_name),
+            error,
+        })
+}
+
+/// Extract the schema qualified name(s) from the list of `name_nodes` supplied. This assumes that
+/// each list item node is a node containing a [Node::String].
+///
+/// Returns a [SchemaQualifiedName] if a name can be extracted. Returns [None] when:
+/// - the schema name is `pg_catalog`
+/// - the name has no schema + the local name is a built-in name (see [is_built_in_name])
+/// - there are no nodes in the list
+///
+/// See [extract_string].
+fn extract_names(name_nodes: &[pg_query::protobuf::Node]) -> Option<SchemaQualifiedName> {
+    match name_nodes {
+        [schema_name, local_name] => {
+            let schema_name = extract_string(schema_name)?;
+            if schema_name == "pg_catalog" {
+                return None;
+            }
+            let local_name = extract_string(local_name)?;
+            Some(SchemaQualifiedName::new(schema_name, local_name))
+        },
+        [local_name] => {
+            let local_name = extract_string(local_name)?;
+            if is_built_in_name(local_name) {
+                return None;
+            }
+            Some(SchemaQualifiedName::from(local_name))
+        },
+        _ => None,
+    }
+}
+
+/// True if the `query` (with the parsed `root_node`) only configures the session. This includes
+/// the `SET` and `SELECT pg_catalog.set_config(...)` statements found at the start of `pg_dump`
+/// output. These statements do not define any object so they are skipped when analyzing a dump
+/// file.
+fn is_session_statement(root_node: &Node, query: &str) -> bool {
+    match root_node {
+        Node::VariableSetStmt(_) => true,
+        Node::SelectStmt(_) => {
+            regex!(r"^select\s+pg_catalog\.set_config\s*\("i).is_match(query.trim_start())
+        },
+        _ => false,
+    }
+}
+
+/// Values of the `ALTER DEFAULT PRIVILEGES` option with the `name` within the `options`. The
+/// `schemas` option contains the schema names of `IN SCHEMA` and the `roles` option contains the
+/// role names of `FOR ROLE`. Special roles (e.g. `CURRENT_USER`) are named by their keyword.
+fn default_privileges_option_values(
+    options: &[pg_query::protobuf::Node],
+    name: &str,
+) -> Vec<String> {
+    options
+        .iter()
+        .filter_map(|option| match &option.node {
+            Some(Node::DefElem(def_element)) if def_element.defname == name => {
+                def_element.arg.as_ref().and_then(|a| a.node.as_ref())
+            },
+            _ => None,
+        })
+        .filter_map(|n| match n {
+            Node::List(list) => Some(list.items.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|n| match &n.node {
+            Some(Node::String(value)) => Some(value.sval.clone()),
+            Some(Node::RoleSpec(role)) if role.rolename.is_empty() => Some(
+                match role.roletype() {
+                    RoleSpecType::RolespecCurrentRole => "current_role",
+                    RoleSpecType::RolespecSessionUser => "session_user",
+                    _ => "current_user",
+                }
+                .to_string(),
+            ),
+            Some(Node::RoleSpec(role)) => Some(role.rolename.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Name of the default privileges altered by the `alter_default_privileges` statement, as they
+/// are named by [DefaultPrivileges]. Only the first schema and role are used when the statement
+/// applies to more than one schema or role. Without a `FOR ROLE` clause, the grantor is the
+/// current user.
+fn default_privileges_name(
+    alter_default_privileges: &pg_query::protobuf::AlterDefaultPrivilegesStmt,
+) -> SchemaQualifiedName {
+    let schema_name =
+        default_privileges_option_values(&alter_default_privileges.options, "schemas")
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+    let grantor = default_privileges_option_values(&alter_default_privileges.options, "roles")
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "current_user".to_string());
+    let object_type = match alter_default_privileges
+        .action
+        .as_ref()
+        .map(|a| a.objtype())
+    {
+        Some(ObjectType::ObjectTable) => "tables",
+        Some(ObjectType::ObjectSequence) => "sequences",
+        Some(
+            ObjectType::ObjectFunction | ObjectType::ObjectProcedure | ObjectType::ObjectRoutine,
+        ) => "functions",
+        Some(ObjectType::ObjectType) => "types",
+        Some(ObjectType::ObjectSchema) => "schemas",
+        _ => "objects",
+    };
+    SchemaQualifiedName::new(&schema_name, &format!("{grantor}.{object_type}"))
+}
+
+/// Names of the objects dropped by the `drop_statement`, as they are named by
+/// [SourceControlDatabase::append_source_file].
+///
+/// ## Errors
+/// If the statement does not include `IF EXISTS` (the temp database starts empty so the statement
+/// would always fail) or a dropped object's name cannot be extracted
+fn dropped_object_names(
+    drop_statement: &pg_query::protobuf::DropStmt,
+) -> Result<Vec<SchemaQualifiedName>, String> {
+    if !drop_statement.missing_ok {
+        return Err(
+            "DROP statements must include IF EXISTS since source files are applied to an empty \
+             database"
+                .into(),
+        );
+    }
+    let remove_type = drop_statement.remove_type();
+    drop_statement
+        .objects
+        .iter()
+        .map(|object| {
+            dropped_object_name(object, remove_type)
+                .ok_or_else(|| format!("Could not extract dropped object name from {object:?}"))
+        })
+        .collect()
+}
+
+/// Name of a single `object` dropped by a `DROP` statement of the `remove_type`. Triggers,
+/// policies and rules are named after their owner table (e.g. `schema.table.trigger`).
+fn dropped_object_name(
+    object: &pg_query::protobuf::Node,
+    remove_type: ObjectType,
+) -> Option<SchemaQualifiedName> {
+    match object.node.as_ref()? {
+        Node::List(list)
+            if matches!(
+                remove_type,
+                ObjectType::ObjectTrigger | ObjectType::ObjectPolicy | ObjectType::ObjectRule
+            ) =>
+        {
+            let (name, table_name) = list.items.split_last()?;
+            let (table_name, schema_name) = table_name.split_last()?;
+            let schema_name = match schema_name {
+                [schema_name] => extract_string(schema_name)?.as_str(),
+                [] => "",
+                _ => return None,
+            };
+            Some(SchemaQualifiedName::new(
+                schema_name,
+                &format!("{}.{}", extract_string(table_name)?, extract_string(name)?),
+            ))
+        },
+        Node::List(list) => extract_names(&list.items),
+        Node::String(pg_query::protobuf::String { sval }) => match remove_type {
+            ObjectType::ObjectSchema => Some(SchemaQualifiedName::new(sval, "")),
+            _ => Some(SchemaQualifiedName::new("", sval)),
+        },
+        Node::ObjectWithArgs(object_with_args) => extract_names(&object_with_args.objname),
+        Node::TypeName(type_name) => extract_names(&type_name.names),
+        _ => None,
+    }
+}
+
+/// Extract the string contained within the `node`. Returns [None] if the `node` does not point to
+/// anything or the inner node is not [Node::String]. Otherwise, the inner string is returned.
+fn extract_string(node: &pg_query::protobuf::Node) -> Option<&String> {
+    match &node.node {
+        Some(pg_query::NodeEnum::String(pg_query::protobuf::String { sval, .. })) => Some(sval),
+        _ => None,
+    }
+}
+
+/// Extract a reference to the value within the `option` if it's [Some]. If the value is [None],
+/// return a [PgDiffError::FileQueryParse] with the `path` and `message`.
+fn extract_option<P, I>(path: P, option: &Option<I>, message: String) -> Result<&I, PgDiffError>
+where
+    P: AsRef<Path>,
+{
+    option.as_ref().ok_or(PgDiffError::FileQueryParse {
+        path: path.as_ref().into(),
+        message,
+    })
+}
+
+/// Contains the LOCAL_PROVIDER database option as well as the other allowed options based upon the
+/// specific provider type.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum LocalProvider {
+    #[serde(rename = "c")]
+    Libc {
+        lc_collate: String,
+        lc_ctype: String,
+    },
+    #[serde(rename = "i")]
+    Icu {
+        icu_locale: String,
+        icu_rules: Option<String>,
+    },
+}
+
+impl Display for LocalProvider {
+    /// Write the LOCAL_PROVIDER option as well as the other options allowed with the specific
+    /// [LocalProvider]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocalProvider::Libc {
+                lc_collate,
+                lc_ctype,
+            } => {
+                write!(
+                    f,
+                    "\n    LOCALE_PROVIDER 'libc'\n    LC_COLLATE '{}'\n    LC_CTYPE '{}'",
+                    lc_collate, lc_ctype
+                )
+            },
+            LocalProvider::Icu {
+                icu_locale,
+                icu_rules,
+            } => {
+                write!(
+                    f,
+                    "\n    LOCALE_PROVIDER 'icu'\n    ICU_LOCALE '{}'",
+                    icu_locale
+                )?;
+                if let Some(icu_rules) = icu_rules {
+                    write!(f, "\n    ICU_RULES '{}'", icu_rules)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Database options that can be supplied when executing a `CREATE DATABASE` command
+#[derive(Debug, sqlx::FromRow)]
+struct DatabaseOptions {
+    encoding: String,
+    locale: Option<String>,
+    #[sqlx(json)]
+    locale_provider: LocalProvider,
+    collation_version: String,
+    /// Template database copied to create the new database. Never scraped so the server default
+    /// (`template1`) is used unless supplied.
+    #[sqlx(skip)]
+    template: Option<String>,
+    /// Tablespace of the new database. Never scraped so the server default is used unless
+    /// supplied.
+    #[sqlx(skip)]
+    tablespace: Option<String>,
+}
+
+impl Display for DatabaseOptions {
+    /// Write the `CREATE DATABASE` options specified by this struct
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            " WITH\n    ENCODING '{}'\n    COLLATION_VERSION '{}'",
+            self.encoding, self.collation_version
+        )?;
+        if let Some(locale) = &self.locale {
+            write!(f, "\n    LOCALE '{}'", locale)?;
+        }
+        write!(f, "{}", self.locale_provider)?;
+        if let Some(template) = &self.template {
+            write!(f, "\n    TEMPLATE {}", quote_identifier(template))?;
+        }
+        if let Some(tablespace) = &self.tablespace {
+            write!(f, "\n    TABLESPACE {}", quote_identifier(tablespace))?;
+        }
+        Ok(())
+    }
+}
+
+impl DatabaseOptions {
+    /// Capture the pool's current database's options
+    async fn from_connection(pool: &PgPool) -> Result<Self, PgDiffError> {
+        let query = include_str!("./../../queries/database.pgsql");
+        let db_options = query_as(query).fetch_one(pool).await?;
+        Ok(db_options)
+    }
+}
+
+/// First server version (as found within `server_version_num`) where `SET NOT NULL` skips the
+/// table scan when a validated check constraint proves the column has no nulls
+const SKIP_NOT_NULL_SCAN_VERSION: i32 = 120000;
+
+/// Configuration parameters that can be set for a database using `ALTER DATABASE ... SET`
+#[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+struct DatabaseSettings {
+    /// Name of the database that the settings belong to
+    name: String,
+    /// Settings as `name=value` pairs as found within `pg_catalog.pg_db_role_setting`
+    settings: Vec<String>,
+    /// Version of the server hosting the database as found within the `server_version_num`
+    /// setting (e.g. `160002`)
+    server_version_num: i32,
+}
+
+impl DatabaseSettings {
+    /// Capture the pool's current database's settings
+    async fn from_connection(pool: &PgPool) -> Result<Self, PgDiffError> {
+        let query = include_str!("./../../queries/database_settings.pgsql");
+        let settings = fetch_with_retry(|| query_as(query).fetch_one(pool)).await?;
+        Ok(settings)
+    }
+
+    /// True if the server hosting the database can use a validated `IS NOT NULL` check constraint
+    /// to skip the table scan of `SET NOT NULL` (Postgresql 12+)
+    fn can_skip_not_null_scan(&self) -> bool {
+        self.server_version_num >= SKIP_NOT_NULL_SCAN_VERSION
+    }
+
+    /// Schemas of the `search_path` set for the database, if any
+    fn search_path(&self) -> Option<Vec<String>> {
+        let settings = self.as_key_value_pairs();
+        let search_path = settings.get("search_path")?;
+        Some(parse_search_path(search_path))
+    }
+
+    /// Settings of the database as a map of setting name to value
+    fn as_key_value_pairs(&self) -> KeyValuePairs {
+        KeyValuePairs::from(self.settings.as_slice())
+    }
+
+    /// Write an `ALTER DATABASE ... SET` statement for each of the database's settings. Settings
+    /// are written in order of the setting name.
+    fn write_set_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        let settings = self.as_key_value_pairs();
+        let mut settings: Vec<_> = settings.iter().collect();
+        settings.sort_by_key(|(key, _)| *key);
+        for (key, value) in settings {
+            self.write_set_statement(w, key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Write a single `ALTER DATABASE ... SET` statement for the setting `key` and `value`. List
+    /// settings (e.g. `search_path`) have each element quoted separately so the list is not
+    /// interpreted as a single value.
+    fn write_set_statement<W: Write>(
+        &self,
+        w: &mut W,
+        key: &str,
+        value: &str,
+    ) -> Result<(), PgDiffError> {
+        write!(w, "ALTER DATABASE {} SET {key} TO ", self.name)?;
+        if LIST_SETTINGS.contains(&key) {
+            write_join!(
+                w,
+                value.split(',').map(|v| v.trim().trim_matches('"')),
+                |w, v| write!(w, "'{}'", v.replace('\'', "''")),
+                ", "
+            );
+        } else {
+            write!(w, "'{}'", value.replace('\'', "''"))?;
+        }
+        w.write_str(";\n")?;
+        Ok(())
+    }
+
+    /// Write the `ALTER DATABASE` statements required to migrate this database's settings to the
+    /// `new` settings. Settings that no longer exist are reset to the server's default.
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        let old_settings = self.as_key_value_pairs();
+        let new_settings = new.as_key_value_pairs();
+        let mut changed_settings: Vec<_> = new_settings
+            .iter()
+            .filter(|(key, value)| old_settings.get(*key) != Some(*value))
+            .collect();
+        changed_settings.sort_by_key(|(key, _)| *key);
+        for (key, value) in changed_settings {
+            self.write_set_statement(w, key, value)?;
+        }
+        let mut reset_settings: Vec<_> = old_settings
+            .keys()
+            .filter(|key| !new_settings.contains_key(*key))
+            .collect();
+        reset_settings.sort();
+        for key in reset_settings {
+            writeln!(w, "ALTER DATABASE {} RESET {key};", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings whose value is a list where each element must be quoted separately
+const LIST_SETTINGS: &[&str] = &[
+    "search_path",
+    "temp_tablespaces",
+    "local_preload_libraries",
+    "session_preload_libraries",
+    "shared_preload_libraries",
+];
+
+/// Struct representing all database objects that can be found within a target database. This
+/// ignores objects that are directly owned by extensions and does not include the public schema
+/// which is already present within a database.
+#[derive(Debug)]
+pub struct Database {
+    /// Roles of the database cluster. Only populated when roles are included (see
+    /// [is_include_roles]).
+    pub(crate) roles: Vec<Role>,
+    pub(crate) schemas: Vec<Schema>,
+    pub(crate) udts: Vec<Udt>,
+    pub(crate) tables: Vec<Table>,
+    pub(crate) policies: Vec<Policy>,
+    pub(crate) constraints: Vec<Constraint>,
+    pub(crate) operators: Vec<Operator>,
+    pub(crate) operator_classes: Vec<OperatorClass>,
+    pub(crate) indexes: Vec<Index>,
+    pub(crate) statistics: Vec<Statistics>,
+    pub(crate) triggers: Vec<Trigger>,
+    pub(crate) sequences: Vec<Sequence>,
+    pub(crate) functions: Vec<Function>,
+    pub(crate) aggregates: Vec<Aggregate>,
+    pub(crate) views: Vec<View>,
+    pub(crate) extensions: Vec<Extension>,
+    pub(crate) access_methods: Vec<AccessMethod>,
+    pub(crate) event_triggers: Vec<EventTrigger>,
+    pub(crate) text_search_dictionaries: Vec<TextSearchDictionary>,
+    pub(crate) text_search_configurations: Vec<TextSearchConfiguration>,
+    pub(crate) publications: Vec<Publication>,
+    pub(crate) default_privileges: Vec<DefaultPrivileges>,
+    settings: DatabaseSettings,
+}
+
+impl Database {
+    /// Create a new [Database] from the database targeted by the supplied `pool`.
+    ///
+    /// Collect all available metadata about the database form the `pg_catalog` tables/views as well
+    /// as attempting to analyze non-compiled functions (i.e. dynamic sql and pl/pgsql functions)
+    /// to figured out dependencies. Function analysis is not guaranteed to work so errors are
+    /// written to STDOUT if the verbose flag is active.
+    ///
+    /// The names of `pg_catalog` types and functions are cached by the first call so later
+    /// dependency analysis can skip built-in names (see [is_built_in_name]).
+    ///
+    /// Queries are executed using a pool that applies the scrape timeout (see [scrape_pool]) and
+    /// transient errors are retried (see [fetch_with_retry]). A pool created for the scrape
+    /// timeout is closed before returning.
+    ///
+    /// ## Errors
+    /// - Errors from the SQL queries executed to fetch metadata
+    /// - SQL query parsing if a function is a dynamic SQL query but the query is invalid
+    /// - A function is not SQL or pl/pgsql (other languages are not supported)
+    pub async fn from_connection(pool: &PgPool) -> Result<Self, PgDiffError> {
+        println!(
+            "Scraping database {} for metadata",
+            pool.connect_options().get_database().unwrap_or_default()
+        );
+        let timeout_pool = scrape_pool(pool).await?;
+        let result = Self::scrape(timeout_pool.as_ref().unwrap_or(pool)).await;
+        if let Some(timeout_pool) = timeout_pool {
+            timeout_pool.close().await;
+        }
+        let database = result?;
+        println!("Done!");
+        println!("{}", database.summary());
+        Ok(database)
+    }
+
+    /// Scrape the metadata of the database using the `pool`. See [Database::from_connection].
+    async fn scrape(pool: &PgPool) -> Result<Self, PgDiffError> {
+        load_built_in_names(pool).await;
+        let roles = if is_include_roles() {
+            get_roles(pool).await?
+        } else {
+            vec![]
+        };
+        let mut schemas = get_schemas(pool).await?;
+        let schema_names: Vec<&str> = schemas
+            .iter()
+            .map(|s| s.name.schema_name.as_str())
+            .collect();
+        let text_search_dictionaries = get_text_search_dictionaries(pool, &schema_names).await?;
+        let text_search_configurations =
+            get_text_search_configurations(pool, &schema_names).await?;
+        let udts = get_udts(pool, &schema_names).await?;
+        let tables = get_tables(pool, &schema_names).await?;
+        let mut table_oids: Vec<Oid> = tables.iter().map(|t| t.oid).collect();
+        let policies = get_policies(pool, &table_oids).await?;
+        let constraints = get_constraints(pool, &table_oids).await?;
+        let indexes = get_indexes(pool, &table_oids).await?;
+        let statistics = get_statistics(pool, &table_oids).await?;
+        let sequences = get_sequences(pool, &schema_names).await?;
+        let functions = get_functions(pool, &schema_names).await?;
+        let aggregates = get_aggregates(pool, &schema_names).await?;
+        let operators = get_operators(pool, &schema_names).await?;
+        let operator_classes = get_operator_classes(pool, &schema_names).await?;
+        let views = get_views(pool, &schema_names).await?;
+        let default_privileges = get_default_privileges(pool, &schema_names).await?;
+        let mut object_oids: Vec<Oid> = views.iter().map(|v| v.oid).collect();
+        object_oids.append(&mut table_oids);
+        let triggers = get_triggers(pool, &object_oids).await?;
+        let extensions = get_extensions(pool).await?;
+        if is_verbose() {
+            for extension in extensions.iter().filter(|e| !e.members.is_empty()) {
+                println!(
+                    "Excluded {} objects owned by extension {}",
+                    extension.members.len(),
+                    extension.name
+                );
+            }
+        }
+        if let Some(index) = schemas
+            .iter()
+            .position(|schema| schema.name.schema_name == "public")
+        {
+            schemas.remove(index);
+        }
+        let mut database = Database {
+            roles,
+            schemas,
+            udts,
+            tables,
+            policies,
+            constraints,
+            operators,
+            operator_classes,
+            indexes,
+            statistics,
+            triggers,
+            sequences,
+            functions,
+            aggregates,
+            views,
+            extensions,
+            access_methods: get_access_methods(pool).await?,
+            event_triggers: get_event_triggers(pool).await?,
+            text_search_dictionaries,
+            text_search_configurations,
+            publications: get_publications(pool).await?,
+            default_privileges,
+            settings: DatabaseSettings::from_connection(pool).await?,
+        };
+        database.remove_excluded_objects();
+        DefaultPrivileges::resolve_dependencies(&mut database.default_privileges, &database.roles);
+        let search_path = database.settings.search_path();
+        for function in database.functions.iter_mut() {
+            function
+                .extract_more_dependencies(pool, search_path.as_deref(), is_fail_on_unsupported())
+                .await?;
+        }
+        for table in database.tables.iter_mut() {
+            let owned_sequences: Vec<&SchemaQualifiedName> = database
+                .sequences
+                .iter()
+                .filter(|s| s.owner.as_ref().is_some_and(|o| o.table_name == table.name))
+                .map(|s| &s.name)
+                .collect();
+            table
+                .extract_default_dependencies(pool, search_path.as_deref(), &owned_sequences)
+                .await?;
+        }
+        Ok(database)
+    }
+
+    /// Remove all objects whose name matches an excluded object pattern. See
+    /// [is_excluded_object].
+    fn remove_excluded_objects(&mut self) {
+        self.remove_objects_matching(is_excluded_object);
+    }
+
+    /// Remove all objects where `is_excluded` is true for the object's name. Objects owned by a
+    /// table or view (e.g. constraints and indexes) are also removed when the owner is excluded.
+    fn remove_objects_matching<F>(&mut self, is_excluded: F)
+    where
+        F: Fn(&SchemaQualifiedName) -> bool,
+    {
+        self.roles.retain(|r| !is_excluded(&r.name));
+        self.schemas.retain(|s| !is_excluded(&s.name));
+        self.extensions.retain(|e| !is_excluded(&e.name));
+        self.access_methods.retain(|a| !is_excluded(&a.name));
+        self.event_triggers.retain(|e| !is_excluded(&e.name));
+        self.text_search_dictionaries
+            .retain(|d| !is_excluded(&d.name));
+        self.text_search_configurations
+            .retain(|c| !is_excluded(&c.name));
+        self.udts.retain(|u| !is_excluded(&u.name));
+        self.tables.retain(|t| !is_excluded(&t.name));
+        self.policies.retain(|p| {
+            !is_excluded(&p.schema_qualified_name) && !is_excluded(&p.owner_table_name)
+        });
+        self.constraints.retain(|c| {
+            !is_excluded(&c.schema_qualified_name) && !is_excluded(&c.owner_table_name)
+        });
+        self.indexes.retain(|i| {
+            !is_excluded(&i.schema_qualified_name) && !is_excluded(&i.owner_table_name)
+        });
+        self.statistics.retain(|s| {
+            !is_excluded(&s.schema_qualified_name) && !is_excluded(&s.owner_table_name)
+        });
+        self.triggers.retain(|t| {
+            !is_excluded(&t.schema_qualified_name) && !is_excluded(&t.owner_object_name)
+        });
+        self.sequences.retain(|s| !is_excluded(&s.name));
+        self.functions.retain(|f| !is_excluded(&f.name));
+        self.aggregates.retain(|a| !is_excluded(&a.name));
+        self.operators.retain(|o| !is_excluded(&o.name));
+        self.operator_classes.retain(|o| !is_excluded(&o.name));
+        self.views.retain(|v| !is_excluded(&v.name));
+        self.publications.retain(|p| !is_excluded(&p.name));
+        self.default_privileges.retain(|d| !is_excluded(&d.name));
+    }
+
+    /// Use the metadata scraped from the database to create SQL source control files in the
+    /// `output_path` provided.
+    ///
+    /// This creates files in subdirectories:
+    /// - role, 1 per role (only when roles are included, see [is_include_roles]) and 1 per stub
+    ///   of a referenced role (only when roles are emitted, see [Database::role_stubs])
+    /// - tablespace, 1 per stub of a referenced tablespace (only when
+    ///   [ScriptOptions::emit_tablespaces] is set, see [Database::tablespace_stubs])
+    /// - schema, 1 per schema with the default privileges that apply to the schema included in
+    ///   this file
+    /// - database, 1 file containing the database's settings (if any)
+    /// - extension, 1 per extension
+    /// - access_method, 1 per access method
+    /// - text_search_dictionary, 1 per text search dictionary
+    /// - text_search_configuration, 1 per text search configuration
+    /// - composite, 1 per composite UDT
+    /// - enum, 1 per enum UDT
+    /// - table, 1 per table with all constraints, indexes, statistics, triggers and policies owned
+    ///   by the table included in this file
+    /// - view, 1 per view with all triggers owned by the view included in this file
+    /// - sequence, 1 per sequence
+    /// - function, 1 per function
+    /// - event_trigger, 1 per event trigger
+    /// - procedure, 1 per procedure
+    /// - aggregate, 1 per aggregate function
+    /// - operator, 1 per operator
+    /// - operator_class, 1 per operator class
+    /// - publication, 1 per publication
+    /// - default_privileges, 1 per grantor and object type of the default privileges that apply
+    ///   to all schemas (or the `public` schema)
+    ///
+    /// ## Errors
+    /// - General format errors when attempting to write the statements to a string buffer
+    /// - General IO errors when writing the string buffer to the file
+    ///
+    /// When `idempotent` is true, the statements are written so they can be re-run against a
+    /// database where the objects already exist (see [SqlObject::create_statements_idempotent]).
+    ///
+    /// The subdirectories listed above are the default [ScriptLayout::Type] layout. Other layouts
+    /// can be chosen using `layout`.
+    ///
+    /// File names are derived from the object names but sanitized so the files can be written on
+    /// all platforms and never share a path on case-insensitive filesystems (see [ScriptFiles]).
+    /// The [SCRIPT_MANIFEST_FILE_NAME] written to the `output_path` maps each file back to the
+    /// object defined within the file.
+    ///
+    /// When `inline_constraints` is true, constraints that [can be
+    /// inlined](Constraint::can_be_inlined) are defined within the `CREATE TABLE` statement of
+    /// the owner table rather than appended to the table file as `ALTER TABLE` statements.
+    ///
+    /// Primary key and unique constraints that can use a unique index of the owner table (see
+    /// [Constraint::can_use_index]) are added with `ADD CONSTRAINT ... USING INDEX` after the
+    /// table's indexes so the index is only created once, within the table file.
+    ///
+    /// Ignored objects (along with objects owned by an ignored table or view) are not scripted.
+    ///
+    /// See [write_create_statements_to_file]
+    /// See [append_create_statements_to_owner_table_file]
+    pub async fn script_out<P>(
+        &self,
+        output_path: P,
+        options: ScriptOptions,
+    ) -> Result<(), PgDiffError>
+    where
+        P: AsRef<Path>,
+    {
+        let ScriptOptions {
+            idempotent,
+            layout,
+            inline_constraints,
+            emit_tablespaces,
+            ignored_objects,
+        } = options;
+        let mut files = ScriptFiles::new(output_path.as_ref(), layout, &ignored_objects);
+        if !self.settings.settings.is_empty() {
+            let mut statements = String::new();
+            self.settings.write_set_statements(&mut statements)?;
+            let path = files.path(
+                "DATABASE",
+                &SchemaQualifiedName::new("", &self.settings.name),
+            );
+            create_parent_directory(&path).await?;
+            let mut file = File::create(path).await?;
+            file.write_all(statements.as_bytes()).await?;
+        }
+        for role in &self.roles {
+            write_create_statements_to_file(role, &mut files, idempotent).await?;
+        }
+        if is_emit_roles() {
+            for role in self.role_stubs() {
+                write_create_statements_to_file(&role, &mut files, idempotent).await?;
+            }
+        }
+        if emit_tablespaces {
+            for tablespace in self.tablespace_stubs() {
+                write_create_statements_to_file(&tablespace, &mut files, idempotent).await?;
+            }
+        }
+        for schema in &self.schemas {
+            write_create_statements_to_file(schema, &mut files, idempotent).await?;
+        }
+        for extension in &self.extensions {
+            write_create_statements_to_file(extension, &mut files, idempotent).await?;
+        }
+        for access_method in &self.access_methods {
+            write_create_statements_to_file(access_method, &mut files, idempotent).await?;
+        }
+        for dictionary in &self.text_search_dictionaries {
+            write_create_statements_to_file(dictionary, &mut files, idempotent).await?;
+        }
+        for configuration in &self.text_search_configurations {
+            write_create_statements_to_file(configuration, &mut files, idempotent).await?;
+        }
+        for udt in &self.udts {
+            write_create_statements_to_file(udt, &mut files, idempotent).await?;
+        }
+        // Constraints that can be added using a unique index of the table are added once the index
+        // exists so the index is not built a second time
+        let indexes: Vec<&Index> = self.indexes.iter().collect();
+        let constraint_indexes = pair_constraint_indexes(self.constraints.iter(), &indexes);
+        for table in &self.tables {
+            let (table_constraints, constraints): (Vec<&Constraint>, Vec<&Constraint>) = self
+                .constraints
+                .iter()
+                .filter(|c| c.table_oid == table.oid)
+                .partition(|c| {
+                    inline_constraints
+                        && table.can_inline_constraints()
+                        && c.can_be_inlined()
+                        && !constraint_indexes.contains_key(&c.schema_qualified_name)
+                });
+            let (index_constraints, constraints): (Vec<&Constraint>, Vec<&Constraint>) =
+                constraints
+                    .into_iter()
+                    .partition(|c| constraint_indexes.contains_key(&c.schema_qualified_name));
+            // Inlined constraints are defined within the table's create statement
+            write_statements_to_file(table, &mut files, |statements| {
+                table.create_statement(statements, idempotent, &table_constraints)
+            })
+            .await?;
+            for constraint in constraints {
+                append_create_statements_to_owner_table_file(
+                    constraint,
+                    &constraint.owner_table_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?
+            }
+            for index in self.indexes.iter().filter(|i| i.table_oid == table.oid) {
+                append_create_statements_to_owner_table_file(
+                    index,
+                    &index.owner_table_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?
+            }
+            if !index_constraints.is_empty() && !files.is_ignored(&table.name) {
+                let mut statements = String::new();
+                for constraint in index_constraints {
+                    constraint.create_statements_using_index_with(
+                        constraint_indexes[&constraint.schema_qualified_name],
+                        idempotent,
+                        &mut statements,
+                    )?;
+                }
+                append_statements_to_path(&statements, &files.path("TABLE", &table.name)).await?;
+            }
+            // Replica identities using a constraint's index are set by the constraint
+            if table.replica_identity_index().is_some()
+                && !self
+                    .constraints
+                    .iter()
+                    .any(|c| c.is_replica_identity && c.owner_table_name == table.name)
+                && !files.is_ignored(&table.name)
+            {
+                let mut statements = String::new();
+                table.write_replica_identity(&mut statements, &table.replica_identity)?;
+                append_statements_to_path(&statements, &files.path("TABLE", &table.name)).await?;
+            }
+            for statistics in self.statistics.iter().filter(|s| s.table_oid == table.oid) {
+                append_create_statements_to_owner_table_file(
+                    statistics,
+                    &statistics.owner_table_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?
+            }
+            for trigger in self.triggers.iter().filter(|t| t.owner_oid == table.oid) {
+                append_create_statements_to_owner_table_file(
+                    trigger,
+                    &trigger.owner_object_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?
+            }
+            for policy in self.policies.iter().filter(|c| c.table_oid == table.oid) {
+                append_create_statements_to_owner_table_file(
+                    policy,
+                    &policy.owner_table_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?
+            }
+        }
+        for view in &self.views {
+            write_create_statements_to_file(view, &mut files, idempotent).await?;
+            for trigger in self.triggers.iter().filter(|t| t.owner_oid == view.oid) {
+                append_create_statements_to_owner_file(trigger, view, &mut files, idempotent)
+                    .await?
+            }
+        }
+        for sequence in &self.sequences {
+            if let Some(owner_table) = &sequence.owner {
+                append_create_statements_to_owner_table_file(
+                    sequence,
+                    &owner_table.table_name,
+                    &mut files,
+                    idempotent,
+                )
+                .await?;
+            } else {
+                write_create_statements_to_file(sequence, &mut files, idempotent).await?;
+            }
+        }
+        for function in &self.functions {
+            write_create_statements_to_file(function, &mut files, idempotent).await?;
+            if is_warn_function_search_path() && !files.is_ignored(&function.name) {
+                if let Some(warning) = function.search_path_warning() {
+                    eprintln!("{warning}");
+                }
+            }
+        }
+        for event_trigger in &self.event_triggers {
+            write_create_statements_to_file(event_trigger, &mut files, idempotent).await?;
+        }
+        for aggregate in &self.aggregates {
+            write_create_statements_to_file(aggregate, &mut files, idempotent).await?;
+        }
+        for operator in &self.operators {
+            write_create_statements_to_file(operator, &mut files, idempotent).await?;
+        }
+        for operator_class in &self.operator_classes {
+            write_create_statements_to_file(operator_class, &mut files, idempotent).await?;
+        }
+        for publication in &self.publications {
+            write_create_statements_to_file(publication, &mut files, idempotent).await?;
+        }
+        for default_privileges in &self.default_privileges {
+            match self
+                .schemas
+                .iter()
+                .find(|s| s.name.schema_name == default_privileges.name.schema_name)
+            {
+                Some(schema) => {
+                    append_create_statements_to_owner_file(
+                        default_privileges,
+                        schema,
+                        &mut files,
+                        idempotent,
+                    )
+                    .await?
+                },
+                None => {
+                    write_create_statements_to_file(default_privileges, &mut files, idempotent)
+                        .await?
+                },
+            }
+        }
+        files.write_manifest().await?;
+        Ok(())
+    }
+
+    /// Script the database the same as [Database::script_out] but only write the files whose
+    /// contents differ from the file of the same relative path within the `baseline_path`. Files are
+    /// compared after normalizing line endings and trailing whitespace.
+    ///
+    /// The files are first scripted into a temp directory that is removed once the changed files
+    /// are written to the `output_path`. Files within the `baseline_path` that are no longer
+    /// scripted are reported as removed but never deleted.
+    ///
+    /// ## Errors
+    /// See [Database::script_out]. Also fails if the baseline or scripted files cannot be read.
+    pub async fn script_out_changed<P, B>(
+        &self,
+        output_path: P,
+        baseline_path: B,
+        options: ScriptOptions,
+    ) -> Result<ScriptChanges, PgDiffError>
+    where
+        P: AsRef<Path>,
+        B: AsRef<Path>,
+    {
+        let staging_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_script_{}", Uuid::new_v4().simple()));
+        let result = match self.script_out(&staging_path, options).await {
+            Ok(_) => {
+                ScriptChanges::write_changed_files(
+                    &staging_path,
+                    output_path.as_ref(),
+                    baseline_path.as_ref(),
+                )
+                .await
+            },
+            Err(error) => Err(error),
+        };
+        if tokio::fs::try_exists(&staging_path).await? {
+            tokio::fs::remove_dir_all(&staging_path).await?;
+        }
+        result
+    }
+
+    /// Compare this database to another database. Assumes the other database is the desired state
+    /// of the database and this object is the current state that needs to be migrated.
+    ///
+    /// The plan is the statements of every step found by [Database::migration_steps]. If
+    /// `include_header` is true, a non-empty plan is prefixed with a comment block summarizing the
+    /// plan (see [PlanSummary::write_header]) and suffixed by a readable summary of the changes
+    /// (see [PlanSummary::write_footer]). Non-empty plans are also surrounded by the session
+    /// preamble and transaction statements requested by the `options` (see
+    /// [MigrationOptions::write_preamble]). Statements of a transactional plan that cannot run
+    /// within a transaction block are moved before or after the transaction (see
+    /// [TransactionSections]). When roles are emitted, the stubs of the roles
+    /// referenced by the other database are created before any step (see
+    /// [Database::write_role_stubs]). If `disable_triggers` is true, the steps are surrounded by
+    /// the statements that disable and enable the triggers of altered tables (see
+    /// [DisabledTriggers]). If `highlight` is true, each step is highlighted for display within a
+    /// terminal (see [MigrationStep::write_highlighted]). If `post_migration_maintenance` is
+    /// true, the maintenance section is written after all other statements (see
+    /// [PostMigrationMaintenance::write]).
+    fn compare_to_other_database(
+        &self,
+        other: &Self,
+        options: MigrationOptions,
+    ) -> Result<String, PgDiffError> {
+        self.plan_to_other_database(other, options)
+            .map(|plan| plan.deploy)
+    }
+
+    /// Plan the migration of this database to another database (see
+    /// [Database::compare_to_other_database]). The returned plan only contains the deploy
+    /// statements and the number of those statements that must be applied.
+    fn plan_to_other_database(
+        &self,
+        other: &Self,
+        options: MigrationOptions,
+    ) -> Result<MigrationPlan, PgDiffError> {
+        let (mut steps, maintenance) = self.migration_steps(other, &options)?;
+        if steps.is_empty() {
+            return Ok(MigrationPlan::default());
+        }
+        let summary = PlanSummary::from_steps(&steps);
+        eprintln!("{summary}");
+        if options.fail_on_manual && !summary.manual_steps.is_empty() {
+            return Err(PgDiffError::ManualChangesRequired {
+                objects: summary.manual_steps,
+            });
+        }
+        let transaction_sections = if options.transactional {
+            TransactionSections::extract(&mut steps)
+        } else {
+            TransactionSections::default()
+        };
+        let mut plan = String::new();
+        if options.include_header {
+            summary.write_header(&mut plan, &self.settings.name)?;
+        }
+        options.write_preamble(&mut plan, &transaction_sections)?;
+        if is_emit_roles() {
+            other.write_role_stubs(&mut plan)?;
+        }
+        let disabled_triggers = if options.disable_triggers {
+            DisabledTriggers::from_steps(&steps, &self.triggers)
+        } else {
+            DisabledTriggers::default()
+        };
+        disabled_triggers.write_disable(&mut plan)?;
+        for step in &steps {
+            if options.highlight {
+                step.write_highlighted(&mut plan)?;
+            } else {
+                plan.push_str(&step.sql);
+            }
+        }
+        disabled_triggers.write_enable(&mut plan)?;
+        options.write_postamble(&mut plan, &transaction_sections)?;
+        if options.post_migration_maintenance {
+            maintenance.write(&mut plan)?;
+        }
+        if options.include_header {
+            summary.write_footer(&mut plan)?;
+        }
+        Ok(MigrationPlan {
+            deploy: plan,
+            statement_count: summary.statement_count + summary.manual_steps.len(),
+            ..Default::default()
+        })
+    }
+
+    /// Compare this database to another database, returning each step required to migrate this
+    /// database to the other database (in the order the steps must be executed) along with the
+    /// maintenance required after the migration. Steps found by the comparison are reordered
+    /// using [order_steps] before any constraint validations and database setting changes. Event
+    /// trigger drops are moved to the start of the plan and all other event trigger steps are
+    /// moved to the end since event triggers fire for the plan's own statements.
+    ///
+    /// If `cascade_schema_drop` is true, dropped schemas are dropped with `CASCADE` and the drops of
+    /// all objects within those schemas are omitted. If `add_constraints_not_valid` is true, new
+    /// check and foreign key constraints are added as `NOT VALID` and validated at the end of the
+    /// migration. If `safe_not_null` is true and this database's server supports it, columns of
+    /// altered tables are set as `NOT NULL` using a validated check constraint (see
+    /// [Table::alter_table]). If `backfill_nulls` is true, `NULL` values of columns becoming
+    /// `NOT NULL` are set to the column's default first. If `idempotent` is true, creates, drops
+    /// and column changes are guarded so the plan can be applied more than once (see
+    /// [RenderContext]). If `detect_renames` is true and all
+    /// drops are included, renamed schemas are renamed before any other step (see
+    /// [SchemaRename]) and the drops and creates replaced by the rename are omitted. Drop steps
+    /// are included, skipped or exclusively included based upon the `drop_mode`. Planning a schema
+    /// drop fails unless `allow_destructive` is true. The other options only apply to the plan as
+    /// a whole.
+    fn migration_steps<'d>(
+        &'d self,
+        other: &'d Self,
+        options: &MigrationOptions,
+    ) -> Result<(Vec<MigrationStep>, PostMigrationMaintenance<'d>), PgDiffError> {
+        println!("Comparing source control database to actual database");
+        let mut steps: Vec<MigrationStep> = vec![];
+        let mut compare = DbCompare::new(self, other, options.cascade_schema_drop);
+        // Views dropped ahead of time because a view they depend on must be recreated
+        let mut dropped_views: Vec<&SchemaQualifiedName> = vec![];
+        // Views dropped (or recreated) by the migration. Triggers on these views are dropped along
+        // with the view so they must be recreated rather than altered or dropped.
+        let mut views_without_triggers: Vec<&SchemaQualifiedName> = vec![];
+        // Functions dropped ahead of time because they reference a dropped table column
+        let mut dropped_functions: Vec<&SchemaQualifiedName> = vec![];
+        // Constraints added as `NOT VALID` that must be validated after all other statements
+        let mut constraints_to_validate: Vec<&Constraint> = vec![];
+        // Existing unique indexes used by new primary key and unique constraints
+        let constraint_indexes = self.constraint_indexes(other);
+        let mut maintenance = PostMigrationMaintenance::default();
+        let schema_renames = if options.detect_renames && options.drop_mode == DropMode::Include {
+            SchemaRename::detect(self, other)?
+        } else {
+            vec![]
+        };
+        let safe_not_null = options.safe_not_null && self.settings.can_skip_not_null_scan();
+        let context = RenderContext {
+            idempotent: options.idempotent,
+            safe_not_null,
+            backfill_nulls: options.backfill_nulls,
+        };
+        for obj in compare.by_ref() {
+            if obj.is_ignored(&options.ignored_objects)
+                || schema_renames.iter().any(|rename| rename.replaces(&obj))
+            {
+                continue;
+            }
+            if !options.drop_mode.includes(&obj) {
+                if options.drop_mode == DropMode::Exclude {
+                    let mut sql = String::new();
+                    obj.write_skipped_drop_comment(&mut sql)?;
+                    steps.push(MigrationStep::new(StepKind::SkippedDrop, &obj, sql, false));
+                }
+                continue;
+            }
+            if !options.drop_roles && matches!(obj, DbCompareResult::Drop(SqlObjectEnum::Role(_))) {
+                let mut sql = String::new();
+                obj.write_skipped_drop_comment(&mut sql)?;
+                steps.push(MigrationStep::new(StepKind::SkippedDrop, &obj, sql, false));
+                continue;
+            }
+            let kind = obj.step_kind();
+            let is_destructive = obj.is_destructive();
+            let maintenance_targets = PostMigrationMaintenance::targets(&obj);
+            let mut sql = String::new();
+            // Comments noting the alterations that must be made manually
+            let mut manual_sql = None;
+            if let DbCompareResult::Alter {
+                old: SqlObjectEnum::Table(old),
+                new: SqlObjectEnum::Table(new),
+            } = &obj
+            {
+                // Views referencing a dropped column block the column's drop so they are dropped
+                // ahead of time and created again once the view is reached
+                let dropped_columns = old.dropped_columns(new);
+                for view in self.views_referencing_columns(old, &dropped_columns) {
+                    if !dropped_views.contains(&&view.name) {
+                        view.drop_statements_with(&context, &mut sql)?;
+                        dropped_views.push(&view.name);
+                        views_without_triggers.push(&view.name);
+                    }
+                }
+                // The same applies to functions with pre-parsed bodies
+                for function in self
+                    .functions
+                    .iter()
+                    .filter(|f| f.references_columns(&old.name, &dropped_columns))
+                {
+                    if !dropped_functions.contains(&&function.name) {
+                        function.drop_statements_with(&context, &mut sql)?;
+                        dropped_functions.push(&function.name);
+                    }
+                }
+            }
+            match &obj {
+                DbCompareResult::Create(SqlObjectEnum::Constraint(new))
+                    if options.add_constraints_not_valid && new.can_be_added_not_valid() =>
+                {
+                    if context.idempotent {
+                        let mut statements = String::new();
+                        new.create_statements_not_valid(&mut statements)?;
+                        new.write_missing_constraint_guard(&mut sql, &statements)?;
+                    } else {
+                        new.create_statements_not_valid(&mut sql)?;
+                    }
+                    constraints_to_validate.push(new);
+                },
+                DbCompareResult::Create(SqlObjectEnum::Constraint(new))
+                    if constraint_indexes.contains_key(&new.schema_qualified_name) =>
+                {
+                    let index = constraint_indexes[&new.schema_qualified_name];
+                    new.create_statements_using_index_with(index, context.idempotent, &mut sql)?;
+                },
+                DbCompareResult::Create(new) => new.create_statements_with(&context, &mut sql)?,
+                DbCompareResult::Alter {
+                    old: SqlObjectEnum::View(old),
+                    new: SqlObjectEnum::View(new),
+                } => {
+                    if dropped_views.contains(&&old.name) {
+                        new.create_statements_with(&context, &mut sql)?;
+                    } else {
+                        if old.requires_recreation(new) {
+                            for view in self.dependent_views(old) {
+                                view.drop_statements_with(&context, &mut sql)?;
+                                dropped_views.push(&view.name);
+                                views_without_triggers.push(&view.name);
+                            }
+                            views_without_triggers.push(&old.name);
+                        }
+                        old.alter_statements_with(new, &context, &mut sql)?;
+                    }
+                },
+                DbCompareResult::Alter {
+                    old: SqlObjectEnum::Function(old),
+                    new: SqlObjectEnum::Function(new),
+                } if dropped_functions.contains(&&old.name) => {
+                    old.recreate_statements(new, &mut sql)?;
+                },
+                DbCompareResult::Alter {
+                    old: SqlObjectEnum::Trigger(old),
+                    new: SqlObjectEnum::Trigger(new),
+                } if views_without_triggers.contains(&&old.owner_object_name) => {
+                    new.create_statements_with(&context, &mut sql)?;
+                },
+                DbCompareResult::Alter {
+                    old: SqlObjectEnum::Table(old),
+                    new: SqlObjectEnum::Table(new),
+                } => {
+                    let result = old.alter_table(new, &context, &mut sql);
+                    manual_sql = options.on_unsupported.handle(result, "TABLE")?;
+                },
+                DbCompareResult::Alter { old, new } => {
+                    let result = old.alter_statements_with(new, &context, &mut sql);
+                    manual_sql = options
+                        .on_unsupported
+                        .handle(result, old.object_type_name())?;
+                },
+                DbCompareResult::Drop(SqlObjectEnum::View(old))
+                    if dropped_views.contains(&&old.name) => {},
+                DbCompareResult::Drop(SqlObjectEnum::Function(old))
+                    if dropped_functions.contains(&&old.name) => {},
+                DbCompareResult::Drop(SqlObjectEnum::View(old)) => {
+                    old.drop_statements_with(&context, &mut sql)?;
+                    views_without_triggers.push(&old.name);
+                },
+                DbCompareResult::Drop(SqlObjectEnum::Index(old))
+                    if constraint_indexes
+                        .values()
+                        .any(|index| index.schema_qualified_name == old.schema_qualified_name) => {
+                },
+                DbCompareResult::Drop(SqlObjectEnum::Trigger(old))
+                    if views_without_triggers.contains(&&old.owner_object_name) => {},
+                DbCompareResult::Drop(old) => old.drop_statements_with(&context, &mut sql)?,
+                DbCompareResult::DropSchemaCascade(schema) => {
+                    schema.drop_cascade_statements(&context, &mut sql)?
+                },
+            }
+            if !sql.is_empty() {
+                steps.push(MigrationStep::new(kind, &obj, sql, is_destructive));
+                maintenance.add(maintenance_targets);
+            }
+            if let Some(manual_sql) = manual_sql {
+                steps.push(MigrationStep::new(
+                    StepKind::Manual,
+                    &obj,
+                    manual_sql,
+                    false,
+                ));
+            }
+            if let Some(step) = MigrationStep::replica_identity(&obj, &other.constraints)? {
+                steps.push(step);
+            }
+        }
+        compare.check_completed()?;
+        if !options.allow_destructive {
+            let schemas: Vec<String> = steps
+                .iter()
+                .filter(|s| s.kind == StepKind::Drop && s.object_type == "SCHEMA")
+                .map(|s| s.object.to_string())
+                .collect();
+            if !schemas.is_empty() {
+                return Err(PgDiffError::DestructiveSchemaDrop { schemas });
+            }
+        }
+        let mut steps = order_steps(steps)?;
+        for (index, rename) in schema_renames.iter().enumerate() {
+            steps.insert(index, rename.step()?);
+        }
+        for constraint in constraints_to_validate {
+            let mut sql = String::new();
+            constraint.validate_statements(&mut sql)?;
+            steps.push(MigrationStep {
+                kind: StepKind::Validate,
+                object: constraint.schema_qualified_name.clone(),
+                object_type: constraint.object_type_name().to_string(),
+                sql,
+                is_destructive: false,
+                table_step: None,
+                dependencies: vec![constraint.schema_qualified_name.clone()],
+            });
+        }
+        if options.drop_mode.includes_alters() {
+            let mut sql = String::new();
+            self.settings.alter_statements(&other.settings, &mut sql)?;
+            if !sql.is_empty() {
+                steps.push(MigrationStep {
+                    kind: StepKind::Alter,
+                    object: SchemaQualifiedName::new("", &self.settings.name),
+                    object_type: "DATABASE".into(),
+                    sql,
+                    is_destructive: false,
+                    table_step: None,
+                    dependencies: vec![],
+                });
+            }
+        }
+        // Event triggers fire for the plan's own DDL so existing event triggers are dropped before
+        // any other step and new or altered event triggers are applied after every other step
+        let (event_trigger_steps, other_steps): (Vec<MigrationStep>, Vec<MigrationStep>) =
+            steps.into_iter().partition(MigrationStep::is_event_trigger);
+        let (mut steps, event_trigger_steps): (Vec<MigrationStep>, Vec<MigrationStep>) =
+            event_trigger_steps
+                .into_iter()
+                .partition(|s| s.kind == StepKind::Drop);
+        steps.extend(other_steps);
+        steps.extend(event_trigger_steps);
+        println!("Done!");
+        Ok((steps, maintenance))
+    }
+
+    /// Find the existing unique index each new primary key or unique constraint of the `other`
+    /// database can be added with (see [pair_constraint_indexes]). Only indexes removed by the
+    /// migration are used since the server renames the index to the constraint's name.
+    fn constraint_indexes<'d>(
+        &'d self,
+        other: &'d Self,
+    ) -> HashMap<&'d SchemaQualifiedName, &'d Index> {
+        let new_constraints = other.constraints.iter().filter(|constraint| {
+            !self
+                .constraints
+                .iter()
+                .any(|c| c.schema_qualified_name == constraint.schema_qualified_name)
+        });
+        let removed_indexes: Vec<&Index> = self
+            .indexes
+            .iter()
+            .filter(|index| {
+                !other
+                    .indexes
+                    .iter()
+                    .any(|i| i.schema_qualified_name == index.schema_qualified_name)
+            })
+            .collect();
+        pair_constraint_indexes(new_constraints, &removed_indexes)
+    }
+
+    /// Find all views that directly or indirectly depend on the `view` provided. Views are
+    /// ordered so that each view comes before any view it depends on (i.e. the order views must
+    /// be dropped).
+    fn dependent_views(&self, view: &View) -> Vec<&View> {
+        let mut dependents: Vec<&View> = vec![];
+        let mut names = vec![&view.name];
+        while let Some(dependent) = self.views.iter().find(|v| {
+            !names.contains(&&v.name) && v.dependencies.iter().any(|d| names.contains(&d))
+        }) {
+            names.push(&dependent.name);
+            dependents.push(dependent);
+        }
+
+        let mut ordered: Vec<&View> = Vec::with_capacity(dependents.len());
+        while !dependents.is_empty() {
+            let index = dependents
+                .iter()
+                .position(|v| {
+                    !dependents
+                        .iter()
+                        .any(|other| other.dependencies.contains(&v.name))
+                })
+                .unwrap_or_default();
+            ordered.push(dependents.remove(index));
+        }
+        ordered
+    }
+
+    /// Find all views that reference any of the `columns` of the `table` (see
+    /// [View::references_columns]) along with the views that depend on those views. Views are
+    /// ordered so that each view comes before any view it depends on (i.e. the order views must
+    /// be dropped).
+    fn views_referencing_columns(&self, table: &Table, columns: &[&str]) -> Vec<&View> {
+        let mut views: Vec<&View> = vec![];
+        for view in self
+            .views
+            .iter()
+            .filter(|v| v.references_columns(&table.name, columns))
+        {
+            for dropped in self.dependent_views(view).into_iter().chain([view]) {
+                if !views.iter().any(|v| v.name == dropped.name) {
+                    views.push(dropped);
+                }
+            }
+        }
+        views
+    }
+
+    /// Number of objects of each type found within this database
+    pub fn summary(&self) -> DatabaseSummary {
+        let mut summary = DatabaseSummary::default();
+        for object in self.objects() {
+            let object_type = object.object_type_name();
+            match summary
+                .object_counts
+                .iter_mut()
+                .find(|(name, _)| name == object_type)
+            {
+                Some((_, count)) => *count += 1,
+                None => summary.object_counts.push((object_type.to_string(), 1)),
+            }
+        }
+        summary
+    }
+
+    /// Object type and name of every object within this database, excluding roles since roles
+    /// exist at the cluster level
+    fn object_descriptions(&self) -> Vec<String> {
+        self.objects()
+            .filter(|object| !matches!(object, SqlObjectEnum::Role(_)))
+            .map(|object| format!("{} {}", object.object_type_name(), object.name()))
+            .collect()
+    }
+
+    /// Names of every role referenced by the objects of this database (i.e. schema and table
+    /// owners, function grantees and policy roles) in name order. `PUBLIC` and the special role
+    /// names (e.g. `CURRENT_USER`) are not included since they are not roles.
+    fn referenced_roles(&self) -> Vec<&str> {
+        let mut roles: Vec<&str> = self
+            .schemas
+            .iter()
+            .map(|schema| schema.owner.as_str())
+            .chain(self.tables.iter().map(|table| table.owner.as_str()))
+            .chain(
+                self.functions
+                    .iter()
+                    .flat_map(|function| function.grants.iter())
+                    .map(|grant| grant.grantee.as_str()),
+            )
+            .chain(
+                self.default_privileges
+                    .iter()
+                    .flat_map(|default_privileges| {
+                        default_privileges
+                            .grants
+                            .iter()
+                            .map(|grant| grant.grantee.as_str())
+                            .chain(std::iter::once(default_privileges.grantor.as_str()))
+                    }),
+            )
+            .chain(
+                self.policies
+                    .iter()
+                    .flat_map(|policy| policy.applies_to.iter())
+                    .map(String::as_str),
+            )
+            .filter(|role| {
+                !["public", "current_user", "current_role", "session_user"]
+                    .contains(&role.to_lowercase().as_str())
+            })
+            .collect();
+        roles.sort();
+        roles.dedup();
+        roles
+    }
+
+    /// Stubs of every role referenced by the objects of this database that is not already a role
+    /// of this database (see [Database::referenced_roles]). Stubs cannot log in and never have a
+    /// password since they only exist so that the objects referencing them can be created.
+    fn role_stubs(&self) -> Vec<Role> {
+        self.referenced_roles()
+            .into_iter()
+            .map(|role| SchemaQualifiedName::new("", role))
+            .filter(|name| !self.roles.iter().any(|r| &r.name == name))
+            .map(|name| Role {
+                name,
+                can_login: false,
+                inherit: true,
+                member_of: vec![],
+                dependencies: vec![],
+            })
+            .collect()
+    }
+
+    /// Write the statements that create each role stub of this database (see
+    /// [Database::role_stubs]). Stubs are only created when the role does not already exist since
+    /// Postgresql does not support `CREATE ROLE IF NOT EXISTS`.
+    fn write_role_stubs<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        for role in self.role_stubs() {
+            role.create_statements_idempotent(w)?;
+        }
+        Ok(())
+    }
+
+    /// Names of every tablespace referenced by the tables, indexes and constraints of this
+    /// database in name order. The built-in tablespaces are not included since they always exist.
+    fn referenced_tablespaces(&self) -> Vec<&str> {
+        let mut tablespaces: Vec<&str> = self
+            .tables
+            .iter()
+            .filter_map(|table| table.tablespace.as_ref())
+            .chain(
+                self.indexes
+                    .iter()
+                    .filter_map(|index| index.parameters.tablespace.as_ref()),
+            )
+            .chain(
+                self.constraints
+                    .iter()
+                    .filter_map(|constraint| constraint.tablespace()),
+            )
+            .map(|tablespace| tablespace.0.as_str())
+            .filter(|tablespace| !["pg_default", "pg_global"].contains(tablespace))
+            .collect();
+        tablespaces.sort();
+        tablespaces.dedup();
+        tablespaces
+    }
+
+    /// Stubs of every tablespace referenced by the objects of this database (see
+    /// [Database::referenced_tablespaces]). Stubs use a placeholder location since the directory
+    /// of a tablespace is specific to the server.
+    fn tablespace_stubs(&self) -> Vec<TablespaceStub> {
+        self.referenced_tablespaces()
+            .into_iter()
+            .map(|tablespace| TablespaceStub {
+                name: SchemaQualifiedName::new("", tablespace),
+            })
+            .collect()
+    }
+
+    /// Copy of this database with the same name, roles and settings but no other objects.
+    /// Comparing this database to the copy gives the statements that drop every object.
+    fn empty_copy(&self) -> Self {
+        Self {
+            roles: self.roles.clone(),
+            schemas: vec![],
+            udts: vec![],
+            tables: vec![],
+            policies: vec![],
+            constraints: vec![],
+            operators: vec![],
+            operator_classes: vec![],
+            indexes: vec![],
+            statistics: vec![],
+            triggers: vec![],
+            sequences: vec![],
+            functions: vec![],
+            aggregates: vec![],
+            views: vec![],
+            extensions: vec![],
+            access_methods: vec![],
+            event_triggers: vec![],
+            text_search_dictionaries: vec![],
+            text_search_configurations: vec![],
+            publications: vec![],
+            default_privileges: vec![],
+            settings: DatabaseSettings {
+                name: self.settings.name.clone(),
+                settings: self.settings.settings.clone(),
+                server_version_num: self.settings.server_version_num,
+            },
+        }
+    }
+
+    /// Iterate over all objects within this database as [SqlObjectEnum]s. Order of the objects
+    /// follows the order of each object type's collection and does not consider dependencies.
+    fn objects(&self) -> impl Iterator<Item = SqlObjectEnum<'_>> {
+        self.roles
+            .iter()
+            .map(SqlObjectEnum::Role)
+            .chain(self.schemas.iter().map(SqlObjectEnum::Schema))
+            .chain(self.extensions.iter().map(SqlObjectEnum::Extension))
+            .chain(
+                self.text_search_dictionaries
+                    .iter()
+                    .map(SqlObjectEnum::TextSearchDictionary),
+            )
+            .chain(
+                self.text_search_configurations
+                    .iter()
+                    .map(SqlObjectEnum::TextSearchConfiguration),
+            )
+            .chain(self.udts.iter().map(SqlObjectEnum::Udt))
+            .chain(self.tables.iter().map(SqlObjectEnum::Table))
+            .chain(self.constraints.iter().map(SqlObjectEnum::Constraint))
+            .chain(self.operators.iter().map(SqlObjectEnum::Operator))
+            .chain(
+                self.operator_classes
+                    .iter()
+                    .map(SqlObjectEnum::OperatorClass),
+            )
+            .chain(self.indexes.iter().map(SqlObjectEnum::Index))
+            .chain(self.statistics.iter().map(SqlObjectEnum::Statistics))
+            .chain(self.triggers.iter().map(SqlObjectEnum::Trigger))
+            .chain(self.policies.iter().map(SqlObjectEnum::Policy))
+            .chain(self.views.iter().map(SqlObjectEnum::View))
+            .chain(self.sequences.iter().map(SqlObjectEnum::Sequence))
+            .chain(self.functions.iter().map(SqlObjectEnum::Function))
+            .chain(self.aggregates.iter().map(SqlObjectEnum::Aggregate))
+            .chain(self.access_methods.iter().map(SqlObjectEnum::AccessMethod))
+            .chain(self.event_triggers.iter().map(SqlObjectEnum::EventTrigger))
+            .chain(self.publications.iter().map(SqlObjectEnum::Publication))
+            .chain(
+                self.default_privileges
+                    .iter()
+                    .map(SqlObjectEnum::DefaultPrivileges),
+            )
+    }
+}
+
+struct DbIter<'d> {
+    database: &'d Database,
+    completed_objects: Vec<&'d SchemaQualifiedName>,
+    completed_roles: usize,
+    completed_schemas: usize,
+    completed_extensions: usize,
+    completed_text_search_dictionaries: usize,
+    completed_text_search_configurations: usize,
+    completed_udt: usize,
+    completed_tables: usize,
+    completed_constraints: usize,
+    completed_operators: usize,
+    completed_operator_classes: usize,
+    completed_indexes: usize,
+    completed_statistics: usize,
+    completed_triggers: usize,
+    completed_policies: usize,
+    completed_views: usize,
+    completed_sequences: usize,
+    completed_functions: usize,
+    completed_aggregates: usize,
+    completed_access_methods: usize,
+    completed_event_triggers: usize,
+    completed_publications: usize,
+    completed_default_privileges: usize,
+}
+
+impl<'d> DbIter<'d> {
+    fn new(database: &'d Database) -> Self {
+        Self {
+            database,
+            completed_objects: vec![],
+            completed_roles: 0,
+            completed_schemas: 0,
+            completed_extensions: 0,
+            completed_text_search_dictionaries: 0,
+            completed_text_search_configurations: 0,
+            completed_udt: 0,
+            completed_tables: 0,
+            completed_constraints: 0,
+            completed_operators: 0,
+            completed_operator_classes: 0,
+            completed_indexes: 0,
+            completed_statistics: 0,
+            completed_triggers: 0,
+            completed_policies: 0,
+            completed_views: 0,
+            completed_sequences: 0,
+            completed_functions: 0,
+            completed_aggregates: 0,
+            completed_access_methods: 0,
+            completed_event_triggers: 0,
+            completed_publications: 0,
+            completed_default_privileges: 0,
+        }
+    }
+
+    /// Describe every object within the database that has not been yielded by this iterator, along
+    /// with the dependencies that are still unmet for each object. The `label` is used to identify
+    /// the database each object belongs to. Returns an empty [Vec] when all objects have been
+    /// processed.
+    fn unprocessed_objects(&self, label: &str) -> Vec<String> {
+        self.database
+            .objects()
+            .filter(|object| !self.completed_objects.contains(&object.name()))
+            .map(|object| {
+                let unmet_dependencies: Vec<String> = object
+                    .dependencies()
+                    .iter()
+                    .filter(|d| {
+                        !d.is_implicit_schema()
+                            && !is_excluded_object(d)
+                            && !self.completed_objects.contains(d)
+                    })
+                    .map(|d| d.to_string())
+                    .collect();
+                format!(
+                    "{label}: {} {} (unmet dependencies: {})",
+                    object.object_type_name(),
+                    object.name(),
+                    unmet_dependencies.join(", ")
+                )
+            })
+            .collect()
+    }
+}
+
+impl<'d> Iterator for DbIter<'d> {
+    type Item = SqlObjectEnum<'d>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.completed_roles < self.database.roles.len() {
+            if let Some(role) = self.database.roles.iter().find(|r| {
+                !self.completed_objects.contains(&&r.name)
+                    && r.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_roles += 1;
+                self.completed_objects.push(&role.name);
+                return Some(SqlObjectEnum::Role(role));
+            }
+        }
+
+        if self.completed_schemas < self.database.schemas.len() {
+            if let Some(schema) = self.database.schemas.iter().find(|s| {
+                !self.completed_objects.contains(&&s.name)
+                    && s.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_schemas += 1;
+                self.completed_objects.push(&schema.name);
+                return Some(SqlObjectEnum::Schema(schema));
+            }
+        }
+
+        // Default privileges only apply to objects created after the privileges are set so the
+        // privileges are yielded as soon as the schema and grantor role exist
+        if self.completed_default_privileges < self.database.default_privileges.len() {
+            if let Some(default_privileges) = self.database.default_privileges.iter().find(|d| {
+                !self.completed_objects.contains(&&d.name)
+                    && d.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_default_privileges += 1;
+                self.completed_objects.push(&default_privileges.name);
+                return Some(SqlObjectEnum::DefaultPrivileges(default_privileges));
+            }
+        }
+
+        if self.completed_extensions < self.database.extensions.len() {
+            if let Some(extension) = self.database.extensions.iter().find(|e| {
+                !self.completed_objects.contains(&&e.name)
+                    && e.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_extensions += 1;
+                self.completed_objects.push(&extension.name);
+                return Some(SqlObjectEnum::Extension(extension));
+            }
+        }
+
+        if self.completed_text_search_dictionaries < self.database.text_search_dictionaries.len() {
+            if let Some(dictionary) = self.database.text_search_dictionaries.iter().find(|d| {
+                !self.completed_objects.contains(&&d.name)
+                    && d.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_text_search_dictionaries += 1;
+                self.completed_objects.push(&dictionary.name);
+                return Some(SqlObjectEnum::TextSearchDictionary(dictionary));
+            }
+        }
+
+        if self.completed_text_search_configurations
+            < self.database.text_search_configurations.len()
+        {
+            if let Some(configuration) = self.database.text_search_configurations.iter().find(|c| {
+                !self.completed_objects.contains(&&c.name)
+                    && c.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_text_search_configurations += 1;
+                self.completed_objects.push(&configuration.name);
+                return Some(SqlObjectEnum::TextSearchConfiguration(configuration));
+            }
+        }
+
+        if self.completed_udt < self.database.udts.len() {
+            if let Some(udt) = self.database.udts.iter().find(|u| {
+                !self.completed_objects.contains(&&u.name)
+                    && u.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_udt += 1;
+                self.completed_objects.push(&udt.name);
+                return Some(SqlObjectEnum::Udt(udt));
+            }
+        }
+
+        if self.completed_tables < self.database.tables.len() {
+            if let Some(table) = self.database.tables.iter().find(|t| {
+                !self.completed_objects.contains(&&t.name)
+                    && t.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_tables += 1;
+                self.completed_objects.push(&table.name);
+                return Some(SqlObjectEnum::Table(table));
+            }
+        }
+
+        if self.completed_statistics < self.database.statistics.len() {
+            if let Some(statistics) = self.database.statistics.iter().find(|s| {
+                !self.completed_objects.contains(&&s.schema_qualified_name)
+                    && s.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_statistics += 1;
+                self.completed_objects
+                    .push(&statistics.schema_qualified_name);
+                return Some(SqlObjectEnum::Statistics(statistics));
+            }
+        }
+
+        if self.completed_constraints < self.database.constraints.len() {
+            if let Some(constraint) = self.database.constraints.iter().find(|c| {
+                !self.completed_objects.contains(&&c.schema_qualified_name)
+                    && c.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_constraints += 1;
+                self.completed_objects
+                    .push(&constraint.schema_qualified_name);
+                return Some(SqlObjectEnum::Constraint(constraint));
+            }
+        }
+
+        if self.completed_operators < self.database.operators.len() {
+            if let Some(operator) = self.database.operators.iter().find(|o| {
+                !self.completed_objects.contains(&&o.name)
+                    && o.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_operators += 1;
+                self.completed_objects.push(&operator.name);
+                return Some(SqlObjectEnum::Operator(operator));
+            }
+        }
+
+        if self.completed_operator_classes < self.database.operator_classes.len() {
+            if let Some(operator_class) = self.database.operator_classes.iter().find(|o| {
+                !self.completed_objects.contains(&&o.name)
+                    && o.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_operator_classes += 1;
+                self.completed_objects.push(&operator_class.name);
+                return Some(SqlObjectEnum::OperatorClass(operator_class));
+            }
+        }
+
+        if self.completed_indexes < self.database.indexes.len() {
+            if let Some(index) = self.database.indexes.iter().find(|i| {
+                !self.completed_objects.contains(&&i.schema_qualified_name)
+                    && i.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_indexes += 1;
+                self.completed_objects.push(&index.schema_qualified_name);
+                return Some(SqlObjectEnum::Index(index));
+            }
+        }
+
+        if self.completed_triggers < self.database.triggers.len() {
+            if let Some(trigger) = self.database.triggers.iter().find(|t| {
+                !self.completed_objects.contains(&&t.schema_qualified_name)
+                    && t.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_triggers += 1;
+                self.completed_objects.push(&trigger.schema_qualified_name);
+                return Some(SqlObjectEnum::Trigger(trigger));
+            }
+        }
+
+        if self.completed_policies < self.database.policies.len() {
+            if let Some(policy) = self.database.policies.iter().find(|s| {
+                !self.completed_objects.contains(&&s.schema_qualified_name)
+                    && s.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_policies += 1;
+                self.completed_objects.push(&policy.schema_qualified_name);
+                return Some(SqlObjectEnum::Policy(policy));
+            }
+        }
+
+        if self.completed_views < self.database.views.len() {
+            if let Some(view) = self.database.views.iter().find(|v| {
+                !self.completed_objects.contains(&&v.name)
+                    && v.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_views += 1;
+                self.completed_objects.push(&view.name);
+                return Some(SqlObjectEnum::View(view));
+            }
+        }
+
+        if self.completed_sequences < self.database.sequences.len() {
+            if let Some(sequence) = self.database.sequences.iter().find(|s| {
+                !self.completed_objects.contains(&&s.name)
+                    && s.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_sequences += 1;
+                self.completed_objects.push(&sequence.name);
+                return Some(SqlObjectEnum::Sequence(sequence));
+            }
+        }
+
+        if self.completed_functions < self.database.functions.len() {
+            if let Some(function) = self.database.functions.iter().find(|f| {
+                !self.completed_objects.contains(&&f.name)
+                    && f.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_functions += 1;
+                self.completed_objects.push(&function.name);
+                return Some(SqlObjectEnum::Function(function));
+            }
+        }
+
+        if self.completed_aggregates < self.database.aggregates.len() {
+            if let Some(aggregate) = self.database.aggregates.iter().find(|a| {
+                !self.completed_objects.contains(&&a.name)
+                    && a.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_aggregates += 1;
+                self.completed_objects.push(&aggregate.name);
+                return Some(SqlObjectEnum::Aggregate(aggregate));
+            }
+        }
+
+        if self.completed_access_methods < self.database.access_methods.len() {
+            if let Some(access_method) = self.database.access_methods.iter().find(|a| {
+                !self.completed_objects.contains(&&a.name)
+                    && a.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_access_methods += 1;
+                self.completed_objects.push(&access_method.name);
+                return Some(SqlObjectEnum::AccessMethod(access_method));
+            }
+        }
+
+        if self.completed_publications < self.database.publications.len() {
+            if let Some(publication) = self.database.publications.iter().find(|p| {
+                !self.completed_objects.contains(&&p.name)
+                    && p.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_publications += 1;
+                self.completed_objects.push(&publication.name);
+                return Some(SqlObjectEnum::Publication(publication));
+            }
+        }
+
+        // Event triggers fire for the DDL of every object created after them so event triggers
+        // are only yielded once no other object is ready
+        if self.completed_event_triggers < self.database.event_triggers.len() {
+            if let Some(event_trigger) = self.database.event_triggers.iter().find(|e| {
+                !self.completed_objects.contains(&&e.name)
+                    && e.dependencies_met(&self.completed_objects)
+            }) {
+                self.completed_event_triggers += 1;
+                self.completed_objects.push(&event_trigger.name);
+                return Some(SqlObjectEnum::EventTrigger(event_trigger));
+            }
+        }
+        None
+    }
+}
+
+enum DbCompareResult<'d> {
+    Create(SqlObjectEnum<'d>),
+    Alter {
+        old: SqlObjectEnum<'d>,
+        new: SqlObjectEnum<'d>,
+    },
+    Drop(SqlObjectEnum<'d>),
+    /// Schema that is dropped along with every object contained within the schema
+    DropSchemaCascade(&'d Schema),
+}
+
+impl DbCompareResult<'_> {
+    /// True if the existing object of an alter or drop matches any of the `ignored_objects`
+    /// patterns (i.e. managed externally) so no statements should be generated. See
+    /// [SqlObjectEnum::is_ignored].
+    fn is_ignored(&self, ignored_objects: &[String]) -> bool {
+        match self {
+            DbCompareResult::Create(_) => false,
+            DbCompareResult::Alter { old, .. } | DbCompareResult::Drop(old) => {
+                old.is_ignored(ignored_objects)
+            },
+            DbCompareResult::DropSchemaCascade(schema) => {
+                matches_any_pattern(ignored_objects, &schema.name)
+            },
+        }
+    }
+
+    /// Name and object type name of the object that this result applies to
+    fn object_name_and_type(&self) -> (&SchemaQualifiedName, &str) {
+        match self {
+            DbCompareResult::Create(object)
+            | DbCompareResult::Alter { new: object, .. }
+            | DbCompareResult::Drop(object) => (object.name(), object.object_type_name()),
+            DbCompareResult::DropSchemaCascade(schema) => (&schema.name, schema.object_type_name()),
+        }
+    }
+
+    /// Dependencies of the object that this result applies to. For drops, these are the
+    /// dependencies of the existing object.
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        match self {
+            DbCompareResult::Create(object)
+            | DbCompareResult::Alter { new: object, .. }
+            | DbCompareResult::Drop(object) => object.dependencies(),
+            DbCompareResult::DropSchemaCascade(schema) => schema.dependencies(),
+        }
+    }
+
+    /// Table that the object of this result belongs to along with the position of the result's
+    /// step within the table's steps. [None] if the object is not a table or owned by a table.
+    fn table_step(&self) -> Option<TableStep> {
+        let is_drop = matches!(self, DbCompareResult::Drop(_));
+        let object = match self {
+            DbCompareResult::Create(object)
+            | DbCompareResult::Alter { new: object, .. }
+            | DbCompareResult::Drop(object) => object,
+            DbCompareResult::DropSchemaCascade(_) => return None,
+        };
+        let (table, order) = match object {
+            SqlObjectEnum::Table(table) => (&table.name, TableStepOrder::Table),
+            SqlObjectEnum::Constraint(constraint) if is_drop && constraint.is_foreign_key() => {
+                (&constraint.owner_table_name, TableStepOrder::DropForeignKey)
+            },
+            SqlObjectEnum::Constraint(constraint) => (
+                &constraint.owner_table_name,
+                TableStepOrder::ConstraintOrIndex,
+            ),
+            SqlObjectEnum::Index(index) => {
+                (&index.owner_table_name, TableStepOrder::ConstraintOrIndex)
+            },
+            SqlObjectEnum::Statistics(statistics) => (
+                &statistics.owner_table_name,
+                TableStepOrder::ConstraintOrIndex,
+            ),
+            SqlObjectEnum::Trigger(trigger) => {
+                (&trigger.owner_object_name, TableStepOrder::TriggerOrPolicy)
+            },
+            SqlObjectEnum::Policy(policy) => {
+                (&policy.owner_table_name, TableStepOrder::TriggerOrPolicy)
+            },
+            _ => return None,
+        };
+        let order = if is_drop && order > TableStepOrder::Table {
+            TableStepOrder::DropOwned
+        } else {
+            order
+        };
+        Some(TableStep {
+            table: table.clone(),
+            order,
+        })
+    }
+
+    /// Kind of migration step created from this result
+    fn step_kind(&self) -> StepKind {
+        match self {
+            DbCompareResult::Create(_) => StepKind::Create,
+            DbCompareResult::Alter { .. } => StepKind::Alter,
+            DbCompareResult::Drop(_) | DbCompareResult::DropSchemaCascade(_) => StepKind::Drop,
+        }
+    }
+
+    /// True if the statements of this result can remove data or drop an object. This is every
+    /// drop, alters of tables that drop columns or change column types and alters that drop the
+    /// object and create it again.
+    fn is_destructive(&self) -> bool {
+        match self {
+            DbCompareResult::Create(_) => false,
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Table(old),
+                new: SqlObjectEnum::Table(new),
+            } => old.drops_columns(new) || old.changes_column_types(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Index(old),
+                new: SqlObjectEnum::Index(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::View(old),
+                new: SqlObjectEnum::View(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Constraint(old),
+                new: SqlObjectEnum::Constraint(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Function(old),
+                new: SqlObjectEnum::Function(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Policy(old),
+                new: SqlObjectEnum::Policy(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::EventTrigger(old),
+                new: SqlObjectEnum::EventTrigger(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::Publication(old),
+                new: SqlObjectEnum::Publication(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::TextSearchDictionary(old),
+                new: SqlObjectEnum::TextSearchDictionary(new),
+            } => old.requires_recreation(new),
+            DbCompareResult::Alter {
+                old: SqlObjectEnum::TextSearchConfiguration(old),
+                new: SqlObjectEnum::TextSearchConfiguration(new),
+            } => old.requires_recreation(new),
+            // These objects are always dropped and created again when altered
+            DbCompareResult::Alter {
+                old:
+                    SqlObjectEnum::AccessMethod(_)
+                    | SqlObjectEnum::Aggregate(_)
+                    | SqlObjectEnum::Operator(_)
+                    | SqlObjectEnum::OperatorClass(_)
+                    | SqlObjectEnum::Statistics(_)
+                    | SqlObjectEnum::Trigger(_),
+                ..
+            } => true,
+            DbCompareResult::Alter { .. } => false,
+            DbCompareResult::Drop(_) | DbCompareResult::DropSchemaCascade(_) => true,
+        }
+    }
+
+    /// Write a comment noting the object that would have been dropped if this result is a drop
+    fn write_skipped_drop_comment<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        match self {
+            DbCompareResult::Drop(old) => {
+                writeln!(
+                    w,
+                    "-- WOULD DROP: {} {}",
+                    old.object_type_name(),
+                    old.name()
+                )?;
+            },
+            DbCompareResult::DropSchemaCascade(schema) => {
+                writeln!(w, "-- WOULD DROP: SCHEMA {} CASCADE", schema.name)?;
+            },
+            DbCompareResult::Create(_) | DbCompareResult::Alter { .. } => {},
+        }
+        Ok(())
+    }
+}
+
+struct DbCompare<'d> {
+    new: &'d Database,
+    old_iter: DbIter<'d>,
+    new_iter: DbIter<'d>,
+    is_done_old: bool,
+    /// True if dropped schemas should be dropped with `CASCADE` instead of dropping each object
+    /// within the schema
+    cascade_schema_drop: bool,
+    /// Names of the schemas already dropped with `CASCADE`. Drops of objects within these schemas
+    /// are skipped since the objects no longer exist.
+    cascade_dropped_schemas: Vec<&'d str>,
+}
+
+impl<'d> DbCompare<'d> {
+    fn new(old: &'d Database, new: &'d Database, cascade_schema_drop: bool) -> Self {
+        Self {
+            new,
+            old_iter: DbIter::new(old),
+            new_iter: DbIter::new(new),
+            is_done_old: false,
+            cascade_schema_drop,
+            cascade_dropped_schemas: vec![],
+        }
+    }
+
+    /// True if the object `name` is owned by any extension of the new database. Extension members
+    /// are excluded when scraping so this only guards against members that slip through the
+    /// scrape (e.g. an object that is only owned by the extension within the new database).
+    fn is_extension_member(&self, name: &SchemaQualifiedName) -> bool {
+        self.new
+            .extensions
+            .iter()
+            .any(|extension| extension.members.contains(name))
+    }
+
+    /// Check that both the old and new databases have had all objects processed. [DbIter]s stop
+    /// once no remaining object has its dependencies met so if any objects remain, a dependency
+    /// cycle (or a dependency on an unknown object) exists and the comparison is incomplete.
+    ///
+    /// ## Errors
+    /// If any object within either database was not processed
+    fn check_completed(&self) -> Result<(), PgDiffError> {
+        let mut unprocessed_objects = self.old_iter.unprocessed_objects("Target database");
+        unprocessed_objects
+            .append(&mut self.new_iter.unprocessed_objects("Source control database"));
+        if unprocessed_objects.is_empty() {
+            return Ok(());
+        }
+        Err(PgDiffError::UnresolvedDependencies {
+            unprocessed_objects,
+        })
+    }
+}
+
+impl<'d> Iterator for DbCompare<'d> {
+    type Item = DbCompareResult<'d>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.is_done_old {
+                let obj = self.new_iter.next()?;
+                return Some(DbCompareResult::Create(obj));
+            }
+
+            let Some(obj) = self.old_iter.next() else {
+                self.is_done_old = true;
+                continue;
+            };
+
+            let new_object = match obj {
+                SqlObjectEnum::Role(role) => self.new.roles.iter().find_map(|r| {
+                    if r.name() == role.name() {
+                        Some(SqlObjectEnum::Role(r))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Schema(schema) => self.new.schemas.iter().find_map(|s| {
+                    if s.name() == schema.name() {
+                        Some(SqlObjectEnum::Schema(s))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Extension(extension) => self.new.extensions.iter().find_map(|e| {
+                    if e.name() == extension.name() {
+                        Some(SqlObjectEnum::Extension(e))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::TextSearchDictionary(dictionary) => {
+                    self.new.text_search_dictionaries.iter().find_map(|d| {
+                        if d.name() == dictionary.name() {
+                            Some(SqlObjectEnum::TextSearchDictionary(d))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::TextSearchConfiguration(configuration) => {
+                    self.new.text_search_configurations.iter().find_map(|c| {
+                        if c.name() == configuration.name() {
+                            Some(SqlObjectEnum::TextSearchConfiguration(c))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::Udt(udt) => self.new.udts.iter().find_map(|u| {
+                    if u.name() == udt.name() {
+                        Some(SqlObjectEnum::Udt(u))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Table(table) => self.new.tables.iter().find_map(|t| {
+                    if t.name() == table.name() {
+                        Some(SqlObjectEnum::Table(t))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Policy(policy) => self.new.policies.iter().find_map(|p| {
+                    if p.name() == policy.name() {
+                        Some(SqlObjectEnum::Policy(p))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Constraint(constraint) => {
+                    self.new.constraints.iter().find_map(|c| {
+                        if c.name() == constraint.name() {
+                            Some(SqlObjectEnum::Constraint(c))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::Operator(operator) => self.new.operators.iter().find_map(|o| {
+                    if o.name() == operator.name() {
+                        Some(SqlObjectEnum::Operator(o))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::OperatorClass(operator_class) => {
+                    self.new.operator_classes.iter().find_map(|o| {
+                        if o.name() == operator_class.name()
+                            && o.index_method == operator_class.index_method
+                        {
+                            Some(SqlObjectEnum::OperatorClass(o))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::Index(index) => self.new.indexes.iter().find_map(|i| {
+                    if i.name() == index.name() {
+                        Some(SqlObjectEnum::Index(i))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Statistics(statistics) => self.new.statistics.iter().find_map(|s| {
+                    if s.name() == statistics.name() {
+                        Some(SqlObjectEnum::Statistics(s))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Trigger(trigger) => self.new.triggers.iter().find_map(|t| {
+                    if t.name() == trigger.name() {
+                        Some(SqlObjectEnum::Trigger(t))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Sequence(sequence) => self.new.sequences.iter().find_map(|s| {
+                    if s.name() == sequence.name() {
+                        Some(SqlObjectEnum::Sequence(s))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Function(function) => self.new.functions.iter().find_map(|f| {
+                    if f.name() == function.name() {
+                        Some(SqlObjectEnum::Function(f))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::Aggregate(aggregate) => self.new.aggregates.iter().find_map(|a| {
+                    if a.name() == aggregate.name() {
+                        Some(SqlObjectEnum::Aggregate(a))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::View(view) => self.new.views.iter().find_map(|v| {
+                    if v.name() == view.name() {
+                        Some(SqlObjectEnum::View(v))
+                    } else {
+                        None
+                    }
+                }),
+                SqlObjectEnum::AccessMethod(access_method) => {
+                    self.new.access_methods.iter().find_map(|a| {
+                        if a.name() == access_method.name() {
+                            Some(SqlObjectEnum::AccessMethod(a))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::EventTrigger(event_trigger) => {
+                    self.new.event_triggers.iter().find_map(|e| {
+                        if e.name() == event_trigger.name() {
+                            Some(SqlObjectEnum::EventTrigger(e))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::Publication(publication) => {
+                    self.new.publications.iter().find_map(|p| {
+                        if p.name() == publication.name() {
+                            Some(SqlObjectEnum::Publication(p))
+                        } else {
+                            None
+                        }
+                    })
+                },
+                SqlObjectEnum::DefaultPrivileges(default_privileges) => {
+                    self.new.default_privileges.iter().find_map(|d| {
+                        if d.name() == default_privileges.name() {
+                            Some(SqlObjectEnum::DefaultPrivileges(d))
+                        } else {
+                            None
+                        }
+                    })
+                },
+            };
+
+            if let Some(other) = new_object {
+                match &other {
+                    SqlObjectEnum::Role(_) => self.new_iter.completed_roles += 1,
+                    SqlObjectEnum::Schema(_) => self.new_iter.completed_schemas += 1,
+                    SqlObjectEnum::Extension(_) => self.new_iter.completed_extensions += 1,
+                    SqlObjectEnum::TextSearchDictionary(_) => {
+                        self.new_iter.completed_text_search_dictionaries += 1
+                    },
+                    SqlObjectEnum::TextSearchConfiguration(_) => {
+                        self.new_iter.completed_text_search_configurations += 1
+                    },
+                    SqlObjectEnum::Udt(_) => self.new_iter.completed_udt += 1,
+                    SqlObjectEnum::Table(_) => self.new_iter.completed_tables += 1,
+                    SqlObjectEnum::Policy(_) => self.new_iter.completed_policies += 1,
+                    SqlObjectEnum::Constraint(_) => self.new_iter.completed_constraints += 1,
+                    SqlObjectEnum::Operator(_) => self.new_iter.completed_operators += 1,
+                    SqlObjectEnum::OperatorClass(_) => {
+                        self.new_iter.completed_operator_classes += 1
+                    },
+                    SqlObjectEnum::Index(_) => self.new_iter.completed_indexes += 1,
+                    SqlObjectEnum::Statistics(_) => self.new_iter.completed_statistics += 1,
+                    SqlObjectEnum::Trigger(_) => self.new_iter.completed_triggers += 1,
+                    SqlObjectEnum::Sequence(_) => self.new_iter.completed_sequences += 1,
+                    SqlObjectEnum::Function(_) => self.new_iter.completed_functions += 1,
+                    SqlObjectEnum::Aggregate(_) => self.new_iter.completed_aggregates += 1,
+                    SqlObjectEnum::View(_) => self.new_iter.completed_views += 1,
+                    SqlObjectEnum::AccessMethod(_) => self.new_iter.completed_access_methods += 1,
+                    SqlObjectEnum::EventTrigger(_) => self.new_iter.completed_event_triggers += 1,
+                    SqlObjectEnum::Publication(_) => self.new_iter.completed_publications += 1,
+                    SqlObjectEnum::DefaultPrivileges(_) => {
+                        self.new_iter.completed_default_privileges += 1
+                    },
+                }
+                self.new_iter.completed_objects.push(other.name());
+                return Some(DbCompareResult::Alter {
+                    old: obj,
+                    new: other,
+                });
+            }
+
+            match obj {
+                SqlObjectEnum::Schema(schema) if self.cascade_schema_drop => {
+                    self.cascade_dropped_schemas.push(&schema.name.schema_name);
+                    return Some(DbCompareResult::DropSchemaCascade(schema));
+                },
+                _ if self
+                    .cascade_dropped_schemas
+                    .contains(&obj.name().schema_name.as_str()) =>
+                {
+                    // Object was already dropped by the schema's cascade. It has still been
+                    // marked as completed by the old iterator.
+                    continue;
+                },
+                _ if self.is_extension_member(obj.name()) => {
+                    // Object belongs to an extension of the new database so the object is
+                    // managed by the extension rather than dropped
+                    continue;
+                },
+                _ => return Some(DbCompareResult::Drop(obj)),
+            }
+        }
+    }
+}
+
+/// Options that change the files written when scripting a database (see [Database::script_out])
+#[derive(Debug, Default, Clone)]
+pub struct ScriptOptions {
+    /// Write statements that can be re-run against a database where the objects already exist
+    pub idempotent: bool,
+    /// Directory layout of the scripted files
+    pub layout: ScriptLayout,
+    /// Define constraints that can be inlined within the `CREATE TABLE` statement of the owner
+    /// table rather than as separate `ALTER TABLE` statements
+    pub inline_constraints: bool,
+    /// Write stubs of the tablespaces referenced by tables, indexes and constraints. Those
+    /// tablespaces might not exist within a fresh cluster (see [Database::tablespace_stubs]).
+    pub emit_tablespaces: bool,
+    /// Glob patterns of object names managed externally. Objects that match (along with objects
+    /// owned by a matching table or view) are not scripted.
+    pub ignored_objects: Vec<String>,
+}
+
+/// Directory layout of the source control files created when scripting a database
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ScriptLayout {
+    /// Files are grouped into a directory per object type (e.g. `table/app.accounts.pgsql`)
+    #[default]
+    Type,
+    /// Files are all written to the output directory with the object type as a file name prefix
+    /// (e.g. `table.app.accounts.pgsql`)
+    Flat,
+    /// Files are grouped by schema and then object type (e.g.
+    /// `schema/app/table/app.accounts.pgsql`). Objects that do not belong to a schema follow the
+    /// `type` layout.
+    Schema,
+}
+
+/// Files of a scripted database that differ from a baseline directory of scripted files. All
+/// paths are relative to the scripted directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptChanges {
+    /// Scripted files that do not exist within the baseline directory
+    pub added: Vec<PathBuf>,
+    /// Scripted files whose contents differ from the baseline file
+    pub changed: Vec<PathBuf>,
+    /// Baseline files that are no longer scripted
+    pub removed: Vec<PathBuf>,
+}
+
+impl ScriptChanges {
+    /// True if no files were added, changed or removed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Compare each file within the `scripted_path` to the file of the same relative path within
+    /// the `baseline_path`, copying the added and changed files to the `output_path`
+    async fn write_changed_files(
+        scripted_path: &Path,
+        output_path: &Path,
+        baseline_path: &Path,
+    ) -> Result<Self, PgDiffError> {
+        let mut changes = Self::default();
+        let scripted_files = relative_file_paths(scripted_path).await?;
+        for relative_path in &scripted_files {
+            let contents = tokio::fs::read_to_string(scripted_path.join(relative_path)).await?;
+            match tokio::fs::read_to_string(baseline_path.join(relative_path)).await {
+                Ok(existing)
+                    if normalize_file_contents(&existing) == normalize_file_contents(&contents) =>
+                {
+                    continue
+                },
+                Ok(_) => changes.changed.push(relative_path.clone()),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                    changes.added.push(relative_path.clone())
+                },
+                Err(error) => return Err(error.into()),
+            }
+            let path = output_path.join(relative_path);
+            create_parent_directory(&path).await?;
+            let mut file = File::create(path).await?;
+            file.write_all(contents.as_bytes()).await?;
+        }
+        if tokio::fs::try_exists(baseline_path).await? {
+            changes.removed = relative_file_paths(baseline_path)
+                .await?
+                .into_iter()
+                .filter(|p| {
+                    p.extension().is_some_and(|e| e == "pgsql") && !scripted_files.contains(p)
+                })
+                .collect();
+        }
+        Ok(changes)
+    }
+}
+
+impl Display for ScriptChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No scripted files changed");
+        }
+        for (label, paths) in [
+            ("Added", &self.added),
+            ("Changed", &self.changed),
+            ("Removed", &self.removed),
+        ] {
+            for path in paths {
+                writeln!(f, "{label}: {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sorted paths (relative to the `directory`) of all files within the `directory`
+async fn relative_file_paths(directory: &Path) -> Result<Vec<PathBuf>, PgDiffError> {
+    let mut paths = vec![];
+    let mut entries = WalkDir::new(directory).map(|entry| entry.map(|e| e.path()));
+    while let Some(result) = entries.next().await {
+        let path = result?;
+        if path.is_dir() {
+            continue;
+        }
+        if let Ok(relative_path) = path.strip_prefix(directory) {
+            paths.push(relative_path.to_path_buf());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Contents of a scripted file with line endings and trailing whitespace normalized
+fn normalize_file_contents(contents: &str) -> String {
+    let mut normalized = String::with_capacity(contents.len());
+    for line in contents.trim_end().lines() {
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    normalized
+}
+
+impl ScriptLayout {
+    /// Path of the file containing the object with the `object_type_name` and `name` within the
+    /// `root_directory`. Object and schema names are sanitized so the path is valid on all
+    /// platforms (see [sanitize_file_name]).
+    fn file_path(
+        &self,
+        root_directory: &Path,
+        object_type_name: &str,
+        name: &SchemaQualifiedName,
+    ) -> PathBuf {
+        let type_directory = object_type_name.to_lowercase().replace(' ', "_");
+        let file_name = sanitize_file_name(&name.to_string());
+        match self {
+            Self::Type => root_directory
+                .join(type_directory)
+                .join(format!("{file_name}.pgsql")),
+            Self::Flat => root_directory.join(format!("{type_directory}.{file_name}.pgsql")),
+            Self::Schema if !name.schema_name.is_empty() => root_directory
+                .join("schema")
+                .join(sanitize_file_name(&name.schema_name))
+                .join(type_directory)
+                .join(format!("{file_name}.pgsql")),
+            Self::Schema => Self::Type.file_path(root_directory, object_type_name, name),
+        }
+    }
+}
+
+/// Device names reserved by Windows that cannot be used as the start of a file name
+const RESERVED_FILE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Map the `name` of an object to a file name that is valid on all platforms. Characters that are
+/// not allowed within Windows or Unix file names (along with `%`) are percent encoded as well as a
+/// trailing `.` or space and the first character of a reserved device name. Names that are already
+/// valid (e.g. unquoted identifiers) are unchanged and the mapping can always be reversed.
+fn sanitize_file_name(name: &str) -> String {
+    let stem = name.split('.').next().unwrap_or_default();
+    let is_reserved = RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved));
+    let last_index = name.chars().count().saturating_sub(1);
+    let mut file_name = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        let is_hostile = matches!(
+            c,
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' | '%'
+        ) || c.is_control()
+            || (i == last_index && matches!(c, '.' | ' '))
+            || (i == 0 && is_reserved);
+        if !is_hostile {
+            file_name.push(c);
+            continue;
+        }
+        let mut buffer = [0; 4];
+        for byte in c.encode_utf8(&mut buffer).bytes() {
+            file_name.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    file_name
+}
+
+/// Object defined within a scripted file as recorded within the [SCRIPT_MANIFEST_FILE_NAME]
+#[derive(Debug, PartialEq, Serialize)]
+struct ScriptManifestEntry {
+    /// Type of the object (e.g. `TABLE`)
+    object_type: String,
+    /// Full name of the object
+    name: String,
+}
+
+/// Name of the file written to the root of a scripted directory that maps the path of each
+/// scripted file (relative to the scripted directory) to the object defined within the file
+pub const SCRIPT_MANIFEST_FILE_NAME: &str = "objects.json";
+
+/// Paths of the files written while scripting a database. Each object is assigned a path using
+/// the `layout` when the object is first scripted and later lookups (e.g. to append constraints to
+/// the owner table's file) return the same path.
+///
+/// Paths that only differ by case (e.g. quoted identifiers) would share a single file on a
+/// case-insensitive filesystem so a numeric suffix is added to the file name of every object after
+/// the first object assigned the path.
+pub struct ScriptFiles<'p> {
+    root_directory: &'p Path,
+    layout: ScriptLayout,
+    /// Glob patterns of the object names that are not scripted (see [ScriptFiles::is_ignored])
+    ignored_objects: &'p [String],
+    /// Path assigned to each object keyed by the object type name and object name
+    paths: HashMap<(String, String), PathBuf>,
+    /// Lower case version of every path assigned
+    assigned_paths: HashSet<String>,
+}
+
+impl<'p> ScriptFiles<'p> {
+    /// Create a new set of files within the `root_directory` that follow the `layout`. Objects
+    /// matching any of the `ignored_objects` patterns are not scripted.
+    pub fn new(
+        root_directory: &'p Path,
+        layout: ScriptLayout,
+        ignored_objects: &'p [String],
+    ) -> Self {
+        Self {
+            root_directory,
+            layout,
+            ignored_objects,
+            paths: HashMap::new(),
+            assigned_paths: HashSet::new(),
+        }
+    }
+
+    /// True if the `name` matches any of the ignored object patterns of these files. Ignored
+    /// objects are managed externally so the objects are never scripted.
+    fn is_ignored(&self, name: &SchemaQualifiedName) -> bool {
+        matches_any_pattern(self.ignored_objects, name)
+    }
+
+    /// Path of the file containing the object with the `object_type_name` and `name`. The path is
+    /// assigned if this is the first time the object is requested.
+    fn path(&mut self, object_type_name: &str, name: &SchemaQualifiedName) -> PathBuf {
+        let key = (object_type_name.to_string(), name.to_string());
+        if let Some(path) = self.paths.get(&key) {
+            return path.clone();
+        }
+        let base_path = self
+            .layout
+            .file_path(self.root_directory, object_type_name, name);
+        let mut path = base_path.clone();
+        let mut suffix = 1;
+        while !self
+            .assigned_paths
+            .insert(path.to_string_lossy().to_lowercase())
+        {
+            suffix += 1;
+            let stem = base_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default();
+            path = base_path.with_file_name(format!("{stem}_{suffix}.pgsql"));
+        }
+        self.paths.insert(key, path.clone());
+        path
+    }
+
+    /// Entries of the [SCRIPT_MANIFEST_FILE_NAME] keyed by the path of each file relative to the
+    /// root directory. Path components are always separated by `/`.
+    fn manifest(&self) -> BTreeMap<String, ScriptManifestEntry> {
+        self.paths
+            .iter()
+            .filter_map(|((object_type, name), path)| {
+                let relative_path = path.strip_prefix(self.root_directory).ok()?;
+                let relative_path = relative_path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let entry = ScriptManifestEntry {
+                    object_type: object_type.clone(),
+                    name: name.clone(),
+                };
+                Some((relative_path, entry))
+            })
+            .collect()
+    }
+
+    /// Write the [SCRIPT_MANIFEST_FILE_NAME] to the root directory
+    ///
+    /// ## Errors
+    /// If the manifest cannot be serialized or written to the file
+    async fn write_manifest(&self) -> Result<(), PgDiffError> {
+        let manifest = serde_json::to_string_pretty(&self.manifest())
+            .map_err(|error| PgDiffError::General(error.to_string()))?;
+        tokio::fs::create_dir_all(self.root_directory).await?;
+        let mut file = File::create(self.root_directory.join(SCRIPT_MANIFEST_FILE_NAME)).await?;
+        file.write_all(manifest.as_bytes()).await?;
+        file.write_all("\n".as_bytes()).await?;
+        Ok(())
+    }
+}
+
+/// Create the parent directory of the file `path` (and all missing ancestors)
+async fn create_parent_directory(path: &Path) -> Result<(), PgDiffError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    Ok(())
+}
+
+/// Write `CREATE` statements to the file specified by the object type and name. If `idempotent`
+/// is true, the [SqlObject::create_statements_idempotent] variant is used. The location of the
+/// file is assigned by the script `files`. Nothing is written if the object is ignored.
+pub async fn write_create_statements_to_file<S>(
+    object: &S,
+    files: &mut ScriptFiles<'_>,
+    idempotent: bool,
+) -> Result<(), PgDiffError>
+where
+    S: SqlObject,
+{
+    write_statements_to_file(object, files, |statements| {
+        if idempotent {
+            object.create_statements_idempotent(statements)
+        } else {
+            object.create_statements(statements)
+        }
+    })
+    .await
+}
+
+/// Write the statements produced by `write_statements` to the file specified by the object type
+/// and name (see [write_create_statements_to_file]). Nothing is written if the object is ignored.
+async fn write_statements_to_file<S, F>(
+    object: &S,
+    files: &mut ScriptFiles<'_>,
+    write_statements: F,
+) -> Result<(), PgDiffError>
+where
+    S: SqlObject,
+    F: FnOnce(&mut String) -> Result<(), PgDiffError>,
+{
+    if files.is_ignored(object.name()) {
+        return Ok(());
+    }
+    let mut statements = String::new();
+    write_statements(&mut statements)?;
+
+    let path = files.path(object.object_type_name(), object.name());
+    create_parent_directory(&path).await?;
+    let mut file = File::create(path).await?;
+    file.write_all(statements.as_bytes()).await?;
+    Ok(())
+}
+
+/// Append the `CREATE` statements to the owning table's file. If `idempotent` is true, the
+/// [SqlObject::create_statements_idempotent] variant is used. The location of the table's file
+/// is the same path assigned to the table by the script `files`.
+pub async fn append_create_statements_to_owner_table_file<S>(
+    object: &S,
+    owner_table: &SchemaQualifiedName,
+    files: &mut ScriptFiles<'_>,
+    idempotent: bool,
+) -> Result<(), PgDiffError>
+where
+    S: SqlObject,
+{
+    if files.is_ignored(owner_table) || files.is_ignored(object.name()) {
+        return Ok(());
+    }
+    let path = files.path("TABLE", owner_table);
+    append_create_statements_to_path(object, &path, idempotent).await
+}
+
+/// Append the `CREATE` statements to the file of the `owner` object (e.g. the view of a trigger).
+/// If `idempotent` is true, the [SqlObject::create_statements_idempotent] variant is used. The
+/// location of the owner's file is the same path assigned to the owner by the script `files`.
+pub async fn append_create_statements_to_owner_file<S, O>(
+    object: &S,
+    owner: &O,
+    files: &mut ScriptFiles<'_>,
+    idempotent: bool,
+) -> Result<(), PgDiffError>
+where
+    S: SqlObject,
+    O: SqlObject,
+{
+    if files.is_ignored(owner.name()) || files.is_ignored(object.name()) {
+        return Ok(());
+    }
+    let path = files.path(owner.object_type_name(), owner.name());
+    append_create_statements_to_path(object, &path, idempotent).await
+}
+
+/// Append the `CREATE` statements to the existing file at `path`
+async fn append_create_statements_to_path<S>(
+    object: &S,
+    path: &Path,
+    idempotent: bool,
+) -> Result<(), PgDiffError>
+where
+    S: SqlObject,
+{
+    let mut statements = String::new();
+    if idempotent {
+        object.create_statements_idempotent(&mut statements)?;
+    } else {
+        object.create_statements(&mut statements)?;
+    }
+    append_statements_to_path(&statements, path).await
+}
+
+/// Pair each primary key or unique constraint of the `constraints` with the first of the
+/// `indexes` it can be added with (see [Constraint::can_use_index]), keyed by the constraint's
+/// name. Each index is used by at most 1 constraint.
+fn pair_constraint_indexes<'d, I>(
+    constraints: I,
+    indexes: &[&'d Index],
+) -> HashMap<&'d SchemaQualifiedName, &'d Index>
+where
+    I: Iterator<Item = &'d Constraint>,
+{
+    let mut constraint_indexes: HashMap<&SchemaQualifiedName, &Index> = HashMap::new();
+    for constraint in constraints {
+        let index = indexes.iter().find(|index| {
+            constraint.can_use_index(index)
+                && !constraint_indexes
+                    .values()
+                    .any(|i| i.schema_qualified_name == index.schema_qualified_name)
+        });
+        if let Some(index) = index {
+            constraint_indexes.insert(&constraint.schema_qualified_name, index);
+        }
+    }
+    constraint_indexes
+}
+
+/// Append the `statements` to the existing file at `path`, separated from the file's current
+/// contents by a blank line
+async fn append_statements_to_path(statements: &str, path: &Path) -> Result<(), PgDiffError> {
+    create_parent_directory(path).await?;
+    let mut file = OpenOptions::new().append(true).open(path).await?;
+    file.write_all("\n".as_bytes()).await?;
+    file.write_all(statements.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::{ready, Ready};
+    use std::path::{Path, PathBuf};
+
+    use futures::executor::block_on;
+    use pg_query::protobuf::node::Node;
+    use sqlx::postgres::types::Oid;
+    use sqlx::types::Uuid;
+
+    use crate::object::constraint::{
+        Constraint, ConstraintTiming, ConstraintType, ForeignKeyAction, ForeignKeyMatch,
+    };
+    use crate::object::event_trigger::{EventTrigger, EventTriggerEnabled};
+    use crate::object::function::{Function, FunctionSourceCode};
+    use crate::object::index::{Index, IndexColumn};
+    use crate::object::table::{Column, ReplicaIdentity};
+    use crate::object::trigger::{Trigger, TriggerEnabled, TriggerEvent, TriggerTiming};
+    use crate::object::udt::{Udt, UdtType};
+    use crate::object::{
+        matches_any_pattern, AclGrant, ColumnDependency, Extension, IndexParameters, Role, Schema,
+        SchemaQualifiedName, Statistics, Table, TableSpace, View,
+    };
+    use crate::{PgDiffError, VERSION};
+
+    use super::{
+        default_privileges_name, dump_source_file, missing_object_name, missing_role_name,
+        non_transactional_section, object_type_label, order_steps, quote_identifier,
+        retarget_database_setting, sort_statements, verification_statement, Database,
+        DatabaseOptions, DatabaseSettings, DdlStatement, DisabledTriggers, DropMode, LocalProvider,
+        MigrationOptions, MigrationStep, NodeIter, OnUnsupported, PlanSummary, ScriptChanges,
+        ScriptLayout, ScriptOptions, SourceControlDatabase, StatementIter, StepKind, TableStep,
+        TableStepOrder, TransactionSection,
+    };
+
+    const SCHEMA: &str = "test_schema";
+    const TABLE: &str = "test_table";
+
+    fn create_database(schemas: Vec<Schema>, tables: Vec<Table>) -> Database {
+        Database {
+            roles: vec![],
+            schemas,
+            udts: vec![],
+            tables,
+            policies: vec![],
+            constraints: vec![],
+            operators: vec![],
+            operator_classes: vec![],
+            indexes: vec![],
+            statistics: vec![],
+            triggers: vec![],
+            sequences: vec![],
+            functions: vec![],
+            aggregates: vec![],
+            views: vec![],
+            extensions: vec![],
+            access_methods: vec![],
+            event_triggers: vec![],
+            text_search_dictionaries: vec![],
+            text_search_configurations: vec![],
+            publications: vec![],
+            default_privileges: vec![],
+            settings: DatabaseSettings::default(),
+        }
+    }
+
+    fn create_table(schema: &str) -> Table {
+        Table {
+            oid: Oid(1),
+            name: SchemaQualifiedName::new(schema, TABLE),
+            columns: vec![],
+            partition_key_def: None,
+            partition_values: None,
+            inherited_tables: None,
+            partitioned_parent_table: None,
+            tablespace: None,
+            with: None,
+            owner: "postgres".into(),
+            replica_identity: ReplicaIdentity::Default,
+            dependencies: vec![SchemaQualifiedName::new(schema, "")],
+        }
+    }
+
+    fn create_schema_with_table() -> Database {
+        create_database(
+            vec![Schema {
+                name: SchemaQualifiedName::new(SCHEMA, ""),
+                owner: "test_owner".into(),
+            }],
+            vec![create_table(SCHEMA)],
+        )
+    }
+
+    #[rstest::rstest]
+    #[case(
+        true,
+        include_str!("../../test-files/sql/database-compare-drop-schema-cascade.pgsql"),
+    )]
+    #[case(
+        false,
+        include_str!("../../test-files/sql/database-compare-drop-schema-no-cascade.pgsql"),
+    )]
+    fn compare_to_other_database_should_drop_removed_schema(
+        #[case] cascade_schema_drop: bool,
+        #[case] statement: &str,
+    ) {
+        let old = create_schema_with_table();
+        let new = create_database(vec![], vec![]);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    cascade_schema_drop,
+                    allow_destructive: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement.trim(), result.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(true)]
+    #[case(false)]
+    fn compare_to_other_database_should_fail_to_drop_schema_unless_destructive_allowed(
+        #[case] cascade_schema_drop: bool,
+    ) {
+        let old = create_schema_with_table();
+        let new = create_database(vec![], vec![]);
+
+        let result = old.compare_to_other_database(
+            &new,
+            MigrationOptions {
+                cascade_schema_drop,
+                ..Default::default()
+            },
+        );
+
+        let Err(PgDiffError::DestructiveSchemaDrop { schemas }) = result else {
+            panic!("Expected the schema drop to be blocked");
+        };
+        assert_eq!(vec![SCHEMA.to_string()], schemas);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        ScriptLayout::Type,
+        "TABLE",
+        SCHEMA,
+        "table/test_schema.test_table.pgsql"
+    )]
+    #[case(
+        ScriptLayout::Flat,
+        "TABLE",
+        SCHEMA,
+        "table.test_schema.test_table.pgsql"
+    )]
+    #[case(
+        ScriptLayout::Schema,
+        "TABLE",
+        SCHEMA,
+        "schema/test_schema/table/test_schema.test_table.pgsql"
+    )]
+    #[case(ScriptLayout::Schema, "EXTENSION", "", "extension/test_table.pgsql")]
+    #[case(
+        ScriptLayout::Type,
+        "TEXT SEARCH DICTIONARY",
+        SCHEMA,
+        "text_search_dictionary/test_schema.test_table.pgsql"
+    )]
+    fn file_path_should_follow_layout(
+        #[case] layout: ScriptLayout,
+        #[case] object_type_name: &str,
+        #[case] schema: &str,
+        #[case] expected: &str,
+    ) {
+        let root_directory = Path::new("output");
+
+        let path = layout.file_path(
+            root_directory,
+            object_type_name,
+            &SchemaQualifiedName::new(schema, TABLE),
+        );
+
+        assert_eq!(root_directory.join(expected), path);
+    }
+
+    #[rstest::rstest]
+    #[case("test_schema.test_table", "test_schema.test_table")]
+    #[case("test_schema.\"Test/Table:1\"", "test_schema.%22Test%2FTable%3A1%22")]
+    #[case("test_schema.\"100%\"", "test_schema.%22100%25%22")]
+    #[case("\"trailing.\"", "%22trailing.%22")]
+    #[case("trailing ", "trailing%20")]
+    #[case("con", "%63on")]
+    #[case("Aux.test_table", "%41ux.test_table")]
+    #[case("console.test_table", "console.test_table")]
+    fn sanitize_file_name_should_encode_path_hostile_characters(
+        #[case] name: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, sanitize_file_name(name));
+    }
+
+    #[test]
+    fn script_files_should_suffix_paths_that_only_differ_by_case() {
+        let root_directory = Path::new("output");
+        let mut files = ScriptFiles::new(root_directory, ScriptLayout::Type, &[]);
+        let upper = SchemaQualifiedName::new(SCHEMA, "\"TEST_TABLE\"");
+        let mixed = SchemaQualifiedName::new(SCHEMA, "\"Test_Table\"");
+
+        let upper_path = files.path("TABLE", &upper);
+        let mixed_path = files.path("TABLE", &mixed);
+
+        assert_eq!(
+            root_directory.join("table/test_schema.%22TEST_TABLE%22.pgsql"),
+            upper_path
+        );
+        assert_eq!(
+            root_directory.join("table/test_schema.%22Test_Table%22_2.pgsql"),
+            mixed_path
+        );
+        assert_eq!(upper_path, files.path("TABLE", &upper));
+        assert_eq!(mixed_path, files.path("TABLE", &mixed));
+        assert_eq!(
+            vec![
+                "table/test_schema.%22TEST_TABLE%22.pgsql",
+                "table/test_schema.%22Test_Table%22_2.pgsql"
+            ],
+            files.manifest().into_keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn script_out_should_append_constraints_to_sanitized_table_file() {
+        let mut database = create_schema_with_table();
+        let table_name = SchemaQualifiedName::new(SCHEMA, "\"Test/Table\"");
+        database.tables[0].name = table_name.clone();
+        database.constraints = vec![Constraint {
+            owner_table_name: table_name.clone(),
+            ..create_constraint(
+                "test_check",
+                ConstraintType::Check {
+                    expression: "CHECK (true)".into(),
+                    columns: vec![],
+                    is_inheritable: true,
+                },
+            )
+        }];
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_sanitize_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(&output_path, ScriptOptions::default())
+            .await;
+        let contents = tokio::fs::read_to_string(
+            output_path.join("table/test_schema.%22Test%2FTable%22.pgsql"),
+        )
+        .await;
+        let manifest = tokio::fs::read_to_string(output_path.join(SCRIPT_MANIFEST_FILE_NAME)).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        let contents = contents.unwrap();
+        assert!(contents.starts_with("CREATE TABLE test_schema.\"Test/Table\""));
+        assert!(contents.contains("ADD CONSTRAINT test_check"));
+        let manifest: serde_json::Value = serde_json::from_str(&manifest.unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "object_type": "TABLE",
+                "name": "test_schema.\"Test/Table\"",
+            }),
+            manifest["table/test_schema.%22Test%2FTable%22.pgsql"]
+        );
+    }
+
+    #[tokio::test]
+    async fn script_out_should_skip_ignored_objects() {
+        let database = create_schema_with_table();
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_ignored_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(
+                &output_path,
+                ScriptOptions {
+                    ignored_objects: vec!["test_schema.test_*".into()],
+                    ..Default::default()
+                },
+            )
+            .await;
+        let schema_file_exists =
+            tokio::fs::try_exists(output_path.join("schema/test_schema.pgsql")).await;
+        let table_file_exists =
+            tokio::fs::try_exists(output_path.join("table/test_schema.test_table.pgsql")).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        assert!(schema_file_exists.unwrap());
+        assert!(!table_file_exists.unwrap());
+    }
+
+    #[tokio::test]
+    async fn script_out_should_add_constraint_using_unique_index_within_table_file() {
+        let mut database = create_schema_with_table();
+        database.indexes = vec![create_unique_index("test_index")];
+        database.constraints = vec![create_constraint(
+            "test_unique",
+            ConstraintType::Unique {
+                columns: vec!["test_col".into()],
+                are_nulls_distinct: true,
+                index_parameters: IndexParameters::default(),
+            },
+        )];
+        let output_path = std::env::temp_dir().join(format!(
+            "pg_diff_rs_using_index_{}",
+            Uuid::new_v4().simple()
+        ));
+
+        let result = database
+            .script_out(&output_path, ScriptOptions::default())
+            .await;
+        let contents =
+            tokio::fs::read_to_string(output_path.join("table/test_schema.test_table.pgsql")).await;
+        let index_directory_exists = tokio::fs::try_exists(output_path.join("index")).await;
+        let manifest = tokio::fs::read_to_string(output_path.join(SCRIPT_MANIFEST_FILE_NAME)).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        let contents = contents.unwrap();
+        let index_position = contents
+            .find("CREATE UNIQUE INDEX test_index ON test_schema.test_table")
+            .unwrap();
+        let constraint_position = contents
+            .find(
+                "ALTER TABLE test_schema.test_table ADD CONSTRAINT test_unique UNIQUE USING INDEX \
+                 test_index NOT DEFERRABLE;",
+            )
+            .unwrap();
+        assert!(index_position < constraint_position);
+        assert_eq!(1, contents.matches("CREATE UNIQUE INDEX").count());
+        assert!(!contents.contains("UNIQUE (test_col)"));
+        assert!(!index_directory_exists.unwrap());
+        let manifest: serde_json::Value = serde_json::from_str(&manifest.unwrap()).unwrap();
+        assert!(manifest
+            .as_object()
+            .unwrap()
+            .values()
+            .all(|entry| entry["object_type"] != "INDEX"));
+    }
+
+    #[tokio::test]
+    async fn script_out_should_write_stub_of_referenced_tablespace_when_emitting_tablespaces() {
+        let mut database = create_schema_with_table();
+        database.tables[0].tablespace = Some(TableSpace("test_tablespace".into()));
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_tablespace_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(
+                &output_path,
+                ScriptOptions {
+                    emit_tablespaces: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        let contents =
+            tokio::fs::read_to_string(output_path.join("tablespace/test_tablespace.pgsql")).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        assert_eq!(
+            include_str!("../../test-files/sql/tablespace-stub-create.pgsql").trim(),
+            contents.unwrap().trim()
+        );
+    }
+
+    #[test]
+    fn referenced_tablespaces_should_skip_built_in_tablespaces() {
+        let mut database = create_schema_with_table();
+        database.tables[0].tablespace = Some(TableSpace("pg_default".into()));
+        database.indexes = vec![Index {
+            parameters: IndexParameters {
+                tablespace: Some(TableSpace("test_tablespace".into())),
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+
+        let tablespaces = database.referenced_tablespaces();
+
+        assert_eq!(vec!["test_tablespace"], tablespaces);
+    }
+
+    #[tokio::test]
+    async fn dump_source_file_should_describe_object_and_dependencies_of_each_statement() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("dump-ast")
+            .join("source.pgsql");
+
+        let result = dump_source_file(path).await.unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/dump-ast/source-analysis.txt").trim(),
+            result.trim()
+        );
+    }
+
+    #[tokio::test]
+    async fn script_out_should_separate_same_named_tables_by_schema_with_schema_layout() {
+        let database = create_database(
+            vec![
+                Schema {
+                    name: SchemaQualifiedName::new("first_schema", ""),
+                    owner: "test_owner".into(),
+                },
+                Schema {
+                    name: SchemaQualifiedName::new("second_schema", ""),
+                    owner: "test_owner".into(),
+                },
+            ],
+            vec![create_table("first_schema"), create_table("second_schema")],
+        );
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_layout_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(
+                &output_path,
+                ScriptOptions {
+                    layout: ScriptLayout::Schema,
+                    ..Default::default()
+                },
+            )
+            .await;
+        let first_exists = output_path
+            .join("schema/first_schema/table/first_schema.test_table.pgsql")
+            .is_file();
+        let second_exists = output_path
+            .join("schema/second_schema/table/second_schema.test_table.pgsql")
+            .is_file();
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        assert!(first_exists);
+        assert!(second_exists);
+    }
+
+    #[tokio::test]
+    async fn script_out_should_inline_constraints_when_inline_constraints() {
+        let mut database = create_schema_with_table();
+        database.tables[0].columns = vec![serde_json::from_value(serde_json::json!({
+            "name": "test_col",
+            "data_type": "integer",
+            "size": 4,
+            "collation": null,
+            "is_non_null": true,
+            "default_expression": null,
+            "generated_column": null,
+            "identity_column": null,
+            "storage": null,
+            "compression": "",
+            "grants": [],
+        }))
+        .unwrap()];
+        database.constraints = vec![
+            create_constraint(
+                "test_pkey",
+                ConstraintType::PrimaryKey {
+                    columns: vec!["test_col".into()],
+                    index_parameters: IndexParameters::default(),
+                },
+            ),
+            Constraint {
+                dependencies: vec![
+                    SchemaQualifiedName::new(SCHEMA, TABLE),
+                    SchemaQualifiedName::new(SCHEMA, "ref_table"),
+                ],
+                ..create_constraint(
+                    "test_fkey",
+                    ConstraintType::ForeignKey {
+                        columns: vec!["test_col".into()],
+                        ref_table: SchemaQualifiedName::new(SCHEMA, "ref_table"),
+                        ref_columns: vec!["test_col".into()],
+                        match_type: ForeignKeyMatch::Simple,
+                        on_delete: ForeignKeyAction::NoAction,
+                        on_update: ForeignKeyAction::NoAction,
+                    },
+                )
+            },
+        ];
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_inline_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(
+                &output_path,
+                ScriptOptions {
+                    inline_constraints: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        let contents =
+            tokio::fs::read_to_string(output_path.join("table/test_schema.test_table.pgsql")).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        assert_eq!(
+            include_str!("../../test-files/sql/database-script-inline-constraints.pgsql").trim(),
+            contents.unwrap().trim()
+        );
+    }
+
+    #[tokio::test]
+    async fn script_out_changed_should_only_write_files_that_differ_from_baseline() {
+        let mut changed_table = create_table(SCHEMA);
+        changed_table.name = SchemaQualifiedName::new(SCHEMA, "changed_table");
+        let database = create_database(vec![], vec![create_table(SCHEMA), changed_table]);
+        let root_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_changed_{}", Uuid::new_v4().simple()));
+        let baseline_path = root_path.join("baseline");
+        let output_path = root_path.join("output");
+        database
+            .script_out(&baseline_path, ScriptOptions::default())
+            .await
+            .unwrap();
+        let unchanged_file = PathBuf::from("table/test_schema.test_table.pgsql");
+        let changed_file = PathBuf::from("table/test_schema.changed_table.pgsql");
+        let removed_file = PathBuf::from("table/test_schema.removed_table.pgsql");
+        let baseline_unchanged = baseline_path.join(&unchanged_file);
+        let unchanged_contents = tokio::fs::read_to_string(&baseline_unchanged)
+            .await
+            .unwrap();
+        tokio::fs::write(
+            &baseline_unchanged,
+            unchanged_contents.replace('\n', "\r\n"),
+        )
+        .await
+        .unwrap();
+        tokio::fs::write(baseline_path.join(&changed_file), "CREATE TABLE stale();\n")
+            .await
+            .unwrap();
+        tokio::fs::write(
+            baseline_path.join(&removed_file),
+            "CREATE TABLE removed();\n",
+        )
+        .await
+        .unwrap();
+
+        let result = database
+            .script_out_changed(&output_path, &baseline_path, ScriptOptions::default())
+            .await;
+        let unchanged_written = output_path.join(&unchanged_file).exists();
+        let changed_written = output_path.join(&changed_file).is_file();
+        tokio::fs::remove_dir_all(&root_path).await.unwrap();
+
+        let changes = result.unwrap();
+        assert_eq!(
+            ScriptChanges {
+                added: vec![],
+                changed: vec![changed_file],
+                removed: vec![removed_file],
+            },
+            changes
+        );
+        assert!(!unchanged_written);
+        assert!(changed_written);
+    }
+
+    #[test]
+    fn empty_copy_should_drop_every_object_when_compared() {
+        let database = create_schema_with_table();
+
+        let result = database
+            .compare_to_other_database(&database.empty_copy(), MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-drop-schema-cascade.pgsql").trim(),
+            result.trim()
+        );
+        assert_eq!(
+            vec!["SCHEMA test_schema", "TABLE test_schema.test_table"],
+            database.object_descriptions()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(None, None, include_str!("../../test-files/sql/database-options-case1.pgsql"))]
+    #[case(
+        Some("template0"),
+        Some("temp_space"),
+        include_str!("../../test-files/sql/database-options-case2.pgsql"),
+    )]
+    fn database_options_should_write_create_database_options(
+        #[case] template: Option<&str>,
+        #[case] tablespace: Option<&str>,
+        #[case] statement: &str,
+    ) {
+        let db_options = DatabaseOptions {
+            encoding: "UTF8".into(),
+            locale: Some("en_US.UTF-8".into()),
+            locale_provider: LocalProvider::Libc {
+                lc_collate: "en_US.UTF-8".into(),
+                lc_ctype: "en_US.UTF-8".into(),
+            },
+            collation_version: "2.36".into(),
+            template: template.map(String::from),
+            tablespace: tablespace.map(String::from),
+        };
+
+        let result = format!("CREATE DATABASE pg_diff_rs_temp{db_options};");
+
+        assert_eq!(statement.trim(), result.trim());
+    }
+
+    #[rstest::rstest]
+    #[case("pg_diff_rs_temp", "\"pg_diff_rs_temp\"")]
+    #[case("MyApp", "\"MyApp\"")]
+    #[case("my\"app", "\"my\"\"app\"")]
+    fn quote_identifier_should_keep_identifier_as_supplied(
+        #[case] identifier: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, quote_identifier(identifier));
+    }
+
+    #[tokio::test]
+    async fn script_out_should_round_trip_statistics_through_owner_table_file() {
+        let mut database = create_schema_with_table();
+        database.tables[0].columns = ["col_a", "col_b"]
+            .into_iter()
+            .map(|name| {
+                serde_json::from_value(serde_json::json!({
+                    "name": name,
+                    "data_type": "integer",
+                    "size": 4,
+                    "collation": null,
+                    "is_non_null": false,
+                    "default_expression": null,
+                    "generated_column": null,
+                    "identity_column": null,
+                    "storage": null,
+                    "compression": "",
+                    "grants": [],
+                }))
+                .unwrap()
+            })
+            .collect();
+        database.statistics.push(Statistics {
+            table_oid: Oid(1),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, "test_statistics"),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            kinds: vec!["ndistinct".into(), "dependencies".into()],
+            columns: "col_a, col_b".into(),
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+        });
+        let output_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_statistics_{}", Uuid::new_v4().simple()));
+
+        let result = database
+            .script_out(&output_path, ScriptOptions::default())
+            .await;
+        let table_file =
+            tokio::fs::read_to_string(output_path.join("table/test_schema.test_table.pgsql")).await;
+        let source_control_database =
+            SourceControlDatabase::from_directory(&output_path, true).await;
+        tokio::fs::remove_dir_all(&output_path).await.unwrap();
+
+        result.unwrap();
+        assert!(table_file.unwrap().contains(
+            "CREATE STATISTICS test_schema.test_statistics (ndistinct, dependencies) ON col_a, \
+             col_b FROM test_schema.test_table;"
+        ));
+        let statement = source_control_database
+            .unwrap()
+            .statements
+            .into_iter()
+            .find(|s| s.object == SchemaQualifiedName::new(SCHEMA, "test_statistics"))
+            .unwrap();
+        assert!(statement
+            .dependencies
+            .contains(&SchemaQualifiedName::new(SCHEMA, TABLE)));
+    }
+
+    #[rstest::rstest]
+    #[case(false)]
+    #[case(true)]
+    fn node_iter_dependencies_should_fail_for_unparsable_function_when_fail_on_unsupported(
+        #[case] fail_on_unsupported: bool,
+    ) {
+        let result = pg_query::parse(
+            "CREATE FUNCTION test_schema.test_function() RETURNS integer LANGUAGE plpgsql AS \
+             $$BEGIN IF THEN; END;$$;",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, fail_on_unsupported);
+
+        if fail_on_unsupported {
+            assert!(dependencies
+                .unwrap_err()
+                .starts_with("Skipping plpg/sql code block"));
+        } else {
+            assert!(dependencies.unwrap().is_empty());
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("CREATE INDEX ON test_schema.test_table (my_schema.my_func(col));")]
+    #[case("CREATE INDEX ON test_schema.test_table (col) WHERE my_schema.my_func(col);")]
+    fn node_iter_dependencies_should_include_functions_of_index_expressions(
+        #[case] statement: &str,
+    ) {
+        let result = pg_query::parse(statement).unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(
+            vec![
+                SchemaQualifiedName::new(SCHEMA, TABLE),
+                SchemaQualifiedName::new("my_schema", "my_func"),
+            ],
+            dependencies
+        );
+    }
+
+    #[test]
+    fn node_iter_dependencies_should_include_index_of_constraint_using_index() {
+        let result = pg_query::parse(
+            "ALTER TABLE test_schema.test_table ADD CONSTRAINT test_unique UNIQUE USING INDEX \
+             test_index;",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(
+            vec![
+                SchemaQualifiedName::new(SCHEMA, TABLE),
+                SchemaQualifiedName::new(SCHEMA, "test_index"),
+            ],
+            dependencies
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "CREATE ACCESS METHOD test_access_method TYPE INDEX HANDLER test_schema.test_handler;",
+        vec![SchemaQualifiedName::new(SCHEMA, "test_handler")]
+    )]
+    #[case(
+        "CREATE INDEX test_index ON test_schema.test_table USING test_access_method (id);",
+        vec![
+            SchemaQualifiedName::new(SCHEMA, TABLE),
+            SchemaQualifiedName::new("", "test_access_method"),
+        ]
+    )]
+    #[case(
+        "CREATE INDEX test_index ON test_schema.test_table USING gin (id);",
+        vec![SchemaQualifiedName::new(SCHEMA, TABLE)]
+    )]
+    fn node_iter_dependencies_should_include_access_method_dependencies(
+        #[case] statement: &str,
+        #[case] expected: Vec<SchemaQualifiedName>,
+    ) {
+        let result = pg_query::parse(statement).unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(expected, dependencies);
+    }
+
+    #[test]
+    fn node_iter_dependencies_should_include_column_collation_and_check_function() {
+        let result = pg_query::parse(
+            "CREATE TABLE test_schema.test_table (name text COLLATE my_coll CHECK (is_valid(name)));",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert!(dependencies.contains(&SchemaQualifiedName::new("", "my_coll")));
+        assert!(dependencies.contains(&SchemaQualifiedName::new("", "is_valid")));
+    }
+
+    #[test]
+    fn node_iter_dependencies_should_include_event_trigger_function() {
+        let result = pg_query::parse(
+            "CREATE EVENT TRIGGER test_event_trigger ON ddl_command_start \
+             EXECUTE FUNCTION test_schema.test_function();",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(
+            vec![SchemaQualifiedName::new(SCHEMA, "test_function")],
+            dependencies
+        );
+    }
+
+    #[test]
+    fn node_iter_dependencies_should_include_published_tables_and_schemas() {
+        let result = pg_query::parse(
+            "CREATE PUBLICATION test_publication FOR TABLE test_schema.test_table (id) WHERE \
+             (id > 5), TABLES IN SCHEMA other_schema;",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(
+            vec![
+                SchemaQualifiedName::new(SCHEMA, TABLE),
+                SchemaQualifiedName::new("other_schema", ""),
+            ],
+            dependencies
+        );
+    }
+
+    #[test]
+    fn node_iter_dependencies_should_include_schemas_of_default_privileges() {
+        let result = pg_query::parse(
+            "ALTER DEFAULT PRIVILEGES FOR ROLE app_owner IN SCHEMA test_schema, other_schema \
+             GRANT SELECT ON TABLES TO readonly;",
+        )
+        .unwrap();
+        let root_node = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|n| n.node.as_ref())
+            .unwrap();
+
+        let dependencies = NodeIter::dependencies(root_node, true).unwrap();
+
+        assert_eq!(
+            vec![
+                SchemaQualifiedName::new(SCHEMA, ""),
+                SchemaQualifiedName::new("other_schema", ""),
+            ],
+            dependencies
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "ALTER DEFAULT PRIVILEGES FOR ROLE app_owner IN SCHEMA test_schema GRANT SELECT ON TABLES \
+         TO readonly;",
+        SchemaQualifiedName::new(SCHEMA, "app_owner.tables")
+    )]
+    #[case(
+        "ALTER DEFAULT PRIVILEGES REVOKE EXECUTE ON FUNCTIONS FROM PUBLIC;",
+        SchemaQualifiedName::new("", "current_user.functions")
+    )]
+    #[case(
+        "ALTER DEFAULT PRIVILEGES FOR ROLE CURRENT_ROLE GRANT USAGE ON SEQUENCES TO app_user;",
+        SchemaQualifiedName::new("", "current_role.sequences")
+    )]
+    fn default_privileges_name_should_name_by_schema_grantor_and_object_type(
+        #[case] statement: &str,
+        #[case] expected: SchemaQualifiedName,
+    ) {
+        let result = pg_query::parse(statement).unwrap();
+        let Some(Node::AlterDefaultPrivilegesStmt(alter_default_privileges)) =
+            result.protobuf.stmts[0]
+                .stmt
+                .as_ref()
+                .and_then(|n| n.node.as_ref())
+        else {
+            panic!("Statement should be ALTER DEFAULT PRIVILEGES");
+        };
+
+        assert_eq!(expected, default_privileges_name(alter_default_privileges));
+    }
+
+    #[rstest::rstest]
+    #[case(None, None)]
+    #[case(Some(64), None)]
+    #[case(
+        Some(16),
+        Some(
+            "Warning: Statement for test_schema.test_table is 39 bytes which exceeds the maximum \
+             statement length of 16 bytes",
+        )
+    )]
+    fn oversized_statement_warning_should_report_statements_over_max_length(
+        #[case] max_statement_length: Option<usize>,
+        #[case] expected: Option<&str>,
+    ) {
+        let mut source_control_database = SourceControlDatabase::new();
+        source_control_database.max_statement_length = max_statement_length;
+        let statement = DdlStatement {
+            id: 0,
+            object: SchemaQualifiedName::new(SCHEMA, TABLE),
+            statement: "CREATE TABLE test_schema.test_table ();".into(),
+            dependencies: vec![],
+            dropped_objects: vec![],
+            is_event_trigger: false,
+        };
+
+        let warning = source_control_database.oversized_statement_warning(&statement);
+
+        assert_eq!(expected, warning.as_deref());
+    }
+
+    /// Create a temp directory with one valid source file and one source file that is not valid
+    /// UTF-8
+    async fn create_source_directory() -> PathBuf {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        tokio::fs::write(files_path.join("valid.sql"), "CREATE SCHEMA test_schema;\n")
+            .await
+            .unwrap();
+        tokio::fs::write(files_path.join("invalid.sql"), [0xFF, 0xFE, 0x00, 0x43])
+            .await
+            .unwrap();
+        files_path
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_skip_invalid_encoding_files_when_not_strict() {
+        let files_path = create_source_directory().await;
+
+        let result = SourceControlDatabase::from_directory(&files_path, false).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let database = result.unwrap();
+        assert_eq!(1, database.statements.len());
+        assert_eq!(1, database.skipped_files.len());
+        assert_eq!(files_path.join("invalid.sql"), database.skipped_files[0].0);
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_fail_for_invalid_encoding_files_when_strict() {
+        let files_path = create_source_directory().await;
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(PgDiffError::FileQueryParse { path, .. }) if path == files_path.join("invalid.sql")
+        ));
+    }
+
+    #[tokio::test]
+    async fn from_dump_file_should_skip_session_statements() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        let dump_path = files_path.join("schema.sql");
+        tokio::fs::write(
+            &dump_path,
+            include_str!("../../test-files/sql/source-pg-dump.pgsql"),
+        )
+        .await
+        .unwrap();
+
+        let result = SourceControlDatabase::from_dump_file(&dump_path).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let database = result.unwrap();
+        assert_eq!(4, database.statements.len());
+        assert!(database.statements.iter().all(|s| {
+            let statement = s.statement.trim_start().to_lowercase();
+            !statement.starts_with("set") && !statement.starts_with("select")
+        }));
+        assert!(database.database_settings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_collect_drop_if_exists_statements() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        tokio::fs::write(
+            files_path.join("rerunnable.sql"),
+            include_str!("../../test-files/sql/source-drop-and-create.pgsql"),
+        )
+        .await
+        .unwrap();
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let database = result.unwrap();
+        let dropped: Vec<(String, Vec<String>)> = database
+            .statements
+            .iter()
+            .filter(|s| s.is_drop())
+            .map(|s| {
+                (
+                    s.object.to_string(),
+                    s.dropped_objects.iter().map(|d| d.to_string()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "test_schema.test_table.test_trigger".to_string(),
+                    vec!["test_schema.test_table.test_trigger".to_string()]
+                ),
+                (
+                    "test_schema.test_function".to_string(),
+                    vec!["test_schema.test_function".to_string()]
+                ),
+                (
+                    "test_schema.test_view".to_string(),
+                    vec![
+                        "test_schema.test_view".to_string(),
+                        "test_schema.other_view".to_string()
+                    ]
+                ),
+            ],
+            dropped
+        );
+        assert_eq!(6, database.statements.len());
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_fail_for_drop_without_if_exists() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        tokio::fs::write(
+            files_path.join("drop.sql"),
+            "DROP VIEW test_schema.test_view;\n",
+        )
+        .await
+        .unwrap();
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(PgDiffError::FileQueryParse { path, .. }) if path == files_path.join("drop.sql")
+        ));
+    }
+
+    #[tokio::test]
+    async fn record_failed_statement_should_only_update_statement_with_same_id() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        let statement = "CREATE OR REPLACE VIEW test_schema.test_view AS SELECT 1;\n";
+        for file_name in ["first.sql", "second.sql"] {
+            tokio::fs::write(files_path.join(file_name), statement)
+                .await
+                .unwrap();
+        }
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let mut database = result.unwrap();
+        let original_statements = database.statements.clone();
+        assert_eq!(2, original_statements.len());
+        assert_eq!(
+            original_statements[0].statement,
+            original_statements[1].statement
+        );
+        assert_ne!(original_statements[0].id, original_statements[1].id);
+
+        let failed_statement = database
+            .record_failed_statement(
+                original_statements[1].clone(),
+                "relation \"test_schema.missing_table\" does not exist",
+            )
+            .unwrap();
+
+        let mut expected_statement = original_statements[1].clone();
+        expected_statement
+            .dependencies
+            .push(SchemaQualifiedName::from("test_schema.missing_table"));
+        assert_eq!(expected_statement, failed_statement);
+        assert_eq!(original_statements[0], database.statements[0]);
+        assert_eq!(expected_statement, database.statements[1]);
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_collect_roles_without_statements() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        tokio::fs::write(
+            files_path.join("roles.sql"),
+            "CREATE ROLE app_base;\nCREATE ROLE app_reader LOGIN IN ROLE app_base;\nCREATE SCHEMA test_schema;\n",
+        )
+        .await
+        .unwrap();
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let database = result.unwrap();
+        assert_eq!(1, database.statements.len());
+        let roles: Vec<String> = database.roles.iter().map(|r| r.name.to_string()).collect();
+        assert_eq!(vec!["app_base", "app_reader"], roles);
+        assert_eq!(vec!["app_base"], database.roles[1].member_of);
+    }
+
+    #[tokio::test]
+    async fn from_directory_should_find_operator_and_operator_class_dependencies() {
+        let files_path =
+            std::env::temp_dir().join(format!("pg_diff_rs_source_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&files_path).await.unwrap();
+        tokio::fs::write(
+            files_path.join("operators.sql"),
+            include_str!("../../test-files/sql/source-operator-dependencies.pgsql"),
+        )
+        .await
+        .unwrap();
+
+        let result = SourceControlDatabase::from_directory(&files_path, true).await;
+        tokio::fs::remove_dir_all(&files_path).await.unwrap();
+
+        let database = result.unwrap();
+        let objects: Vec<(String, Vec<String>)> = database
+            .statements
+            .iter()
+            .map(|s| {
+                (
+                    s.object.to_string(),
+                    s.dependencies.iter().map(|d| d.to_string()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "test_schema.<<<".to_string(),
+                    vec!["test_schema.abs_lt".to_string()]
+                ),
+                (
+                    "test_schema.abs_int_ops".to_string(),
+                    vec![
+                        "test_schema.<<<".to_string(),
+                        "test_schema.abs_cmp".to_string()
+                    ]
+                ),
+                (
+                    "test_schema.test_index".to_string(),
+                    vec![
+                        "test_schema.test_table".to_string(),
+                        "test_schema.abs_int_ops".to_string()
+                    ]
+                ),
+            ],
+            objects
+        );
+    }
+
+    fn create_view(name: &str, query: &str, dependencies: &[&str]) -> View {
+        let mut dependencies: Vec<SchemaQualifiedName> = dependencies
+            .iter()
+            .map(|d| SchemaQualifiedName::new(SCHEMA, d))
+            .collect();
+        dependencies.push(SchemaQualifiedName::new(SCHEMA, ""));
+        View {
+            oid: Oid(1),
+            name: SchemaQualifiedName::new(SCHEMA, name),
+            columns: Some(vec!["test_col".into()]),
+            column_types: Some(vec!["integer".into()]),
+            query: query.into(),
+            options: None,
+            check_option: None,
+            dependencies,
+            column_dependencies: None,
+        }
+    }
+
+    fn create_schema_with_views(base_view_query: &str) -> Database {
+        let mut database = create_database(
+            vec![Schema {
+                name: SchemaQualifiedName::new(SCHEMA, ""),
+                owner: "test_owner".into(),
+            }],
+            vec![],
+        );
+        database.views = vec![
+            create_view(
+                "top_view",
+                "SELECT test_col FROM test_schema.middle_view;",
+                &["middle_view", "base_view"],
+            ),
+            create_view(
+                "middle_view",
+                "SELECT test_col FROM test_schema.base_view;",
+                &["base_view"],
+            ),
+            create_view("base_view", base_view_query, &[]),
+        ];
+        database
+    }
+
+    fn create_constraint(name: &str, constraint_type: ConstraintType) -> Constraint {
+        Constraint {
+            table_oid: Oid(1),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            name: name.into(),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, &format!("{TABLE}.{name}")),
+            constraint_type,
+            timing: ConstraintTiming::NotDeferrable,
+            is_validated: true,
+            is_enforced: true,
+            is_replica_identity: false,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+        }
+    }
+
+    #[test]
+    fn compare_to_other_database_should_add_constraints_not_valid_and_validate_last() {
+        let old = create_schema_with_table();
+        let mut new = create_schema_with_table();
+        new.constraints = vec![
+            create_constraint(
+                "test_check",
+                ConstraintType::Check {
+                    columns: vec!["test_col".into()],
+                    expression: "test_col > 0".into(),
+                    is_inheritable: true,
+                },
+            ),
+            create_constraint(
+                "test_unique",
+                ConstraintType::Unique {
+                    columns: vec!["test_col".into()],
+                    are_nulls_distinct: true,
+                    index_parameters: IndexParameters::default(),
+                },
+            ),
+            create_constraint(
+                "test_fk",
+                ConstraintType::ForeignKey {
+                    columns: vec!["test_col".into()],
+                    ref_table: SchemaQualifiedName::new(SCHEMA, TABLE),
+                    ref_columns: vec!["id".into()],
+                    match_type: ForeignKeyMatch::Simple,
+                    on_delete: ForeignKeyAction::NoAction,
+                    on_update: ForeignKeyAction::NoAction,
+                },
+            ),
+        ];
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    add_constraints_not_valid: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-constraints-not-valid.pgsql")
+                .trim(),
+            result.trim()
+        );
+    }
+
+    #[test]
+    fn compare_to_other_database_should_guard_statements_when_idempotent() {
+        let old = create_schema_with_table();
+        let mut new = create_schema_with_table();
+        new.constraints = vec![create_constraint(
+            "test_check",
+            ConstraintType::Check {
+                columns: vec!["test_col".into()],
+                expression: "test_col > 0".into(),
+                is_inheritable: true,
+            },
+        )];
+        let options = MigrationOptions {
+            cascade_schema_drop: false,
+            add_constraints_not_valid: true,
+            idempotent: true,
+            allow_destructive: true,
+            ..Default::default()
+        };
+        let empty = create_database(vec![], vec![]);
+
+        let create_result = old
+            .compare_to_other_database(&new, options.clone())
+            .unwrap();
+        let drop_result = old
+            .compare_to_other_database(&empty, options.clone())
+            .unwrap();
+        let cascade_result = old
+            .compare_to_other_database(
+                &empty,
+                MigrationOptions {
+                    cascade_schema_drop: true,
+                    ..options
+                },
+            )
+            .unwrap();
+
+        assert!(create_result.contains("FROM pg_catalog.pg_constraint"));
+        assert!(create_result.contains("AND conname = 'test_check'"));
+        assert!(create_result.contains("NOT VALID;"));
+        assert!(drop_result.contains("DROP TABLE IF EXISTS test_schema.test_table;"));
+        assert!(drop_result.contains("DROP SCHEMA IF EXISTS test_schema;"));
+        assert!(cascade_result.contains("DROP SCHEMA IF EXISTS test_schema CASCADE;"));
+    }
+
+    #[test]
+    fn compare_to_other_database_should_set_replica_identity_index_after_creating_index() {
+        let old = create_schema_with_table();
+        let mut new = create_schema_with_table();
+        new.tables[0].replica_identity =
+            ReplicaIdentity::Index(SchemaQualifiedName::new(SCHEMA, "test_index"));
+        new.indexes = vec![Index {
+            table_oid: Oid(1),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, "test_index"),
+            columns: vec!["test_col".into()],
+            key_columns: vec![IndexColumn {
+                expression: "test_col".into(),
+                ..Default::default()
+            }],
+            definition_statement:
+                "CREATE UNIQUE INDEX test_index ON test_schema.test_table USING btree (test_col)"
+                    .into(),
+            access_method: "btree".into(),
+            is_unique: true,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            ..Default::default()
+        }];
+
+        let migrate = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+        let revert = new
+            .compare_to_other_database(&old, MigrationOptions::default())
+            .unwrap();
+
+        let create_index = migrate.find("CREATE UNIQUE INDEX test_index").unwrap();
+        let set_identity = migrate
+            .find("ALTER TABLE test_schema.test_table REPLICA IDENTITY USING INDEX test_index;")
+            .unwrap();
+        assert!(create_index < set_identity);
+        assert!(revert.contains("DROP INDEX test_schema.test_index;"));
+        assert!(revert.contains("ALTER TABLE test_schema.test_table REPLICA IDENTITY DEFAULT;"));
+        assert!(!revert.contains("USING INDEX"));
+    }
+
+    fn create_unique_index(name: &str) -> Index {
+        Index {
+            table_oid: Oid(1),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, name),
+            columns: vec!["test_col".into()],
+            key_columns: vec![IndexColumn {
+                expression: "test_col".into(),
+                ..Default::default()
+            }],
+            definition_statement: format!(
+                "CREATE UNIQUE INDEX {name} ON test_schema.test_table USING btree (test_col)"
+            ),
+            access_method: "btree".into(),
+            is_unique: true,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_to_other_database_should_add_constraint_using_removed_unique_index() {
+        let mut old = create_schema_with_table();
+        old.indexes = vec![create_unique_index("test_index")];
+        let mut new = create_schema_with_table();
+        new.constraints = vec![Constraint {
+            timing: ConstraintTiming::Deferrable {
+                is_immediate: false,
+            },
+            ..create_constraint(
+                "test_unique",
+                ConstraintType::Unique {
+                    columns: vec!["test_col".into()],
+                    are_nulls_distinct: true,
+                    index_parameters: IndexParameters::default(),
+                },
+            )
+        }];
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert!(result.contains(
+            "ALTER TABLE test_schema.test_table ADD CONSTRAINT test_unique UNIQUE USING INDEX \
+             test_index DEFERRABLE INITIALLY DEFERRED;"
+        ));
+        assert!(!result.contains("DROP INDEX"));
+    }
+
+    #[test]
+    fn compare_to_other_database_should_not_drop_members_of_new_extensions() {
+        let old = create_schema_with_table();
+        let mut new = create_schema_with_table();
+        new.tables = vec![];
+        new.extensions = vec![Extension {
+            name: SchemaQualifiedName::new("", "test_extension"),
+            version: "1.0".into(),
+            schema_name: SCHEMA.into(),
+            members: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            ..Default::default()
+        }];
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert!(result.contains("CREATE EXTENSION test_extension"));
+        assert!(!result.contains("DROP TABLE"));
+    }
+
+    fn create_schema_with_owner(schema: &str, owner: &str, table_name: &str) -> Database {
+        create_database(
+            vec![Schema {
+                name: SchemaQualifiedName::new(schema, ""),
+                owner: owner.into(),
+            }],
+            vec![Table {
+                name: SchemaQualifiedName::new(schema, table_name),
+                ..create_table(schema)
+            }],
+        )
+    }
+
+    #[test]
+    fn compare_to_other_database_should_alter_schema_owner() {
+        let old = create_schema_with_owner(SCHEMA, "test_owner", TABLE);
+        let new = create_schema_with_owner(SCHEMA, "other_owner", TABLE);
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            "ALTER SCHEMA test_schema OWNER TO other_owner;",
+            result.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("test_owner", "ALTER SCHEMA old_schema RENAME TO new_schema;")]
+    #[case(
+        "other_owner",
+        "ALTER SCHEMA old_schema RENAME TO new_schema;\nALTER SCHEMA new_schema OWNER TO other_owner;"
+    )]
+    fn compare_to_other_database_should_rename_schema_when_detecting_renames(
+        #[case] new_owner: &str,
+        #[case] statement: &str,
+    ) {
+        let old = create_schema_with_owner("old_schema", "test_owner", TABLE);
+        let new = create_schema_with_owner("new_schema", new_owner, TABLE);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    detect_renames: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement, result.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(true, "other_table")]
+    #[case(false, TABLE)]
+    fn compare_to_other_database_should_not_rename_schema_unless_contents_match(
+        #[case] detect_renames: bool,
+        #[case] new_table: &str,
+    ) {
+        let old = create_schema_with_owner("old_schema", "test_owner", TABLE);
+        let new = create_schema_with_owner("new_schema", "test_owner", new_table);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    detect_renames,
+                    allow_destructive: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(!result.contains("RENAME TO"));
+        assert!(result.contains("DROP SCHEMA old_schema CASCADE;"));
+        assert!(result.contains("CREATE SCHEMA new_schema"));
+    }
+
+    fn create_schema_with_nullable_column(is_non_null: bool) -> Database {
+        create_schema_with_column("integer", is_non_null)
+    }
+
+    fn create_schema_with_column(data_type: &str, is_non_null: bool) -> Database {
+        let mut database = create_schema_with_table();
+        database.tables[0].columns = vec![serde_json::from_value(serde_json::json!({
+            "name": "test_col",
+            "data_type": data_type,
+            "size": 4,
+            "collation": null,
+            "is_non_null": is_non_null,
+            "default_expression": null,
+            "generated_column": null,
+            "identity_column": null,
+            "storage": null,
+            "compression": "",
+            "grants": [],
+        }))
+        .unwrap()];
+        database
+    }
+
+    #[rstest::rstest]
+    #[case(
+        120000,
+        include_str!("../../test-files/sql/database-compare-safe-not-null.pgsql"),
+    )]
+    #[case(
+        110000,
+        "ALTER TABLE test_schema.test_table ALTER COLUMN test_col SET NOT NULL;"
+    )]
+    fn compare_to_other_database_should_only_set_not_null_safely_when_supported(
+        #[case] server_version_num: i32,
+        #[case] statement: &str,
+    ) {
+        let mut old = create_schema_with_nullable_column(false);
+        old.settings.server_version_num = server_version_num;
+        let new = create_schema_with_nullable_column(true);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    safe_not_null: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement.trim(), result.trim());
+    }
+
+    /// Database with a `test_col` column of the `data_type` and a `NOT NULL` `other_col` column
+    /// only when `is_other_non_null` is true
+    fn create_schema_with_columns(data_type: &str, is_other_non_null: bool) -> Database {
+        let mut database = create_schema_with_column(data_type, false);
+        database.tables[0].columns.push(
+            serde_json::from_value(serde_json::json!({
+                "name": "other_col",
+                "data_type": "text",
+                "size": -1,
+                "collation": null,
+                "is_non_null": is_other_non_null,
+                "default_expression": null,
+                "generated_column": null,
+                "identity_column": null,
+                "storage": null,
+                "compression": "",
+                "grants": [],
+            }))
+            .unwrap(),
+        );
+        database
+    }
+
+    #[test]
+    fn compare_to_other_database_should_comment_manual_changes_when_unsupported() {
+        let old = create_schema_with_columns("integer", true);
+        let new = create_schema_with_columns("bigint", false);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    on_unsupported: OnUnsupported::Comment,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-manual-change.pgsql").trim(),
+            result.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        OnUnsupported::Error,
+        false,
+        "Could not construct a migration strategy for test_schema.test_table: column test_col type integer -> bigint"
+    )]
+    #[case(
+        OnUnsupported::Comment,
+        true,
+        "Plan requires manual changes to TABLE test_schema.test_table"
+    )]
+    fn compare_to_other_database_should_fail_for_unsupported_changes(
+        #[case] on_unsupported: OnUnsupported,
+        #[case] fail_on_manual: bool,
+        #[case] expected_error: &str,
+    ) {
+        let old = create_schema_with_columns("integer", true);
+        let new = create_schema_with_columns("bigint", false);
+
+        let result = old.compare_to_other_database(
+            &new,
+            MigrationOptions {
+                on_unsupported,
+                fail_on_manual,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(expected_error, result.unwrap_err().to_string());
+    }
+
+    fn create_event_trigger(name: &str) -> EventTrigger {
+        EventTrigger {
+            name: SchemaQualifiedName::new("", name),
+            event: "ddl_command_start".into(),
+            tags: vec![],
+            function_name: SchemaQualifiedName::new(SCHEMA, "guard"),
+            enabled: EventTriggerEnabled::Origin,
+            dependencies: vec![],
+        }
+    }
+
+    #[test]
+    fn migration_steps_should_plan_event_triggers_around_all_other_steps() {
+        let mut old = create_database(vec![], vec![]);
+        old.event_triggers = vec![create_event_trigger("old_guard")];
+        let mut new = create_schema_with_table();
+        new.event_triggers = vec![create_event_trigger("block_ddl")];
+
+        let (steps, _) = old
+            .migration_steps(&new, &MigrationOptions::default())
+            .unwrap();
+
+        let steps: Vec<(StepKind, String)> = steps
+            .into_iter()
+            .map(|s| (s.kind, s.object.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                (StepKind::Drop, "old_guard".to_string()),
+                (StepKind::Create, SCHEMA.to_string()),
+                (StepKind::Create, format!("{SCHEMA}.{TABLE}")),
+                (StepKind::Create, "block_ddl".to_string()),
+            ],
+            steps
+        );
+    }
+
+    #[test]
+    fn compare_to_other_database_should_analyze_each_touched_table_once() {
+        let old = create_schema_with_table();
+        let mut new = create_schema_with_table();
+        new.tables[0].tablespace = Some(TableSpace("test_tablespace".into()));
+        new.constraints = vec![create_constraint(
+            "test_check",
+            ConstraintType::Check {
+                columns: vec!["test_col".into()],
+                expression: "test_col > 0".into(),
+                is_inheritable: true,
+            },
+        )];
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    post_migration_maintenance: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(1, result.matches("ANALYZE ").count());
+        assert!(result.contains(&format!("ANALYZE {SCHEMA}.{TABLE};")));
+    }
+
+    /// Trigger on the test table with the `enabled` firing state
+    fn create_table_trigger(name: &str, enabled: TriggerEnabled) -> Trigger {
+        Trigger {
+            owner_oid: Oid(1),
+            name: name.into(),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, &format!("{TABLE}.{name}")),
+            owner_object_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            timing: TriggerTiming::After,
+            events: vec![TriggerEvent::Insert],
+            is_row_level: true,
+            function_name: SchemaQualifiedName::new(SCHEMA, "test_trigger_function"),
+            enabled,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            ..Default::default()
+        }
+    }
+
+    /// Triggers of the test table covering every firing state
+    fn create_table_triggers() -> Vec<Trigger> {
+        vec![
+            create_table_trigger("origin_trigger", TriggerEnabled::Origin),
+            create_table_trigger("replica_trigger", TriggerEnabled::Replica),
+            create_table_trigger("always_trigger", TriggerEnabled::Always),
+            create_table_trigger("disabled_trigger", TriggerEnabled::Disabled),
+        ]
+    }
+
+    #[test]
+    fn compare_to_other_database_should_disable_triggers_around_altered_tables() {
+        let mut old = create_schema_with_table();
+        old.tables.push(Table {
+            name: SchemaQualifiedName::new(SCHEMA, "old_table"),
+            ..create_table(SCHEMA)
+        });
+        old.triggers = create_table_triggers();
+        let mut new = create_schema_with_table();
+        new.triggers = create_table_triggers();
+        new.constraints = vec![create_constraint(
+            "test_check",
+            ConstraintType::Check {
+                columns: vec!["test_col".into()],
+                expression: "test_col > 0".into(),
+                is_inheritable: true,
+            },
+        )];
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    disable_triggers: true,
+                    transactional: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-disable-triggers.pgsql").trim(),
+            result.trim()
+        );
+    }
+
+    #[test]
+    fn disabled_triggers_should_skip_triggers_migrated_by_plan() {
+        let triggers = create_table_triggers();
+        let steps = vec![
+            MigrationStep {
+                kind: StepKind::Alter,
+                object: SchemaQualifiedName::new(SCHEMA, TABLE),
+                object_type: "TABLE".into(),
+                sql: String::new(),
+                is_destructive: false,
+                table_step: Some(TableStep {
+                    table: SchemaQualifiedName::new(SCHEMA, TABLE),
+                    order: TableStepOrder::Table,
+                }),
+                dependencies: vec![],
+            },
+            MigrationStep {
+                kind: StepKind::Alter,
+                object: triggers[0].schema_qualified_name.clone(),
+                object_type: "TRIGGER".into(),
+                sql: String::new(),
+                is_destructive: false,
+                table_step: Some(TableStep {
+                    table: SchemaQualifiedName::new(SCHEMA, TABLE),
+                    order: TableStepOrder::TriggerOrPolicy,
+                }),
+                dependencies: vec![],
+            },
+        ];
+
+        let disabled_triggers = DisabledTriggers::from_steps(&steps, &triggers);
+
+        let names: Vec<&str> = disabled_triggers
+            .triggers
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(vec!["replica_trigger", "always_trigger"], names);
+    }
+
+    #[test]
+    fn compare_to_other_database_should_recreate_dependent_views() {
+        let old = create_schema_with_views("SELECT 1 AS test_col;");
+        let new = create_schema_with_views("SELECT 2 AS test_col;");
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-recreate-dependent-views.pgsql")
+                .trim(),
+            result.trim()
+        );
+    }
+
+    fn create_column_dependency(relation: &str, column: &str) -> ColumnDependency {
+        ColumnDependency {
+            relation: SchemaQualifiedName::new(SCHEMA, relation),
+            column: column.into(),
+        }
+    }
+
+    /// Create a view over the `column` of the `relation`. Column dependencies are only included
+    /// if `include_column_dependencies` is true.
+    fn create_column_view(
+        name: &str,
+        relation: &str,
+        column: &str,
+        include_column_dependencies: bool,
+    ) -> View {
+        View {
+            column_dependencies: include_column_dependencies
+                .then(|| vec![create_column_dependency(relation, column)]),
+            ..create_view(
+                name,
+                &format!("SELECT {column} AS test_col FROM {SCHEMA}.{relation};"),
+                &[relation],
+            )
+        }
+    }
+
+    fn create_column(name: &str, data_type: &str) -> Column {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "data_type": data_type,
+            "size": 4,
+            "collation": null,
+            "is_non_null": false,
+            "default_expression": null,
+            "generated_column": null,
+            "identity_column": null,
+            "storage": null,
+            "compression": "",
+            "grants": [],
+        }))
+        .unwrap()
+    }
+
+    fn create_schema_with_column_views(
+        columns: &[&str],
+        include_column_dependencies: bool,
+    ) -> Database {
+        let mut database = create_schema_with_table();
+        database.tables[0].columns = columns
+            .iter()
+            .map(|column| create_column(column, "integer"))
+            .collect();
+        database.views = vec![
+            create_column_view(
+                "dropped_view",
+                TABLE,
+                "other_col",
+                include_column_dependencies,
+            ),
+            create_column_view("kept_view", TABLE, "test_col", include_column_dependencies),
+            create_column_view(
+                "top_view",
+                "dropped_view",
+                "test_col",
+                include_column_dependencies,
+            ),
+        ];
+        database
+    }
+
+    #[rstest::rstest]
+    #[case(true)]
+    #[case(false)]
+    fn compare_to_other_database_should_recreate_views_referencing_dropped_columns(
+        #[case] include_column_dependencies: bool,
+    ) {
+        let old = create_schema_with_column_views(
+            &["test_col", "other_col"],
+            include_column_dependencies,
+        );
+        let new = create_schema_with_column_views(&["test_col"], include_column_dependencies);
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        let position = |statement: &str| {
+            result
+                .find(statement)
+                .unwrap_or_else(|| panic!("Expected '{statement}' in plan\n{result}"))
+        };
+        assert!(
+            position("DROP VIEW test_schema.top_view;")
+                < position("DROP VIEW test_schema.dropped_view;")
+        );
+        assert!(
+            position("DROP VIEW test_schema.dropped_view;")
+                < position("ALTER TABLE test_schema.test_table DROP COLUMN other_col;")
+        );
+        assert!(
+            position("ALTER TABLE test_schema.test_table DROP COLUMN other_col;")
+                < position("CREATE OR REPLACE VIEW test_schema.dropped_view")
+        );
+        assert!(
+            position("CREATE OR REPLACE VIEW test_schema.dropped_view")
+                < position("CREATE OR REPLACE VIEW test_schema.top_view")
+        );
+        // Without column dependencies, every view of the table is assumed to use the column
+        assert_eq!(
+            !include_column_dependencies,
+            result.contains("DROP VIEW test_schema.kept_view;")
+        );
+    }
+
+    /// Create a function with a pre-parsed body selecting the `column` of the test table
+    fn create_column_function(name: &str, column: &str) -> Function {
+        Function {
+            name: SchemaQualifiedName::new(SCHEMA, name),
+            return_type: Some("integer".into()),
+            estimated_cost: 100.0,
+            source_code: FunctionSourceCode::Sql {
+                source: format!("BEGIN ATOMIC\n SELECT {column} FROM {SCHEMA}.{TABLE};\nEND"),
+                is_pre_parsed: true,
+            },
+            grants: vec![AclGrant {
+                grantee: "test_role".into(),
+                privilege_type: "EXECUTE".into(),
+                is_grantable: false,
+            }],
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+            column_dependencies: vec![create_column_dependency(TABLE, column)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_to_other_database_should_recreate_functions_referencing_dropped_columns() {
+        let mut old = create_schema_with_column_views(&["test_col", "other_col"], true);
+        old.views.clear();
+        old.functions = vec![
+            create_column_function("changed_function", "other_col"),
+            create_column_function("kept_function", "test_col"),
+        ];
+        let mut new = create_schema_with_column_views(&["test_col"], true);
+        new.views.clear();
+        new.functions = vec![
+            create_column_function("changed_function", "test_col"),
+            create_column_function("kept_function", "test_col"),
+        ];
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        let position = |statement: &str| {
+            result
+                .find(statement)
+                .unwrap_or_else(|| panic!("Expected '{statement}' in plan\n{result}"))
+        };
+        assert!(
+            position("DROP FUNCTION test_schema.changed_function;")
+                < position("ALTER TABLE test_schema.test_table DROP COLUMN other_col;")
+        );
+        assert!(
+            position("ALTER TABLE test_schema.test_table DROP COLUMN other_col;")
+                < position("CREATE OR REPLACE FUNCTION test_schema.changed_function")
+        );
+        assert!(
+            position("CREATE OR REPLACE FUNCTION test_schema.changed_function")
+                < position(
+                    "GRANT EXECUTE ON FUNCTION test_schema.changed_function() TO test_role;"
+                )
+        );
+        assert!(!result.contains("test_schema.kept_function"));
+    }
+
+    fn create_view_trigger(view: &str) -> Trigger {
+        Trigger {
+            owner_oid: Oid(1),
+            name: "test_trigger".into(),
+            schema_qualified_name: SchemaQualifiedName::new(
+                SCHEMA,
+                &format!("{view}.test_trigger"),
+            ),
+            owner_object_name: SchemaQualifiedName::new(SCHEMA, view),
+            timing: TriggerTiming::InsteadOf,
+            events: vec![TriggerEvent::Insert],
+            is_row_level: true,
+            function_name: SchemaQualifiedName::new(SCHEMA, "test_trigger_function"),
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, view)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_to_other_database_should_recreate_triggers_of_recreated_views() {
+        let mut old = create_schema_with_views("SELECT 1 AS test_col;");
+        old.triggers = vec![create_view_trigger("middle_view")];
+        let mut new = create_schema_with_views("SELECT 2 AS test_col;");
+        new.triggers = vec![create_view_trigger("middle_view")];
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-recreate-view-triggers.pgsql")
+                .trim(),
+            result.trim()
+        );
+    }
+
+    fn create_statement(object: &str, dependencies: &[&str]) -> DdlStatement {
+        DdlStatement {
+            id: 0,
+            statement: format!("CREATE TABLE {object}();"),
+            object: SchemaQualifiedName::from(object),
+            dependencies: dependencies.iter().map(SchemaQualifiedName::from).collect(),
+            dropped_objects: vec![],
+            is_event_trigger: false,
+        }
+    }
+
+    fn create_event_trigger_statement(object: &str, dependencies: &[&str]) -> DdlStatement {
+        DdlStatement {
+            id: 0,
+            statement: format!(
+                "CREATE EVENT TRIGGER {object} ON ddl_command_start EXECUTE FUNCTION guard();"
+            ),
+            object: SchemaQualifiedName::from(object),
+            dependencies: dependencies.iter().map(SchemaQualifiedName::from).collect(),
+            dropped_objects: vec![],
+            is_event_trigger: true,
+        }
+    }
+
+    fn create_drop_statement(object: &str) -> DdlStatement {
+        DdlStatement {
+            id: 0,
+            statement: format!("DROP TABLE IF EXISTS {object};"),
+            object: SchemaQualifiedName::from(object),
+            dependencies: vec![],
+            dropped_objects: vec![SchemaQualifiedName::from(object)],
+            is_event_trigger: false,
+        }
+    }
+
+    /// Drive the [StatementIter] the same way as applying statements to a temp database (see
+    /// [super::apply_adaptively]). A statement fails if any of the object's `hidden_dependencies`
+    /// (dependencies not declared within the [DdlStatement]) have not been executed yet. Returns
+    /// the executed objects in order.
+    fn execute_statements(
+        statements: &[DdlStatement],
+        hidden_dependencies: &[(&str, &str)],
+    ) -> Result<Vec<String>, PgDiffError> {
+        let mut executed: Vec<String> = vec![];
+        block_on(super::apply_adaptively(
+            StatementIter::new(statements),
+            |statement| execute(statement, hidden_dependencies, &mut executed),
+            |statement, _| Ok(statement),
+            0,
+            statements.len(),
+        ))?;
+        Ok(executed)
+    }
+
+    /// Execute the `statement`, adding the object to the `executed` objects unless a declared or
+    /// hidden dependency has not been executed yet
+    fn execute(
+        statement: &DdlStatement,
+        hidden_dependencies: &[(&str, &str)],
+        executed: &mut Vec<String>,
+    ) -> Ready<Result<(), ()>> {
+        let object = statement.object.to_string();
+        let is_failing = statement
+            .dependencies
+            .iter()
+            .map(|d| d.to_string())
+            .chain(
+                hidden_dependencies
+                    .iter()
+                    .filter(|(o, _)| *o == object)
+                    .map(|(_, d)| d.to_string()),
+            )
+            .any(|d| d != object && !executed.contains(&d));
+        if is_failing {
+            return ready(Err(()));
+        }
+        executed.push(object);
+        ready(Ok(()))
+    }
+
+    /// Apply the statements the same way as [SourceControlDatabase::apply_to_temp_database] (see
+    /// [super::apply_statements]). Returns the executed objects in order and true if every
+    /// statement was executed in sorted order without falling back to adaptive application.
+    fn apply_statements(
+        statements: &[DdlStatement],
+        hidden_dependencies: &[(&str, &str)],
+    ) -> Result<(Vec<String>, bool), PgDiffError> {
+        let mut executed: Vec<String> = vec![];
+        let is_fast_path = block_on(super::apply_statements(
+            sort_statements(statements),
+            |statement| execute(statement, hidden_dependencies, &mut executed),
+            |statement, _| Ok(statement),
+        ))?;
+        Ok((executed, is_fast_path))
+    }
+
+    #[test]
+    fn sort_statements_should_order_drop_before_statements_of_dropped_object() {
+        let statements = vec![
+            create_statement("test_schema.test_view", &["test_schema.test_table"]),
+            create_statement("test_schema.test_table", &[]),
+            create_drop_statement("test_schema.test_view"),
+            create_drop_statement("test_schema.test_table"),
+        ];
+
+        let sorted: Vec<String> = sort_statements(&statements)
+            .into_iter()
+            .map(|s| s.statement)
+            .collect();
+
+        assert_eq!(
+            vec![
+                "DROP TABLE IF EXISTS test_schema.test_view;",
+                "DROP TABLE IF EXISTS test_schema.test_table;",
+                "CREATE TABLE test_schema.test_table();",
+                "CREATE TABLE test_schema.test_view();",
+            ],
+            sorted
+        );
+    }
+
+    #[test]
+    fn statement_iter_should_not_complete_object_of_drop_statement() {
+        let statements = vec![
+            create_statement("test_schema.test_view", &["test_schema.test_table"]),
+            create_drop_statement("test_schema.test_table"),
+            create_statement("test_schema.test_table", &[]),
+        ];
+
+        let executed = execute_statements(&statements, &[]).unwrap();
+
+        assert_eq!(
+            vec![
+                "test_schema.test_table",
+                "test_schema.test_table",
+                "test_schema.test_view",
+            ],
+            executed
+        );
+    }
+
+    #[test]
+    fn sort_statements_should_order_statements_by_declared_dependencies() {
+        let statements = vec![
+            create_statement("test_schema.test_view", &["test_schema.test_table"]),
+            create_statement(
+                "test_schema.test_table",
+                &["test_schema", "pg_catalog.int4"],
+            ),
+            create_statement("test_schema.other_table", &[]),
+            create_statement("test_schema", &[]),
+        ];
+
+        let sorted: Vec<String> = sort_statements(&statements)
+            .into_iter()
+            .map(|s| s.object.to_string())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "test_schema.other_table",
+                "test_schema",
+                "test_schema.test_table",
+                "test_schema.test_view"
+            ],
+            sorted
+        );
+    }
+
+    #[test]
+    fn apply_statements_should_take_fast_path_for_large_reversed_chain() {
+        // Each statement depends on the next statement so the declared order is the worst case
+        // for application in file order
+        let names: Vec<String> = (0..1000)
+            .map(|i| format!("test_schema.table_{i}"))
+            .collect();
+        let statements: Vec<DdlStatement> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match names.get(i + 1) {
+                Some(next) => create_statement(name, &[next.as_str()]),
+                None => create_statement(name, &[]),
+            })
+            .collect();
+
+        let (executed, is_fast_path) = apply_statements(&statements, &[]).unwrap();
+
+        assert!(is_fast_path);
+        let expected: Vec<String> = names.into_iter().rev().collect();
+        assert_eq!(expected, executed);
+    }
+
+    #[test]
+    fn statement_iter_should_drain_large_batch_of_independent_statements() {
+        let names: Vec<String> = (0..1000)
+            .map(|i| format!("test_schema.table_{i}"))
+            .collect();
+        let statements: Vec<DdlStatement> = names
+            .iter()
+            .map(|name| create_statement(name, &[]))
+            .collect();
+        let mut iter = StatementIter::new(&statements);
+
+        iter.drain_ready_statements();
+
+        assert!(iter.statements.is_empty());
+        let drained: Vec<String> = iter
+            .ready_statements
+            .iter()
+            .map(|s| s.object.to_string())
+            .collect();
+        assert_eq!(names, drained);
+    }
+
+    #[rstest::rstest]
+    #[case::sorted_order(&[])]
+    #[case::adaptive(&[("test_schema.test_table", "test_schema.other_table")])]
+    fn apply_statements_should_apply_event_triggers_after_all_other_statements(
+        #[case] hidden_dependencies: &[(&str, &str)],
+    ) {
+        let statements = vec![
+            create_statement("test_schema.guard", &[]),
+            create_event_trigger_statement("block_ddl", &["test_schema.guard"]),
+            create_statement("test_schema.test_table", &[]),
+            create_statement("test_schema.other_table", &[]),
+        ];
+        let mut executed: Vec<String> = vec![];
+
+        block_on(super::apply_statements(
+            sort_statements(&statements),
+            |statement| {
+                // The ddl_command_start guard blocks every statement executed after it is created
+                if executed.iter().any(|o| o == "block_ddl") {
+                    return ready(Err(()));
+                }
+                execute(statement, hidden_dependencies, &mut executed)
+            },
+            |statement, _| Ok(statement),
+        ))
+        .unwrap();
+
+        assert_eq!(Some("block_ddl"), executed.last().map(String::as_str));
+        assert_eq!(statements.len(), executed.len());
+    }
+
+    #[test]
+    fn apply_statements_should_fall_back_when_dependency_is_not_declared() {
+        let statements = vec![
+            create_statement("test_schema.first", &[]),
+            create_statement("test_schema.second", &[]),
+        ];
+
+        let (executed, is_fast_path) =
+            apply_statements(&statements, &[("test_schema.first", "test_schema.second")]).unwrap();
+
+        assert!(!is_fast_path);
+        assert_eq!(vec!["test_schema.second", "test_schema.first"], executed);
+    }
+
+    #[rstest::rstest]
+    #[case(vec![create_statement("test_schema.failing", &[])])]
+    #[case(vec![
+        create_statement("test_schema.first", &[]),
+        create_statement("test_schema.failing", &[]),
+    ])]
+    fn statement_iter_should_stop_when_statement_always_fails(
+        #[case] statements: Vec<DdlStatement>,
+    ) {
+        let result = execute_statements(&statements, &[("test_schema.failing", "missing")]);
+
+        let Err(PgDiffError::SourceControlScript {
+            remaining_statements,
+        }) = result
+        else {
+            panic!("Expected a SourceControlScript error but got {result:?}");
+        };
+        assert_eq!(
+            vec!["CREATE TABLE test_schema.failing();".to_string()],
+            remaining_statements
+        );
+    }
+
+    #[test]
+    fn statement_iter_should_retry_failed_statements_until_dependencies_are_met() {
+        let statements = vec![
+            create_statement("test_schema.first", &[]),
+            create_statement("test_schema.second", &[]),
+            create_statement("test_schema.third", &[]),
+        ];
+        let hidden_dependencies = [
+            ("test_schema.first", "test_schema.second"),
+            ("test_schema.second", "test_schema.third"),
+        ];
+
+        let executed = execute_statements(&statements, &hidden_dependencies).unwrap();
+
+        assert_eq!(
+            vec![
+                "test_schema.third",
+                "test_schema.second",
+                "test_schema.first"
+            ],
+            executed
+        );
+    }
+
+    fn create_settings(settings: &[&str]) -> DatabaseSettings {
+        DatabaseSettings {
+            name: "test_db".into(),
+            settings: settings.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn write_set_statements_should_add_alter_database_statements() {
+        let settings = create_settings(&["work_mem=64MB", "search_path=app, \"$user\", public"]);
+        let mut writeable = String::new();
+
+        settings.write_set_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-settings-script.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn compare_to_other_database_should_alter_database_settings() {
+        let mut old = create_database(vec![], vec![]);
+        old.settings = create_settings(&["work_mem=64MB", "statement_timeout=30s"]);
+        let mut new = create_database(vec![], vec![]);
+        new.settings = create_settings(&["work_mem=128MB", "search_path=app, public"]);
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-settings-alter.pgsql").trim(),
+            result.trim()
+        );
+    }
+
+    #[test]
+    fn compare_to_other_database_should_create_roles_before_other_objects() {
+        let old = create_database(vec![], vec![]);
+        let mut new = create_schema_with_table();
+        new.roles = vec![
+            Role {
+                name: SchemaQualifiedName::new("", "app_reader"),
+                can_login: true,
+                inherit: true,
+                member_of: vec!["app_base".into()],
+                dependencies: vec![SchemaQualifiedName::new("", "app_base")],
+            },
+            Role {
+                name: SchemaQualifiedName::new("", "app_base"),
+                inherit: true,
+                ..Default::default()
+            },
+        ];
+
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-create-roles.pgsql").trim(),
+            result.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(false, "-- WOULD DROP: ROLE app_reader")]
+    #[case(true, "DROP ROLE app_reader;")]
+    fn compare_to_other_database_should_only_drop_roles_when_requested(
+        #[case] drop_roles: bool,
+        #[case] statement: &str,
+    ) {
+        let mut old = create_database(vec![], vec![]);
+        old.roles = vec![Role {
+            name: SchemaQualifiedName::new("", "app_reader"),
+            inherit: true,
+            ..Default::default()
+        }];
+        let new = create_database(vec![], vec![]);
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    drop_roles,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement, result.trim());
+    }
+
+    #[test]
+    fn summary_should_count_objects_by_type() {
+        let mut database = create_schema_with_table();
+        database.tables.push(Table {
+            name: SchemaQualifiedName::new(SCHEMA, "other_table"),
+            ..create_table(SCHEMA)
+        });
+        database.views.push(View {
+            name: SchemaQualifiedName::new(SCHEMA, "test_view"),
+            ..Default::default()
+        });
+
+        let summary = database.summary();
+
+        assert_eq!(
+            vec![
+                ("SCHEMA".to_string(), 1),
+                ("TABLE".to_string(), 2),
+                ("VIEW".to_string(), 1)
+            ],
+            summary.object_counts
+        );
+        assert_eq!("Found SCHEMA: 1, TABLE: 2, VIEW: 1", summary.to_string());
+    }
+
+    #[test]
+    fn plan_summary_should_count_changes_by_object_type() {
+        let (old, new) = create_databases_with_create_alter_drop();
+        let (steps, _) = old
+            .migration_steps(&new, &MigrationOptions::default())
+            .unwrap();
+
+        let summary = PlanSummary::from_steps(&steps);
+
+        let counts = |object_type: &str| {
+            summary
+                .object_types
+                .iter()
+                .find(|(name, _)| name == object_type)
+                .map(|(_, counts)| (counts.creates, counts.alters, counts.drops))
+        };
+        assert_eq!(Some((1, 0, 0)), counts("SCHEMA"));
+        assert_eq!(Some((0, 0, 1)), counts("TABLE"));
+        assert_eq!(Some((0, 1, 0)), counts("DATABASE"));
+        assert_eq!("Creates: 1, Alters: 1, Drops: 1", summary.total.to_string());
+    }
+
+    #[test]
+    fn plan_summary_should_list_destructive_steps_and_count_statements() {
+        let steps = vec![
+            MigrationStep {
+                object_type: "TABLE".into(),
+                sql: "ALTER TABLE test_schema.test_table DROP COLUMN old_col;\n".into(),
+                is_destructive: true,
+                ..create_step(StepKind::Alter, TABLE, None, &[])
+            },
+            MigrationStep {
+                object_type: "TABLE".into(),
+                sql: "DROP TABLE test_schema.old_table;\n".into(),
+                ..create_step(StepKind::Drop, "old_table", None, &[])
+            },
+            MigrationStep {
+                object_type: "VIEW".into(),
+                sql: "-- comment\nCREATE VIEW test_schema.v AS SELECT 1;\nCOMMENT ON VIEW \
+                      test_schema.v IS 'test';\n"
+                    .into(),
+                ..create_step(StepKind::Create, "v", None, &[])
+            },
+            MigrationStep {
+                object_type: "FUNCTION".into(),
+                sql: "-- Skipped drop of FUNCTION test_schema.f\n".into(),
+                ..create_step(StepKind::SkippedDrop, "f", None, &[])
+            },
+        ];
+
+        let summary = PlanSummary::from_steps(&steps);
+
+        assert_eq!(
+            vec![
+                "ALTER TABLE test_schema.test_table".to_string(),
+                "DROP TABLE test_schema.old_table".to_string(),
+            ],
+            summary.destructive_steps
+        );
+        assert_eq!(4, summary.statement_count);
+    }
+
+    #[test]
+    fn plan_summary_write_footer_should_describe_changes() {
+        let (old, new) = create_databases_with_create_alter_drop();
+        let (steps, _) = old
+            .migration_steps(&new, &MigrationOptions::default())
+            .unwrap();
+        let summary = PlanSummary::from_steps(&steps);
+        let mut writable = String::new();
+
+        summary.write_footer(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-plan-summary-footer.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn plan_summary_write_footer_should_list_manual_changes() {
+        let old = create_schema_with_column("integer", false);
+        let new = create_schema_with_column("bigint", false);
+        let options = MigrationOptions {
+            on_unsupported: OnUnsupported::Comment,
+            ..Default::default()
+        };
+        let (steps, _) = old.migration_steps(&new, &options).unwrap();
+        let summary = PlanSummary::from_steps(&steps);
+        let mut writable = String::new();
+
+        summary.write_footer(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-plan-summary-footer-manual.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("TABLE", 1, "table")]
+    #[case("TABLE", 3, "tables")]
+    #[case("INDEX", 2, "indexes")]
+    #[case("POLICY", 2, "policies")]
+    #[case("STATISTICS", 2, "statistics")]
+    #[case("OPERATOR CLASS", 2, "operator classes")]
+    fn object_type_label_should_pluralize_when_count_is_not_one(
+        #[case] object_type: &str,
+        #[case] count: usize,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, object_type_label(object_type, count));
+    }
+
+    #[test]
+    fn write_role_stubs_should_create_stub_of_table_owner() {
+        let mut database = create_schema_with_table();
+        database.tables[0].owner = "app_user".into();
+        database.roles = vec![Role {
+            name: SchemaQualifiedName::new("", "test_owner"),
+            ..Default::default()
+        }];
+        let mut writable = String::new();
+
+        database.write_role_stubs(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-role-stubs.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn referenced_roles_should_skip_special_role_names() {
+        let mut database = create_schema_with_table();
+        database.schemas[0].owner = "CURRENT_USER".into();
+        database.tables[0].owner = "app_user".into();
+        let mut other_table = create_table(SCHEMA);
+        other_table.owner = "PUBLIC".into();
+        database.tables.push(other_table);
+
+        let roles = database.referenced_roles();
+
+        assert_eq!(vec!["app_user"], roles);
+    }
+
+    #[test]
+    fn compare_to_other_database_should_skip_ignored_objects() {
+        let mut old = create_schema_with_table();
+        old.tables.push(Table {
+            name: SchemaQualifiedName::new(SCHEMA, "schema_migrations"),
+            ..create_table(SCHEMA)
+        });
+        old.tables.push(Table {
+            name: SchemaQualifiedName::new(SCHEMA, "old_table"),
+            ..create_table(SCHEMA)
+        });
+        old.constraints.push(Constraint {
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, "schema_migrations"),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, "schema_migrations.test_pk"),
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, "schema_migrations")],
+            ..create_constraint(
+                "test_pk",
+                ConstraintType::PrimaryKey {
+                    columns: vec!["id".into()],
+                    index_parameters: IndexParameters::default(),
+                },
+            )
+        });
+        let new = create_schema_with_table();
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    ignored_objects: vec!["test_schema.schema_migrations".into()],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!("DROP TABLE test_schema.old_table;", result.trim());
+    }
+
+    #[test]
+    fn remove_objects_matching_should_remove_objects_owned_by_excluded_tables() {
+        let excluded_table = SchemaQualifiedName::new(SCHEMA, "orders_tmp");
+        let mut database = create_schema_with_table();
+        database.tables.push(Table {
+            name: excluded_table.clone(),
+            ..create_table(SCHEMA)
+        });
+        database.constraints.push(Constraint {
+            owner_table_name: excluded_table.clone(),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, "orders_tmp.orders_pkey"),
+            dependencies: vec![excluded_table.clone()],
+            ..create_constraint(
+                "orders_pkey",
+                ConstraintType::PrimaryKey {
+                    columns: vec!["id".into()],
+                    index_parameters: IndexParameters::default(),
+                },
+            )
+        });
+        database.indexes.push(Index {
+            owner_table_name: excluded_table.clone(),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, "orders_created_idx"),
+            dependencies: vec![excluded_table],
+            ..Default::default()
+        });
+        let patterns = vec!["*_tmp".to_string()];
+
+        database.remove_objects_matching(|name| matches_any_pattern(&patterns, name));
+
+        assert_eq!(1, database.tables.len());
+        assert!(database.constraints.is_empty());
+        assert!(database.indexes.is_empty());
+    }
+
+    /// Create an old and new database where the migration requires a create (new schema), an
+    /// alter (database settings) and a drop (removed table)
+    fn create_databases_with_create_alter_drop() -> (Database, Database) {
+        let mut old = create_schema_with_table();
+        old.settings = create_settings(&["work_mem=64MB"]);
+        let mut new = create_database(
+            vec![
+                Schema {
+                    name: SchemaQualifiedName::new(SCHEMA, ""),
+                    owner: "test_owner".into(),
+                },
+                Schema {
+                    name: SchemaQualifiedName::new("new_schema", ""),
+                    owner: "test_owner".into(),
+                },
+            ],
+            vec![],
+        );
+        new.settings = create_settings(&["work_mem=128MB"]);
+        (old, new)
+    }
+
+    #[rstest::rstest]
+    #[case(
+        DropMode::Include,
+        include_str!("../../test-files/sql/database-compare-drop-mode-include.pgsql"),
+    )]
+    #[case(
+        DropMode::Exclude,
+        include_str!("../../test-files/sql/database-compare-drop-mode-exclude.pgsql"),
+    )]
+    #[case(
+        DropMode::Only,
+        include_str!("../../test-files/sql/database-compare-drop-mode-only.pgsql"),
+    )]
+    fn compare_to_other_database_should_filter_drops_by_drop_mode(
+        #[case] drop_mode: DropMode,
+        #[case] statement: &str,
+    ) {
+        let (old, new) = create_databases_with_create_alter_drop();
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    drop_mode,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement.trim(), result.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(DropMode::Include, 1)]
+    #[case(DropMode::Exclude, 0)]
+    fn plan_to_other_database_should_not_count_skipped_drops(
+        #[case] drop_mode: DropMode,
+        #[case] statement_count: usize,
+    ) {
+        let old = create_schema_with_table();
+        let new = create_database(
+            vec![Schema {
+                name: SchemaQualifiedName::new(SCHEMA, ""),
+                owner: "test_owner".into(),
+            }],
+            vec![],
+        );
+
+        let plan = old
+            .plan_to_other_database(
+                &new,
+                MigrationOptions {
+                    drop_mode,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(plan.deploy.contains("test_schema.test_table"));
+        assert_eq!(statement_count, plan.statement_count);
+        assert_eq!(statement_count == 0, plan.is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case(DropMode::Include, true, true, true)]
+    #[case(DropMode::Exclude, false, true, true)]
+    #[case(DropMode::Only, true, false, false)]
+    fn revert_should_only_undo_statements_included_by_drop_mode(
+        #[case] drop_mode: DropMode,
+        #[case] creates_dropped_table: bool,
+        #[case] drops_created_schema: bool,
+        #[case] reverts_settings: bool,
+    ) {
+        let (old, new) = create_databases_with_create_alter_drop();
+
+        let revert = new
+            .compare_to_other_database(
+                &old,
+                MigrationOptions {
+                    drop_mode: drop_mode.revert(),
+                    allow_destructive: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            creates_dropped_table,
+            revert.contains("CREATE TABLE test_schema.test_table")
+        );
+        assert_eq!(
+            drops_created_schema,
+            revert.contains("DROP SCHEMA new_schema")
+        );
+        assert_eq!(reverts_settings, revert.contains("SET work_mem TO '64MB'"));
+    }
+
+    fn create_step(
+        kind: StepKind,
+        object: &str,
+        table_order: Option<TableStepOrder>,
+        dependencies: &[&str],
+    ) -> MigrationStep {
+        MigrationStep {
+            kind,
+            object: SchemaQualifiedName::new(SCHEMA, object),
+            object_type: String::new(),
+            sql: format!("{object};"),
+            is_destructive: kind == StepKind::Drop,
+            table_step: table_order.map(|order| TableStep {
+                table: SchemaQualifiedName::new(SCHEMA, TABLE),
+                order,
+            }),
+            dependencies: dependencies
+                .iter()
+                .map(|d| SchemaQualifiedName::new(SCHEMA, d))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn order_steps_should_group_table_steps_in_table_step_order() {
+        let steps = vec![
+            create_step(
+                StepKind::Create,
+                "test_trigger",
+                Some(TableStepOrder::TriggerOrPolicy),
+                &[TABLE, "trigger_function"],
+            ),
+            create_step(StepKind::Alter, TABLE, Some(TableStepOrder::Table), &[]),
+            create_step(StepKind::Create, "trigger_function", None, &[]),
+            create_step(
+                StepKind::Create,
+                "new_check",
+                Some(TableStepOrder::ConstraintOrIndex),
+                &[TABLE],
+            ),
+            create_step(
+                StepKind::Drop,
+                "old_check",
+                Some(TableStepOrder::DropOwned),
+                &[TABLE],
+            ),
+            create_step(
+                StepKind::Drop,
+                "old_fk",
+                Some(TableStepOrder::DropForeignKey),
+                &[TABLE],
+            ),
+        ];
+
+        let ordered = order_steps(steps).unwrap();
+
+        let objects: Vec<&str> = ordered
+            .iter()
+            .map(|s| s.object.local_name.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "old_fk",
+                "old_check",
+                TABLE,
+                "new_check",
+                "trigger_function",
+                "test_trigger"
+            ],
+            objects
+        );
+    }
+
+    #[test]
+    fn order_steps_should_set_default_privileges_before_creating_schema_objects() {
+        let steps = vec![
+            create_step(StepKind::Create, TABLE, Some(TableStepOrder::Table), &[]),
+            MigrationStep {
+                object_type: "DEFAULT PRIVILEGES".into(),
+                ..create_step(StepKind::Create, "app_owner.tables", None, &[""])
+            },
+            create_step(StepKind::Create, "", None, &[]),
+        ];
+
+        let ordered = order_steps(steps).unwrap();
+
+        let objects: Vec<&str> = ordered
+            .iter()
+            .map(|s| s.object.local_name.as_str())
+            .collect();
+        assert_eq!(vec!["", "app_owner.tables", TABLE], objects);
+    }
+
+    #[test]
+    fn order_steps_should_drop_dependents_before_dependencies() {
+        let steps = vec![
+            create_step(StepKind::Drop, "test_function", None, &[]),
+            create_step(StepKind::Drop, "test_view", None, &["test_function"]),
+        ];
+
+        let ordered = order_steps(steps).unwrap();
+
+        let objects: Vec<&str> = ordered
+            .iter()
+            .map(|s| s.object.local_name.as_str())
+            .collect();
+        assert_eq!(vec!["test_view", "test_function"], objects);
+    }
+
+    #[test]
+    fn order_steps_should_fail_for_cyclic_steps() {
+        let steps = vec![
+            create_step(StepKind::Create, "unrelated_table", None, &[]),
+            create_step(StepKind::Create, "first_view", None, &["second_view"]),
+            create_step(StepKind::Create, "second_view", None, &["first_view"]),
+        ]
+        .into_iter()
+        .map(|step| MigrationStep {
+            object_type: "VIEW".into(),
+            ..step
+        })
+        .collect();
+
+        let result = order_steps(steps);
+
+        let Err(PgDiffError::StepCycle { steps }) = result else {
+            panic!("Expected StepCycle error, found {result:?}");
+        };
+        assert_eq!(
+            vec![
+                "VIEW test_schema.second_view",
+                "VIEW test_schema.first_view"
+            ],
+            steps
+        );
+    }
+
+    #[test]
+    fn migration_steps_should_return_typed_steps_with_destructive_flags() {
+        let (old, new) = create_databases_with_create_alter_drop();
+
+        let (steps, _) = old
+            .migration_steps(&new, &MigrationOptions::default())
+            .unwrap();
+
+        let kinds: Vec<(StepKind, &str, bool)> = steps
+            .iter()
+            .map(|s| (s.kind, s.object_type.as_str(), s.is_destructive))
+            .collect();
+        assert_eq!(
+            vec![
+                (StepKind::Drop, "TABLE", true),
+                (StepKind::Create, "SCHEMA", false),
+                (StepKind::Alter, "DATABASE", false),
+            ],
+            kinds
+        );
+        assert_eq!(SchemaQualifiedName::new("new_schema", ""), steps[1].object);
+    }
 
-impl<'d> DbIter<'d> {
-    fn new(database: &'d Database) -> Self {
-        Self {
-            database,
-            completed_objects: vec![],
-            completed_schemas: 0,
-            completed_extensions: 0,
-            completed_udt: 0,
-            completed_tables: 0,
-            completed_constraints: 0,
-            completed_indexes: 0,
-            completed_triggers: 0,
-            completed_policies: 0,
-            completed_views: 0,
-            completed_sequences: 0,
-            completed_functions: 0,
-        }
+    #[test]
+    fn migration_steps_should_flag_recreated_index_as_destructive() {
+        let mut old = create_schema_with_table();
+        old.indexes = vec![create_unique_index("test_index")];
+        let mut new = create_schema_with_table();
+        new.indexes = vec![Index {
+            is_unique: false,
+            ..create_unique_index("test_index")
+        }];
+
+        let (steps, _) = old
+            .migration_steps(&new, &MigrationOptions::default())
+            .unwrap();
+
+        assert_eq!(1, steps.len());
+        assert_eq!("INDEX", steps[0].object_type);
+        assert!(steps[0].is_destructive);
     }
-}
 
-impl<'d> Iterator for DbIter<'d> {
-    type Item = SqlObjectEnum<'d>;
+    #[test]
+    fn migration_steps_should_flag_column_type_change_as_destructive() {
+        let mut old = create_schema_with_table();
+        old.tables[0].columns = vec![create_column("test_col", "integer")];
+        let mut new = create_schema_with_table();
+        new.tables[0].columns = vec![create_column("test_col", "bigint")];
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.completed_schemas < self.database.schemas.len() {
-            if let Some(schema) = self.database.schemas.iter().find(|s| {
-                !self.completed_objects.contains(&&s.name)
-                    && s.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_schemas += 1;
-                self.completed_objects.push(&schema.name);
-                return Some(SqlObjectEnum::Schema(schema));
-            }
-        }
+        let (steps, _) = old
+            .migration_steps(
+                &new,
+                &MigrationOptions {
+                    on_unsupported: OnUnsupported::Comment,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        if self.completed_extensions < self.database.extensions.len() {
-            if let Some(extension) = self.database.extensions.iter().find(|e| {
-                !self.completed_objects.contains(&&e.name)
-                    && e.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_extensions += 1;
-                self.completed_objects.push(&extension.name);
-                return Some(SqlObjectEnum::Extension(extension));
-            }
-        }
+        let table_step = steps.iter().find(|s| s.object_type == "TABLE").unwrap();
+        assert!(table_step.is_destructive);
+    }
 
-        if self.completed_udt < self.database.udts.len() {
-            if let Some(udt) = self.database.udts.iter().find(|u| {
-                !self.completed_objects.contains(&&u.name)
-                    && u.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_udt += 1;
-                self.completed_objects.push(&udt.name);
-                return Some(SqlObjectEnum::Udt(udt));
-            }
-        }
+    #[test]
+    fn compare_to_other_database_should_add_header_when_include_header() {
+        let (old, new) = create_databases_with_create_alter_drop();
 
-        if self.completed_tables < self.database.tables.len() {
-            if let Some(table) = self.database.tables.iter().find(|t| {
-                !self.completed_objects.contains(&&t.name)
-                    && t.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_tables += 1;
-                self.completed_objects.push(&table.name);
-                return Some(SqlObjectEnum::Table(table));
-            }
-        }
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    include_header: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        if self.completed_constraints < self.database.constraints.len() {
-            if let Some(constraint) = self.database.constraints.iter().find(|c| {
-                !self.completed_objects.contains(&&c.schema_qualified_name)
-                    && c.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_constraints += 1;
-                self.completed_objects
-                    .push(&constraint.schema_qualified_name);
-                return Some(SqlObjectEnum::Constraint(constraint));
-            }
-        }
+        assert!(result.starts_with(&format!("-- Generated by pg-diff-rs {VERSION}\n")));
+        assert!(result.contains("-- Database: test_db\n"));
+        assert!(result.contains("-- Creates: 1, Alters: 1, Drops: 1\n"));
+        assert!(result.ends_with("-- Total statements: 3\n"));
+    }
 
-        if self.completed_indexes < self.database.indexes.len() {
-            if let Some(index) = self.database.indexes.iter().find(|i| {
-                !self.completed_objects.contains(&&i.schema_qualified_name)
-                    && i.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_indexes += 1;
-                self.completed_objects.push(&index.schema_qualified_name);
-                return Some(SqlObjectEnum::Index(index));
-            }
-        }
+    #[test]
+    fn compare_to_other_database_should_not_emit_escape_codes_when_not_highlighted() {
+        let (old, new) = create_databases_with_create_alter_drop();
 
-        if self.completed_triggers < self.database.triggers.len() {
-            if let Some(trigger) = self.database.triggers.iter().find(|t| {
-                !self.completed_objects.contains(&&t.schema_qualified_name)
-                    && t.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_triggers += 1;
-                self.completed_objects.push(&trigger.schema_qualified_name);
-                return Some(SqlObjectEnum::Trigger(trigger));
-            }
-        }
+        let result = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
 
-        if self.completed_policies < self.database.policies.len() {
-            if let Some(policy) = self.database.policies.iter().find(|s| {
-                !self.completed_objects.contains(&&s.schema_qualified_name)
-                    && s.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_policies += 1;
-                self.completed_objects.push(&policy.schema_qualified_name);
-                return Some(SqlObjectEnum::Policy(policy));
-            }
-        }
+        assert!(!result.is_empty());
+        assert!(!result.contains('\x1b'));
+    }
 
-        if self.completed_views < self.database.views.len() {
-            if let Some(view) = self.database.views.iter().find(|v| {
-                !self.completed_objects.contains(&&v.name)
-                    && v.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_views += 1;
-                self.completed_objects.push(&view.name);
-                return Some(SqlObjectEnum::View(view));
-            }
-        }
+    #[test]
+    fn compare_to_other_database_should_color_steps_by_kind_when_highlighted() {
+        let (old, new) = create_databases_with_create_alter_drop();
+        let plain = old
+            .compare_to_other_database(&new, MigrationOptions::default())
+            .unwrap();
 
-        if self.completed_sequences < self.database.sequences.len() {
-            if let Some(sequence) = self.database.sequences.iter().find(|s| {
-                !self.completed_objects.contains(&&s.name)
-                    && s.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_sequences += 1;
-                self.completed_objects.push(&sequence.name);
-                return Some(SqlObjectEnum::Sequence(sequence));
-            }
-        }
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    highlight: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        if self.completed_functions < self.database.functions.len() {
-            if let Some(function) = self.database.functions.iter().find(|f| {
-                !self.completed_objects.contains(&&f.name)
-                    && f.dependencies_met(&self.completed_objects)
-            }) {
-                self.completed_functions += 1;
-                self.completed_objects.push(&function.name);
-                return Some(SqlObjectEnum::Function(function));
-            }
-        }
-        None
+        assert!(result.contains("\x1b[1m-- TABLE "));
+        assert!(result.contains("\x1b[32mCREATE "));
+        assert!(result.contains("\x1b[33m"));
+        assert!(result.contains("\x1b[31mDROP "));
+        let stripped = crate::ansi::strip(&result);
+        let without_headers: String = stripped
+            .lines()
+            .filter(|line| !line.starts_with("-- "))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        assert_eq!(plain.trim(), without_headers.trim());
     }
-}
 
-enum DbCompareResult<'d> {
-    Create(SqlObjectEnum<'d>),
-    Alter {
-        old: SqlObjectEnum<'d>,
-        new: SqlObjectEnum<'d>,
-    },
-    Drop(SqlObjectEnum<'d>),
-}
+    #[test]
+    fn compare_to_other_database_should_not_add_header_when_plan_is_empty() {
+        let old = create_schema_with_table();
+        let new = create_schema_with_table();
 
-struct DbCompare<'d> {
-    new: &'d Database,
-    old_iter: DbIter<'d>,
-    new_iter: DbIter<'d>,
-    is_done_old: bool,
-}
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    include_header: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-impl<'d> DbCompare<'d> {
-    fn new(old: &'d Database, new: &'d Database) -> Self {
-        Self {
-            new,
-            old_iter: DbIter::new(old),
-            new_iter: DbIter::new(new),
-            is_done_old: false,
-        }
+        assert!(result.is_empty());
     }
-}
 
-impl<'d> Iterator for DbCompare<'d> {
-    type Item = DbCompareResult<'d>;
+    #[rstest::rstest]
+    #[case(
+        false,
+        true,
+        include_str!("../../test-files/sql/database-compare-session-preamble.pgsql"),
+    )]
+    #[case(
+        true,
+        false,
+        include_str!("../../test-files/sql/database-compare-transactional.pgsql"),
+    )]
+    #[case(
+        true,
+        true,
+        include_str!("../../test-files/sql/database-compare-transactional-session-preamble.pgsql"),
+    )]
+    fn compare_to_other_database_should_surround_plan_with_requested_statements(
+        #[case] transactional: bool,
+        #[case] session_preamble: bool,
+        #[case] statement: &str,
+    ) {
+        let mut old = create_database(vec![], vec![]);
+        old.settings = create_settings(&["work_mem=64MB"]);
+        let mut new = create_database(vec![], vec![]);
+        new.settings = create_settings(&["work_mem=128MB"]);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.is_done_old {
-            let obj = self.new_iter.next()?;
-            return Some(DbCompareResult::Create(obj));
-        }
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    transactional,
+                    session_preamble,
+                    lock_timeout: "5s".into(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(statement.trim(), result.trim());
+    }
 
-        let Some(obj) = self.old_iter.next() else {
-            self.is_done_old = true;
-            return self.next();
+    #[test]
+    fn compare_to_other_database_should_move_non_transactional_statements_outside_transaction() {
+        let create_enum = |labels: &[&str]| Udt {
+            name: SchemaQualifiedName::new(SCHEMA, "test_enum"),
+            udt_type: UdtType::Enum {
+                labels: labels.iter().map(|l| l.to_string()).collect(),
+            },
+            dependencies: vec![],
         };
+        let mut old = create_database(vec![], vec![]);
+        old.settings = create_settings(&["work_mem=64MB"]);
+        old.udts = vec![create_enum(&["a"])];
+        let mut new = create_database(vec![], vec![]);
+        new.settings = create_settings(&["work_mem=128MB"]);
+        new.udts = vec![create_enum(&["a", "b"])];
 
-        let new_object = match obj {
-            SqlObjectEnum::Schema(schema) => self.new.schemas.iter().find_map(|s| {
-                if s.name() == schema.name() {
-                    Some(SqlObjectEnum::Schema(s))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Extension(extension) => self.new.extensions.iter().find_map(|e| {
-                if e.name() == extension.name() {
-                    Some(SqlObjectEnum::Extension(e))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Udt(udt) => self.new.udts.iter().find_map(|u| {
-                if u.name() == udt.name() {
-                    Some(SqlObjectEnum::Udt(u))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Table(table) => self.new.tables.iter().find_map(|t| {
-                if t.name() == table.name() {
-                    Some(SqlObjectEnum::Table(t))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Policy(policy) => self.new.policies.iter().find_map(|p| {
-                if p.name() == policy.name() {
-                    Some(SqlObjectEnum::Policy(p))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Constraint(constraint) => self.new.constraints.iter().find_map(|c| {
-                if c.name() == constraint.name() {
-                    Some(SqlObjectEnum::Constraint(c))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Index(index) => self.new.indexes.iter().find_map(|i| {
-                if i.name() == index.name() {
-                    Some(SqlObjectEnum::Index(i))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Trigger(trigger) => self.new.triggers.iter().find_map(|t| {
-                if t.name() == trigger.name() {
-                    Some(SqlObjectEnum::Trigger(t))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Sequence(sequence) => self.new.sequences.iter().find_map(|s| {
-                if s.name() == sequence.name() {
-                    Some(SqlObjectEnum::Sequence(s))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::Function(function) => self.new.functions.iter().find_map(|f| {
-                if f.name() == function.name() {
-                    Some(SqlObjectEnum::Function(f))
-                } else {
-                    None
-                }
-            }),
-            SqlObjectEnum::View(view) => self.new.views.iter().find_map(|v| {
-                if v.name() == view.name() {
-                    Some(SqlObjectEnum::View(v))
-                } else {
-                    None
-                }
-            }),
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    transactional: true,
+                    session_preamble: true,
+                    lock_timeout: "5s".into(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/database-compare-transactional-sections.pgsql")
+                .trim(),
+            result.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("ALTER TYPE s.e ADD VALUE 'b';", Some(TransactionSection::Before))]
+    #[case("ALTER TYPE s.e RENAME VALUE 'a' TO 'b';", None)]
+    #[case("CREATE DATABASE test;", Some(TransactionSection::Before))]
+    #[case(
+        "CREATE INDEX CONCURRENTLY i ON s.t (c);",
+        Some(TransactionSection::After)
+    )]
+    #[case("CREATE INDEX i ON s.t (c);", None)]
+    #[case("DROP INDEX CONCURRENTLY s.i;", Some(TransactionSection::After))]
+    #[case("REINDEX (CONCURRENTLY) TABLE s.t;", Some(TransactionSection::After))]
+    #[case("REINDEX TABLE s.t;", None)]
+    #[case(
+        "ALTER TABLE s.t DETACH PARTITION s.p CONCURRENTLY;",
+        Some(TransactionSection::After)
+    )]
+    #[case("ALTER TABLE s.t DETACH PARTITION s.p;", None)]
+    #[case("VACUUM s.t;", Some(TransactionSection::After))]
+    #[case("ANALYZE s.t;", None)]
+    #[case("ALTER SYSTEM SET work_mem = '64MB';", Some(TransactionSection::After))]
+    #[case("DROP DATABASE test;", Some(TransactionSection::After))]
+    fn non_transactional_section_should_find_section_of_statement(
+        #[case] statement: &str,
+        #[case] expected: Option<TransactionSection>,
+    ) {
+        let result = non_transactional_section(statement).map(|(section, _)| section);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn compare_to_other_database_should_not_add_preamble_when_plan_is_empty() {
+        let old = create_schema_with_table();
+        let new = create_schema_with_table();
+
+        let result = old
+            .compare_to_other_database(
+                &new,
+                MigrationOptions {
+                    transactional: true,
+                    session_preamble: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(result.is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case(r#"role "app_reader" does not exist"#, Some("app_reader"))]
+    #[case(r#"relation "test_schema.test_table" does not exist"#, None)]
+    fn missing_role_name_should_extract_role_from_error_message(
+        #[case] message: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(expected, missing_role_name(message));
+    }
+
+    #[rstest::rstest]
+    #[case(
+        r#"relation "test_schema.test_table" does not exist"#,
+        Some("test_schema.test_table")
+    )]
+    #[case(
+        r#"type "test_schema.test_type" does not exist"#,
+        Some("test_schema.test_type")
+    )]
+    #[case(r#"schema "test_schema" does not exist"#, Some("test_schema"))]
+    #[case(r#"relation "" does not exist"#, None)]
+    #[case("function test_schema.test_function(integer) does not exist", None)]
+    #[case(
+        r#"column "test_column" of relation "test_table" does not exist"#,
+        None
+    )]
+    fn missing_object_name_should_extract_object_from_error_message(
+        #[case] message: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(expected, missing_object_name(message));
+    }
+
+    #[test]
+    fn retarget_database_setting_should_replace_database_name() {
+        let result = pg_query::parse("ALTER DATABASE prod SET search_path TO app, public").unwrap();
+        let Some(Node::AlterDatabaseSetStmt(alter_database)) = result.protobuf.stmts[0]
+            .stmt
+            .as_ref()
+            .and_then(|s| s.node.as_ref())
+        else {
+            panic!("Expected an ALTER DATABASE statement");
         };
 
-        if let Some(other) = new_object {
-            match &other {
-                SqlObjectEnum::Schema(_) => self.new_iter.completed_schemas += 1,
-                SqlObjectEnum::Extension(_) => self.new_iter.completed_extensions += 1,
-                SqlObjectEnum::Udt(_) => self.new_iter.completed_udt += 1,
-                SqlObjectEnum::Table(_) => self.new_iter.completed_tables += 1,
-                SqlObjectEnum::Policy(_) => self.new_iter.completed_policies += 1,
-                SqlObjectEnum::Constraint(_) => self.new_iter.completed_constraints += 1,
-                SqlObjectEnum::Index(_) => self.new_iter.completed_indexes += 1,
-                SqlObjectEnum::Trigger(_) => self.new_iter.completed_triggers += 1,
-                SqlObjectEnum::Sequence(_) => self.new_iter.completed_sequences += 1,
-                SqlObjectEnum::Function(_) => self.new_iter.completed_functions += 1,
-                SqlObjectEnum::View(_) => self.new_iter.completed_views += 1,
-            }
-            self.new_iter.completed_objects.push(other.name());
-            Some(DbCompareResult::Alter {
-                old: obj,
-                new: other,
-            })
-        } else {
-            Some(DbCompareResult::Drop(obj))
-        }
+        let statement = retarget_database_setting(alter_database, "temp_db").unwrap();
+
+        assert_eq!(
+            "ALTER DATABASE temp_db SET search_path TO app, public",
+            statement
+        );
     }
-}
 
-/// Write `CREATE` statements to the file specified by the object type and name
-pub async fn write_create_statements_to_file<S, P>(
-    object: &S,
-    root_directory: P,
-) -> Result<(), PgDiffError>
-where
-    S: SqlObject,
-    P: AsRef<Path>,
-{
-    let mut statements = String::new();
-    object.create_statements(&mut statements)?;
+    #[rstest::rstest]
+    #[case("CREATE ROLE app_user WITH LOGIN;")]
+    #[case("ALTER ROLE app_user WITH NOLOGIN;")]
+    #[case("ALTER ROLE app_user SET search_path TO app;")]
+    #[case("GRANT app_reader TO app_user;")]
+    #[case("REVOKE app_reader FROM app_user;")]
+    #[case("DROP ROLE app_user;")]
+    fn verification_statement_should_skip_role_statements(#[case] statement: &str) {
+        let result = verification_statement(statement, "temp_db").unwrap();
 
-    let path = root_directory
-        .as_ref()
-        .join(object.object_type_name().to_lowercase());
-    tokio::fs::create_dir_all(&path).await?;
-    let mut file = File::create(path.join(format!("{}.pgsql", object.name()))).await?;
-    file.write_all(statements.as_bytes()).await?;
-    Ok(())
-}
+        assert_eq!(None, result);
+    }
 
-/// Append the `CREATE` statements to the owning table's file
-pub async fn append_create_statements_to_owner_table_file<S, P>(
-    object: &S,
-    owner_table: &SchemaQualifiedName,
-    root_directory: P,
-) -> Result<(), PgDiffError>
-where
-    S: SqlObject,
-    P: AsRef<Path>,
-{
-    let mut statements = String::new();
-    object.create_statements(&mut statements)?;
+    #[rstest::rstest]
+    #[case(
+        "\nCREATE TABLE test_schema.test_table();",
+        "CREATE TABLE test_schema.test_table();"
+    )]
+    #[case(
+        "ALTER DATABASE prod SET work_mem TO 64;",
+        "ALTER DATABASE temp_db SET work_mem TO 64"
+    )]
+    fn verification_statement_should_execute_other_statements(
+        #[case] statement: &str,
+        #[case] expected: &str,
+    ) {
+        let result = verification_statement(statement, "temp_db").unwrap();
 
-    let path = root_directory.as_ref().join("table");
-    tokio::fs::create_dir_all(&path).await?;
-    let mut file = OpenOptions::new()
-        .append(true)
-        .open(path.join(format!("{}.pgsql", owner_table)))
-        .await?;
-    file.write_all("\n".as_bytes()).await?;
-    file.write_all(statements.as_bytes()).await?;
-    Ok(())
+        assert_eq!(Some(expected.to_string()), result);
+    }
 }
@@ -0,0 +1,305 @@
+use std::fmt::Write;
+
+use sqlx::{query_as, PgPool};
+
+use crate::PgDiffError;
+
+use super::role::Role;
+use super::{
+    fetch_with_retry, write_acl_changes, AclGrant, RenderContext, SchemaQualifiedName, SqlObject,
+};
+
+/// Fetch all default privileges that apply to the `schemas` specified as well as the default
+/// privileges that apply to every schema
+pub async fn get_default_privileges(
+    pool: &PgPool,
+    schemas: &[&str],
+) -> Result<Vec<DefaultPrivileges>, PgDiffError> {
+    let default_privileges_query = include_str!("./../../queries/default_privileges.pgsql");
+    let default_privileges = match fetch_with_retry(|| {
+        query_as(default_privileges_query)
+            .bind(schemas)
+            .fetch_all(pool)
+    })
+    .await
+    {
+        Ok(inner) => inner,
+        Err(error) => {
+            println!("Could not load default privileges");
+            return Err(error.into());
+        },
+    };
+    Ok(default_privileges)
+}
+
+/// Privileges granted to objects created in the future by a role (i.e. `ALTER DEFAULT
+/// PRIVILEGES`). Each entry is unique by the schema, object type and grantor role.
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct DefaultPrivileges {
+    /// Name of the entry. The schema name is the schema the privileges apply to (empty if the
+    /// privileges apply to all schemas) and the local name is the grantor followed by the object
+    /// type (e.g. `app_owner.tables`).
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Role whose future objects are granted the privileges
+    pub(crate) grantor: String,
+    /// Type of objects the privileges apply to. One of `TABLES`, `SEQUENCES`, `FUNCTIONS`,
+    /// `TYPES` or `SCHEMAS`.
+    pub(crate) object_type: String,
+    /// Privileges granted to objects created by the grantor
+    #[sqlx(json)]
+    pub(crate) grants: Vec<AclGrant>,
+    /// Privileges granted by the server when no default privileges exist. Only populated when the
+    /// privileges apply to all schemas since those privileges replace the built-in privileges
+    /// while schema specific privileges are added to the built-in privileges.
+    #[sqlx(json)]
+    pub(crate) built_in_grants: Vec<AclGrant>,
+    /// Dependencies of the default privileges. This is the schema the privileges apply to (if
+    /// any) and the grantor role when the role is managed (see
+    /// [DefaultPrivileges::resolve_dependencies]).
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl DefaultPrivileges {
+    /// Add the grantor role of each entry within `default_privileges` as a dependency of the
+    /// entry. Only roles found within `roles` are included since other roles are not managed.
+    /// Previously resolved grantor dependencies are replaced so the entries can be resolved again
+    /// against a different set of roles.
+    pub fn resolve_dependencies(default_privileges: &mut [DefaultPrivileges], roles: &[Role]) {
+        for entry in default_privileges.iter_mut() {
+            entry.dependencies.retain(|d| !d.schema_name.is_empty());
+            let grantor = SchemaQualifiedName::new("", &entry.grantor);
+            if roles.iter().any(|r| r.name == grantor) {
+                entry.dependencies.push(grantor);
+            }
+        }
+    }
+
+    /// Beginning of every statement that grants or revokes these default privileges
+    fn statement_prefix(&self) -> String {
+        let mut prefix = format!("ALTER DEFAULT PRIVILEGES FOR ROLE {}", self.grantor);
+        if !self.name.schema_name.is_empty() {
+            prefix.push_str(" IN SCHEMA ");
+            prefix.push_str(&self.name.schema_name);
+        }
+        prefix.push(' ');
+        prefix
+    }
+}
+
+impl SqlObject for DefaultPrivileges {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "DEFAULT PRIVILEGES"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write_acl_changes(
+            w,
+            &self.built_in_grants,
+            &self.grants,
+            &self.statement_prefix(),
+            &self.object_type,
+        )
+    }
+
+    /// Granting and revoking default privileges never fails when the privileges already exist
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statements(w)
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        write_acl_changes(
+            w,
+            &self.grants,
+            &new.grants,
+            &new.statement_prefix(),
+            &new.object_type,
+        )
+    }
+
+    /// Default privileges cannot be dropped so the privileges are reverted to the built-in
+    /// privileges, which removes the entry
+    /// Default privileges cannot be dropped so the privileges are reverted to the built-in
+    /// privileges. The `context` is ignored since revoking privileges that are not granted
+    /// already succeeds.
+    fn drop_statements_with<W: Write>(
+        &self,
+        _context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        write_acl_changes(
+            w,
+            &self.grants,
+            &self.built_in_grants,
+            &self.statement_prefix(),
+            &self.object_type,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{AclGrant, SchemaQualifiedName, SqlObject};
+
+    use super::{DefaultPrivileges, Role};
+
+    const SCHEMA: &str = "test_schema";
+    const GRANTOR: &str = "app_owner";
+
+    fn create_grant(grantee: &str, privilege_type: &str, is_grantable: bool) -> AclGrant {
+        AclGrant {
+            grantee: grantee.into(),
+            privilege_type: privilege_type.into(),
+            is_grantable,
+        }
+    }
+
+    fn create_default_privileges(
+        schema: &str,
+        object_type: &str,
+        grants: Vec<AclGrant>,
+    ) -> DefaultPrivileges {
+        DefaultPrivileges {
+            name: SchemaQualifiedName::new(
+                schema,
+                &format!("{GRANTOR}.{}", object_type.to_lowercase()),
+            ),
+            grantor: GRANTOR.into(),
+            object_type: object_type.into(),
+            grants,
+            built_in_grants: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_default_privileges(
+            SCHEMA,
+            "TABLES",
+            vec![
+                create_grant("readonly", "SELECT", false),
+                create_grant("app_user", "INSERT", true),
+            ],
+        ),
+        include_str!("../../test-files/sql/default-privileges-create-case1.pgsql"),
+    )]
+    #[case(
+        DefaultPrivileges {
+            built_in_grants: vec![
+                create_grant("PUBLIC", "EXECUTE", false),
+                create_grant(GRANTOR, "EXECUTE", false),
+            ],
+            ..create_default_privileges(
+                "",
+                "FUNCTIONS",
+                vec![create_grant(GRANTOR, "EXECUTE", false)],
+            )
+        },
+        include_str!("../../test-files/sql/default-privileges-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_alter_default_privileges_statements(
+        #[case] default_privileges: DefaultPrivileges,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        default_privileges.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_revoke_and_grant_changed_privileges() {
+        let old = create_default_privileges(
+            SCHEMA,
+            "SEQUENCES",
+            vec![
+                create_grant("readonly", "SELECT", false),
+                create_grant("app_user", "USAGE", false),
+            ],
+        );
+        let new = create_default_privileges(
+            SCHEMA,
+            "SEQUENCES",
+            vec![
+                create_grant("app_user", "USAGE", true),
+                create_grant("app_user", "SELECT", false),
+            ],
+        );
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/default-privileges-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_revert_to_built_in_privileges() {
+        let default_privileges = DefaultPrivileges {
+            built_in_grants: vec![
+                create_grant("PUBLIC", "USAGE", false),
+                create_grant(GRANTOR, "USAGE", false),
+            ],
+            ..create_default_privileges(
+                "",
+                "TYPES",
+                vec![
+                    create_grant(GRANTOR, "USAGE", false),
+                    create_grant("app_user", "USAGE", false),
+                ],
+            )
+        };
+        let mut writable = String::new();
+
+        default_privileges.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/default-privileges-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn resolve_dependencies_should_only_include_managed_grantors() {
+        let mut default_privileges = vec![
+            DefaultPrivileges {
+                dependencies: vec![SchemaQualifiedName::new(SCHEMA, "")],
+                ..create_default_privileges(SCHEMA, "TABLES", vec![])
+            },
+            DefaultPrivileges {
+                grantor: "unmanaged".into(),
+                dependencies: vec![SchemaQualifiedName::new("", "unmanaged")],
+                ..create_default_privileges("", "TABLES", vec![])
+            },
+        ];
+        let roles = vec![Role {
+            name: SchemaQualifiedName::new("", GRANTOR),
+            ..Default::default()
+        }];
+
+        DefaultPrivileges::resolve_dependencies(&mut default_privileges, &roles);
+
+        assert_eq!(
+            vec![
+                SchemaQualifiedName::new(SCHEMA, ""),
+                SchemaQualifiedName::new("", GRANTOR),
+            ],
+            default_privileges[0].dependencies
+        );
+        assert!(default_privileges[1].dependencies.is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt::Write;
+
+use sqlx::{query_as, PgPool};
+
+use crate::{write_join, PgDiffError};
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all event triggers found within the current database that are not owned by an extension
+pub async fn get_event_triggers(pool: &PgPool) -> Result<Vec<EventTrigger>, PgDiffError> {
+    let event_triggers_query = include_str!("./../../queries/event_triggers.pgsql");
+    let event_triggers =
+        match fetch_with_retry(|| query_as(event_triggers_query).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load event triggers");
+                return Err(error.into());
+            },
+        };
+    Ok(event_triggers)
+}
+
+/// Postgresql event trigger object. Unlike table triggers, event triggers are not associated with
+/// a table and fire for DDL events within the entire database.
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct EventTrigger {
+    /// Full name of the event trigger (never includes a schema name since event triggers reside
+    /// outside a schema)
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Event that fires the trigger (e.g. `ddl_command_start`)
+    pub(crate) event: String,
+    /// Command tags that filter the event (e.g. `CREATE TABLE`). Empty if the trigger fires for
+    /// every command of the event.
+    pub(crate) tags: Vec<String>,
+    /// Full name of the event trigger function executed
+    #[sqlx(json)]
+    pub(crate) function_name: SchemaQualifiedName,
+    /// Firing state of the event trigger
+    pub(crate) enabled: EventTriggerEnabled,
+    /// Dependencies of the event trigger. This is only ever populated with the event trigger
+    /// function when the function is not built-in.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl EventTrigger {
+    /// True if migrating to the `new` event trigger drops and creates the event trigger again.
+    /// This is any change other than the firing state.
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.event != new.event || self.tags != new.tags || self.function_name != new.function_name
+    }
+
+    /// Write the `ALTER EVENT TRIGGER` statement that sets the firing state of the event trigger
+    fn write_enabled_statement<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "ALTER EVENT TRIGGER {} {};",
+            self.name,
+            self.enabled.as_ref()
+        )?;
+        Ok(())
+    }
+}
+
+impl SqlObject for EventTrigger {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "EVENT TRIGGER"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    /// Event triggers are always created enabled so the firing state is set afterward when the
+    /// event trigger is not enabled
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(w, "CREATE EVENT TRIGGER {} ON {}", self.name, self.event)?;
+        if !self.tags.is_empty() {
+            w.write_str("\n    WHEN TAG IN (")?;
+            write_join!(
+                w,
+                self.tags
+                    .iter()
+                    .map(|tag| format!("'{}'", tag.replace('\'', "''"))),
+                ", "
+            );
+            w.write_char(')')?;
+        }
+        writeln!(w, "\n    EXECUTE FUNCTION {}();", self.function_name)?;
+        if self.enabled != EventTriggerEnabled::Origin {
+            self.write_enabled_statement(w)?;
+        }
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    /// Only the firing state of an event trigger can be altered so any other change drops and
+    /// recreates the event trigger
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            return new.create_statements_with(context, w);
+        }
+        if self.enabled != new.enabled {
+            new.write_enabled_statement(w)?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP EVENT TRIGGER {}{};",
+            context.if_exists(),
+            self.name
+        )?;
+        Ok(())
+    }
+}
+
+/// Firing state of an event trigger with respect to the `session_replication_role`
+#[derive(Debug, PartialEq, strum::AsRefStr, sqlx::Type)]
+#[cfg_attr(test, derive(Default))]
+#[sqlx(type_name = "text")]
+pub enum EventTriggerEnabled {
+    /// Event trigger fires in origin and local modes (i.e. the default state)
+    #[cfg_attr(test, default)]
+    #[sqlx(rename = "O")]
+    #[strum(serialize = "ENABLE")]
+    Origin,
+    /// Event trigger never fires
+    #[sqlx(rename = "D")]
+    #[strum(serialize = "DISABLE")]
+    Disabled,
+    /// Event trigger only fires in replica mode
+    #[sqlx(rename = "R")]
+    #[strum(serialize = "ENABLE REPLICA")]
+    Replica,
+    /// Event trigger fires in every mode
+    #[sqlx(rename = "A")]
+    #[strum(serialize = "ENABLE ALWAYS")]
+    Always,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{EventTrigger, EventTriggerEnabled};
+
+    const NAME: &str = "test_event_trigger";
+    const SCHEMA: &str = "test_schema";
+    const FUNCTION: &str = "test_function";
+
+    fn create_event_trigger(tags: Vec<&str>, enabled: EventTriggerEnabled) -> EventTrigger {
+        EventTrigger {
+            name: SchemaQualifiedName::new("", NAME),
+            event: "ddl_command_start".into(),
+            tags: tags.into_iter().map(String::from).collect(),
+            function_name: SchemaQualifiedName::new(SCHEMA, FUNCTION),
+            enabled,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, FUNCTION)],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_event_trigger(vec![], EventTriggerEnabled::Origin),
+        include_str!("../../test-files/sql/event-trigger-create-case1.pgsql"),
+    )]
+    #[case(
+        create_event_trigger(vec!["CREATE TABLE", "DROP TABLE"], EventTriggerEnabled::Origin),
+        include_str!("../../test-files/sql/event-trigger-create-case2.pgsql"),
+    )]
+    #[case(
+        create_event_trigger(vec![], EventTriggerEnabled::Disabled),
+        include_str!("../../test-files/sql/event-trigger-create-case3.pgsql"),
+    )]
+    fn create_statements_should_add_create_event_trigger_statement(
+        #[case] event_trigger: EventTrigger,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        event_trigger.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_event_trigger(vec![], EventTriggerEnabled::Origin),
+        create_event_trigger(vec![], EventTriggerEnabled::Always),
+        include_str!("../../test-files/sql/event-trigger-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_event_trigger(vec![], EventTriggerEnabled::Origin),
+        create_event_trigger(vec!["CREATE TABLE"], EventTriggerEnabled::Origin),
+        include_str!("../../test-files/sql/event-trigger-alter-case2.pgsql"),
+    )]
+    fn alter_statements_should_alter_enabled_state_or_recreate(
+        #[case] old: EventTrigger,
+        #[case] new: EventTrigger,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_event_trigger_statement() {
+        let event_trigger = create_event_trigger(vec![], EventTriggerEnabled::Origin);
+        let mut writable = String::new();
+
+        event_trigger.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/event-trigger-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
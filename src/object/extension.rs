@@ -4,12 +4,12 @@ use sqlx::{query_as, PgPool};
 
 use crate::PgDiffError;
 
-use super::{SchemaQualifiedName, SqlObject};
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
 
 /// Fetch all extensions found within the current database
 pub async fn get_extensions(pool: &PgPool) -> Result<Vec<Extension>, PgDiffError> {
     let extensions_query = include_str!("./../../queries/extensions.pgsql");
-    let extensions = match query_as(extensions_query).fetch_all(pool).await {
+    let extensions = match fetch_with_retry(|| query_as(extensions_query).fetch_all(pool)).await {
         Ok(inner) => inner,
         Err(error) => {
             println!("Could not load extensions");
@@ -20,7 +20,8 @@ pub async fn get_extensions(pool: &PgPool) -> Result<Vec<Extension>, PgDiffError
 }
 
 /// Postgresql extension object
-#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Extension {
     /// Full name of the extension (never includes a schema name since extensions reside outside a
     /// schema even though the objects owned by the extension are within a schema)
@@ -37,6 +38,42 @@ pub struct Extension {
     /// is relocatable.
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
+    /// Objects owned by the extension (i.e. an extension dependency within `pg_depend`). These
+    /// objects are never scraped and are only managed through the extension.
+    #[sqlx(json)]
+    pub(crate) members: Vec<SchemaQualifiedName>,
+}
+
+impl PartialEq for Extension {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.version == other.version
+            && self.schema_name == other.schema_name
+            && self.is_relocatable == other.is_relocatable
+            && self.dependencies == other.dependencies
+    }
+}
+
+impl Extension {
+    /// Write the `CREATE` statement to the writable object. Includes the `IF NOT EXISTS` clause
+    /// when `if_not_exists` is true.
+    fn create_statement<W>(&self, w: &mut W, if_not_exists: bool) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "CREATE EXTENSION {}{} VERSION '{}'",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.name,
+            self.version
+        )?;
+        if self.is_relocatable {
+            write!(w, " SCHEMA {}", self.schema_name)?;
+        }
+        w.write_str(";\n")?;
+        Ok(())
+    }
 }
 
 impl SqlObject for Extension {
@@ -53,16 +90,11 @@ impl SqlObject for Extension {
     }
 
     fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        write!(
-            w,
-            "CREATE EXTENSION {} VERSION '{}'",
-            self.name, self.version
-        )?;
-        if self.is_relocatable {
-            write!(w, " SCHEMA {}", self.schema_name)?;
-        }
-        w.write_str(";\n")?;
-        Ok(())
+        self.create_statement(w, false)
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, true)
     }
 
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
@@ -83,8 +115,12 @@ impl SqlObject for Extension {
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP EXTENSION {};", self.name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP EXTENSION {}{};", context.if_exists(), self.name)?;
         Ok(())
     }
 }
@@ -107,6 +143,7 @@ mod test {
             schema_name: schema_name.into(),
             is_relocatable,
             dependencies: vec![],
+            members: vec![],
         }
     }
 
@@ -187,4 +224,19 @@ mod test {
 
         assert_eq!(statement.trim(), writeable.trim());
     }
+
+    #[test]
+    fn create_statements_idempotent_should_add_if_not_exists() {
+        let extension = create_extension(VERSION_1, SCHEMA_NAME, true);
+        let mut writeable = String::new();
+
+        extension
+            .create_statements_idempotent(&mut writeable)
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/extension-create-idempotent.pgsql").trim(),
+            writeable.trim()
+        )
+    }
 }
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::fmt::{Display, Formatter, Write};
 
 use lazy_regex::regex;
+use pg_query::protobuf::node::Node;
 use serde::Deserialize;
 use sqlx::error::BoxDynError;
 use sqlx::postgres::{PgTypeInfo, PgValueRef};
@@ -12,37 +13,38 @@ use crate::object::table::get_table_by_qualified_name;
 use crate::{impl_type_for_kvp_wrapper, write_join, PgDiffError};
 
 use super::{
-    check_names_in_database, compare_key_value_pairs, is_verbose, KeyValuePairs,
-    SchemaQualifiedName, SqlObject, PG_CATALOG_SCHEMA_NAME,
+    check_names_in_database, fetch_with_retry, is_verbose, parse_search_path,
+    resolve_with_search_path, sources_match, AclGrant, ColumnDependency, KeyValuePairs,
+    RenderContext, SchemaQualifiedName, SqlObject, PG_CATALOG_SCHEMA_NAME,
 };
 
 /// Fetch all functions within the `schemas` specified
 pub async fn get_functions(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Function>, PgDiffError> {
     let functions_query = include_str!("../../queries/functions.pgsql");
-    let functions = match query_as(functions_query)
-        .bind(schemas)
-        .fetch_all(pool)
-        .await
-    {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load functions");
-            return Err(error.into());
-        },
-    };
+    let functions =
+        match fetch_with_retry(|| query_as(functions_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load functions");
+                return Err(error.into());
+            },
+        };
     Ok(functions)
 }
 
 /// Fetch all functions that match the provided `schema_qualified_name`. If the schema portion of
-/// the name is not supplied (e.g. the referenced name is a builtin function) then supply the
-/// schemas to search as `public` and `pg_catalog`.
-async fn get_functions_by_qualified_name(
+/// the name is not supplied (e.g. the referenced name is a builtin function) then the schemas of
+/// the `search_path` are searched or `public` and `pg_catalog` when no `search_path` is available.
+pub async fn get_functions_by_qualified_name(
     pool: &PgPool,
     schema_qualified_name: &SchemaQualifiedName,
+    search_path: Option<&[String]>,
 ) -> Result<Vec<SchemaQualifiedName>, PgDiffError> {
     let functions_query = include_str!("../../queries/dependency_functions.pgsql");
     let functions =
-        match check_names_in_database(pool, schema_qualified_name, functions_query).await {
+        match check_names_in_database(pool, schema_qualified_name, functions_query, search_path)
+            .await
+        {
             Ok(inner) => inner,
             Err(error) => {
                 if is_verbose() {
@@ -55,15 +57,18 @@ async fn get_functions_by_qualified_name(
 }
 
 /// Fetch all objects that match the provided `schema_qualified_name`. If the schema portion of the
-/// name is not supplied (e.g. the referenced name is a builtin object) then supply the schemas to
-/// search as `public` and `pg_catalog`.
+/// name is not supplied (e.g. the referenced name is a builtin object) then the schemas of the
+/// `search_path` are searched or `public` and `pg_catalog` when no `search_path` is available.
 async fn get_objects_by_qualified_name(
     pool: &PgPool,
     schema_qualified_name: &SchemaQualifiedName,
+    search_path: Option<&[String]>,
 ) -> Result<Vec<SchemaQualifiedName>, PgDiffError> {
     let all_objects_query = include_str!("../../queries/all_objects.pgsql");
     let objects =
-        match check_names_in_database(pool, schema_qualified_name, all_objects_query).await {
+        match check_names_in_database(pool, schema_qualified_name, all_objects_query, search_path)
+            .await
+        {
             Ok(inner) => inner,
             Err(error) => {
                 if is_verbose() {
@@ -89,37 +94,73 @@ pub struct FunctionArgument<'s> {
 
 impl<'s> FunctionArgument<'s> {
     /// Create list of [FunctionArgument] from the string specified as the comma separated list of
-    /// arguments as seen in a `CREATE FUNCTION` statement.
+    /// arguments as seen in a `CREATE FUNCTION` statement. Commas within quotes, parentheses or
+    /// brackets (e.g. `numeric(10, 2)` or a default of `ARRAY[1, 2]`) do not separate arguments.
+    /// Blank arguments are skipped.
     fn from_arg_list(args: &'s str) -> Vec<Self> {
-        if args.is_empty() {
-            return vec![];
+        let mut arguments = vec![];
+        let mut start = 0;
+        for i in top_level_indices(args) {
+            if args[i..].starts_with(',') {
+                arguments.push(&args[start..i]);
+                start = i + 1;
+            }
         }
-        args
-            .split(',')
+        arguments.push(&args[start..]);
+        arguments
+            .into_iter()
+            .map(str::trim)
+            .filter(|arg| !arg.is_empty())
             .enumerate()
-            .map(|(i, arg)| {
-                let parts: Vec<&str> = arg.split_whitespace().collect();
-                let (_, arg_name, arg_type, default_expression) = match parts.as_slice() {
-                    [arg_mode, arg_name, arg_type, "DEFAULT", default_expression] => (*arg_mode, *arg_name, *arg_type, *default_expression),
-                    [arg_mode, arg_type, "DEFAULT", default_expression] => (*arg_mode, "", *arg_type, *default_expression),
-                    [arg_type, "DEFAULT", default_expression] => ("IN", "", *arg_type, *default_expression),
-                    [arg_mode, arg_name, arg_type] => (*arg_mode, *arg_name, *arg_type, ""),
-                    [arg_mode, arg_type] => (*arg_mode, "", *arg_type, ""),
-                    [arg_type] => ("IN", "", *arg_type, ""),
-                    _ => {
-                        panic!("Core assertion of postgresql syntax has been violated for args = {args:?}, arg = {arg:?}");
-                    }
-                };
-                FunctionArgument {
-                    index: i + 1,
-                    arg_name,
-                    arg_type,
-                    default_expression
-                }
-            })
+            .map(|(i, arg)| Self::from_arg(i + 1, arg))
             .collect()
     }
 
+    /// Split a single argument into the argument's mode, name, data type and default expression.
+    /// The default expression follows the first `DEFAULT` keyword (or `=`) outside of quotes,
+    /// parentheses or brackets. The data type starts where the parser locates the argument's type
+    /// so multi-word types (e.g. `double precision`) are never mistaken for an argument name. If
+    /// the argument cannot be parsed, the entire declaration is used as the data type.
+    fn from_arg(index: usize, arg: &'s str) -> Self {
+        let default_start = top_level_indices(arg).into_iter().find_map(|i| {
+            if arg[i..].starts_with('=') {
+                return Some((i, i + 1));
+            }
+            let is_default = arg
+                .get(i..i + 7)
+                .is_some_and(|word| word.eq_ignore_ascii_case("DEFAULT"))
+                && arg[..i].ends_with(char::is_whitespace)
+                && arg[i + 7..].starts_with(char::is_whitespace);
+            is_default.then_some((i, i + 7))
+        });
+        let (declaration, default_expression) = match default_start {
+            Some((keyword_start, expression_start)) => (
+                arg[..keyword_start].trim_end(),
+                arg[expression_start..].trim(),
+            ),
+            None => (arg, ""),
+        };
+        let Some((has_name, type_start)) = parse_argument_declaration(declaration) else {
+            return FunctionArgument {
+                index,
+                arg_name: "",
+                arg_type: declaration,
+                default_expression,
+            };
+        };
+        let arg_name = if has_name {
+            strip_argument_mode(&declaration[..type_start])
+        } else {
+            ""
+        };
+        FunctionArgument {
+            index,
+            arg_name,
+            arg_type: declaration[type_start..].trim(),
+            default_expression,
+        }
+    }
+
     /// Name of the argument. If the argument is unnamed then `param{index}` is returned.
     fn argument_name(&self) -> String {
         if self.arg_name.is_empty() {
@@ -144,37 +185,149 @@ impl<'s> Display for FunctionArgument<'s> {
     }
 }
 
+/// Modes that can precede or follow the name of a function argument
+const ARGUMENT_MODES: &[&str] = &["IN", "OUT", "INOUT", "VARIADIC"];
+
+/// Remove the argument mode that precedes (`OUT name`) or follows (`name OUT`) the name of a
+/// function argument within the `head` of the argument's declaration
+fn strip_argument_mode(head: &str) -> &str {
+    let is_mode = |word: &str| {
+        ARGUMENT_MODES
+            .iter()
+            .any(|mode| word.eq_ignore_ascii_case(mode))
+    };
+    let mut head = head.trim();
+    if let Some((first, rest)) = head.split_once(char::is_whitespace) {
+        if is_mode(first) {
+            head = rest.trim_start();
+        }
+    }
+    if let Some((rest, last)) = head.rsplit_once(char::is_whitespace) {
+        if is_mode(last) {
+            head = rest.trim_end();
+        }
+    }
+    head
+}
+
+/// Byte offsets of the characters within `text` that are not enclosed within quotes,
+/// parentheses or brackets
+fn top_level_indices(text: &str) -> Vec<usize> {
+    let mut indices = vec![];
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, _) if depth == 0 => indices.push(i),
+            (None, _) => {},
+        }
+    }
+    indices
+}
+
+/// Parse the function argument `declaration` (without a default expression) as the only argument
+/// of a `CREATE FUNCTION` statement. Returns true if the argument is named along with the byte
+/// offset of the argument's data type within the `declaration`.
+///
+/// Returns [None] if the declaration cannot be parsed as a single function argument.
+fn parse_argument_declaration(declaration: &str) -> Option<(bool, usize)> {
+    const PREFIX: &str = "CREATE FUNCTION f(";
+    let result = pg_query::parse(&format!(
+        "{PREFIX}{declaration}) RETURNS void LANGUAGE sql AS ''"
+    ))
+    .ok()?;
+    let [statement] = result.protobuf.stmts.as_slice() else {
+        return None;
+    };
+    let Some(Node::CreateFunctionStmt(create_function)) =
+        statement.stmt.as_ref().and_then(|s| s.node.as_ref())
+    else {
+        return None;
+    };
+    let [parameter] = create_function.parameters.as_slice() else {
+        return None;
+    };
+    let Some(Node::FunctionParameter(parameter)) = parameter.node.as_ref() else {
+        return None;
+    };
+    let location = usize::try_from(parameter.arg_type.as_ref()?.location).ok()?;
+    let type_start = location.checked_sub(PREFIX.len())?;
+    Some((!parameter.name.is_empty(), type_start))
+}
+
+/// Configuration parameters whose value is a list of names. The server stores these values with
+/// each name already quoted as an identifier (when required) so the value is used as is.
+const LIST_CONFIG_PARAMETERS: &[&str] = &[
+    "local_preload_libraries",
+    "search_path",
+    "session_preload_libraries",
+    "shared_preload_libraries",
+    "temp_tablespaces",
+    "unix_socket_directories",
+];
+
+/// Configuration parameters set when the function is executed (i.e. `SET name TO value` clauses)
 #[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Default))]
 pub struct FunctionConfig(KeyValuePairs);
 
 impl_type_for_kvp_wrapper!(FunctionConfig);
 
+impl FunctionConfig {
+    /// Configuration parameters and their values in parameter name order
+    fn sorted(&self) -> Vec<(&String, &String)> {
+        let mut parameters: Vec<(&String, &String)> = self.0.iter().collect();
+        parameters.sort();
+        parameters
+    }
+
+    /// Write the `name TO value` portion of a `SET` clause for the configuration `parameter`.
+    /// List parameters (e.g. `search_path`) are written as the list of identifiers stored by the
+    /// server while all other values are written as string literals.
+    fn write_set_clause<W>(w: &mut W, parameter: &str, value: &str) -> std::fmt::Result
+    where
+        W: Write,
+    {
+        write!(w, "{parameter} TO ")?;
+        if !LIST_CONFIG_PARAMETERS.contains(&parameter) {
+            return write!(w, "'{}'", value.replace('\'', "''"));
+        }
+        // An empty list is stored as an empty quoted identifier which is not valid SQL
+        match value.trim() {
+            "" | "\"\"" => w.write_str("''"),
+            value => w.write_str(value),
+        }
+    }
+}
+
 impl Display for FunctionConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.0.is_empty() {
-            return Ok(());
+        for (parameter, value) in self.sorted() {
+            f.write_str("SET ")?;
+            Self::write_set_clause(f, parameter, value)?;
+            f.write_char('\n')?;
         }
-        write_join!(
-            f,
-            "\nSET ",
-            self.0.iter(),
-            |w, (key, value)| write!(w, "{key}={value}"),
-            "\nSET ",
-            ""
-        );
         Ok(())
     }
 }
 
 /// Postgresql function object. This includes procedures which are highlighted with the
 /// `is_procedure` field.
-#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Function {
     /// Full name of the function
     #[sqlx(json)]
     pub(crate) name: SchemaQualifiedName,
     /// True if this is a stored procedure (i.e. no return value)
     pub(crate) is_procedure: bool,
+    /// True if this is a window function (i.e. declared with `WINDOW`)
+    pub(crate) is_window: bool,
     /// Number of arguments
     pub(crate) input_arg_count: i16,
     /// Names of the arguments. All unnamed parameters will be empty string. If all arguments are
@@ -183,6 +336,11 @@ pub struct Function {
     /// Declaration block for the function arguments as returned from
     /// `pg_catalog.pg_get_function_arguments`
     pub(crate) arguments: String,
+    /// Argument types (with names and modes) that identify the function as returned from
+    /// `pg_catalog.pg_get_function_identity_arguments`
+    pub(crate) identity_arguments: String,
+    /// Number of arguments that have default values
+    pub(crate) default_arg_count: i16,
     /// Return type of the function as returned from `pg_catalog.pg_get_function_result`
     pub(crate) return_type: Option<String>,
     /// Estimated cost of function execution (in most cases this is only generated by the server)
@@ -205,92 +363,352 @@ pub struct Function {
     pub(crate) source_code: FunctionSourceCode,
     /// Function configuration option
     pub(crate) config: Option<FunctionConfig>,
+    /// Planner support function attached to the function, if any. The support function is always
+    /// found within the function's dependencies since the server records the dependency.
+    #[sqlx(json)]
+    pub(crate) support_function: Option<SchemaQualifiedName>,
+    /// Roles granted `EXECUTE` on the function. Only used to restore the privileges when the
+    /// function must be recreated so this is not included in equality checks.
+    #[sqlx(json)]
+    pub(crate) grants: Vec<AclGrant>,
+    /// Comment on the function, if any. Only used to restore the comment when the function must be
+    /// recreated so this is not included in equality checks.
+    pub(crate) comment: Option<String>,
     /// Function dependencies found in database. This can be updated later is `source_code` can be
     /// analyzed.
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
+    /// Columns of tables referenced by the function. The server only records the columns
+    /// referenced by pre-parsed SQL bodies so this is empty for all other functions.
+    #[sqlx(json)]
+    pub(crate) column_dependencies: Vec<ColumnDependency>,
+}
+
+impl PartialEq for Function {
+    #[inline]
+    fn eq(&self, other: &Function) -> bool {
+        self.name == other.name
+            && self.is_procedure == other.is_procedure
+            && self.is_window == other.is_window
+            && self.input_arg_count == other.input_arg_count
+            && self.arg_names == other.arg_names
+            && self.arguments == other.arguments
+            && self.identity_arguments == other.identity_arguments
+            && self.default_arg_count == other.default_arg_count
+            && self.return_type == other.return_type
+            && self.estimated_cost == other.estimated_cost
+            && self.estimated_rows == other.estimated_rows
+            && self.security == other.security
+            && self.is_leak_proof == other.is_leak_proof
+            && self.strict == other.strict
+            && self.behaviour == other.behaviour
+            && self.parallel == other.parallel
+            && self.source_code == other.source_code
+            && self.config == other.config
+            && self.support_function == other.support_function
+            && self.dependencies == other.dependencies
+    }
 }
 
 impl Function {
-    /// Attempt to extract additional dependencies if the source code of the procedure is executed
-    /// at runtime.
+    /// True if the `new` function only extends the signature of this function with trailing
+    /// arguments that all have default values. Existing calls to this function are still valid
+    /// against the `new` function.
+    fn is_extended_by(&self, new: &Self) -> bool {
+        let added_arg_count = new.input_arg_count - self.input_arg_count;
+        if self.is_procedure != new.is_procedure
+            || self.return_type != new.return_type
+            || added_arg_count <= 0
+            || new.default_arg_count - self.default_arg_count != added_arg_count
+        {
+            return false;
+        }
+        if self.arguments.is_empty() {
+            return true;
+        }
+        new.arguments
+            .strip_prefix(&self.arguments)
+            .is_some_and(|added_arguments| added_arguments.starts_with(", "))
+    }
+
+    /// True if migrating to the `new` function drops and creates the function again (or creates
+    /// a new overload, see [Function::is_extended_by]). The kind of routine, the signature and
+    /// the support function cannot be changed in place.
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.is_procedure != new.is_procedure
+            || self.is_window != new.is_window
+            || self.support_function != new.support_function
+            || self.arguments != new.arguments
+            || (!self.is_procedure && self.return_type != new.return_type)
+    }
+
+    /// True if the body of this function references any of the `columns` of the `relation`.
+    /// Only pre-parsed SQL bodies block the columns they reference from being dropped, which are
+    /// also the only bodies with known column references.
+    pub(crate) fn references_columns(
+        &self,
+        relation: &SchemaQualifiedName,
+        columns: &[&str],
+    ) -> bool {
+        self.column_dependencies
+            .iter()
+            .any(|d| &d.relation == relation && columns.contains(&d.column.as_str()))
+    }
+
+    /// Write the statements that create the `new` function in place of this function after this
+    /// function has been dropped. The privileges and comment of this function are restored.
+    pub(crate) fn recreate_statements<W>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        new.create_statements(w)?;
+        self.restore_grants_and_comment(new, w)
+    }
+
+    /// Write the statements required to restore the privileges and comment of this function onto
+    /// the `new` function after it has been recreated.
+    fn restore_grants_and_comment<W>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        let object_type_name = new.object_type_name();
+        if !self.grants.is_empty() {
+            writeln!(
+                w,
+                "REVOKE ALL ON {object_type_name} {}({}) FROM PUBLIC;",
+                new.name, new.identity_arguments
+            )?;
+        }
+        let target = format!(
+            "{object_type_name} {}({})",
+            new.name, new.identity_arguments
+        );
+        for grant in &self.grants {
+            grant.write_grant(w, "", &target)?;
+        }
+        if let Some(comment) = &self.comment {
+            writeln!(
+                w,
+                "COMMENT ON {object_type_name} {}({}) IS '{}';",
+                new.name,
+                new.identity_arguments,
+                comment.replace('\'', "''")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the `ALTER` statements that set the configuration parameters that are new or changed
+    /// within the `new` function and reset the parameters that were removed
+    fn write_config_alter_statements<W>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        let old_config = self
+            .config
+            .as_ref()
+            .map(FunctionConfig::sorted)
+            .unwrap_or_default();
+        let new_config = new
+            .config
+            .as_ref()
+            .map(FunctionConfig::sorted)
+            .unwrap_or_default();
+        for (parameter, value) in new_config.iter().filter(|p| !old_config.contains(p)) {
+            self.write_alter_prefix(w)?;
+            w.write_str(" SET ")?;
+            FunctionConfig::write_set_clause(w, parameter, value)?;
+            w.write_str(";\n")?;
+        }
+        for (parameter, _) in old_config
+            .iter()
+            .filter(|(old, _)| !new_config.iter().any(|(new, _)| new == old))
+        {
+            self.write_alter_prefix(w)?;
+            writeln!(w, " RESET {parameter};")?;
+        }
+        Ok(())
+    }
+
+    /// Schemas of the `search_path` set within the function's configuration, if any
+    fn search_path(&self) -> Option<Vec<String>> {
+        let search_path = self.config.as_ref()?.get("search_path")?;
+        Some(parse_search_path(search_path))
+    }
+
+    /// Warning noting that this function is `SECURITY DEFINER` but does not set a `search_path`.
+    /// Unqualified names within the function's body then resolve against the caller's
+    /// `search_path` which allows the caller to substitute their own objects while the body runs
+    /// with the privileges of the function owner.
+    pub(crate) fn search_path_warning(&self) -> Option<String> {
+        if self.security != FunctionSecurity::Definer || self.search_path().is_some() {
+            return None;
+        }
+        Some(format!(
+            "WARNING: SECURITY DEFINER {} {} does not set a search_path",
+            self.object_type_name(),
+            self.name
+        ))
+    }
+
+    /// Attempt to extract additional dependencies from the source code of the procedure.
     ///
-    /// This is only valid for non-parsed SQL and pl/pgsql functions since the code is only
-    /// evaluated at function creation and execution time (i.e. dependencies are not tracked which
-    /// is the case for parsed SQL functions).
+    /// This is mostly required for non-parsed SQL and pl/pgsql functions since the code is only
+    /// evaluated at function creation and execution time (i.e. dependencies are not tracked).
+    /// Pre-parsed SQL functions (`BEGIN ATOMIC` or `RETURN` bodies) are also analyzed since the
+    /// tracked dependencies are not always complete.
+    ///
+    /// Unqualified names are resolved against the function's `search_path` configuration or the
+    /// `default_search_path` (i.e. the database's `search_path`) if the function does not set one.
     ///
     /// ## Errors
     /// - if the SQL source code cannot be analyzed (this should not happen unless the source code
-    ///     is invalid)
+    ///   is invalid)
+    /// - if the pl/pgsql source code cannot be analyzed and `fail_on_unsupported` is true (see
+    ///   [Function::plpgsql_references])
     /// - searching the database for SQL objects referenced fails
-    pub async fn extract_more_dependencies(&mut self, pool: &PgPool) -> Result<(), PgDiffError> {
-        if let FunctionSourceCode::Sql {
-            source,
-            is_pre_parsed,
-        } = &self.source_code
-        {
-            if *is_pre_parsed {
-                return Ok(());
-            }
-            let result = pg_query::parse(source.trim()).map_err(|e| PgDiffError::PgQuery {
-                object_name: self.name.clone(),
-                error: e,
-            })?;
-            for table in result.tables() {
-                let table_name = SchemaQualifiedName::from(&table);
-                let tables = get_table_by_qualified_name(pool, &table_name).await?;
-                self.add_dependencies_if_match(&table_name, tables);
+    pub async fn extract_more_dependencies(
+        &mut self,
+        pool: &PgPool,
+        default_search_path: Option<&[String]>,
+        fail_on_unsupported: bool,
+    ) -> Result<(), PgDiffError> {
+        let search_path = self
+            .search_path()
+            .or_else(|| default_search_path.map(<[String]>::to_vec));
+        let search_path = search_path.as_deref();
+        let (tables, functions) = self.sql_references()?;
+        for table_name in tables {
+            let tables = get_table_by_qualified_name(pool, &table_name, search_path).await?;
+            self.add_dependencies_if_match(&table_name, tables, search_path);
+        }
+        for function_name in functions {
+            let functions =
+                get_functions_by_qualified_name(pool, &function_name, search_path).await?;
+            self.add_dependencies_if_match(&function_name, functions, search_path);
+        }
+        for name in self.plpgsql_references(fail_on_unsupported)? {
+            let objects = get_objects_by_qualified_name(pool, &name, search_path).await?;
+            self.add_dependencies_if_match(&name, objects, search_path);
+        }
+        Ok(())
+    }
+
+    /// Names of the objects referenced within the source code of a pl/pgsql function. Empty for
+    /// other languages.
+    ///
+    /// If the source code cannot be parsed or the objects of the source code cannot be extracted,
+    /// the analysis is skipped (logged when verbose) and the names found so far are returned.
+    ///
+    /// ## Errors
+    /// If the analysis is skipped and `fail_on_unsupported` is true
+    fn plpgsql_references(
+        &self,
+        fail_on_unsupported: bool,
+    ) -> Result<Vec<SchemaQualifiedName>, PgDiffError> {
+        let FunctionSourceCode::Plpgsql { .. } = &self.source_code else {
+            return Ok(vec![]);
+        };
+        let mut block = String::new();
+        self.create_statement(&mut block, true)?;
+        let skip = |reason: String| {
+            if fail_on_unsupported {
+                return Err(PgDiffError::UnsupportedAnalysis {
+                    object_name: self.name.to_string(),
+                    reason,
+                });
             }
-            for function in result.functions() {
-                let function_name = SchemaQualifiedName::from(&function);
-                let functions = get_functions_by_qualified_name(pool, &function_name).await?;
-                self.add_dependencies_if_match(&function_name, functions);
+            if is_verbose() {
+                println!("Object Name: {}. {reason}\n", self.name);
             }
-        }
-        if let FunctionSourceCode::Plpgsql { .. } = &self.source_code {
-            let mut block = String::new();
-            self.create_statement(&mut block, true)?;
-            let result: Vec<PlPgSqlFunction> = match parse_plpgsql_function(&block) {
-                Ok(inner) => inner,
+            Ok(())
+        };
+        let result: Vec<PlPgSqlFunction> = match parse_plpgsql_function(&block) {
+            Ok(inner) => inner,
+            Err(error) => {
+                skip(error.to_string())?;
+                return Ok(vec![]);
+            },
+        };
+        let mut names = vec![];
+        for function in result {
+            match function.get_objects() {
+                Ok(mut inner) => names.append(&mut inner),
                 Err(error) => {
-                    if is_verbose() {
-                        println!("Object Name: {}. {error}\n", self.name);
-                    }
-                    return Ok(());
+                    skip(format!(
+                        "Could not get dependencies of dynamic function due to object \
+                         extraction error. {error}"
+                    ))?;
+                    break;
                 },
-            };
-            for function in result {
-                let names = match function.get_objects() {
-                    Ok(inner) => inner,
-                    Err(error) => {
-                        if is_verbose() {
-                            println!("Could not get dependencies of dynamic function {} due to object extraction error. {error}", self.name);
-                        }
-                        return Ok(());
-                    },
-                };
-                for name in names {
-                    let objects = get_objects_by_qualified_name(pool, &name).await?;
-                    self.add_dependencies_if_match(&name, objects);
-                }
             }
         }
-        Ok(())
+        Ok(names)
+    }
+
+    /// Names of the tables and functions referenced within the source code of a SQL function.
+    /// Both lists are empty for other languages.
+    ///
+    /// Pre-parsed source code is reduced to the statements of the body since the parser does not
+    /// report references within the body of a `CREATE FUNCTION` statement.
+    ///
+    /// ## Errors
+    /// If the SQL source code cannot be parsed
+    fn sql_references(
+        &self,
+    ) -> Result<(Vec<SchemaQualifiedName>, Vec<SchemaQualifiedName>), PgDiffError> {
+        let FunctionSourceCode::Sql {
+            source,
+            is_pre_parsed,
+        } = &self.source_code
+        else {
+            return Ok((vec![], vec![]));
+        };
+        let statements = if *is_pre_parsed {
+            pre_parsed_body_statements(source)
+        } else {
+            source.trim().to_string()
+        };
+        let result = pg_query::parse(&statements).map_err(|e| PgDiffError::PgQuery {
+            object_name: self.name.clone(),
+            error: e,
+        })?;
+        let tables = result
+            .tables()
+            .iter()
+            .map(SchemaQualifiedName::from)
+            .collect();
+        let functions = result
+            .functions()
+            .iter()
+            .map(SchemaQualifiedName::from)
+            .collect();
+        Ok((tables, functions))
     }
 
     /// Add additional dependencies to the function object.
     ///
     /// Only cases where a single object is found for a given qualified name are actually added. If
-    /// multiple objects are found then they are ignored since we do not currently support checking
-    /// function overloads.
+    /// the name is unqualified and a `search_path` is available, multiple objects are narrowed to
+    /// the object found in the first schema of the `search_path`. Otherwise, multiple objects are
+    /// ignored since we do not currently support checking function overloads.
     fn add_dependencies_if_match(
         &mut self,
         name: &SchemaQualifiedName,
         objects: Vec<SchemaQualifiedName>,
+        search_path: Option<&[String]>,
     ) {
+        let objects = match search_path {
+            Some(search_path) if name.schema_name.is_empty() && objects.len() > 1 => {
+                resolve_with_search_path(objects, search_path)
+            },
+            _ => objects,
+        };
         match &objects[..] {
             [object] => {
-                if object.schema_name == PG_CATALOG_SCHEMA_NAME {
+                if object.schema_name == PG_CATALOG_SCHEMA_NAME
+                    || self.dependencies.contains(object)
+                {
                     return;
                 }
                 if is_verbose() {
@@ -360,9 +778,13 @@ impl Function {
         }
         w.write_str(")\n")?;
 
-        if let Some(returns) = &self.return_type {
+        // Procedures never have a `RETURNS` clause, even when output arguments are declared
+        if let Some(returns) = self.return_type.as_ref().filter(|_| !self.is_procedure) {
             writeln!(w, "RETURNS {returns}")?;
         }
+        if self.is_window {
+            w.write_str("WINDOW\n")?;
+        }
         writeln!(w, "LANGUAGE {}", self.source_code.language())?;
         if !self.is_procedure {
             writeln!(
@@ -377,6 +799,9 @@ impl Function {
             if let Some(estimated_rows) = &self.estimated_rows {
                 writeln!(w, "ROWS {estimated_rows}")?;
             }
+            if let Some(support_function) = &self.support_function {
+                writeln!(w, "SUPPORT {support_function}")?;
+            }
         }
         writeln!(w, "{}", self.security.as_ref())?;
         if let Some(config) = &self.config {
@@ -415,81 +840,90 @@ impl SqlObject for Function {
         self.create_statement(w, false)
     }
 
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        // Functions and procedures are always created using `CREATE OR REPLACE`
+        self.create_statements(w)
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        if self.arguments != new.arguments || self.return_type != new.return_type {
-            self.drop_statements(w)?;
-            self.create_statements(w)?;
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            if self.is_extended_by(new) {
+                // The new signature is a separate overload so the old function must be dropped
+                // explicitly using its signature to avoid ambiguous function calls
+                new.create_statements_with(context, w)?;
+                writeln!(
+                    w,
+                    "DROP {} {}{}({});",
+                    self.object_type_name(),
+                    context.if_exists(),
+                    self.name,
+                    self.identity_arguments
+                )?;
+            } else {
+                self.drop_statements_with(context, w)?;
+                new.create_statements_with(context, w)?;
+            }
+            self.restore_grants_and_comment(new, w)?;
+            return Ok(());
+        }
+
+        if self.source_code != new.source_code {
+            // Replacing the function also replaces all other options but keeps the owner, grants
+            // and comment
+            new.create_statements(w)?;
             return Ok(());
         }
 
         if self.security != new.security {
-            writeln!(
-                w,
-                "ALTER FUNCTION {}({}) {};",
-                self.name,
-                self.arguments,
-                new.security.as_ref()
-            )?;
+            self.write_alter_prefix(w)?;
+            writeln!(w, " {};", new.security.as_ref())?;
         }
 
-        compare_key_value_pairs(w, self, &self.config, &new.config, false)?;
+        self.write_config_alter_statements(new, w)?;
 
         if self.is_procedure {
             return Ok(());
         }
 
         if self.strict != new.strict {
-            writeln!(
-                w,
-                "ALTER FUNCTION {}({}) {};",
-                self.name,
-                self.arguments,
-                new.strict.as_ref()
-            )?;
+            self.write_alter_prefix(w)?;
+            writeln!(w, " {};", new.strict.as_ref())?;
         }
         if self.behaviour != new.behaviour {
-            writeln!(
-                w,
-                "ALTER FUNCTION {}({}) {};",
-                self.name,
-                self.arguments,
-                new.behaviour.as_ref()
-            )?;
+            self.write_alter_prefix(w)?;
+            writeln!(w, " {};", new.behaviour.as_ref())?;
         }
         if self.is_leak_proof != new.is_leak_proof {
+            self.write_alter_prefix(w)?;
             writeln!(
                 w,
-                "ALTER FUNCTION {}({}) {}LEAKPROOF;",
-                self.name,
-                self.arguments,
+                " {}LEAKPROOF;",
                 if new.is_leak_proof { "" } else { "NOT " }
             )?;
         }
         if self.parallel != new.parallel {
-            writeln!(
-                w,
-                "ALTER FUNCTION {}({}) {};",
-                self.name,
-                self.arguments,
-                new.parallel.as_ref()
-            )?;
+            self.write_alter_prefix(w)?;
+            writeln!(w, " {};", new.parallel.as_ref())?;
         }
         if self.estimated_cost != new.estimated_cost {
-            writeln!(
-                w,
-                "ALTER FUNCTION {}({}) COST {};",
-                self.name, self.arguments, new.estimated_cost
-            )?;
+            self.write_alter_prefix(w)?;
+            writeln!(w, " COST {};", new.estimated_cost)?;
         }
         match (&self.estimated_rows, &new.estimated_rows) {
             (Some(old_estimated_rows), Some(new_estimated_rows))
                 if old_estimated_rows != new_estimated_rows =>
             {
-                writeln!(
-                    w,
-                    "ALTER FUNCTION {}({}) ROWS {new_estimated_rows};",
-                    self.name, self.arguments
-                )?;
+                self.write_alter_prefix(w)?;
+                writeln!(w, " ROWS {new_estimated_rows};")?;
             },
             (None, None) => {},
             _ => {
@@ -503,8 +937,18 @@ impl SqlObject for Function {
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP {} {};", self.object_type_name(), self.name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP {} {}{};",
+            self.object_type_name(),
+            context.if_exists(),
+            self.name
+        )?;
         Ok(())
     }
 
@@ -526,10 +970,41 @@ impl SqlObject for Function {
     }
 }
 
+/// Convert the `source` of a pre-parsed SQL function (as returned by
+/// `pg_catalog.pg_get_function_sqlbody`) into standalone statements. The statements within a
+/// `BEGIN ATOMIC ... END` block are returned as is while a `RETURN` expression becomes a `SELECT`
+/// statement.
+fn pre_parsed_body_statements(source: &str) -> String {
+    let source = source.trim().trim_end_matches(';').trim_end();
+    if let Some(body) = strip_keyword(source, "BEGIN ATOMIC") {
+        let body = body.trim_end();
+        let body = match body.len().checked_sub(3) {
+            Some(end) if body[end..].eq_ignore_ascii_case("END") => &body[..end],
+            _ => body,
+        };
+        return body.trim().to_string();
+    }
+    match strip_keyword(source, "RETURN") {
+        Some(expression) => format!("SELECT {}", expression.trim()),
+        None => source.to_string(),
+    }
+}
+
+/// Remove the `keyword` from the start of the `text` (ignoring case). [None] if the `text` does
+/// not start with the `keyword` followed by whitespace.
+fn strip_keyword<'t>(text: &'t str, keyword: &str) -> Option<&'t str> {
+    let prefix = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    if prefix.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace) {
+        return Some(rest);
+    }
+    None
+}
+
 /// Function source code variants.
 ///
 /// Variants are defined by language and include the options valid for that language.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum FunctionSourceCode {
     /// Dynamically or statically executed SQL code
@@ -567,6 +1042,59 @@ pub enum FunctionSourceCode {
     },
 }
 
+impl PartialEq for FunctionSourceCode {
+    /// SQL and pl/pgsql source code is compared ignoring formatting differences
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Sql {
+                    source,
+                    is_pre_parsed,
+                },
+                Self::Sql {
+                    source: other_source,
+                    is_pre_parsed: other_is_pre_parsed,
+                },
+            ) => is_pre_parsed == other_is_pre_parsed && sources_match(source, other_source),
+            (
+                Self::Plpgsql { source },
+                Self::Plpgsql {
+                    source: other_source,
+                },
+            ) => sources_match(source, other_source),
+            (
+                Self::C { name, link_symbol },
+                Self::C {
+                    name: other_name,
+                    link_symbol: other_link_symbol,
+                },
+            ) => name == other_name && link_symbol == other_link_symbol,
+            (Self::Internal { name }, Self::Internal { name: other_name }) => name == other_name,
+            (
+                Self::Invalid {
+                    function_name,
+                    language_name,
+                },
+                Self::Invalid {
+                    function_name: other_function_name,
+                    language_name: other_language_name,
+                },
+            ) => function_name == other_function_name && language_name == other_language_name,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Default for FunctionSourceCode {
+    fn default() -> Self {
+        Self::Sql {
+            source: String::new(),
+            is_pre_parsed: false,
+        }
+    }
+}
+
 impl FunctionSourceCode {
     /// Language name of the source code
     fn language(&self) -> &str {
@@ -601,7 +1129,7 @@ impl FunctionSourceCode {
                 } else {
                     writeln!(w, "{}", source.trim())?;
                 }
-                w.write_str("$function$;")?;
+                w.write_str("$function$;\n")?;
             },
             Self::C {
                 name,
@@ -662,6 +1190,7 @@ where
 
 /// Function behaviour variant
 #[derive(Debug, PartialEq, sqlx::Type, strum::AsRefStr)]
+#[cfg_attr(test, derive(Default))]
 #[sqlx(type_name = "text")]
 pub enum FunctionBehaviour {
     /// Function does not modify the database (i.e. no lookup or modification statements are
@@ -672,6 +1201,7 @@ pub enum FunctionBehaviour {
     #[strum(serialize = "STABLE")]
     Stable,
     /// Function makes no guarantees about output stability so optimizations cannot be performed
+    #[cfg_attr(test, default)]
     #[strum(serialize = "VOLATILE")]
     Volatile,
 }
@@ -744,4 +1274,477 @@ pub enum FunctionSecurity {
 impl_type_for_bool!(FunctionSecurity, FunctionSecurity::Definer);
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use crate::object::{AclGrant, SchemaQualifiedName, SqlObject};
+    use crate::PgDiffError;
+
+    use super::{
+        pre_parsed_body_statements, Function, FunctionArgument, FunctionBehaviour, FunctionConfig,
+        FunctionSecurity, FunctionSourceCode, FunctionStrict,
+    };
+
+    const SCHEMA: &str = "test_schema";
+    const NAME: &str = "test_function";
+
+    fn create_function(arguments: &str, source_code: FunctionSourceCode) -> Function {
+        Function {
+            name: SchemaQualifiedName::new(SCHEMA, NAME),
+            arguments: arguments.into(),
+            return_type: Some("integer".into()),
+            estimated_cost: 100.0,
+            source_code,
+            ..Default::default()
+        }
+    }
+
+    fn create_procedure(arguments: &str) -> Function {
+        Function {
+            is_procedure: true,
+            return_type: None,
+            ..create_function(
+                arguments,
+                FunctionSourceCode::Plpgsql {
+                    source: "\nBEGIN\n    RAISE NOTICE '%', value;\nEND;\n".into(),
+                },
+            )
+        }
+    }
+
+    fn sql_source() -> FunctionSourceCode {
+        FunctionSourceCode::Sql {
+            source: "SELECT $1 + 1;".into(),
+            is_pre_parsed: false,
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_function("value integer", sql_source()),
+        include_str!("../../test-files/sql/function-create-case1.pgsql"),
+    )]
+    #[case(
+        create_function(
+            "value integer",
+            FunctionSourceCode::Sql {
+                source: "RETURN (value + 1)".into(),
+                is_pre_parsed: true,
+            },
+        ),
+        include_str!("../../test-files/sql/function-create-case2.pgsql"),
+    )]
+    #[case(
+        Function {
+            is_procedure: true,
+            return_type: None,
+            ..create_function(
+                "value integer",
+                FunctionSourceCode::Plpgsql {
+                    source: "\nBEGIN\n    RAISE NOTICE '%', value;\nEND;\n".into(),
+                },
+            )
+        },
+        include_str!("../../test-files/sql/function-create-case3.pgsql"),
+    )]
+    #[case(
+        Function {
+            return_type: Some("integer".into()),
+            ..create_procedure("value integer, INOUT total integer")
+        },
+        include_str!("../../test-files/sql/function-create-case4.pgsql"),
+    )]
+    #[case(
+        Function {
+            config: Some(FunctionConfig::from(
+                &["work_mem=256MB", "search_path=\"$user\", public"][..],
+            )),
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-create-case5.pgsql"),
+    )]
+    #[case(
+        Function {
+            is_window: true,
+            support_function: Some(SchemaQualifiedName::new(SCHEMA, "test_support")),
+            source_code: FunctionSourceCode::C {
+                name: "window_row_number".into(),
+                link_symbol: "$libdir/test".into(),
+            },
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-create-case6.pgsql"),
+    )]
+    #[case(
+        Function {
+            security: FunctionSecurity::Definer,
+            config: Some(FunctionConfig::from(
+                &["search_path=pg_catalog, test_schema"][..],
+            )),
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-create-case7.pgsql"),
+    )]
+    fn create_statements_should_add_create_function_statement(
+        #[case] function: Function,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        function.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(FunctionSecurity::Definer, None, true)]
+    #[case(FunctionSecurity::Definer, Some("work_mem=256MB"), true)]
+    #[case(FunctionSecurity::Definer, Some("search_path=pg_catalog"), false)]
+    #[case(FunctionSecurity::Invoker, None, false)]
+    fn search_path_warning_should_flag_security_definer_without_search_path(
+        #[case] security: FunctionSecurity,
+        #[case] config: Option<&str>,
+        #[case] has_warning: bool,
+    ) {
+        let function = Function {
+            security,
+            config: config.map(|c| FunctionConfig::from(&[c][..])),
+            ..create_function("value integer", sql_source())
+        };
+
+        let warning = function.search_path_warning();
+
+        assert_eq!(has_warning, warning.is_some());
+        if let Some(warning) = warning {
+            assert_eq!(
+                "WARNING: SECURITY DEFINER FUNCTION test_schema.test_function does not set a \
+                search_path",
+                warning
+            );
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_function("value integer", sql_source()),
+        Function {
+            behaviour: FunctionBehaviour::Immutable,
+            strict: FunctionStrict::Strict,
+            is_leak_proof: true,
+            estimated_cost: 10.0,
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_function("value integer", sql_source()),
+        create_function("value bigint", sql_source()),
+        include_str!("../../test-files/sql/function-alter-case2.pgsql"),
+    )]
+    #[case(
+        Function {
+            input_arg_count: 1,
+            identity_arguments: "value integer".into(),
+            ..create_function("value integer", sql_source())
+        },
+        Function {
+            input_arg_count: 2,
+            identity_arguments: "value integer, step integer".into(),
+            default_arg_count: 1,
+            ..create_function("value integer, step integer DEFAULT 1", sql_source())
+        },
+        include_str!("../../test-files/sql/function-alter-case3.pgsql"),
+    )]
+    #[case(
+        Function {
+            input_arg_count: 1,
+            identity_arguments: "value integer".into(),
+            grants: vec![
+                AclGrant {
+                    grantee: "postgres".into(),
+                    privilege_type: "EXECUTE".into(),
+                    is_grantable: false,
+                },
+                AclGrant {
+                    grantee: "app_user".into(),
+                    privilege_type: "EXECUTE".into(),
+                    is_grantable: true,
+                },
+            ],
+            comment: Some("Increment the value's count".into()),
+            ..create_function("value integer", sql_source())
+        },
+        Function {
+            input_arg_count: 1,
+            identity_arguments: "value text".into(),
+            ..create_function("value text", sql_source())
+        },
+        include_str!("../../test-files/sql/function-alter-case4.pgsql"),
+    )]
+    #[case(
+        create_function("value integer", sql_source()),
+        Function {
+            security: FunctionSecurity::Definer,
+            ..create_function(
+                "value integer",
+                FunctionSourceCode::Sql {
+                    source: "SELECT $1 + 2;".into(),
+                    is_pre_parsed: false,
+                },
+            )
+        },
+        include_str!("../../test-files/sql/function-alter-case5.pgsql"),
+    )]
+    #[case(
+        create_procedure("value integer"),
+        Function {
+            security: FunctionSecurity::Definer,
+            ..create_procedure("value integer")
+        },
+        include_str!("../../test-files/sql/function-alter-case6.pgsql"),
+    )]
+    #[case(
+        Function {
+            return_type: None,
+            ..create_function("value integer", sql_source())
+        },
+        Function {
+            source_code: sql_source(),
+            ..create_procedure("value integer")
+        },
+        include_str!("../../test-files/sql/function-alter-case7.pgsql"),
+    )]
+    #[case(
+        Function {
+            config: Some(FunctionConfig::from(
+                &["search_path=app", "work_mem=64MB", "statement_timeout=5s"][..],
+            )),
+            ..create_function("value integer", sql_source())
+        },
+        Function {
+            config: Some(FunctionConfig::from(
+                &["search_path=\"$user\", public", "work_mem=256MB"][..],
+            )),
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-alter-case8.pgsql"),
+    )]
+    #[case(
+        create_function("value integer", sql_source()),
+        Function {
+            support_function: Some(SchemaQualifiedName::new(SCHEMA, "test_support")),
+            ..create_function("value integer", sql_source())
+        },
+        include_str!("../../test-files/sql/function-alter-case9.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_function_statements(
+        #[case] old: Function,
+        #[case] new: Function,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_skip_function_only_options_for_procedures() {
+        let old = create_procedure("value integer");
+        let new = Function {
+            behaviour: FunctionBehaviour::Immutable,
+            strict: FunctionStrict::Strict,
+            is_leak_proof: true,
+            estimated_cost: 10.0,
+            return_type: Some("integer".into()),
+            ..create_procedure("value integer")
+        };
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!("", writable);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        FunctionSourceCode::Sql {
+            source: "select $1+1".into(),
+            is_pre_parsed: false,
+        },
+        true,
+    )]
+    #[case(
+        FunctionSourceCode::Sql {
+            source: "SELECT $1 + 1;".into(),
+            is_pre_parsed: true,
+        },
+        false,
+    )]
+    #[case(
+        FunctionSourceCode::Plpgsql {
+            source: "\nBEGIN\n    RAISE NOTICE '%', $1;\nEND;\n".into(),
+        },
+        false,
+    )]
+    fn source_code_eq_should_ignore_formatting(
+        #[case] other: FunctionSourceCode,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, sql_source() == other);
+    }
+
+    #[test]
+    fn source_code_eq_should_ignore_plpgsql_comments_and_keyword_case() {
+        let old = FunctionSourceCode::Plpgsql {
+            source: "\nBEGIN\n    -- Notify the caller\n    RAISE NOTICE '%', value;\nEND;\n"
+                .into(),
+        };
+        let new = FunctionSourceCode::Plpgsql {
+            source: "begin raise notice '%', value; end;".into(),
+        };
+        let changed = FunctionSourceCode::Plpgsql {
+            source: "begin raise notice 'Value: %', value; end;".into(),
+        };
+
+        assert_eq!(old, new);
+        assert_ne!(old, changed);
+    }
+
+    #[rstest::rstest]
+    #[case(false)]
+    #[case(true)]
+    fn plpgsql_references_should_fail_for_unparsable_source_when_fail_on_unsupported(
+        #[case] fail_on_unsupported: bool,
+    ) {
+        let function = create_function(
+            "value integer",
+            FunctionSourceCode::Plpgsql {
+                source: "\nBEGIN\n    IF THEN;\nEND;\n".into(),
+            },
+        );
+
+        let result = function.plpgsql_references(fail_on_unsupported);
+
+        if fail_on_unsupported {
+            assert!(matches!(
+                result,
+                Err(PgDiffError::UnsupportedAnalysis { object_name, .. })
+                    if object_name == "test_schema.test_function"
+            ));
+        } else {
+            assert!(result.unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_function_statement() {
+        let function = create_function("value integer", sql_source());
+        let mut writable = String::new();
+
+        function.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/function-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(Some(&["search_path=other, app"][..]), None, "other")]
+    #[case(None, Some(vec!["app".to_string(), "other".to_string()]), "app")]
+    #[case(Some(&["search_path=app"][..]), Some(vec!["other".to_string()]), "app")]
+    fn add_dependencies_if_match_should_resolve_unqualified_names_with_search_path(
+        #[case] config: Option<&[&str]>,
+        #[case] default_search_path: Option<Vec<String>>,
+        #[case] expected_schema: &str,
+    ) {
+        let mut function = Function {
+            config: config.map(FunctionConfig::from),
+            ..create_function("value integer", sql_source())
+        };
+        let search_path = function.search_path().or(default_search_path);
+        let name = SchemaQualifiedName::new("", "test_table");
+        let tables = vec![
+            SchemaQualifiedName::new("app", "test_table"),
+            SchemaQualifiedName::new("other", "test_table"),
+        ];
+
+        function.add_dependencies_if_match(&name, tables, search_path.as_deref());
+
+        assert_eq!(
+            vec![SchemaQualifiedName::new(expected_schema, "test_table")],
+            function.dependencies
+        );
+    }
+
+    #[test]
+    fn add_dependencies_if_match_should_skip_ambiguous_names_without_search_path() {
+        let mut function = create_function("value integer", sql_source());
+        let name = SchemaQualifiedName::new("", "test_table");
+        let tables = vec![
+            SchemaQualifiedName::new("app", "test_table"),
+            SchemaQualifiedName::new("other", "test_table"),
+        ];
+
+        function.add_dependencies_if_match(&name, tables, None);
+
+        assert!(function.dependencies.is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "BEGIN ATOMIC\n SELECT count(*) AS count\n    FROM test_schema.test_table;\n SELECT 1;\nEND",
+        "SELECT count(*) AS count\n    FROM test_schema.test_table;\n SELECT 1;",
+    )]
+    #[case("begin atomic select 1; end;", "select 1;")]
+    #[case("RETURN (value + 1)", "SELECT (value + 1)")]
+    fn pre_parsed_body_statements_should_extract_body_statements(
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, pre_parsed_body_statements(source));
+    }
+
+    #[test]
+    fn sql_references_should_include_tables_within_begin_atomic_body() {
+        let function = create_function(
+            "",
+            FunctionSourceCode::Sql {
+                source: "BEGIN ATOMIC\n SELECT count(*) AS count\n    FROM test_schema.test_table\n    WHERE test_schema.is_active(id);\nEND".into(),
+                is_pre_parsed: true,
+            },
+        );
+
+        let (tables, functions) = function.sql_references().unwrap();
+
+        assert_eq!(vec![SchemaQualifiedName::new(SCHEMA, "test_table")], tables);
+        assert!(functions.contains(&SchemaQualifiedName::new(SCHEMA, "is_active")));
+    }
+
+    #[rstest::rstest]
+    #[case("", "")]
+    #[case(" ", "")]
+    #[case("IN value integer, ", "value integer")]
+    #[case("integer,  ,text", "param1 integer,param2 text")]
+    #[case("value double precision", "value double precision")]
+    #[case("double precision, OUT text", "param1 double precision,param2 text")]
+    #[case(
+        "VARIADIC vals character varying(10)[] DEFAULT '{a, b}'::character varying[]",
+        "vals character varying(10)[] = '{a, b}'::character varying[]"
+    )]
+    #[case(
+        "amount numeric(10,2) DEFAULT round(1.5, 2), ts timestamp with time zone DEFAULT now()",
+        "amount numeric(10,2) = round(1.5, 2),ts timestamp with time zone = now()"
+    )]
+    #[case("\"Value\" INOUT integer = 1", "\"Value\" integer = 1")]
+    fn from_arg_list_should_parse_arguments(#[case] args: &str, #[case] expected: &str) {
+        let arguments = FunctionArgument::from_arg_list(args);
+
+        let result = arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(expected, result);
+    }
+}
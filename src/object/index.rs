@@ -1,29 +1,213 @@
 use std::fmt::Write;
 
+use pg_query::protobuf::node::Node;
+use pg_query::protobuf::{IndexStmt, SortByDir, SortByNulls};
 use sqlx::postgres::types::Oid;
 use sqlx::{query_as, PgPool};
 
-use crate::PgDiffError;
+use crate::{write_join, PgDiffError};
 
 use super::{
-    compare_key_value_pairs, compare_tablespaces, IndexParameters, SchemaQualifiedName, SqlObject,
+    compare_key_value_pairs, compare_tablespaces, fetch_with_retry, optional_expressions_match,
+    IndexParameters, RenderContext, SchemaQualifiedName, SqlObject,
 };
 
 /// Fetch all indexes associated with the tables specified (as table OID)
 pub async fn get_indexes(pool: &PgPool, tables: &[Oid]) -> Result<Vec<Index>, PgDiffError> {
     let indexes_query = include_str!("./../../queries/indexes.pgsql");
-    let indexes = match query_as(indexes_query).bind(tables).fetch_all(pool).await {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load index");
-            return Err(error.into());
-        },
-    };
+    let mut indexes: Vec<Index> =
+        match fetch_with_retry(|| query_as(indexes_query).bind(tables).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load index");
+                return Err(error.into());
+            },
+        };
+    for index in indexes.iter_mut() {
+        index.parse_definition()?;
+    }
     Ok(indexes)
 }
 
+/// Parse the `CREATE INDEX` statement of the index from the index's `definition` as returned by
+/// `pg_catalog.pg_get_indexdef`
+///
+/// ## Errors
+/// - the definition cannot be parsed
+/// - the definition is not a single `CREATE INDEX` statement
+fn parse_index_statement(
+    index_name: &SchemaQualifiedName,
+    definition: &str,
+) -> Result<IndexStmt, PgDiffError> {
+    let result = pg_query::parse(definition).map_err(|error| PgDiffError::PgQuery {
+        object_name: index_name.clone(),
+        error,
+    })?;
+    let [statement] = result.protobuf.stmts.as_slice() else {
+        return Err(format!("Definition of index {index_name} is not a single statement").into());
+    };
+    let Some(Node::IndexStmt(index_statement)) =
+        statement.stmt.as_ref().and_then(|s| s.node.as_ref())
+    else {
+        return Err(
+            format!("Definition of index {index_name} is not a CREATE INDEX statement").into(),
+        );
+    };
+    Ok(index_statement.as_ref().clone())
+}
+
+/// Extract the key columns of the index from the parsed `index_statement`
+///
+/// ## Errors
+/// If a key column expression cannot be deparsed
+fn parse_key_columns(
+    index_name: &SchemaQualifiedName,
+    index_statement: &IndexStmt,
+) -> Result<Vec<IndexColumn>, PgDiffError> {
+    let map_error = |error| PgDiffError::PgQuery {
+        object_name: index_name.clone(),
+        error,
+    };
+    index_statement
+        .index_params
+        .iter()
+        .filter_map(|p| match &p.node {
+            Some(Node::IndexElem(index_element)) => Some(index_element),
+            _ => None,
+        })
+        .map(|index_element| {
+            let expression = match index_element.expr.as_deref() {
+                Some(expression) => deparse_expression(expression).map_err(map_error)?,
+                None => index_element.name.clone(),
+            };
+            let mut operator_class = join_names(&index_element.opclass);
+            if let (Some(operator_class), false) = (
+                operator_class.as_mut(),
+                index_element.opclassopts.is_empty(),
+            ) {
+                write_join!(
+                    operator_class,
+                    "(",
+                    index_element
+                        .opclassopts
+                        .iter()
+                        .filter_map(|o| match &o.node {
+                            Some(Node::DefElem(def_element)) => Some(def_element),
+                            _ => None,
+                        }),
+                    |w, def_element| {
+                        write!(w, "{}=", def_element.defname)?;
+                        write_constant(w, def_element.arg.as_deref())
+                    },
+                    ",",
+                    ")"
+                );
+            }
+            Ok(IndexColumn {
+                expression,
+                collation: join_names(&index_element.collation),
+                operator_class,
+                is_descending: index_element.ordering() == SortByDir::SortbyDesc,
+                nulls_order: match index_element.nulls_ordering() {
+                    SortByNulls::SortbyNullsFirst => Some(IndexNullsOrder::First),
+                    SortByNulls::SortbyNullsLast => Some(IndexNullsOrder::Last),
+                    _ => None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Deparse the index key `expression` into SQL text. Expressions other than function calls are
+/// wrapped in parentheses as required within an index definition.
+///
+/// Expressions cannot be deparsed directly so the expression is placed as the only target of a
+/// `SELECT` statement that is then deparsed.
+fn deparse_expression(expression: &pg_query::protobuf::Node) -> Result<String, pg_query::Error> {
+    let mut result = pg_query::parse("SELECT NULL")?;
+    let target = result
+        .protobuf
+        .stmts
+        .first_mut()
+        .and_then(|s| s.stmt.as_deref_mut())
+        .and_then(|s| match s.node.as_mut() {
+            Some(Node::SelectStmt(select)) => select.target_list.first_mut(),
+            _ => None,
+        })
+        .and_then(|t| match t.node.as_mut() {
+            Some(Node::ResTarget(res_target)) => Some(res_target),
+            _ => None,
+        });
+    if let Some(res_target) = target {
+        res_target.val = Some(Box::new(expression.clone()));
+    }
+    let statement = result.deparse()?;
+    let deparsed = statement.trim_start_matches("SELECT ");
+    if let Some(Node::FuncCall(_)) = &expression.node {
+        return Ok(deparsed.to_string());
+    }
+    Ok(format!("({deparsed})"))
+}
+
+/// Join the list of string `name_nodes` into a single dot separated name. Returns [None] if the
+/// list is empty.
+fn join_names(name_nodes: &[pg_query::protobuf::Node]) -> Option<String> {
+    let names: Vec<&str> = name_nodes
+        .iter()
+        .filter_map(|n| match &n.node {
+            Some(Node::String(name)) => Some(name.sval.as_str()),
+            _ => None,
+        })
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+    Some(names.join("."))
+}
+
+/// Write the constant value `node` of an operator class parameter
+fn write_constant<W>(w: &mut W, node: Option<&pg_query::protobuf::Node>) -> std::fmt::Result
+where
+    W: Write,
+{
+    match node.and_then(|n| n.node.as_ref()) {
+        Some(Node::Integer(integer)) => write!(w, "{}", integer.ival),
+        Some(Node::Float(float)) => write!(w, "{}", float.fval),
+        Some(Node::Boolean(boolean)) => write!(w, "{}", boolean.boolval),
+        Some(Node::String(string)) => write!(w, "'{}'", string.sval.replace('\'', "''")),
+        _ => Ok(()),
+    }
+}
+
+/// Key column of an index along with the per column options
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Default))]
+pub struct IndexColumn {
+    /// Column name or expression (wrapped in parentheses if not a function call)
+    pub(crate) expression: String,
+    /// Collation of the column if specified in the definition
+    pub(crate) collation: Option<String>,
+    /// Operator class of the column, including any operator class parameters, if not the default
+    /// operator class of the column's type
+    pub(crate) operator_class: Option<String>,
+    /// True if the column is sorted in descending order
+    pub(crate) is_descending: bool,
+    /// Nulls ordering of the column if it differs from the default of the sort order
+    pub(crate) nulls_order: Option<IndexNullsOrder>,
+}
+
+/// Nulls ordering variants of an index column
+#[derive(Debug, PartialEq)]
+pub enum IndexNullsOrder {
+    /// `NULLS FIRST`
+    First,
+    /// `NULLS LAST`
+    Last,
+}
+
 /// Struct representing a SQL index object
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Index {
     /// Owner table's OID
     pub(crate) table_oid: Oid,
@@ -36,19 +220,73 @@ pub struct Index {
     pub(crate) schema_qualified_name: SchemaQualifiedName,
     /// Columns specified in the index
     pub(crate) columns: Vec<String>,
+    /// Key columns of the index (including expressions) with the column's collation, operator
+    /// class, sort order and nulls ordering. Parsed from the `definition_statement`.
+    #[sqlx(skip)]
+    pub(crate) key_columns: Vec<IndexColumn>,
     /// Full SQL text of the index definition as found by `pg_catalog.pg_get_indexdef`
     pub(crate) definition_statement: String,
+    /// Name of the index access method (e.g. `btree` or `gin`)
+    pub(crate) access_method: String,
+    /// True if the index is a standalone unique index. Indexes backing a constraint are never
+    /// included since those indexes are created by the constraint.
+    pub(crate) is_unique: bool,
+    /// True if a unique index treats `NULL` values as equal (i.e. `NULLS NOT DISTINCT`). Parsed
+    /// from the `definition_statement`.
+    #[sqlx(skip)]
+    pub(crate) are_nulls_not_distinct: bool,
+    /// Predicate of a partial index as found by `pg_catalog.pg_get_expr`. [None] if the index is
+    /// not partial.
+    pub(crate) predicate: Option<String>,
     /// Optional parameters of the index
     #[sqlx(flatten)]
     pub(crate) parameters: IndexParameters,
-    /// Dependencies of the index. This is always just the owner table name
+    /// Dependencies of the index. This is the owner table name and any text search
+    /// configurations referenced within the index expressions
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
 }
 
 impl PartialEq for Index {
     fn eq(&self, other: &Self) -> bool {
-        self.definition_statement == other.definition_statement
+        self.schema_qualified_name == other.schema_qualified_name
+            && self.owner_table_name == other.owner_table_name
+            && !self.requires_recreation(other)
+            && self.parameters.with == other.parameters.with
+            && self.parameters.tablespace == other.parameters.tablespace
+    }
+}
+
+impl Index {
+    /// Parse the key columns and `NULLS NOT DISTINCT` option of the index from the
+    /// `definition_statement`
+    ///
+    /// ## Errors
+    /// See [parse_index_statement] and [parse_key_columns]
+    fn parse_definition(&mut self) -> Result<(), PgDiffError> {
+        let index_statement =
+            parse_index_statement(&self.schema_qualified_name, &self.definition_statement)?;
+        self.key_columns = parse_key_columns(&self.schema_qualified_name, &index_statement)?;
+        self.are_nulls_not_distinct = index_statement.nulls_not_distinct;
+        Ok(())
+    }
+
+    /// Returns true if the index must be dropped and created again to migrate to the `new` index.
+    /// This is the case unless only the storage parameters and/or the tablespace change (e.g. a
+    /// tablespace change is migrated using `ALTER INDEX ... SET TABLESPACE`). Partial index
+    /// predicates are compared structurally so formatting differences do not force a
+    /// recreation.
+    ///
+    /// When this is false, the index is altered in place and must be rebuilt for the new storage
+    /// parameters to apply to existing index pages.
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.access_method != new.access_method
+            || self.is_unique != new.is_unique
+            || self.are_nulls_not_distinct != new.are_nulls_not_distinct
+            || self.columns != new.columns
+            || self.key_columns != new.key_columns
+            || self.parameters.include != new.parameters.include
+            || !optional_expressions_match(self.predicate.as_deref(), new.predicate.as_deref())
     }
 }
 
@@ -70,12 +308,29 @@ impl SqlObject for Index {
         Ok(())
     }
 
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        // The definition always starts with `CREATE INDEX` or `CREATE UNIQUE INDEX`
+        writeln!(
+            w,
+            "{};",
+            self.definition_statement
+                .replacen("INDEX ", "INDEX IF NOT EXISTS ", 1)
+        )?;
+        Ok(())
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        if self.columns == new.columns
-            && self.parameters.include == new.parameters.include
-            && self.parameters.with != new.parameters.with
-        {
-            compare_key_value_pairs(w, self, &self.parameters.with, &new.parameters.with, true)?;
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if !self.requires_recreation(new) {
+            compare_key_value_pairs(w, self, &self.parameters.with, &new.parameters.with)?;
             compare_tablespaces(
                 self,
                 self.parameters.tablespace.as_ref(),
@@ -85,13 +340,22 @@ impl SqlObject for Index {
             return Ok(());
         }
 
-        self.drop_statements(w)?;
-        self.create_statements(w)?;
+        self.drop_statements_with(context, w)?;
+        new.create_statements_with(context, w)?;
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP INDEX {};", self.schema_qualified_name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP INDEX {}{};",
+            context.if_exists(),
+            self.schema_qualified_name
+        )?;
         Ok(())
     }
 }
@@ -102,7 +366,7 @@ mod test {
 
     use crate::object::{IndexParameters, SchemaQualifiedName, SqlObject, TableSpace};
 
-    use super::Index;
+    use super::{Index, IndexColumn, IndexNullsOrder};
 
     const SCHEMA: &str = "test_schema";
     const TABLE: &str = "test_table";
@@ -113,6 +377,33 @@ mod test {
     const OPTION_1_2: &str = "fillfactor=90";
     const OPTION_2_1: &str = "buffering=ON";
     const OPTION_2_2: &str = "buffering=OFF";
+    const INDEX_DEFINITION: &str =
+        "CREATE INDEX test_index ON test_schema.test_table USING btree (test_col)";
+    const OPCLASS_INDEX_DEFINITION: &str =
+        "CREATE INDEX test_index ON test_schema.test_table USING gin (test_col jsonb_path_ops)";
+    const UNIQUE_INDEX_DEFINITION: &str =
+        "CREATE UNIQUE INDEX test_index ON test_schema.test_table USING btree (test_col)";
+    const NULLS_NOT_DISTINCT_INDEX_DEFINITION: &str = "CREATE UNIQUE INDEX test_index ON \
+        test_schema.test_table USING btree (test_col) NULLS NOT DISTINCT";
+    const INCLUDE_INDEX_DEFINITION: &str = "CREATE INDEX test_index ON test_schema.test_table \
+        USING btree (created_at DESC NULLS LAST, lower(name) COLLATE \"C\") INCLUDE (id)";
+
+    /// Index with the `definition_statement` parsed the same way as fetched indexes
+    fn create_index_with_definition(definition_statement: &str, index: Index) -> Index {
+        let mut index = Index {
+            definition_statement: definition_statement.into(),
+            ..index
+        };
+        index.parse_definition().unwrap();
+        index
+    }
+
+    fn create_unique_index() -> Index {
+        Index {
+            is_unique: true,
+            ..create_index(None, None)
+        }
+    }
 
     fn create_index(with: Option<Vec<&str>>, tablespace: Option<&str>) -> Index {
         Index {
@@ -120,7 +411,12 @@ mod test {
             owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
             schema_qualified_name: SchemaQualifiedName::new(SCHEMA, NAME),
             columns: vec![],
+            key_columns: vec![],
             definition_statement: String::from(""),
+            access_method: String::from("btree"),
+            is_unique: false,
+            are_nulls_not_distinct: false,
+            predicate: None,
             parameters: IndexParameters {
                 include: None,
                 with: with.map(|w| w.as_slice().into()),
@@ -186,4 +482,257 @@ mod test {
 
         assert_eq!(statement.trim(), writeable.trim());
     }
+
+    #[rstest::rstest]
+    #[case(
+        INDEX_DEFINITION,
+        include_str!("../../test-files/sql/index-create-idempotent-case1.pgsql"),
+    )]
+    #[case(
+        "CREATE UNIQUE INDEX test_index ON test_schema.test_table USING btree (test_col)",
+        include_str!("../../test-files/sql/index-create-idempotent-case2.pgsql"),
+    )]
+    fn create_statements_idempotent_should_add_if_not_exists(
+        #[case] definition_statement: &str,
+        #[case] statement: &str,
+    ) {
+        let index = Index {
+            definition_statement: definition_statement.into(),
+            ..create_index(None, None)
+        };
+        let mut writable = String::new();
+
+        index.create_statements_idempotent(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_recreate_index_when_key_columns_change() {
+        let old = create_index_with_definition(
+            INDEX_DEFINITION,
+            create_index(Some(vec![OPTION_1_1]), None),
+        );
+        let definition_statement = "CREATE INDEX test_index ON test_schema.test_table USING btree \
+            (test_col text_pattern_ops) WITH (fillfactor='90')";
+        let new = create_index_with_definition(
+            definition_statement,
+            create_index(Some(vec![OPTION_1_2]), None),
+        );
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/index-alter-case5.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    fn create_partial_index(definition_statement: &str, predicate: &str) -> Index {
+        create_index_with_definition(
+            definition_statement,
+            Index {
+                predicate: Some(predicate.into()),
+                ..create_index(None, None)
+            },
+        )
+    }
+
+    #[rstest::rstest]
+    #[case("(deleted_at IS NULL)", "deleted_at IS NULL", true)]
+    #[case("(status = 'active'::text)", "status = 'active'", true)]
+    #[case("(deleted_at IS NULL)", "(deleted_at IS NOT NULL)", false)]
+    fn eq_should_compare_partial_index_predicates_structurally(
+        #[case] old_predicate: &str,
+        #[case] new_predicate: &str,
+        #[case] expected: bool,
+    ) {
+        let old = create_partial_index(INDEX_DEFINITION, old_predicate);
+        let new = create_partial_index(INDEX_DEFINITION, new_predicate);
+
+        assert_eq!(expected, old == new);
+    }
+
+    #[rstest::rstest]
+    #[case("CREATE INDEX test_index ON test_schema.test_table USING btree (test_col DESC)")]
+    #[case("CREATE INDEX test_index ON test_schema.test_table USING btree (test_col NULLS FIRST)")]
+    #[case(
+        "CREATE INDEX test_index ON test_schema.test_table USING btree (test_col COLLATE \"C\")"
+    )]
+    #[case(
+        "CREATE INDEX test_index ON test_schema.test_table USING btree (test_col text_pattern_ops)"
+    )]
+    fn eq_should_be_false_when_column_options_change(#[case] definition_statement: &str) {
+        let old = create_partial_index(INDEX_DEFINITION, "(deleted_at IS NULL)");
+        let new = create_partial_index(definition_statement, "(deleted_at IS NULL)");
+
+        assert_ne!(old, new);
+        assert!(old.requires_recreation(&new));
+    }
+
+    #[test]
+    fn alter_statements_should_recreate_index_when_predicate_changes() {
+        let old = create_partial_index(
+            "CREATE INDEX test_index ON test_schema.test_table USING btree (test_col) WHERE \
+            (deleted_at IS NULL)",
+            "(deleted_at IS NULL)",
+        );
+        let new = create_partial_index(
+            "CREATE INDEX test_index ON test_schema.test_table USING btree (test_col) WHERE \
+            (deleted_at IS NOT NULL)",
+            "(deleted_at IS NOT NULL)",
+        );
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/index-alter-case6.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        OPCLASS_INDEX_DEFINITION,
+        vec![IndexColumn {
+            expression: "test_col".into(),
+            operator_class: Some("jsonb_path_ops".into()),
+            ..Default::default()
+        }],
+    )]
+    #[case(
+        INCLUDE_INDEX_DEFINITION,
+        vec![
+            IndexColumn {
+                expression: "created_at".into(),
+                is_descending: true,
+                nulls_order: Some(IndexNullsOrder::Last),
+                ..Default::default()
+            },
+            IndexColumn {
+                expression: "lower(name)".into(),
+                collation: Some("C".into()),
+                ..Default::default()
+            },
+        ],
+    )]
+    #[case(
+        "CREATE INDEX test_index ON test_schema.test_table USING gist \
+        ((a + b), name gist_trgm_ops(siglen='32') NULLS FIRST)",
+        vec![
+            IndexColumn {
+                expression: "(a + b)".into(),
+                ..Default::default()
+            },
+            IndexColumn {
+                expression: "name".into(),
+                operator_class: Some("gist_trgm_ops(siglen='32')".into()),
+                nulls_order: Some(IndexNullsOrder::First),
+                ..Default::default()
+            },
+        ],
+    )]
+    fn parse_key_columns_should_capture_column_options(
+        #[case] definition_statement: &str,
+        #[case] expected: Vec<IndexColumn>,
+    ) {
+        let index = create_index_with_definition(definition_statement, create_index(None, None));
+
+        assert_eq!(expected, index.key_columns);
+    }
+
+    #[rstest::rstest]
+    #[case(UNIQUE_INDEX_DEFINITION, false)]
+    #[case(NULLS_NOT_DISTINCT_INDEX_DEFINITION, true)]
+    fn parse_definition_should_capture_nulls_not_distinct(
+        #[case] definition_statement: &str,
+        #[case] expected: bool,
+    ) {
+        let index = create_index_with_definition(definition_statement, create_index(None, None));
+
+        assert_eq!(expected, index.are_nulls_not_distinct);
+    }
+
+    #[test]
+    fn alter_statements_should_recreate_index_when_nulls_not_distinct_changes() {
+        let old = create_index_with_definition(UNIQUE_INDEX_DEFINITION, create_unique_index());
+        let new = create_index_with_definition(
+            NULLS_NOT_DISTINCT_INDEX_DEFINITION,
+            create_unique_index(),
+        );
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_ne!(old, new);
+        assert_eq!(
+            include_str!("../../test-files/sql/index-alter-nulls-not-distinct.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_only_set_tablespace_when_tablespace_changes() {
+        let old = create_index_with_definition(
+            UNIQUE_INDEX_DEFINITION,
+            create_index(None, Some(TABLESPACE_1)),
+        );
+        let new = create_index_with_definition(
+            UNIQUE_INDEX_DEFINITION,
+            create_index(None, Some(TABLESPACE_2)),
+        );
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_ne!(old, new);
+        assert!(!old.requires_recreation(&new));
+        assert_eq!(
+            "ALTER INDEX test_schema.test_index SET TABLESPACE other_tbl_space;",
+            writeable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        INDEX_DEFINITION,
+        include_str!("../../test-files/sql/index-create.pgsql"),
+    )]
+    #[case(
+        OPCLASS_INDEX_DEFINITION,
+        include_str!("../../test-files/sql/index-create-opclass.pgsql"),
+    )]
+    #[case(
+        INCLUDE_INDEX_DEFINITION,
+        include_str!("../../test-files/sql/index-create-include.pgsql"),
+    )]
+    fn create_statements_should_add_index_definition(
+        #[case] definition_statement: &str,
+        #[case] statement: &str,
+    ) {
+        let index = Index {
+            definition_statement: definition_statement.into(),
+            ..create_index(None, None)
+        };
+        let mut writable = String::new();
+
+        index.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_index_statement() {
+        let index = create_index(None, None);
+        let mut writable = String::new();
+
+        index.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/index-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
 }
@@ -1,39 +1,70 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter, Write};
+use std::future::Future;
 use std::ops::Deref;
 use std::sync::OnceLock;
+use std::time::Duration;
 
+use lazy_regex::regex;
+use pg_query::protobuf::{KeywordKind, Token};
 use serde::Deserialize;
 use sqlx::error::BoxDynError;
 use sqlx::postgres::types::Oid;
 use sqlx::postgres::{PgTypeInfo, PgValueRef};
 use sqlx::{query_scalar, PgPool, Postgres};
 
+use access_method::{get_access_methods, AccessMethod};
+use aggregate::{get_aggregates, Aggregate};
 use constraint::{get_constraints, Constraint};
-pub use database::{Database, DatabaseMigration};
+pub use database::{
+    dump_source_file, Database, DatabaseMigration, DropMode, FilesMigration, MigrationOptions,
+    MigrationPlan, OnUnsupported, ScriptLayout, ScriptOptions, SourceFiles, StagingCleanup,
+    StagingDatabase, TempDatabaseOptions, DEFAULT_LOCK_TIMEOUT,
+};
+use default_privileges::{get_default_privileges, DefaultPrivileges};
+use event_trigger::{get_event_triggers, EventTrigger};
 use extension::{get_extensions, Extension};
 use function::{get_functions, Function};
 use index::{get_indexes, Index};
+use operator::{get_operator_classes, get_operators, Operator, OperatorClass};
 use policy::{get_policies, Policy};
+use publication::{get_publications, Publication};
+use role::{get_roles, Role};
 use schema::{get_schemas, Schema};
 use sequence::{get_sequences, Sequence};
+use statistics::{get_statistics, Statistics};
 use table::{get_tables, Table};
+use tablespace::TablespaceStub;
+use text_search::{
+    get_text_search_configurations, get_text_search_dictionaries, TextSearchConfiguration,
+    TextSearchDictionary,
+};
 use trigger::{get_triggers, Trigger};
 use udt::{get_udts, Udt};
 use view::{get_views, View};
 
 use crate::PgDiffError;
 
+mod access_method;
+mod aggregate;
 mod constraint;
 mod database;
+mod default_privileges;
+mod event_trigger;
 mod extension;
 mod function;
 mod index;
+mod operator;
 mod plpgsql;
 mod policy;
+mod publication;
+mod role;
 mod schema;
 mod sequence;
+mod statistics;
 mod table;
+mod tablespace;
+mod text_search;
 mod trigger;
 mod udt;
 mod view;
@@ -51,6 +82,15 @@ const BUILT_IN_FUNCTIONS: &[&str] = &[
     "format",
 ];
 
+/// Text search functions that accept a text search configuration as the first of 2 arguments
+const TEXT_SEARCH_FUNCTIONS: &[&str] = &[
+    "to_tsvector",
+    "to_tsquery",
+    "plainto_tsquery",
+    "phraseto_tsquery",
+    "websearch_to_tsquery",
+];
+
 fn write_join_map<W, T, I, F>(
     write: &mut W,
     mut iter: I,
@@ -167,11 +207,418 @@ fn is_verbose() -> bool {
     false
 }
 
+/// Static state of the include roles option within the application. DO NOT ACCESS directly but
+/// rather use the [set_include_roles_flag] and [is_include_roles] functions.
+static INCLUDE_ROLES_FLAG: OnceLock<bool> = OnceLock::new();
+
+/// Initialize the [INCLUDE_ROLES_FLAG] option if not already set. If already set, then this
+/// function does nothing.
+pub fn set_include_roles_flag(value: bool) {
+    INCLUDE_ROLES_FLAG.get_or_init(|| value);
+}
+
+/// Get the state of the [INCLUDE_ROLES_FLAG] option. If the value cannot be obtained, false is
+/// returned.
+///
+/// Roles are often managed outside of source control so roles are only scripted and diffed when
+/// this option is set.
+fn is_include_roles() -> bool {
+    if let Some(flag) = INCLUDE_ROLES_FLAG.get() {
+        return *flag;
+    }
+    false
+}
+
+/// Static state of the emit roles option within the application. DO NOT ACCESS directly but
+/// rather use the [set_emit_roles_flag] and [is_emit_roles] functions.
+static EMIT_ROLES_FLAG: OnceLock<bool> = OnceLock::new();
+
+/// Initialize the [EMIT_ROLES_FLAG] option if not already set. If already set, then this function
+/// does nothing.
+pub fn set_emit_roles_flag(value: bool) {
+    EMIT_ROLES_FLAG.get_or_init(|| value);
+}
+
+/// Get the state of the [EMIT_ROLES_FLAG] option. If the value cannot be obtained, false is
+/// returned.
+///
+/// Objects can reference roles that do not exist within a fresh cluster so, when this option is
+/// set, stubs of the referenced roles are scripted and added to the start of plans.
+fn is_emit_roles() -> bool {
+    if let Some(flag) = EMIT_ROLES_FLAG.get() {
+        return *flag;
+    }
+    false
+}
+
+/// Static state of the fail on unsupported option within the application. DO NOT ACCESS directly
+/// but rather use the [set_fail_on_unsupported_flag] and [is_fail_on_unsupported] functions.
+static FAIL_ON_UNSUPPORTED_FLAG: OnceLock<bool> = OnceLock::new();
+
+/// Initialize the [FAIL_ON_UNSUPPORTED_FLAG] option if not already set. If already set, then this
+/// function does nothing.
+pub fn set_fail_on_unsupported_flag(value: bool) {
+    FAIL_ON_UNSUPPORTED_FLAG.get_or_init(|| value);
+}
+
+/// Get the state of the [FAIL_ON_UNSUPPORTED_FLAG] option. If the value cannot be obtained, false
+/// is returned.
+///
+/// Source code that cannot be analyzed for dependencies is skipped by default which can lead to an
+/// incorrect object ordering. When this option is set, the skipped analysis is an error instead.
+fn is_fail_on_unsupported() -> bool {
+    if let Some(flag) = FAIL_ON_UNSUPPORTED_FLAG.get() {
+        return *flag;
+    }
+    false
+}
+
+/// Static state of the no sequence values option within the application. DO NOT ACCESS directly
+/// but rather use the [set_no_sequence_values_flag] and [is_no_sequence_values] functions.
+static NO_SEQUENCE_VALUES_FLAG: OnceLock<bool> = OnceLock::new();
+
+/// Initialize the [NO_SEQUENCE_VALUES_FLAG] option if not already set. If already set, then this
+/// function does nothing.
+pub fn set_no_sequence_values_flag(value: bool) {
+    NO_SEQUENCE_VALUES_FLAG.get_or_init(|| value);
+}
+
+/// Get the state of the [NO_SEQUENCE_VALUES_FLAG] option. If the value cannot be obtained, false
+/// is returned.
+///
+/// Sequence start values often differ between environments so the start value of a sequence is
+/// neither scripted nor diffed when this option is set.
+fn is_no_sequence_values() -> bool {
+    if let Some(flag) = NO_SEQUENCE_VALUES_FLAG.get() {
+        return *flag;
+    }
+    false
+}
+
+/// Static state of the warn function search path option within the application. DO NOT ACCESS
+/// directly but rather use the [set_warn_function_search_path_flag] and
+/// [is_warn_function_search_path] functions.
+static WARN_FUNCTION_SEARCH_PATH_FLAG: OnceLock<bool> = OnceLock::new();
+
+/// Initialize the [WARN_FUNCTION_SEARCH_PATH_FLAG] option if not already set. If already set,
+/// then this function does nothing.
+pub fn set_warn_function_search_path_flag(value: bool) {
+    WARN_FUNCTION_SEARCH_PATH_FLAG.get_or_init(|| value);
+}
+
+/// Get the state of the [WARN_FUNCTION_SEARCH_PATH_FLAG] option. If the value cannot be
+/// obtained, false is returned.
+///
+/// Unqualified names within a function body resolve against the `search_path` of the session
+/// calling the function unless the function sets its own `search_path`. When this option is set,
+/// a warning is printed for each scripted `SECURITY DEFINER` function without a `search_path`.
+fn is_warn_function_search_path() -> bool {
+    if let Some(flag) = WARN_FUNCTION_SEARCH_PATH_FLAG.get() {
+        return *flag;
+    }
+    false
+}
+
+/// Static state of the object exclusion glob patterns within the application. DO NOT ACCESS
+/// directly but rather use the [set_excluded_object_patterns] and [is_excluded_object] functions.
+static EXCLUDED_OBJECT_PATTERNS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Initialize the [EXCLUDED_OBJECT_PATTERNS] option if not already set. If already set, then this
+/// function does nothing.
+pub fn set_excluded_object_patterns(patterns: Vec<String>) {
+    EXCLUDED_OBJECT_PATTERNS.get_or_init(|| patterns);
+}
+
+/// Returns true if the display string of the `name` matches any of the
+/// [EXCLUDED_OBJECT_PATTERNS]. If the patterns have not been set, false is returned.
+fn is_excluded_object(name: &SchemaQualifiedName) -> bool {
+    let Some(patterns) = EXCLUDED_OBJECT_PATTERNS.get() else {
+        return false;
+    };
+    matches_any_pattern(patterns, name)
+}
+
+/// Default number of times a scrape query is retried after a transient error
+pub const DEFAULT_SCRAPE_RETRIES: u32 = 3;
+
+/// Static state of the scrape retries option within the application. DO NOT ACCESS directly but
+/// rather use the [set_scrape_retries] and [fetch_with_retry] functions.
+static SCRAPE_RETRIES: OnceLock<u32> = OnceLock::new();
+
+/// Initialize the [SCRAPE_RETRIES] option if not already set. If already set, then this function
+/// does nothing.
+pub fn set_scrape_retries(value: u32) {
+    SCRAPE_RETRIES.get_or_init(|| value);
+}
+
+/// Static state of the scrape timeout option within the application. DO NOT ACCESS directly but
+/// rather use the [set_scrape_timeout] and [scrape_pool] functions.
+static SCRAPE_TIMEOUT: OnceLock<Option<String>> = OnceLock::new();
+
+/// Initialize the [SCRAPE_TIMEOUT] option if not already set. If already set, then this function
+/// does nothing.
+pub fn set_scrape_timeout(value: Option<String>) {
+    SCRAPE_TIMEOUT.get_or_init(|| value);
+}
+
+/// Pool used to scrape the database of the `pool` when a [SCRAPE_TIMEOUT] is set. The new pool
+/// has the same options but each connection sets the timeout as the `statement_timeout`. This
+/// keeps the timeout from applying to other statements executed using the `pool` (e.g. applying
+/// source files to a temp database). Returns [None] when no timeout is set so the `pool` is used
+/// directly.
+///
+/// The caller must close the new pool once the scrape is done so its sessions do not outlive the
+/// scrape (e.g. when the scraped temp database is dropped).
+async fn scrape_pool(pool: &PgPool) -> Result<Option<PgPool>, PgDiffError> {
+    let Some(Some(timeout)) = SCRAPE_TIMEOUT.get() else {
+        return Ok(None);
+    };
+    let connect_options = (*pool.connect_options())
+        .clone()
+        .options([("statement_timeout", timeout)]);
+    let scrape_pool = pool.options().clone().connect_with(connect_options).await?;
+    Ok(Some(scrape_pool))
+}
+
+/// Execute the scrape query created by `fetch`, retrying up to [SCRAPE_RETRIES] times (with an
+/// exponential backoff) when the query fails with a transient error (see [is_transient_error]).
+/// Retries are written to STDOUT if the verbose flag is active.
+///
+/// Only used for read-only queries. Statements applied to a temp database are never retried.
+async fn fetch_with_retry<T, F, R>(fetch: F) -> Result<T, sqlx::Error>
+where
+    F: Fn() -> R,
+    R: Future<Output = Result<T, sqlx::Error>>,
+{
+    let retries = SCRAPE_RETRIES
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_SCRAPE_RETRIES);
+    let mut attempt = 0;
+    loop {
+        match fetch().await {
+            Err(error) if attempt < retries && is_transient_error(&error) => {
+                attempt += 1;
+                if is_verbose() {
+                    println!("Retrying scrape query ({attempt} of {retries}) after error: {error}");
+                }
+                tokio::time::sleep(retry_backoff(attempt)).await;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Time waited before the retry `attempt` (starting at 1). Doubles with each attempt starting at
+/// 200ms.
+fn retry_backoff(attempt: u32) -> Duration {
+    Duration::from_millis(200 * 2u64.pow(attempt.saturating_sub(1).min(5)))
+}
+
+/// True if the `error` is likely to succeed when the query is executed again. This includes
+/// IO errors (e.g. connection reset), pool timeouts and database errors within the
+/// `connection_exception` class, `serialization_failure` (e.g. conflicts with recovery on a
+/// replica), `deadlock_detected` and `too_many_connections`.
+fn is_transient_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(error) => error
+            .code()
+            .is_some_and(|code| is_transient_error_code(&code)),
+        _ => false,
+    }
+}
+
+/// True if the SQLSTATE `code` is a transient error (see [is_transient_error])
+fn is_transient_error_code(code: &str) -> bool {
+    code.starts_with("08") || matches!(code, "40001" | "40P01" | "53300")
+}
+
+/// Static state of the names found within `pg_catalog` of the first database scraped. DO NOT
+/// ACCESS directly but rather use the [load_built_in_names] and [is_built_in_name] functions.
+static CATALOG_BUILT_IN_NAMES: OnceLock<HashSet<String>> = OnceLock::new();
+
+/// Static state of the extra built-in names option within the application. DO NOT ACCESS directly
+/// but rather use the [set_extra_built_in_names] and [is_built_in_name] functions.
+static EXTRA_BUILT_IN_NAMES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Initialize the [EXTRA_BUILT_IN_NAMES] option if not already set. If already set, then this
+/// function does nothing.
+pub fn set_extra_built_in_names(names: Vec<String>) {
+    EXTRA_BUILT_IN_NAMES.get_or_init(|| names);
+}
+
+/// Fetch the names of all types and functions within `pg_catalog` to initialize
+/// [CATALOG_BUILT_IN_NAMES]. If already initialized, then this function does nothing. If the
+/// names cannot be fetched, a warning is printed and [CATALOG_BUILT_IN_NAMES] is left
+/// uninitialized so only the bundled lists are checked (see [check_built_in_name]).
+async fn load_built_in_names(pool: &PgPool) {
+    if CATALOG_BUILT_IN_NAMES.get().is_some() {
+        return;
+    }
+    let built_in_names_query = include_str!("./../../queries/built_in_names.pgsql");
+    let names: Vec<String> =
+        match fetch_with_retry(|| query_scalar(built_in_names_query).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                eprintln!(
+                    "Warning: Could not load built-in names, using the bundled list instead. \
+                     {error}"
+                );
+                return;
+            },
+        };
+    CATALOG_BUILT_IN_NAMES.get_or_init(|| names.into_iter().collect());
+}
+
+/// Returns true if the unqualified `name` refers to a built-in type or function and therefore is
+/// never a dependency. See [check_built_in_name] for the names checked.
+fn is_built_in_name(name: &str) -> bool {
+    check_built_in_name(
+        name,
+        CATALOG_BUILT_IN_NAMES.get(),
+        EXTRA_BUILT_IN_NAMES
+            .get()
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    )
+}
+
+/// Returns true if the `name` is found within the `extra_names`, the `catalog_names` or the static
+/// [BUILT_IN_NAMES] and [BUILT_IN_FUNCTIONS] lists. The static lists are always checked since
+/// they include SQL standard aliases (e.g. `bigint`) that are not found within `pg_catalog` but
+/// are the only names checked when working offline (i.e. `catalog_names` is [None]).
+fn check_built_in_name(
+    name: &str,
+    catalog_names: Option<&HashSet<String>>,
+    extra_names: &[String],
+) -> bool {
+    BUILT_IN_NAMES.contains(&name)
+        || BUILT_IN_FUNCTIONS.contains(&name)
+        || extra_names.iter().any(|extra| extra == name)
+        || catalog_names.is_some_and(|names| names.contains(name))
+}
+
+/// Returns true if the display string of the `name` matches any of the glob `patterns`
+fn matches_any_pattern(patterns: &[String], name: &SchemaQualifiedName) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let name = name.to_string();
+    patterns.iter().any(|pattern| glob_match(pattern, &name))
+}
+
+/// Returns true if the `text` matches the glob `pattern`. Supports `*` to match any sequence of
+/// characters (including none) and `?` to match exactly 1 character. All other characters are
+/// matched literally.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let mut pattern_index = 0;
+    let mut text_index = 0;
+    // Position of the last `*` seen in the pattern and the text position it is currently
+    // matched up to. Used to backtrack when a literal match fails.
+    let mut last_wildcard: Option<(usize, usize)> = None;
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                last_wildcard = Some((pattern_index, text_index));
+                pattern_index += 1;
+            },
+            Some(c) if *c == '?' || *c == text[text_index] => {
+                pattern_index += 1;
+                text_index += 1;
+            },
+            _ => {
+                let Some((wildcard_index, matched_index)) = last_wildcard else {
+                    return false;
+                };
+                pattern_index = wildcard_index + 1;
+                text_index = matched_index + 1;
+                last_wildcard = Some((wildcard_index, matched_index + 1));
+            },
+        }
+    }
+    pattern[pattern_index..].iter().all(|c| *c == '*')
+}
+
+/// Write the `statements` provided within an anonymous `DO` block that swallows errors raised
+/// when the object being created already exists. Used for objects that have no `IF NOT EXISTS`
+/// option.
+fn write_duplicate_object_guard<W: Write>(w: &mut W, statements: &str) -> Result<(), PgDiffError> {
+    writeln!(w, "DO $do$\nBEGIN\n{}", statements.trim_end())?;
+    w.write_str(
+        "EXCEPTION\n    WHEN duplicate_object OR duplicate_table THEN NULL;\nEND;\n$do$;\n",
+    )?;
+    Ok(())
+}
+
+/// Options that change how the statements of objects are rendered when planning a migration. The
+/// context is passed through statement generation so each object decides how an option applies.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderContext {
+    /// True if statements should use guarded forms that succeed when the database is already
+    /// partially migrated (e.g. `IF NOT EXISTS`, `IF EXISTS` and `OR REPLACE`)
+    pub(crate) idempotent: bool,
+    /// True if columns of altered tables are set as `NOT NULL` using a validated check constraint
+    /// (see [Table::alter_table])
+    pub(crate) safe_not_null: bool,
+    /// True if `NULL` values of columns becoming `NOT NULL` are set to the column's default first
+    pub(crate) backfill_nulls: bool,
+}
+
+impl RenderContext {
+    /// `IF EXISTS ` clause (with a trailing space) to place before the name of a dropped object
+    /// when the context is idempotent. Otherwise, an empty string.
+    pub(crate) fn if_exists(&self) -> &'static str {
+        if self.idempotent {
+            "IF EXISTS "
+        } else {
+            ""
+        }
+    }
+}
+
 /// Storage parameters for data objects persisted within a database (i.e. tables and indexes).
 /// Although this is a string, the underlining value is a key value pair separated by an `=`.
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(test, derive(Default))]
 pub struct KeyValuePairs(HashMap<String, String>);
 
+impl KeyValuePairs {
+    /// Pairs sorted by key for deterministic output
+    fn sorted(&self) -> Vec<(&String, &String)> {
+        let mut pairs: Vec<_> = self.0.iter().collect();
+        pairs.sort_by_key(|(key, _)| *key);
+        pairs
+    }
+}
+
+/// Pairs match when they have the same keys and each value matches (see [key_values_match])
+impl PartialEq for KeyValuePairs {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self.0.iter().all(|(key, value)| {
+                other
+                    .0
+                    .get(key)
+                    .is_some_and(|other_value| key_values_match(value, other_value))
+            })
+    }
+}
+
+/// True if the option values are the same. Numeric values are compared as numbers so equivalent
+/// values (e.g. `90` and `90.0`) match.
+fn key_values_match(value: &str, other: &str) -> bool {
+    if value == other {
+        return true;
+    }
+    match (value.parse::<f64>(), other.parse::<f64>()) {
+        (Ok(value), Ok(other)) => value == other,
+        _ => false,
+    }
+}
+
 impl<S> From<&[S]> for KeyValuePairs
 where
     S: AsRef<str>,
@@ -180,9 +627,9 @@ where
         Self(
             value
                 .iter()
-                .map(|kvp| {
-                    let (key, value) = kvp.as_ref().split_once('=').unwrap();
-                    (key.to_string(), value.to_string())
+                .map(|kvp| match kvp.as_ref().split_once('=') {
+                    Some((key, value)) => (key.to_string(), value.to_string()),
+                    None => (kvp.as_ref().to_string(), String::new()),
                 })
                 .collect(),
         )
@@ -261,6 +708,7 @@ macro_rules! impl_type_for_kvp_wrapper {
 /// Storage parameters for data objects persisted within a database (i.e. tables and indexes).
 /// Although this is a string, the underlining value is a key value pair separated by an `=`.
 #[derive(Debug, Deserialize, PartialEq, Clone)]
+#[cfg_attr(test, derive(Default))]
 pub struct StorageParameters(KeyValuePairs);
 
 impl_type_for_kvp_wrapper!(StorageParameters);
@@ -270,16 +718,21 @@ impl Display for StorageParameters {
         if self.0.is_empty() {
             return Ok(());
         }
-        f.write_str("WITH(")?;
-        for (key, value) in self.0.iter() {
-            write!(f, "{key}={value}")?;
-        }
-        f.write_char(')')
+        write_join!(
+            f,
+            "WITH(",
+            self.0.sorted().into_iter(),
+            |w, (key, value)| write!(w, "{key}={value}"),
+            ",",
+            ")"
+        );
+        Ok(())
     }
 }
 
 /// Options that can be specified by a table index
 #[derive(Debug, PartialEq, Deserialize, sqlx::FromRow, Clone)]
+#[cfg_attr(test, derive(Default))]
 pub struct IndexParameters {
     /// Optional list of columns included in an index
     pub(crate) include: Option<Vec<String>>,
@@ -314,17 +767,28 @@ impl Display for IndexParameters {
 /// of the enum of not copy data, all items are references to their respective [SqlObject].
 #[derive(Debug)]
 pub enum SqlObjectEnum<'o> {
+    Role(&'o Role),
     Schema(&'o Schema),
     Extension(&'o Extension),
+    AccessMethod(&'o AccessMethod),
+    EventTrigger(&'o EventTrigger),
+    TextSearchDictionary(&'o TextSearchDictionary),
+    TextSearchConfiguration(&'o TextSearchConfiguration),
     Udt(&'o Udt),
     Table(&'o Table),
     Policy(&'o Policy),
     Constraint(&'o Constraint),
+    Operator(&'o Operator),
+    OperatorClass(&'o OperatorClass),
     Index(&'o Index),
+    Statistics(&'o Statistics),
     Trigger(&'o Trigger),
     Sequence(&'o Sequence),
     Function(&'o Function),
+    Aggregate(&'o Aggregate),
     View(&'o View),
+    Publication(&'o Publication),
+    DefaultPrivileges(&'o DefaultPrivileges),
 }
 
 #[allow(dead_code)]
@@ -332,111 +796,266 @@ impl<'o> SqlObjectEnum<'o> {
     /// Calls the trait method [SqlObject::name] of each variant
     fn name(&self) -> &'o SchemaQualifiedName {
         match self {
+            Self::Role(role) => &role.name,
             Self::Schema(schema) => &schema.name,
             Self::Extension(extension) => &extension.name,
+            Self::AccessMethod(access_method) => &access_method.name,
+            Self::EventTrigger(event_trigger) => &event_trigger.name,
+            Self::TextSearchDictionary(dictionary) => &dictionary.name,
+            Self::TextSearchConfiguration(configuration) => &configuration.name,
             Self::Udt(udt) => &udt.name,
             Self::Table(table) => &table.name,
             Self::Policy(policy) => &policy.schema_qualified_name,
             Self::Constraint(constraint) => &constraint.schema_qualified_name,
+            Self::Operator(operator) => &operator.name,
+            Self::OperatorClass(operator_class) => &operator_class.name,
             Self::Index(index) => &index.schema_qualified_name,
+            Self::Statistics(statistics) => &statistics.schema_qualified_name,
             Self::Trigger(trigger) => &trigger.schema_qualified_name,
             Self::Sequence(sequence) => &sequence.name,
             Self::Function(function) => &function.name,
+            Self::Aggregate(aggregate) => &aggregate.name,
             Self::View(view) => &view.name,
+            Self::Publication(publication) => &publication.name,
+            Self::DefaultPrivileges(default_privileges) => &default_privileges.name,
         }
     }
 
     /// Calls the trait method [SqlObject::object_type_name] of each variant
     fn object_type_name(&self) -> &str {
         match self {
+            Self::Role(role) => role.object_type_name(),
             Self::Schema(schema) => schema.object_type_name(),
             Self::Extension(extension) => extension.object_type_name(),
+            Self::AccessMethod(access_method) => access_method.object_type_name(),
+            Self::EventTrigger(event_trigger) => event_trigger.object_type_name(),
+            Self::TextSearchDictionary(dictionary) => dictionary.object_type_name(),
+            Self::TextSearchConfiguration(configuration) => configuration.object_type_name(),
             Self::Udt(udt) => udt.object_type_name(),
             Self::Table(table) => table.object_type_name(),
             Self::Policy(policy) => policy.object_type_name(),
             Self::Constraint(constraint) => constraint.object_type_name(),
+            Self::Operator(operator) => operator.object_type_name(),
+            Self::OperatorClass(operator_class) => operator_class.object_type_name(),
             Self::Index(index) => index.object_type_name(),
+            Self::Statistics(statistics) => statistics.object_type_name(),
             Self::Trigger(trigger) => trigger.object_type_name(),
             Self::Sequence(sequence) => sequence.object_type_name(),
             Self::Function(function) => function.object_type_name(),
+            Self::Aggregate(aggregate) => aggregate.object_type_name(),
             Self::View(view) => view.object_type_name(),
+            Self::Publication(publication) => publication.object_type_name(),
+            Self::DefaultPrivileges(default_privileges) => default_privileges.object_type_name(),
         }
     }
 
     /// Calls the trait method [SqlObject::dependencies] of each variant
     fn dependencies(&self) -> &[SchemaQualifiedName] {
         match self {
+            Self::Role(role) => role.dependencies(),
             Self::Schema(schema) => schema.dependencies(),
             Self::Extension(extension) => extension.dependencies(),
+            Self::AccessMethod(access_method) => access_method.dependencies(),
+            Self::EventTrigger(event_trigger) => event_trigger.dependencies(),
+            Self::TextSearchDictionary(dictionary) => dictionary.dependencies(),
+            Self::TextSearchConfiguration(configuration) => configuration.dependencies(),
             Self::Udt(udt) => udt.dependencies(),
             Self::Table(table) => table.dependencies(),
             Self::Policy(policy) => policy.dependencies(),
             Self::Constraint(constraint) => constraint.dependencies(),
+            Self::Operator(operator) => operator.dependencies(),
+            Self::OperatorClass(operator_class) => operator_class.dependencies(),
             Self::Index(index) => index.dependencies(),
+            Self::Statistics(statistics) => statistics.dependencies(),
             Self::Trigger(trigger) => trigger.dependencies(),
             Self::Sequence(sequence) => sequence.dependencies(),
             Self::Function(function) => function.dependencies(),
+            Self::Aggregate(aggregate) => aggregate.dependencies(),
             Self::View(view) => view.dependencies(),
+            Self::Publication(publication) => publication.dependencies(),
+            Self::DefaultPrivileges(default_privileges) => default_privileges.dependencies(),
         }
     }
 
     /// Calls the trait method [SqlObject::create_statements] of each variant
     fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
         match self {
+            Self::Role(role) => role.create_statements(w),
             Self::Schema(schema) => schema.create_statements(w),
             Self::Extension(extension) => extension.create_statements(w),
+            Self::AccessMethod(access_method) => access_method.create_statements(w),
+            Self::EventTrigger(event_trigger) => event_trigger.create_statements(w),
+            Self::TextSearchDictionary(dictionary) => dictionary.create_statements(w),
+            Self::TextSearchConfiguration(configuration) => configuration.create_statements(w),
             Self::Udt(udt) => udt.create_statements(w),
             Self::Table(table) => table.create_statements(w),
             Self::Policy(policy) => policy.create_statements(w),
             Self::Constraint(constraint) => constraint.create_statements(w),
+            Self::Operator(operator) => operator.create_statements(w),
+            Self::OperatorClass(operator_class) => operator_class.create_statements(w),
             Self::Index(index) => index.create_statements(w),
+            Self::Statistics(statistics) => statistics.create_statements(w),
             Self::Trigger(trigger) => trigger.create_statements(w),
             Self::Sequence(sequence) => sequence.create_statements(w),
             Self::Function(function) => function.create_statements(w),
+            Self::Aggregate(aggregate) => aggregate.create_statements(w),
             Self::View(view) => view.create_statements(w),
+            Self::Publication(publication) => publication.create_statements(w),
+            Self::DefaultPrivileges(default_privileges) => default_privileges.create_statements(w),
+        }
+    }
+
+    /// Calls the trait method [SqlObject::create_statements_with] of each variant
+    fn create_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        match self {
+            Self::Role(role) => role.create_statements_with(context, w),
+            Self::Schema(schema) => schema.create_statements_with(context, w),
+            Self::Extension(extension) => extension.create_statements_with(context, w),
+            Self::AccessMethod(access_method) => access_method.create_statements_with(context, w),
+            Self::EventTrigger(event_trigger) => event_trigger.create_statements_with(context, w),
+            Self::TextSearchDictionary(dictionary) => dictionary.create_statements_with(context, w),
+            Self::TextSearchConfiguration(configuration) => {
+                configuration.create_statements_with(context, w)
+            },
+            Self::Udt(udt) => udt.create_statements_with(context, w),
+            Self::Table(table) => table.create_statements_with(context, w),
+            Self::Policy(policy) => policy.create_statements_with(context, w),
+            Self::Constraint(constraint) => constraint.create_statements_with(context, w),
+            Self::Operator(operator) => operator.create_statements_with(context, w),
+            Self::OperatorClass(operator_class) => {
+                operator_class.create_statements_with(context, w)
+            },
+            Self::Index(index) => index.create_statements_with(context, w),
+            Self::Statistics(statistics) => statistics.create_statements_with(context, w),
+            Self::Trigger(trigger) => trigger.create_statements_with(context, w),
+            Self::Sequence(sequence) => sequence.create_statements_with(context, w),
+            Self::Function(function) => function.create_statements_with(context, w),
+            Self::Aggregate(aggregate) => aggregate.create_statements_with(context, w),
+            Self::View(view) => view.create_statements_with(context, w),
+            Self::Publication(publication) => publication.create_statements_with(context, w),
+            Self::DefaultPrivileges(default_privileges) => {
+                default_privileges.create_statements_with(context, w)
+            },
         }
     }
 
-    /// Calls the trait method [SqlObject::alter_statements] of each variant
-    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+    /// Calls the trait method [SqlObject::alter_statements_with] of each variant
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
         match (self, new) {
-            (Self::Schema(old), Self::Schema(new)) if old != new => old.alter_statements(new, w),
+            (Self::Role(old), Self::Role(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Schema(old), Self::Schema(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
             (Self::Extension(old), Self::Extension(new)) if old != new => {
-                old.alter_statements(new, w)
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::AccessMethod(old), Self::AccessMethod(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::EventTrigger(old), Self::EventTrigger(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::TextSearchDictionary(old), Self::TextSearchDictionary(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::TextSearchConfiguration(old), Self::TextSearchConfiguration(new))
+                if old != new =>
+            {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Udt(old), Self::Udt(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Table(old), Self::Table(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Policy(old), Self::Policy(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
             },
-            (Self::Udt(old), Self::Udt(new)) if old != new => old.alter_statements(new, w),
-            (Self::Table(old), Self::Table(new)) if old != new => old.alter_statements(new, w),
-            (Self::Policy(old), Self::Policy(new)) if old != new => old.alter_statements(new, w),
             (Self::Constraint(old), Self::Constraint(new)) if old != new => {
-                old.alter_statements(new, w)
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Operator(old), Self::Operator(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::OperatorClass(old), Self::OperatorClass(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Index(old), Self::Index(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Statistics(old), Self::Statistics(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Trigger(old), Self::Trigger(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
             },
-            (Self::Index(old), Self::Index(new)) if old != new => old.alter_statements(new, w),
-            (Self::Trigger(old), Self::Trigger(new)) if old != new => old.alter_statements(new, w),
             (Self::Sequence(old), Self::Sequence(new)) if old != new => {
-                old.alter_statements(new, w)
+                old.alter_statements_with(new, context, w)
             },
             (Self::Function(old), Self::Function(new)) if old != new => {
-                old.alter_statements(new, w)
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Aggregate(old), Self::Aggregate(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::View(old), Self::View(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::Publication(old), Self::Publication(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
+            },
+            (Self::DefaultPrivileges(old), Self::DefaultPrivileges(new)) if old != new => {
+                old.alter_statements_with(new, context, w)
             },
-            (Self::View(old), Self::View(new)) if old != new => old.alter_statements(new, w),
             _ => Ok(()),
         }
     }
 
-    /// Calls the trait method [SqlObject::drop_statements] of each variant
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+    /// Calls the trait method [SqlObject::drop_statements_with] of each variant
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
         match self {
-            Self::Schema(schema) => schema.drop_statements(w),
-            Self::Extension(extension) => extension.drop_statements(w),
-            Self::Udt(udt) => udt.drop_statements(w),
-            Self::Table(table) => table.drop_statements(w),
-            Self::Policy(policy) => policy.drop_statements(w),
-            Self::Constraint(constraint) => constraint.drop_statements(w),
-            Self::Index(index) => index.drop_statements(w),
-            Self::Trigger(trigger) => trigger.drop_statements(w),
-            Self::Sequence(sequence) => sequence.drop_statements(w),
-            Self::Function(function) => function.drop_statements(w),
-            Self::View(view) => view.drop_statements(w),
+            Self::Role(role) => role.drop_statements_with(context, w),
+            Self::Schema(schema) => schema.drop_statements_with(context, w),
+            Self::Extension(extension) => extension.drop_statements_with(context, w),
+            Self::AccessMethod(access_method) => access_method.drop_statements_with(context, w),
+            Self::EventTrigger(event_trigger) => event_trigger.drop_statements_with(context, w),
+            Self::TextSearchDictionary(dictionary) => dictionary.drop_statements_with(context, w),
+            Self::TextSearchConfiguration(configuration) => {
+                configuration.drop_statements_with(context, w)
+            },
+            Self::Udt(udt) => udt.drop_statements_with(context, w),
+            Self::Table(table) => table.drop_statements_with(context, w),
+            Self::Policy(policy) => policy.drop_statements_with(context, w),
+            Self::Constraint(constraint) => constraint.drop_statements_with(context, w),
+            Self::Operator(operator) => operator.drop_statements_with(context, w),
+            Self::OperatorClass(operator_class) => operator_class.drop_statements_with(context, w),
+            Self::Index(index) => index.drop_statements_with(context, w),
+            Self::Statistics(statistics) => statistics.drop_statements_with(context, w),
+            Self::Trigger(trigger) => trigger.drop_statements_with(context, w),
+            Self::Sequence(sequence) => sequence.drop_statements_with(context, w),
+            Self::Function(function) => function.drop_statements_with(context, w),
+            Self::Aggregate(aggregate) => aggregate.drop_statements_with(context, w),
+            Self::View(view) => view.drop_statements_with(context, w),
+            Self::Publication(publication) => publication.drop_statements_with(context, w),
+            Self::DefaultPrivileges(default_privileges) => {
+                default_privileges.drop_statements_with(context, w)
+            },
         }
     }
 
@@ -444,8 +1063,26 @@ impl<'o> SqlObjectEnum<'o> {
     fn dependencies_met(&self, completed_objects: &[SchemaQualifiedName]) -> bool {
         self.dependencies()
             .iter()
+            .filter(|d| !is_excluded_object(d))
             .all(|d| completed_objects.contains(d))
     }
+
+    /// Returns true if the name of the object or the table/view that owns the object matches any
+    /// of the `ignored_objects` glob patterns. Ignored objects are managed externally so existing
+    /// objects are never altered or dropped.
+    fn is_ignored(&self, ignored_objects: &[String]) -> bool {
+        let owner_name = match self {
+            Self::Policy(policy) => Some(&policy.owner_table_name),
+            Self::Constraint(constraint) => Some(&constraint.owner_table_name),
+            Self::Index(index) => Some(&index.owner_table_name),
+            Self::Statistics(statistics) => Some(&statistics.owner_table_name),
+            Self::Trigger(trigger) => Some(&trigger.owner_object_name),
+            Self::Sequence(sequence) => sequence.owner.as_ref().map(|o| &o.table_name),
+            _ => None,
+        };
+        matches_any_pattern(ignored_objects, self.name())
+            || owner_name.is_some_and(|name| matches_any_pattern(ignored_objects, name))
+    }
 }
 
 trait SqlObject: PartialEq {
@@ -460,6 +1097,18 @@ trait SqlObject: PartialEq {
     /// ## Errors
     /// If a drop statement cannot be derived or a formatting error occurs
     fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError>;
+    /// Create the `CREATE` statement for this object so that it can be safely run against a
+    /// database where the object already exists. By default, this wraps the output of
+    /// [SqlObject::create_statements] within a `DO` block that ignores duplicate object errors.
+    /// Objects that support `IF NOT EXISTS` or `OR REPLACE` should override this method.
+    ///
+    /// ## Errors
+    /// If a create statement cannot be derived or a formatting error occurs
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        let mut statements = String::new();
+        self.create_statements(&mut statements)?;
+        write_duplicate_object_guard(w, &statements)
+    }
     /// Create the `ALTER` statement(s) required for this SQL object to be migrated to the new state
     /// provided.
     ///
@@ -467,17 +1116,62 @@ trait SqlObject: PartialEq {
     /// If the migration is not possible either due to an unsupported, impossible or invalid
     /// migration. Can also fail when a formatting error occurs.
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError>;
+    /// Create the `ALTER` statement(s) required for this SQL object to be migrated to the new state
+    /// provided as requested by the `context`. By default, the context is ignored. Objects that
+    /// are dropped and created again when altered should override this method so the drop and
+    /// create follow the context.
+    ///
+    /// ## Errors
+    /// See [SqlObject::alter_statements]
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        _context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        self.alter_statements(new, w)
+    }
     /// Create the `DROP` statement for this object.
     ///
     /// ## Errors
     /// If a drop statement cannot be derived or a formatting error occurs
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError>;
+    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.drop_statements_with(&RenderContext::default(), w)
+    }
+    /// Create the `CREATE` statement for this object as requested by the `context`. Uses
+    /// [SqlObject::create_statements_idempotent] when the context is idempotent.
+    ///
+    /// ## Errors
+    /// If a create statement cannot be derived or a formatting error occurs
+    fn create_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if context.idempotent {
+            self.create_statements_idempotent(w)
+        } else {
+            self.create_statements(w)
+        }
+    }
+    /// Create the `DROP` statement for this object as requested by the `context`. Each drop
+    /// includes `IF EXISTS` when the context is idempotent (see [RenderContext::if_exists]) so the
+    /// statement can be safely run against a database where the object has already been dropped.
+    ///
+    /// ## Errors
+    /// If a drop statement cannot be derived or a formatting error occurs
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError>;
     /// Returns true if all dependencies of this object have been resolved based upon the list of
-    /// `completed_objects` provided.
+    /// `completed_objects` provided. Dependencies on excluded objects are always considered
+    /// resolved.
     fn dependencies_met(&self, completed_objects: &[&SchemaQualifiedName]) -> bool {
         self.dependencies()
             .iter()
-            .filter(|d| !d.is_implicit_schema())
+            .filter(|d| !d.is_implicit_schema() && !is_excluded_object(d))
             .all(|d| completed_objects.contains(&d))
     }
     /// Write the beginning of an `ALTER` statement based upon the object's
@@ -497,8 +1191,9 @@ trait SqlObject: PartialEq {
 /// are:
 /// - schema objects which only have a `schema_name` and `local_name` is empty
 /// - extension objects which only have a  `local_name` since extensions are not always linked to a
-///     schema
+///   schema
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Deserialize)]
+#[cfg_attr(test, derive(Default))]
 pub struct SchemaQualifiedName {
     /// Schema name that owned the object. Can be empty if extension object
     pub(crate) schema_name: String,
@@ -576,8 +1271,90 @@ impl Display for SchemaQualifiedName {
     }
 }
 
+/// Column of a table or view referenced by another object. Column names are not quoted to match
+/// the names of table columns.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ColumnDependency {
+    /// Table or view that owns the column
+    pub(crate) relation: SchemaQualifiedName,
+    /// Name of the referenced column
+    pub(crate) column: String,
+}
+
+/// Privilege granted to a role as found by exploding an ACL using `aclexplode`. Privileges of
+/// functions, table columns and default privileges are all parsed into this type.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct AclGrant {
+    /// Name of the role or `PUBLIC`
+    pub(crate) grantee: String,
+    /// Privilege granted (e.g. `SELECT` or `EXECUTE`)
+    pub(crate) privilege_type: String,
+    /// True if the grantee can grant the privilege to other roles
+    pub(crate) is_grantable: bool,
+}
+
+impl AclGrant {
+    /// Write the `GRANT` statement of this privilege on the `target` (e.g. `FUNCTION name(args)`).
+    /// The `prefix` is written before the statement as is (e.g. `ALTER DEFAULT PRIVILEGES `).
+    fn write_grant<W: Write>(
+        &self,
+        w: &mut W,
+        prefix: &str,
+        target: &str,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "{prefix}GRANT {} ON {target} TO {}{};",
+            self.privilege_type,
+            self.grantee,
+            if self.is_grantable {
+                " WITH GRANT OPTION"
+            } else {
+                ""
+            }
+        )?;
+        Ok(())
+    }
+
+    /// Write the `REVOKE` statement of this privilege on the `target`. The `prefix` is written
+    /// before the statement as is.
+    fn write_revoke<W: Write>(
+        &self,
+        w: &mut W,
+        prefix: &str,
+        target: &str,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "{prefix}REVOKE {} ON {target} FROM {};",
+            self.privilege_type, self.grantee
+        )?;
+        Ok(())
+    }
+}
+
+/// Write the `REVOKE` and `GRANT` statements that change the `old` privileges into the `new`
+/// privileges. Privileges whose grant option changes are revoked and then granted again. The
+/// `prefix` and `target` are passed to [AclGrant::write_grant] and [AclGrant::write_revoke].
+fn write_acl_changes<W: Write>(
+    w: &mut W,
+    old: &[AclGrant],
+    new: &[AclGrant],
+    prefix: &str,
+    target: &str,
+) -> Result<(), PgDiffError> {
+    for grant in old.iter().filter(|g| !new.contains(g)) {
+        grant.write_revoke(w, prefix, target)?;
+    }
+    for grant in new.iter().filter(|g| !old.contains(g)) {
+        grant.write_grant(w, prefix, target)?;
+    }
+    Ok(())
+}
+
 /// Collation name wrapper type
 #[derive(Debug, PartialEq, Deserialize, sqlx::Type)]
+#[cfg_attr(test, derive(Default))]
 #[sqlx(transparent)]
 pub struct Collation(pub(crate) String);
 
@@ -596,6 +1373,7 @@ impl Collation {
 
 /// Wrapper type for a tablespace name
 #[derive(Debug, Deserialize, PartialEq, sqlx::Type, Clone)]
+#[cfg_attr(test, derive(Default))]
 #[sqlx(transparent)]
 pub struct TableSpace(pub(crate) String);
 
@@ -620,15 +1398,15 @@ where
     match (old, new) {
         (Some(old_tablespace), Some(new_tablespace)) if old_tablespace != new_tablespace => {
             object.write_alter_prefix(w)?;
-            write!(w, " SET TABLESPACE {new_tablespace};")?;
+            writeln!(w, " SET TABLESPACE {new_tablespace};")?;
         },
         (Some(_), None) => {
             object.write_alter_prefix(w)?;
-            write!(w, " SET TABLESPACE pg_default;")?;
+            writeln!(w, " SET TABLESPACE pg_default;")?;
         },
         (None, Some(new_tablespace)) => {
             object.write_alter_prefix(w)?;
-            write!(w, " SET TABLESPACE {new_tablespace};")?;
+            writeln!(w, " SET TABLESPACE {new_tablespace};")?;
         },
         _ => {},
     }
@@ -636,13 +1414,12 @@ where
 }
 
 /// Compare the old and new versions of an object's option list and write the required `SET`/`RESET`
-/// statements for the object.
+/// statements for the object. Options are written within brackets (e.g. `SET (key=value)`).
 fn compare_key_value_pairs<A, K, W>(
     w: &mut W,
     object: &A,
     old: &Option<K>,
     new: &Option<K>,
-    within_brackets: bool,
 ) -> Result<(), PgDiffError>
 where
     A: SqlObject,
@@ -659,11 +1436,10 @@ where
                 object,
                 new_options.iter().filter(|(key, value)| {
                     if let Some(old) = old_options.get(*key) {
-                        return old != *value;
+                        return !key_values_match(old, value);
                     }
                     true
                 }),
-                within_brackets,
             )?;
             reset_key_value_pairs(
                 w,
@@ -671,14 +1447,13 @@ where
                 old_options
                     .iter()
                     .filter(|(key, _)| !new_options.contains_key(*key)),
-                within_brackets,
             )?;
         },
         (_, Some(new_options)) if !new_options.is_empty() => {
-            set_key_value_pairs(w, object, new_options.iter(), within_brackets)?;
+            set_key_value_pairs(w, object, new_options.iter())?;
         },
         (Some(old_options), _) if !old_options.is_empty() => {
-            reset_key_value_pairs(w, object, old_options.iter(), within_brackets)?;
+            reset_key_value_pairs(w, object, old_options.iter())?;
         },
         _ => {},
     };
@@ -689,7 +1464,6 @@ fn set_key_value_pairs<'a, W, A, I>(
     w: &'a mut W,
     object: &'a A,
     set_options: I,
-    within_brackets: bool,
 ) -> Result<(), PgDiffError>
 where
     W: Write,
@@ -701,27 +1475,16 @@ where
         return Ok(());
     }
 
-    set_options.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    set_options.sort_by_key(|(key, _)| *key);
     object.write_alter_prefix(w)?;
-    if within_brackets {
-        write_join!(
-            w,
-            " SET (",
-            set_options,
-            |write, (key, value)| write!(write, "{key}={value}"),
-            ",",
-            ");\n"
-        );
-    } else {
-        write_join!(
-            w,
-            "\nSET ",
-            set_options,
-            |write, (key, value)| write!(write, "{key}={value}"),
-            "\nSET",
-            ";\n"
-        );
-    }
+    write_join!(
+        w,
+        " SET (",
+        set_options,
+        |write, (key, value)| write!(write, "{key}={value}"),
+        ",",
+        ");\n"
+    );
     Ok(())
 }
 
@@ -729,7 +1492,6 @@ fn reset_key_value_pairs<'a, W, A, I>(
     w: &'a mut W,
     object: &'a A,
     reset_options: I,
-    within_brackets: bool,
 ) -> Result<(), PgDiffError>
 where
     W: Write,
@@ -741,62 +1503,513 @@ where
         return Ok(());
     }
 
-    reset_options.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    reset_options.sort_by_key(|(key, _)| *key);
     object.write_alter_prefix(w)?;
-    if within_brackets {
-        write_join!(
-            w,
-            " RESET (",
-            reset_options,
-            |write, (key, _)| write!(write, "{key}"),
-            ",",
-            ");\n"
-        );
-    } else {
-        write_join!(
-            w,
-            "\nRESET ",
-            reset_options,
-            |write, (key, _)| write!(write, "{key}"),
-            "\nRESET ",
-            ";\n"
-        );
-    }
+    write_join!(
+        w,
+        " RESET (",
+        reset_options,
+        |write, (key, _)| write!(write, "{key}"),
+        ",",
+        ");\n"
+    );
     Ok(())
 }
 
-/// Find the index of the first element found within the `slice` using the `predicate` as an element
-/// selector. Returns [None] if no element is found that matches the `predicate`.
-fn find_index<T, F>(slice: &[T], predicate: F) -> Option<usize>
-where
-    F: Fn(&T) -> bool,
-{
-    slice
-        .iter()
-        .enumerate()
-        .filter_map(|(i, item)| if predicate(item) { Some(i) } else { None })
-        .next()
-}
-
 const PUBLIC_SCHEMA_NAME: &str = "public";
 const PG_CATALOG_SCHEMA_NAME: &str = "pg_catalog";
 
+/// Find all objects matching the `schema_qualified_name` using the `query` provided. If the name is
+/// not schema qualified, the schemas of the `search_path` (along with `pg_catalog`) are searched.
+/// When no `search_path` is available, only `public` and `pg_catalog` are searched.
 async fn check_names_in_database(
     pool: &PgPool,
     schema_qualified_name: &SchemaQualifiedName,
     query: &str,
+    search_path: Option<&[String]>,
 ) -> Result<Vec<SchemaQualifiedName>, sqlx::Error> {
-    let schemas = if !schema_qualified_name.schema_name.is_empty() {
-        [&schema_qualified_name.schema_name, ""]
+    let schemas: Vec<&str> = match search_path {
+        _ if !schema_qualified_name.schema_name.is_empty() => {
+            vec![&schema_qualified_name.schema_name, ""]
+        },
+        Some(search_path) => search_path
+            .iter()
+            .map(String::as_str)
+            .chain([PG_CATALOG_SCHEMA_NAME])
+            .collect(),
+        None => vec![PUBLIC_SCHEMA_NAME, PG_CATALOG_SCHEMA_NAME],
+    };
+    fetch_with_retry(|| {
+        query_scalar(query)
+            .bind(&schemas)
+            .bind(&schema_qualified_name.local_name)
+            .fetch_all(pool)
+    })
+    .await
+}
+
+/// Split a `search_path` setting value into the names of the schemas searched. Quotes are removed
+/// from quoted schema names while unquoted names are folded to lower case. The `$user` placeholder
+/// is omitted since the role resolving the names is not known.
+fn parse_search_path(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter_map(|schema| {
+            let schema = match schema.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                Some(quoted) => quoted.replace("\"\"", "\""),
+                None => schema.to_lowercase(),
+            };
+            if schema.is_empty() || schema == "$user" {
+                return None;
+            }
+            Some(schema)
+        })
+        .collect()
+}
+
+/// Resolve the unqualified `objects` found for a single name to the object that Postgresql would
+/// choose using the `search_path`. `pg_catalog` is searched first unless it is explicitly included
+/// within the `search_path`. If no object is found within the `search_path`, the `objects` are
+/// returned unchanged.
+fn resolve_with_search_path(
+    objects: Vec<SchemaQualifiedName>,
+    search_path: &[String],
+) -> Vec<SchemaQualifiedName> {
+    let implicit_catalog = if search_path.iter().any(|s| s == PG_CATALOG_SCHEMA_NAME) {
+        None
     } else {
-        [PUBLIC_SCHEMA_NAME, PG_CATALOG_SCHEMA_NAME]
+        Some(PG_CATALOG_SCHEMA_NAME)
     };
-    query_scalar(query)
-        .bind(schemas)
-        .bind(&schema_qualified_name.local_name)
-        .fetch_all(pool)
-        .await
+    let resolved = implicit_catalog
+        .into_iter()
+        .chain(search_path.iter().map(String::as_str))
+        .find_map(|schema| objects.iter().find(|o| o.schema_name == schema));
+    match resolved {
+        Some(object) => vec![object.clone()],
+        None => objects,
+    }
+}
+
+/// True if the SQL expressions `left` and `right` are equivalent. The catalog returns expressions
+/// in a normalized form (e.g. `'active'::text` or `(a > 0)`) that rarely matches the text written
+/// within source files so each expression is parsed and deparsed to remove formatting differences
+/// and casts of literal values are removed. Falls back to string equality if either expression
+/// cannot be parsed.
+fn expressions_match(left: &str, right: &str) -> bool {
+    if left == right {
+        return true;
+    }
+    match (normalize_expression(left), normalize_expression(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// True if both optional expressions are [None] or both are equivalent expressions (see
+/// [expressions_match])
+fn optional_expressions_match(left: Option<&str>, right: Option<&str>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => expressions_match(left, right),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Normalized text of the `expression` or [None] if the expression cannot be parsed
+fn normalize_expression(expression: &str) -> Option<String> {
+    let statement = pg_query::parse(&format!("SELECT {expression}")).ok()?;
+    let deparsed = statement.deparse().ok()?;
+    Some(strip_literal_casts(&deparsed))
+}
+
+/// True if the SQL source code `left` and `right` are equivalent ignoring formatting (i.e.
+/// whitespace, comments and the casing of keywords). Used for view queries and function bodies
+/// where a cosmetic change should never produce a migration. Falls back to string equality if
+/// either source cannot be scanned.
+fn sources_match(left: &str, right: &str) -> bool {
+    if left == right {
+        return true;
+    }
+    match (normalize_source(left), normalize_source(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Quote the `identifier` the same way as the Postgresql `quote_ident` function so identifiers
+/// parsed from source files match the scraped names. Identifiers are only quoted when they
+/// contain characters other than lowercase letters, digits and underscores (or start with a
+/// digit) or when they are keywords that are not unreserved.
+fn quote_ident(identifier: &str) -> String {
+    let is_safe = identifier.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && identifier
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && pg_query::scan(identifier).is_ok_and(|result| {
+            result.tokens.iter().all(|token| {
+                token.keyword_kind == KeywordKind::NoKeyword as i32
+                    || token.keyword_kind == KeywordKind::UnreservedKeyword as i32
+            })
+        });
+    if is_safe {
+        return identifier.to_string();
+    }
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Canonical text of the SQL `source`. Sources that parse as SQL statements are deparsed. Other
+/// sources (e.g. pl/pgsql blocks or `BEGIN ATOMIC` bodies) are scanned and tokens are joined with
+/// a single space after removing comments and lowercasing keywords and unquoted identifiers.
+///
+/// Returns [None] if the source cannot be scanned.
+fn normalize_source(source: &str) -> Option<String> {
+    if let Ok(deparsed) = pg_query::parse(source).and_then(|result| result.deparse()) {
+        return Some(deparsed);
+    }
+    let scan_result = pg_query::scan(source).ok()?;
+    let mut result = String::with_capacity(source.len());
+    for token in &scan_result.tokens {
+        if token.token == Token::SqlComment as i32 || token.token == Token::CComment as i32 {
+            continue;
+        }
+        let text = source.get(token.start as usize..token.end as usize)?;
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        if token.keyword_kind != KeywordKind::NoKeyword as i32 || token.token == Token::Ident as i32
+        {
+            result.push_str(&text.to_lowercase());
+        } else {
+            result.push_str(text);
+        }
+    }
+    Some(result)
+}
+
+/// Remove all casts applied directly to string literals within the `sql` text (e.g.
+/// `'active'::text` becomes `'active'`). Quoted identifiers and literals are skipped so their
+/// contents are never altered.
+fn strip_literal_casts(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find(['\'', '"']) {
+        let quote = rest.as_bytes()[start];
+        let is_escape_string = quote == b'\'' && rest[..start].ends_with(['E', 'e']);
+        let end = start + quoted_length(&rest[start..], quote, is_escape_string);
+        result.push_str(&rest[..end]);
+        rest = &rest[end..];
+        if quote == b'\'' {
+            while let Some(type_name) = rest.strip_prefix("::") {
+                rest = skip_type_name(type_name);
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Length in bytes of the quoted literal or identifier at the start of the `text` (including the
+/// surrounding `quote` characters). Doubled quotes, and backslash escapes within escape strings,
+/// are treated as part of the quoted text.
+fn quoted_length(text: &str, quote: u8, is_escape_string: bool) -> usize {
+    let bytes = text.as_bytes();
+    let mut index = 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' if is_escape_string => index += 1,
+            b if b == quote && bytes.get(index + 1) == Some(&quote) => index += 1,
+            b if b == quote => return index + 1,
+            _ => {},
+        }
+        index += 1;
+    }
+    bytes.len()
+}
+
+/// Skip the type name at the start of the `text`, returning the remaining text. This includes
+/// multi-word type names, type modifiers and array brackets.
+fn skip_type_name(text: &str) -> &str {
+    let mut rest = if text.starts_with('"') {
+        &text[quoted_length(text, b'"', false)..]
+    } else {
+        text.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+    for suffix in [
+        " with time zone",
+        " without time zone",
+        " varying",
+        " precision",
+    ] {
+        if let Some(remaining) = rest.strip_prefix(suffix) {
+            rest = remaining;
+            break;
+        }
+    }
+    if rest.starts_with('(') {
+        rest = rest.find(')').map_or("", |end| &rest[end + 1..]);
+    }
+    rest.trim_start_matches("[]")
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use super::{
+        check_built_in_name, expressions_match, fetch_with_retry, glob_match, is_transient_error,
+        is_transient_error_code, key_values_match, matches_any_pattern, parse_search_path,
+        quote_ident, resolve_with_search_path, retry_backoff, sources_match, strip_literal_casts,
+        SchemaQualifiedName,
+    };
+
+    #[rstest::rstest]
+    #[case("app_reader", "app_reader")]
+    #[case("_app2", "_app2")]
+    #[case("name", "name")]
+    #[case("AppReader", "\"AppReader\"")]
+    #[case("app-reader", "\"app-reader\"")]
+    #[case("2app", "\"2app\"")]
+    #[case("user", "\"user\"")]
+    #[case("app\"reader", "\"app\"\"reader\"")]
+    fn quote_ident_should_only_quote_when_required(
+        #[case] identifier: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, quote_ident(identifier));
+    }
+
+    #[rstest::rstest]
+    #[case("90", "90", true)]
+    #[case("90", "90.0", true)]
+    #[case("0.2", "0.20", true)]
+    #[case("90", "70", false)]
+    #[case("on", "true", false)]
+    fn key_values_match_should_compare_numeric_values_as_numbers(
+        #[case] value: &str,
+        #[case] other: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, key_values_match(value, other));
+    }
+
+    #[rstest::rstest]
+    #[case(sqlx::Error::Io(std::io::ErrorKind::ConnectionReset.into()), true)]
+    #[case(sqlx::Error::PoolTimedOut, true)]
+    #[case(sqlx::Error::RowNotFound, false)]
+    #[case(sqlx::Error::PoolClosed, false)]
+    fn is_transient_error_should_find_retryable_errors(
+        #[case] error: sqlx::Error,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, is_transient_error(&error));
+    }
+
+    #[rstest::rstest]
+    #[case("08006", true)]
+    #[case("40001", true)]
+    #[case("40P01", true)]
+    #[case("53300", true)]
+    #[case("57014", false)]
+    #[case("42P01", false)]
+    fn is_transient_error_code_should_match_transient_classes(
+        #[case] code: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, is_transient_error_code(code));
+    }
+
+    #[rstest::rstest]
+    #[case(1, Duration::from_millis(200))]
+    #[case(2, Duration::from_millis(400))]
+    #[case(3, Duration::from_millis(800))]
+    #[case(20, Duration::from_millis(6400))]
+    fn retry_backoff_should_double_with_each_attempt(
+        #[case] attempt: u32,
+        #[case] expected: Duration,
+    ) {
+        assert_eq!(expected, retry_backoff(attempt));
+    }
+
+    #[tokio::test]
+    async fn fetch_with_retry_should_retry_transient_errors() {
+        let attempts = AtomicU32::new(0);
+
+        let result = fetch_with_retry(|| async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(sqlx::Error::PoolTimedOut);
+            }
+            Ok(1)
+        })
+        .await;
+
+        assert_eq!(1, result.unwrap());
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn fetch_with_retry_should_not_retry_other_errors() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<i32, sqlx::Error> = fetch_with_retry(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(1, attempts.load(Ordering::SeqCst));
+    }
+
+    #[rstest::rstest]
+    #[case("timestamptz", Some(&["timestamptz", "now"][..]), &[][..], true)]
+    #[case("now", Some(&["timestamptz", "now"][..]), &[][..], true)]
+    #[case("timestamptz", None, &[][..], false)]
+    #[case("timestamptz", None, &["timestamptz"][..], true)]
+    #[case("bigint", Some(&["int8"][..]), &[][..], true)]
+    #[case("format", None, &[][..], true)]
+    #[case("my_type", Some(&["timestamptz"][..]), &[][..], false)]
+    fn check_built_in_name_should_find_catalog_static_and_extra_names(
+        #[case] name: &str,
+        #[case] catalog_names: Option<&[&str]>,
+        #[case] extra_names: &[&str],
+        #[case] expected: bool,
+    ) {
+        let catalog_names: Option<HashSet<String>> =
+            catalog_names.map(|names| names.iter().map(|n| n.to_string()).collect());
+        let extra_names: Vec<String> = extra_names.iter().map(|n| n.to_string()).collect();
+
+        let result = check_built_in_name(name, catalog_names.as_ref(), &extra_names);
+
+        assert_eq!(expected, result);
+    }
+
+    #[rstest::rstest]
+    #[case("*_tmp", SchemaQualifiedName::new("public", "orders_tmp"), true)]
+    #[case("*_tmp", SchemaQualifiedName::new("audit", "orders_tmp"), true)]
+    #[case("*_tmp", SchemaQualifiedName::new("public", "orders_tmp_log"), false)]
+    #[case("*_tmp", SchemaQualifiedName::new("public", "orders"), false)]
+    #[case("audit.*", SchemaQualifiedName::new("audit", "log"), true)]
+    #[case("audit.*", SchemaQualifiedName::new("audit", "log.log_pkey"), true)]
+    #[case("audit.*", SchemaQualifiedName::new("audit_old", "log"), false)]
+    #[case("audit.*", SchemaQualifiedName::new("public", "audit"), false)]
+    #[case("audit.?og", SchemaQualifiedName::new("audit", "log"), true)]
+    fn glob_match_should_match_schema_qualified_name(
+        #[case] pattern: &str,
+        #[case] name: SchemaQualifiedName,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, glob_match(pattern, &name.to_string()));
+    }
+
+    #[rstest::rstest]
+    #[case(SchemaQualifiedName::new("ext_postgis", "spatial_ref_sys"), true)]
+    #[case(SchemaQualifiedName::new("ext_audit", "log.log_pkey"), true)]
+    #[case(SchemaQualifiedName::new("public", "ext_table"), false)]
+    #[case(SchemaQualifiedName::new("ext_postgis", ""), false)]
+    #[case(SchemaQualifiedName::new("public", "schema_migrations"), true)]
+    #[case(SchemaQualifiedName::new("public", "schema_migrations_old"), false)]
+    #[case(SchemaQualifiedName::new("app", "schema_migrations"), false)]
+    fn matches_any_pattern_should_match_schema_wildcards_and_exact_names(
+        #[case] name: SchemaQualifiedName,
+        #[case] expected: bool,
+    ) {
+        let patterns = vec![
+            "ext_*.*".to_string(),
+            "public.schema_migrations".to_string(),
+        ];
+
+        assert_eq!(expected, matches_any_pattern(&patterns, &name));
+    }
+
+    #[test]
+    fn matches_any_pattern_should_not_match_when_no_patterns() {
+        let name = SchemaQualifiedName::new("public", "schema_migrations");
+
+        assert!(!matches_any_pattern(&[], &name));
+    }
+
+    #[rstest::rstest]
+    #[case("app, public", vec!["app", "public"])]
+    #[case("\"$user\", public", vec!["public"])]
+    #[case("\"My Schema\", APP", vec!["My Schema", "app"])]
+    #[case("", vec![])]
+    fn parse_search_path_should_split_schema_names(
+        #[case] value: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        assert_eq!(expected, parse_search_path(value));
+    }
+
+    #[rstest::rstest]
+    #[case(&["other", "app"], &["other"])]
+    #[case(&["app", "other"], &["app"])]
+    #[case(&["missing"], &["app", "other"])]
+    fn resolve_with_search_path_should_choose_first_schema_in_search_path(
+        #[case] search_path: &[&str],
+        #[case] expected_schemas: &[&str],
+    ) {
+        let objects = vec![
+            SchemaQualifiedName::new("app", "test_table"),
+            SchemaQualifiedName::new("other", "test_table"),
+        ];
+        let search_path: Vec<String> = search_path.iter().map(|s| s.to_string()).collect();
+
+        let result = resolve_with_search_path(objects, &search_path);
+
+        let expected: Vec<SchemaQualifiedName> = expected_schemas
+            .iter()
+            .map(|s| SchemaQualifiedName::new(s, "test_table"))
+            .collect();
+        assert_eq!(expected, result);
+    }
+
+    #[rstest::rstest]
+    #[case("'active'::text", "'active'", true)]
+    #[case("nextval('test_seq'::regclass)", "nextval('test_seq')", true)]
+    #[case("(now() AT TIME ZONE 'utc'::text)", "now() at time zone 'utc'", true)]
+    #[case("((a > 0) AND (b IS NOT NULL))", "a > 0 and b is not null", true)]
+    #[case("'2024-01-01'::timestamp with time zone", "'2024-01-01'", true)]
+    #[case("'active'::text", "'inactive'", false)]
+    #[case("a + 1", "a + 2", false)]
+    #[case("a::text", "a", false)]
+    #[case("not a valid (expression", "not a valid (expression", true)]
+    #[case("not a valid (expression", "not a valid(expression", false)]
+    fn expressions_match_should_ignore_formatting_and_literal_casts(
+        #[case] left: &str,
+        #[case] right: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, expressions_match(left, right));
+    }
+
+    #[rstest::rstest]
+    #[case("SELECT a FROM t WHERE b", "select a\n  from t\n where b", true)]
+    #[case("SELECT a FROM t", "SELECT a FROM t /* all rows */", true)]
+    #[case("SELECT 'Text'", "select 'text'", false)]
+    #[case("BEGIN\n    RETURN x; -- value\nEND;", "begin return X; end;", true)]
+    #[case("BEGIN RETURN x; END;", "BEGIN RETURN \"X\"; END;", false)]
+    #[case("BEGIN RETURN 'x'; END;", "BEGIN RETURN 'X'; END;", false)]
+    fn sources_match_should_ignore_formatting(
+        #[case] left: &str,
+        #[case] right: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, sources_match(left, right));
+    }
+
+    #[rstest::rstest]
+    #[case("'a'::varchar(10)[] || x", "'a' || x")]
+    #[case("'it''s'::text", "'it''s'")]
+    #[case("E'\\''::text", "E'\\''")]
+    #[case("\"col'\"::text", "\"col'\"::text")]
+    #[case("'a'::\"char\"::text", "'a'")]
+    fn strip_literal_casts_should_only_remove_casts_of_literals(
+        #[case] sql: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, strip_literal_casts(sql));
+    }
+}
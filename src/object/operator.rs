@@ -0,0 +1,519 @@
+use std::fmt::Write;
+
+use serde::Deserialize;
+use sqlx::{query_as, PgPool};
+
+use crate::PgDiffError;
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all operators found within the specified schemas
+pub async fn get_operators(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Operator>, PgDiffError> {
+    let operators_query = include_str!("./../../queries/operators.pgsql");
+    let operators =
+        match fetch_with_retry(|| query_as(operators_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load operators");
+                return Err(error.into());
+            },
+        };
+    Ok(operators)
+}
+
+/// Fetch all operator classes found within the specified schemas
+pub async fn get_operator_classes(
+    pool: &PgPool,
+    schemas: &[&str],
+) -> Result<Vec<OperatorClass>, PgDiffError> {
+    let operator_classes_query = include_str!("./../../queries/operator_classes.pgsql");
+    let operator_classes = match fetch_with_retry(|| {
+        query_as(operator_classes_query)
+            .bind(schemas)
+            .fetch_all(pool)
+    })
+    .await
+    {
+        Ok(inner) => inner,
+        Err(error) => {
+            println!("Could not load operator classes");
+            return Err(error.into());
+        },
+    };
+    Ok(operator_classes)
+}
+
+/// Postgresql operator object
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct Operator {
+    /// Full name of the operator. Since operators are overloaded by their operand types, the local
+    /// name is the operator symbol followed by the operand types (e.g. `===(integer,integer)`).
+    /// Use [Operator::symbol] when writing statements.
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Symbol of the operator (e.g. `===`)
+    pub(crate) symbol: String,
+    /// Data type of the left operand. [None] if this is a prefix operator.
+    pub(crate) left_type: Option<String>,
+    /// Data type of the right operand
+    pub(crate) right_type: String,
+    /// Full name of the function implementing the operator
+    #[sqlx(json)]
+    pub(crate) function: SchemaQualifiedName,
+    /// Commutator of the operator as an `OPERATOR(schema.symbol)` reference, if any
+    pub(crate) commutator: Option<String>,
+    /// Negator of the operator as an `OPERATOR(schema.symbol)` reference, if any
+    pub(crate) negator: Option<String>,
+    /// Full name of the restriction selectivity estimator function, if any
+    pub(crate) restrict: Option<SchemaQualifiedName>,
+    /// Full name of the join selectivity estimator function, if any
+    pub(crate) join: Option<SchemaQualifiedName>,
+    /// True if the operator can support a hash join
+    pub(crate) hashes: bool,
+    /// True if the operator can support a merge join
+    pub(crate) merges: bool,
+    /// Dependencies of the operator. This includes the schema, the implementing function and the
+    /// operand types.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl Operator {
+    /// Write the schema qualified symbol of the operator along with the operand types as required
+    /// by the `DROP` and `ALTER` statements
+    fn write_signature<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "{}.{} ({}, {})",
+            self.name.schema_name,
+            self.symbol,
+            self.left_type.as_deref().unwrap_or("NONE"),
+            self.right_type
+        )?;
+        Ok(())
+    }
+}
+
+impl SqlObject for Operator {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "OPERATOR"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "CREATE OPERATOR {}.{} (\n    FUNCTION = {}",
+            self.name.schema_name, self.symbol, self.function
+        )?;
+        if let Some(left_type) = &self.left_type {
+            write!(w, ",\n    LEFTARG = {left_type}")?;
+        }
+        write!(w, ",\n    RIGHTARG = {}", self.right_type)?;
+        if let Some(commutator) = &self.commutator {
+            write!(w, ",\n    COMMUTATOR = {commutator}")?;
+        }
+        if let Some(negator) = &self.negator {
+            write!(w, ",\n    NEGATOR = {negator}")?;
+        }
+        if let Some(restrict) = &self.restrict {
+            write!(w, ",\n    RESTRICT = {restrict}")?;
+        }
+        if let Some(join) = &self.join {
+            write!(w, ",\n    JOIN = {join}")?;
+        }
+        if self.hashes {
+            w.write_str(",\n    HASHES")?;
+        }
+        if self.merges {
+            w.write_str(",\n    MERGES")?;
+        }
+        w.write_str("\n);\n")?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self != new {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        write!(w, "DROP OPERATOR {}", context.if_exists())?;
+        self.write_signature(w)?;
+        w.write_str(";\n")?;
+        Ok(())
+    }
+
+    /// Override the alter prefix to include the operand types to distinguish between operator
+    /// overloads when altering.
+    fn write_alter_prefix<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        w.write_str("ALTER OPERATOR ")?;
+        self.write_signature(w)
+    }
+}
+
+/// Postgresql operator class object
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct OperatorClass {
+    /// Full name of the operator class
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Name of the index access method the operator class is for (e.g. `btree`)
+    pub(crate) index_method: String,
+    /// Data type the operator class accepts
+    pub(crate) data_type: String,
+    /// True if the operator class is the default for the data type and index access method
+    pub(crate) is_default: bool,
+    /// Full name of the operator family the class belongs to. [None] if the class belongs to the
+    /// family implicitly created with the same name as the class.
+    pub(crate) family: Option<SchemaQualifiedName>,
+    /// Data type actually stored in the index, if it differs from [OperatorClass::data_type]
+    pub(crate) storage_type: Option<String>,
+    /// Operators bound to the operator class, ordered by strategy number
+    #[sqlx(json)]
+    pub(crate) operators: Vec<OperatorClassOperator>,
+    /// Support functions bound to the operator class, ordered by support number
+    #[sqlx(json)]
+    pub(crate) functions: Vec<OperatorClassFunction>,
+    /// Dependencies of the operator class. This includes the schema and every non-builtin
+    /// operator, function and data type referenced.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+/// Operator associated with an operator class
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct OperatorClassOperator {
+    /// Index access method strategy number of the operator
+    pub(crate) strategy_number: i16,
+    /// Schema qualified symbol of the operator
+    pub(crate) name: String,
+    /// Data type of the left operand
+    pub(crate) left_type: String,
+    /// Data type of the right operand
+    pub(crate) right_type: String,
+    /// Full name of the btree operator family used to sort the results of an ordering operator.
+    /// [None] if the operator is a search operator.
+    pub(crate) order_family: Option<String>,
+}
+
+/// Support function associated with an operator class
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct OperatorClassFunction {
+    /// Index access method support function number
+    pub(crate) support_number: i16,
+    /// Full name of the function
+    pub(crate) name: String,
+    /// Argument types of the function
+    pub(crate) arguments: String,
+    /// Data type of the left operand the function supports
+    pub(crate) left_type: String,
+    /// Data type of the right operand the function supports
+    pub(crate) right_type: String,
+}
+
+impl OperatorClass {
+    /// Write the operand types of an operator class item if the types differ from the data type
+    /// of the operator class. Otherwise, the types are implied and nothing is written.
+    fn write_operand_types<W>(
+        &self,
+        w: &mut W,
+        left_type: &str,
+        right_type: &str,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        if left_type != self.data_type || right_type != self.data_type {
+            write!(w, " ({left_type}, {right_type})")?;
+        }
+        Ok(())
+    }
+}
+
+impl SqlObject for OperatorClass {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "OPERATOR CLASS"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "CREATE OPERATOR CLASS {}{} FOR TYPE {} USING {}",
+            self.name,
+            if self.is_default { " DEFAULT" } else { "" },
+            self.data_type,
+            self.index_method
+        )?;
+        if let Some(family) = &self.family {
+            write!(w, " FAMILY {family}")?;
+        }
+        w.write_str(" AS")?;
+        let mut separator = "\n    ";
+        for operator in &self.operators {
+            write!(
+                w,
+                "{separator}OPERATOR {} {}",
+                operator.strategy_number, operator.name
+            )?;
+            self.write_operand_types(w, &operator.left_type, &operator.right_type)?;
+            if let Some(order_family) = &operator.order_family {
+                write!(w, " FOR ORDER BY {order_family}")?;
+            }
+            separator = ",\n    ";
+        }
+        for function in &self.functions {
+            write!(w, "{separator}FUNCTION {}", function.support_number)?;
+            self.write_operand_types(w, &function.left_type, &function.right_type)?;
+            write!(w, " {}({})", function.name, function.arguments)?;
+            separator = ",\n    ";
+        }
+        if let Some(storage_type) = &self.storage_type {
+            write!(w, "{separator}STORAGE {storage_type}")?;
+        }
+        w.write_str(";\n")?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self != new {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the operator class. When the class belongs to the family implicitly created with the
+    /// class, the family is dropped instead which also drops the class.
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP OPERATOR {} {}{} USING {};",
+            if self.family.is_some() {
+                "CLASS"
+            } else {
+                "FAMILY"
+            },
+            context.if_exists(),
+            self.name,
+            self.index_method
+        )?;
+        Ok(())
+    }
+
+    /// Override the alter prefix to include the index access method that is required to identify
+    /// the operator class.
+    fn write_alter_prefix<W>(&self, w: &mut W) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "ALTER OPERATOR CLASS {} USING {}",
+            self.name, self.index_method
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{RenderContext, SchemaQualifiedName, SqlObject};
+
+    use super::{Operator, OperatorClass, OperatorClassFunction, OperatorClassOperator};
+
+    const SCHEMA: &str = "test_schema";
+
+    fn create_operator(symbol: &str, function: &str, commutator: Option<&str>) -> Operator {
+        Operator {
+            name: SchemaQualifiedName::new(SCHEMA, &format!("{symbol}(integer,integer)")),
+            symbol: symbol.into(),
+            left_type: Some("integer".into()),
+            right_type: "integer".into(),
+            function: SchemaQualifiedName::new(SCHEMA, function),
+            commutator: commutator.map(|c| format!("OPERATOR({SCHEMA}.{c})")),
+            negator: None,
+            restrict: None,
+            join: None,
+            hashes: false,
+            merges: false,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, function)],
+        }
+    }
+
+    fn create_operator_class_operator(
+        strategy_number: i16,
+        symbol: &str,
+        right_type: &str,
+    ) -> OperatorClassOperator {
+        OperatorClassOperator {
+            strategy_number,
+            name: format!("{SCHEMA}.{symbol}"),
+            left_type: "integer".into(),
+            right_type: right_type.into(),
+            order_family: None,
+        }
+    }
+
+    fn create_operator_class(family: Option<&str>) -> OperatorClass {
+        OperatorClass {
+            name: SchemaQualifiedName::new(SCHEMA, "abs_int_ops"),
+            index_method: "btree".into(),
+            data_type: "integer".into(),
+            is_default: false,
+            family: family.map(|f| SchemaQualifiedName::new(SCHEMA, f)),
+            storage_type: None,
+            operators: vec![
+                create_operator_class_operator(1, "<<<", "integer"),
+                create_operator_class_operator(3, "===", "bigint"),
+            ],
+            functions: vec![OperatorClassFunction {
+                support_number: 1,
+                name: format!("{SCHEMA}.abs_cmp"),
+                arguments: "integer, integer".into(),
+                left_type: "integer".into(),
+                right_type: "integer".into(),
+            }],
+            dependencies: vec![],
+        }
+    }
+
+    #[test]
+    fn create_statements_should_add_create_operator_statement() {
+        let mut operator = create_operator("<<<", "abs_lt", Some(">>>"));
+        operator.restrict = Some(SchemaQualifiedName::new("pg_catalog", "scalarltsel"));
+        operator.join = Some(SchemaQualifiedName::new("pg_catalog", "scalarltjoinsel"));
+        let mut writeable = String::new();
+
+        operator.create_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/operator-create.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_drop_and_recreate_when_operator_changes() {
+        let old = create_operator("<<<", "abs_lt", None);
+        let new = create_operator("<<<", "abs_lt", Some(">>>"));
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/operator-alter.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_with_should_add_if_exists_when_idempotent() {
+        let operator = create_operator("<<<", "abs_lt", None);
+        let context = RenderContext {
+            idempotent: true,
+            ..Default::default()
+        };
+        let mut writeable = String::new();
+
+        operator
+            .drop_statements_with(&context, &mut writeable)
+            .unwrap();
+
+        assert_eq!(
+            "DROP OPERATOR IF EXISTS test_schema.<<< (integer, integer);",
+            writeable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        None,
+        include_str!("../../test-files/sql/operator-class-create-case1.pgsql"),
+    )]
+    #[case(
+        Some("abs_ops"),
+        include_str!("../../test-files/sql/operator-class-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_operator_class_statement(
+        #[case] family: Option<&str>,
+        #[case] expected_statement: &str,
+    ) {
+        let operator_class = create_operator_class(family);
+        let mut writeable = String::new();
+
+        operator_class.create_statements(&mut writeable).unwrap();
+
+        assert_eq!(expected_statement.trim(), writeable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(None, "DROP OPERATOR FAMILY test_schema.abs_int_ops USING btree;")]
+    #[case(
+        Some("abs_ops"),
+        "DROP OPERATOR CLASS test_schema.abs_int_ops USING btree;"
+    )]
+    fn drop_statements_should_add_drop_operator_class_statement(
+        #[case] family: Option<&str>,
+        #[case] expected_statement: &str,
+    ) {
+        let operator_class = create_operator_class(family);
+        let mut writeable = String::new();
+
+        operator_class.drop_statements(&mut writeable).unwrap();
+
+        assert_eq!(expected_statement, writeable.trim());
+    }
+}
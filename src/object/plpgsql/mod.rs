@@ -6,13 +6,16 @@ use pg_query::Error;
 use serde::Deserialize;
 use serde_repr::Deserialize_repr;
 
-use crate::object::{SchemaQualifiedName, BUILT_IN_FUNCTIONS, BUILT_IN_NAMES};
+use crate::object::{is_built_in_name, is_fail_on_unsupported, is_verbose, SchemaQualifiedName};
 use crate::PgDiffError;
 
 #[cfg(test)]
 mod test;
 
-/// Parse the pl/pgsql function declaration to 1 or more [PlPgSqlFunction]s
+/// Parse the pl/pgsql function declaration to 1 or more [PlPgSqlFunction]s. Statements and datums
+/// that are not recognized are kept as [PlPgSqlStatement::Unknown] and [PlPgSqlVariable::Unknown]
+/// rather than failing the parse. Those nodes are reported when objects are extracted (see
+/// [skip_unknown_node]).
 ///
 /// ## Errors
 /// - If the function code cannot be parsed into an intermediary JSON structure
@@ -28,6 +31,27 @@ pub fn parse_plpgsql_function(function_code: &str) -> Result<Vec<PlPgSqlFunction
     })
 }
 
+/// Skip the extraction of objects from the unrecognized `node` (a statement or datum as described
+/// by `kind`). The node is written to STDOUT if the verbose flag is active since any dependencies
+/// within the node are missed.
+///
+/// ## Errors
+/// If `fail_on_unsupported` is true
+fn skip_unknown_node(
+    kind: &str,
+    node: &serde_json::Value,
+    fail_on_unsupported: bool,
+) -> Result<(), Error> {
+    let reason = format!("Could not extract objects from unrecognized pl/pgsql {kind}. {node}");
+    if fail_on_unsupported {
+        return Err(Error::Parse(reason));
+    }
+    if is_verbose() {
+        println!("{reason}");
+    }
+    Ok(())
+}
+
 /// Trait to designate a type that can extract object name referenced within the node into a
 /// supplied buffer
 trait ObjectNode {
@@ -120,10 +144,17 @@ pub enum PlPgSqlVariable {
         #[serde(rename = "recparentno")]
         rec_parent_number: u32,
     },
+    /// Datum that could not be deserialized as any known variable type. The raw JSON node is kept
+    /// but no objects are extracted from the datum (see [skip_unknown_node]).
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
 }
 
 impl ObjectNode for PlPgSqlVariable {
     fn extract_objects(&self, buffer: &mut Vec<SchemaQualifiedName>) -> Result<(), Error> {
+        if let PlPgSqlVariable::Unknown(node) = self {
+            return skip_unknown_node("datum", node, is_fail_on_unsupported());
+        }
         let PlPgSqlVariable::Var {
             data_type,
             default_value,
@@ -134,11 +165,132 @@ impl ObjectNode for PlPgSqlVariable {
         };
         default_value.extract_objects(buffer)?;
         let PlPgSqlType::Inner { type_name } = data_type;
-        buffer.push(SchemaQualifiedName::from(type_name));
+        if let Some(name) = type_name_dependency(type_name) {
+            buffer.push(name);
+        }
         Ok(())
     }
 }
 
+/// Built-in type names (lower case) that never need to be resolved as a dependency. Multi-word
+/// built-in types (e.g. `double precision`) are handled separately.
+const BUILT_IN_TYPES: &[&str] = &[
+    "unknown",
+    "bool",
+    "boolean",
+    "smallint",
+    "int",
+    "int2",
+    "int4",
+    "int8",
+    "integer",
+    "bigint",
+    "numeric",
+    "decimal",
+    "real",
+    "float",
+    "float4",
+    "float8",
+    "money",
+    "varchar",
+    "character",
+    "bpchar",
+    "name",
+    "bytea",
+    "time",
+    "timetz",
+    "timestamp",
+    "timestamptz",
+    "interval",
+    "json",
+    "xml",
+    "cidr",
+    "macaddr",
+    "tsvector",
+    "tsquery",
+    "record",
+    "void",
+    "regproc",
+    "regtype",
+    "refcursor",
+];
+
+/// Convert the `type_name` of a declared pl/pgsql variable into the name of the object the
+/// variable depends upon.
+///
+/// Array brackets and type modifiers are removed. A `%ROWTYPE` reference depends on the referenced
+/// relation and a `%TYPE` reference to a column depends on the column's relation. Unquoted
+/// identifiers are folded to lower case. [None] is returned for built-in types and `%TYPE`
+/// references to other variables.
+fn type_name_dependency(type_name: &str) -> Option<SchemaQualifiedName> {
+    let mut type_name = type_name.trim();
+    let mut is_column_reference = false;
+    if let Some(relation) = strip_suffix_ignore_case(type_name, "%ROWTYPE") {
+        type_name = relation;
+    } else if let Some(column) = strip_suffix_ignore_case(type_name, "%TYPE") {
+        type_name = column;
+        is_column_reference = true;
+    }
+    while let Some(array_type) = type_name.strip_suffix(']') {
+        type_name = array_type.rsplit_once('[')?.0.trim_end();
+    }
+    if let Some(type_modifiers) = type_name.strip_suffix(')') {
+        type_name = type_modifiers.rsplit_once('(')?.0.trim_end();
+    }
+
+    let mut parts = split_identifiers(type_name)?;
+    if is_column_reference {
+        parts.pop();
+    }
+    match &parts[..] {
+        [local_name]
+            if BUILT_IN_TYPES.contains(&local_name.as_str()) || is_built_in_name(local_name) =>
+        {
+            None
+        },
+        [local_name] => Some(SchemaQualifiedName::new("", local_name)),
+        [schema_name, _] if schema_name == "pg_catalog" => None,
+        [schema_name, local_name] => Some(SchemaQualifiedName::new(schema_name, local_name)),
+        _ => None,
+    }
+}
+
+/// Strip the `suffix` from the end of the `text` ignoring ASCII case
+fn strip_suffix_ignore_case<'s>(text: &'s str, suffix: &str) -> Option<&'s str> {
+    let index = text.len().checked_sub(suffix.len())?;
+    if !text.is_char_boundary(index) || !text[index..].eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+    Some(&text[..index])
+}
+
+/// Split the dotted identifier `name` into its parts. Quoted identifiers are kept as is (including
+/// the quotes) while unquoted identifiers are folded to lower case. [None] is returned if the name
+/// contains unquoted whitespace since that only occurs for multi-word built-in types (e.g.
+/// `timestamp with time zone`).
+fn split_identifiers(name: &str) -> Option<Vec<String>> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in name.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            },
+            _ if in_quotes => current.push(c),
+            '.' => parts.push(std::mem::take(&mut current)),
+            _ if c.is_whitespace() => return None,
+            _ => current.extend(c.to_lowercase()),
+        }
+    }
+    if current.is_empty() {
+        return None;
+    }
+    parts.push(current);
+    Some(parts)
+}
+
 #[derive(Debug, Default, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum RawParseMode {
@@ -166,31 +318,33 @@ impl ObjectNode for PlPgSqlExpr {
     /// Extract the objects referenced within this expression.
     ///
     /// These expressions can be regular DML statements, variable assignment or simple expressions
-    /// such as `TRIM(x)`. If the statement is not a DML statement, the actual expression part will
-    /// be prefixed with `select {expression}` to pass that expression to the SQL parser for
-    /// evaluation. The result of the parsing is then checks for tables and function that are
-    /// referenced.
+    /// such as `TRIM(x)`. If the statement is not a DML statement, the actual expression part
+    /// (i.e. the value after the first `:=` found outside of quoted text) will be prefixed with
+    /// `select {expression}` to pass that expression to the SQL parser for evaluation. If that
+    /// fails to parse, the expression is parsed again without the prefix. The result of the
+    /// parsing is then checks for tables and function that are referenced.
     ///
     /// ## Errors
     /// If the SQL query parsing fails
     fn extract_objects(&self, buffer: &mut Vec<SchemaQualifiedName>) -> Result<(), Error> {
         let PlPgSqlExpr::Inner { query, .. } = self;
         let query = query.trim();
-        let dml_query_regex = regex!("^select|insert|update|delete|truncate"i);
-        let query = if dml_query_regex.is_match(query) {
-            query.to_string()
+        let dml_query_regex =
+            regex!(r"^(select|insert|update|delete|truncate|with|values|table)\b"i);
+        let parse_result = if dml_query_regex.is_match(query) {
+            pg_query::parse(query)?
         } else {
-            match query.split_once(":=") {
-                Some((_, assign_value)) => format!("select {assign_value}"),
-                None => format!("select {query}"),
+            let expression = split_assignment(query).unwrap_or(query).trim();
+            match pg_query::parse(&format!("select {expression}")) {
+                Ok(result) => result,
+                Err(error) => pg_query::parse(expression).map_err(|_| error)?,
             }
         };
-        let parse_result = pg_query::parse(&query)?;
         for table in parse_result.tables() {
             buffer.push(SchemaQualifiedName::from(&table));
         }
         for function in parse_result.functions() {
-            if BUILT_IN_FUNCTIONS.contains(&function.as_str()) {
+            if is_built_in_name(&function) {
                 continue;
             }
             buffer.push(SchemaQualifiedName::from(&function));
@@ -199,6 +353,41 @@ impl ObjectNode for PlPgSqlExpr {
     }
 }
 
+/// Find the first assignment operator (`:=`) within the `query` that is not within a string
+/// literal, quoted identifier or dollar quoted string. Returns the text after the operator if
+/// found.
+fn split_assignment(query: &str) -> Option<&str> {
+    let dollar_quote_regex = regex!(r"^\$([A-Za-z_][A-Za-z_0-9]*)?\$");
+    let mut closing_quote: Option<&str> = None;
+    let mut index = 0;
+    while index < query.len() {
+        let remaining = &query[index..];
+        if let Some(quote) = closing_quote {
+            if remaining.starts_with(quote) {
+                closing_quote = None;
+                index += quote.len();
+            } else {
+                index += remaining.chars().next().map_or(1, char::len_utf8);
+            }
+            continue;
+        }
+        if let Some(assign_value) = remaining.strip_prefix(":=") {
+            return Some(assign_value);
+        }
+        if remaining.starts_with('\'') {
+            closing_quote = Some("'");
+        } else if remaining.starts_with('"') {
+            closing_quote = Some("\"");
+        } else if let Some(tag) = dollar_quote_regex.find(remaining) {
+            closing_quote = Some(tag.as_str());
+            index += tag.len();
+            continue;
+        }
+        index += remaining.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
 /// `ELSIF` block within an `IF` structure
 #[derive(Debug, Deserialize)]
 pub enum PlPgSqlElsIf {
@@ -774,6 +963,7 @@ pub enum PlPgSqlStatement {
         returns_multiple_rows: bool,
     },
     /// `CLOSE` cursor statement
+    #[serde(rename = "PLpgSQL_stmt_close")]
     Close {
         #[serde(rename = "lineno")]
         line_no: u32,
@@ -817,6 +1007,11 @@ pub enum PlPgSqlStatement {
         /// True if the `AND CHAIN` modifier to the `ROLLBACK` are present
         chain: bool,
     },
+    /// Statement that could not be deserialized as any known statement (e.g. a statement type or
+    /// shape added by a newer Postgresql version). The raw JSON node is kept but no objects are
+    /// extracted from the statement (see [skip_unknown_node]).
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
 }
 
 /// `RAISE` statement option as a key value pair. The key is a known option type and the value is
@@ -892,7 +1087,10 @@ impl ObjectNode for PlPgSqlStatement {
             PlPgSqlStatement::Loop { body, .. } => {
                 body.extract_objects(buffer)?;
             },
-            PlPgSqlStatement::While { body, .. } => {
+            PlPgSqlStatement::While {
+                condition, body, ..
+            } => {
+                condition.extract_objects(buffer)?;
                 body.extract_objects(buffer)?;
             },
             PlPgSqlStatement::ForI {
@@ -957,8 +1155,11 @@ impl ObjectNode for PlPgSqlStatement {
                 params.extract_objects(buffer)?;
                 options.extract_objects(buffer)?;
             },
-            PlPgSqlStatement::Assert { condition, .. } => {
+            PlPgSqlStatement::Assert {
+                condition, message, ..
+            } => {
                 condition.extract_objects(buffer)?;
+                message.extract_objects(buffer)?;
             },
             PlPgSqlStatement::ExecSql {
                 sql_statement,
@@ -979,11 +1180,13 @@ impl ObjectNode for PlPgSqlStatement {
                 params.extract_objects(buffer)?;
             },
             PlPgSqlStatement::DynForS {
+                var,
                 body,
                 query,
                 params,
                 ..
             } => {
+                var.extract_objects(buffer)?;
                 body.extract_objects(buffer)?;
                 query.extract_objects(buffer)?;
                 params.extract_objects(buffer)?;
@@ -1009,6 +1212,9 @@ impl ObjectNode for PlPgSqlStatement {
             },
             PlPgSqlStatement::Commit { .. } => {},
             PlPgSqlStatement::Rollback { .. } => {},
+            PlPgSqlStatement::Unknown(node) => {
+                skip_unknown_node("statement", node, is_fail_on_unsupported())?;
+            },
         }
         Ok(())
     }
@@ -1045,18 +1251,13 @@ impl PlPgSqlFunction {
                 object_name: SchemaQualifiedName::from("pl_pgsql_block"),
                 error,
             })?;
-        for datum in datums {
-            if let PlPgSqlVariable::Var {
-                data_type: PlPgSqlType::Inner { type_name },
-                ..
-            } = datum
-            {
-                if type_name == "UNKNOWN" || BUILT_IN_NAMES.contains(&type_name.as_str()) {
-                    continue;
-                }
-                result.push(SchemaQualifiedName::from(type_name))
-            }
-        }
+        // Declared variables contribute their data type and default value expression
+        datums
+            .extract_objects(&mut result)
+            .map_err(|error| PgDiffError::PgQuery {
+                object_name: SchemaQualifiedName::from("pl_pgsql_block"),
+                error,
+            })?;
         Ok(result)
     }
 }
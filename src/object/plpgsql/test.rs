@@ -1,10 +1,14 @@
+use std::path::{Path, PathBuf};
+
 use super::{
     parse_plpgsql_function, CursorOption, FetchDirection, PlPgSqlDiagnosticsItem,
     PlPgSqlDiagnosticsKind, PlPgSqlElsIf, PlPgSqlExpr, PlPgSqlFunction, PlPgSqlOpenCursor,
     PlPgSqlRaiseLogLevel, PlPgSqlRaiseOption, PlPgSqlRaiseOptionType, PlPgSqlStatement,
     PlPgSqlVariable, RawParseMode, RowField,
 };
-use lazy_regex::{lazy_regex, Lazy, Regex};
+use super::{skip_unknown_node, split_assignment, type_name_dependency};
+use crate::object::SchemaQualifiedName;
+use lazy_regex::{lazy_regex, regex, Lazy, Regex};
 
 static CLEAN_QUERY: Lazy<Regex> = lazy_regex!("\\s+");
 
@@ -1187,3 +1191,289 @@ fn parse_plpgsql_function_should_parse_commit_rollback_statements() {
         ]
     ));
 }
+
+#[rstest::rstest]
+#[case(
+    include_str!("./../../../test-files/plpgsql/cte-expression.pgsql"),
+    &["test_schema.source_table", "test_schema.transform_id"],
+)]
+#[case(
+    include_str!("./../../../test-files/plpgsql/string-literal-assignment.pgsql"),
+    &["test_schema.is_valid", "test_schema.format_value"],
+)]
+#[case(
+    include_str!("./../../../test-files/plpgsql/assert-message-subquery.pgsql"),
+    &["test_schema.source_table", "test_schema.has_pending", "test_schema.count_table"],
+)]
+fn get_objects_should_extract_dependencies_from_expressions(
+    #[case] function_block: &str,
+    #[case] expected_objects: &[&str],
+) {
+    let function = parse_plpgsql_function(function_block).unwrap();
+
+    let objects = function.first().unwrap().get_objects().unwrap();
+
+    for expected in expected_objects {
+        let expected = SchemaQualifiedName::from(*expected);
+        assert!(
+            objects.contains(&expected),
+            "{expected} not found in {objects:?}"
+        );
+    }
+}
+
+#[rstest::rstest]
+#[case("test_int := test_int + 1", Some(" test_int + 1"))]
+#[case("test_text := 'a := b'", Some(" 'a := b'"))]
+#[case("test_text = 'a := b'", None)]
+#[case("test_text = $q$a := b$q$ || \"col:=\"", None)]
+fn split_assignment_should_ignore_operators_within_quotes(
+    #[case] query: &str,
+    #[case] expected: Option<&str>,
+) {
+    assert_eq!(expected, split_assignment(query));
+}
+
+#[rstest::rstest]
+#[case(
+    "my_schema.my_type[]",
+    Some(SchemaQualifiedName::new("my_schema", "my_type"))
+)]
+#[case(
+    "My_Schema.My_Type[][]",
+    Some(SchemaQualifiedName::new("my_schema", "my_type"))
+)]
+#[case("my_type[3]", Some(SchemaQualifiedName::new("", "my_type")))]
+#[case(
+    "my_schema.my_domain(10)",
+    Some(SchemaQualifiedName::new("my_schema", "my_domain"))
+)]
+#[case(
+    "test_schema.tab.col%TYPE",
+    Some(SchemaQualifiedName::new("test_schema", "tab"))
+)]
+#[case("tab.col%type", Some(SchemaQualifiedName::new("", "tab")))]
+#[case("other_variable%TYPE", None)]
+#[case(
+    "test_schema.tab%ROWTYPE",
+    Some(SchemaQualifiedName::new("test_schema", "tab"))
+)]
+#[case(
+    "\"My Schema\".\"My Type\"",
+    Some(SchemaQualifiedName::new("\"My Schema\"", "\"My Type\""))
+)]
+#[case("MyType", Some(SchemaQualifiedName::new("", "mytype")))]
+#[case("numeric(12,2)", None)]
+#[case("CHARACTER VARYING(30)", None)]
+#[case("timestamp with time zone", None)]
+#[case("INTEGER", None)]
+#[case("int[][]", None)]
+#[case("pg_catalog.int4", None)]
+#[case("UNKNOWN", None)]
+fn type_name_dependency_should_normalize_type_names(
+    #[case] type_name: &str,
+    #[case] expected: Option<SchemaQualifiedName>,
+) {
+    assert_eq!(expected, type_name_dependency(type_name));
+}
+
+#[test]
+fn get_objects_should_extract_anchored_and_array_declaration_types() {
+    let function = parse_plpgsql_function(include_str!(
+        "./../../../test-files/plpgsql/anchored-declarations.pgsql"
+    ))
+    .unwrap();
+
+    let objects = function.first().unwrap().get_objects().unwrap();
+
+    assert_eq!(
+        vec![
+            SchemaQualifiedName::new("test_schema", "row_table"),
+            SchemaQualifiedName::new("test_schema", "column_table"),
+            SchemaQualifiedName::new("test_schema", "array_type"),
+        ],
+        objects
+    );
+}
+
+// pg_query cannot resolve the type of cursor variables so functions with cursor statements fail to
+// parse (see parse_plpgsql_function_should_parse_cursor_statement). Instead, the `CLOSE` statement
+// node is added to the parse result of a function without cursors.
+#[test]
+fn plpgsql_function_should_deserialize_close_statement() {
+    let mut parse_result = pg_query::parse_plpgsql(
+        "create function test_func() returns void language plpgsql as $$
+        begin
+            perform id from test_schema.source_table;
+        end;
+        $$;",
+    )
+    .unwrap();
+    parse_result[0]["PLpgSQL_function"]["action"]["PLpgSQL_stmt_block"]["body"]
+        .as_array_mut()
+        .unwrap()
+        .insert(
+            1,
+            serde_json::json!({"PLpgSQL_stmt_close": {"lineno": 4, "curvar": 0}}),
+        );
+    let function: Vec<PlPgSqlFunction> = serde_json::from_value(parse_result).unwrap();
+    let PlPgSqlFunction::Inner { action, .. } = function.first().unwrap();
+    let PlPgSqlStatement::Block { body, .. } = action else {
+        panic!("Top level function block is not a block");
+    };
+
+    assert!(
+        matches!(
+            &body[..],
+            [
+                PlPgSqlStatement::Perform { .. },
+                PlPgSqlStatement::Close {
+                    cursor_variable: 0,
+                    ..
+                },
+                PlPgSqlStatement::Return { .. }
+            ]
+        ),
+        "Actions within block are not perform and close statements\n{body:#?}"
+    );
+    let objects = function.first().unwrap().get_objects().unwrap();
+    assert!(objects.contains(&SchemaQualifiedName::from("test_schema.source_table")));
+}
+
+#[test]
+fn plpgsql_function_should_deserialize_unknown_statements_and_datums() {
+    let mut parse_result = pg_query::parse_plpgsql(
+        "create function test_func() returns void language plpgsql as $$
+        begin
+            perform id from test_schema.source_table;
+        end;
+        $$;",
+    )
+    .unwrap();
+    let function_node = &mut parse_result[0]["PLpgSQL_function"];
+    function_node["action"]["PLpgSQL_stmt_block"]["body"]
+        .as_array_mut()
+        .unwrap()
+        .insert(
+            0,
+            serde_json::json!({"PLpgSQL_stmt_future": {"lineno": 2, "expr": {}}}),
+        );
+    function_node["datums"] = serde_json::json!([{"PLpgSQL_future_datum": {"dno": 0}}]);
+    let function: Vec<PlPgSqlFunction> = serde_json::from_value(parse_result).unwrap();
+    let PlPgSqlFunction::Inner { action, datums, .. } = function.first().unwrap();
+    let PlPgSqlStatement::Block { body, .. } = action else {
+        panic!("Top level function block is not a block");
+    };
+
+    assert!(
+        matches!(
+            &body[..],
+            [
+                PlPgSqlStatement::Unknown(_),
+                PlPgSqlStatement::Perform { .. },
+                PlPgSqlStatement::Return { .. }
+            ]
+        ),
+        "Actions within block are not unknown and perform statements\n{body:#?}"
+    );
+    assert!(matches!(&datums[..], [PlPgSqlVariable::Unknown(_)]));
+    let objects = function.first().unwrap().get_objects().unwrap();
+    assert_eq!(
+        vec![SchemaQualifiedName::from("test_schema.source_table")],
+        objects
+    );
+}
+
+#[rstest::rstest]
+#[case(false)]
+#[case(true)]
+fn skip_unknown_node_should_fail_when_fail_on_unsupported(#[case] fail_on_unsupported: bool) {
+    let node = serde_json::json!({"PLpgSQL_stmt_new": {"lineno": 1}});
+
+    let result = skip_unknown_node("statement", &node, fail_on_unsupported);
+
+    assert_eq!(fail_on_unsupported, result.is_err());
+}
+
+/// Collect the paths of all `.pgsql` files within the `directory` and its subdirectories
+fn collect_sql_files(directory: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_sql_files(&path, paths);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "pgsql")
+        {
+            paths.push(path);
+        }
+    }
+}
+
+/// Variations of the pl/pgsql `function` statement that are also valid pl/pgsql. This includes the
+/// statement with `OR REPLACE`, with another dollar quote tag and with the body nested within an
+/// outer labeled block.
+fn function_variations(function: &str) -> Vec<String> {
+    let mut variations = vec![function.to_string()];
+    let create_regex = regex!(r"^(?i)create\s+(function|procedure)\b");
+    if create_regex.is_match(function) {
+        variations.push(
+            create_regex
+                .replace(function, "CREATE OR REPLACE $1")
+                .into_owned(),
+        );
+    }
+    let Some(tag) = regex!(r"\$([A-Za-z_][A-Za-z_0-9]*)?\$").find(function) else {
+        return variations;
+    };
+    let tag = tag.as_str();
+    let (Some(start), Some(end)) = (function.find(tag), function.rfind(tag)) else {
+        return variations;
+    };
+    if start == end {
+        return variations;
+    }
+    let prefix = &function[..start];
+    let body = &function[(start + tag.len())..end];
+    let suffix = &function[(end + tag.len())..];
+    variations.push(format!("{prefix}$variation${body}$variation${suffix}"));
+    variations.push(format!(
+        "{prefix}{tag}\n<<variation>>\nBEGIN\n{body}\nEND variation;\n{tag}{suffix}"
+    ));
+    variations
+}
+
+#[test]
+fn parse_plpgsql_function_should_parse_every_test_file_function() {
+    let mut paths = vec![];
+    collect_sql_files(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("test-files"),
+        &mut paths,
+    );
+    let plpgsql_regex = regex!(r"(?i)\blanguage\s+'?plpgsql\b");
+    let mut function_count = 0;
+    for path in paths {
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(statements) = pg_query::split_with_parser(&contents) else {
+            continue;
+        };
+        for statement in statements.iter().map(|s| s.trim()) {
+            if !plpgsql_regex.is_match(statement) || pg_query::parse(statement).is_err() {
+                continue;
+            }
+            function_count += 1;
+            for variation in function_variations(statement) {
+                if let Err(error) = parse_plpgsql_function(&variation) {
+                    panic!(
+                        "Could not parse function of {}\n{variation}\n{error}",
+                        path.display()
+                    );
+                }
+            }
+        }
+    }
+
+    assert!(function_count > 0);
+}
@@ -5,21 +5,25 @@ use sqlx::{query_as, PgPool};
 
 use crate::PgDiffError;
 
-use super::{SchemaQualifiedName, SqlObject};
+use super::{
+    fetch_with_retry, optional_expressions_match, RenderContext, SchemaQualifiedName, SqlObject,
+};
 
 pub async fn get_policies(pool: &PgPool, schemas: &[Oid]) -> Result<Vec<Policy>, PgDiffError> {
     let tables_query = include_str!("./../../queries/policies.pgsql");
-    let tables = match query_as(tables_query).bind(schemas).fetch_all(pool).await {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load policies");
-            return Err(error.into());
-        },
-    };
+    let tables =
+        match fetch_with_retry(|| query_as(tables_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load policies");
+                return Err(error.into());
+            },
+        };
     Ok(tables)
 }
 
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Policy {
     pub(crate) table_oid: Oid,
     pub(crate) name: String,
@@ -46,13 +50,27 @@ impl PartialEq for Policy {
             && self.is_permissive == other.is_permissive
             && self.applies_to == other.applies_to
             && self.command == other.command
-            && self.check_expression == other.check_expression
-            && self.using_expression == other.using_expression
+            && optional_expressions_match(
+                self.check_expression.as_deref(),
+                other.check_expression.as_deref(),
+            )
+            && optional_expressions_match(
+                self.using_expression.as_deref(),
+                other.using_expression.as_deref(),
+            )
             && self.columns == other.columns
             && self.dependencies == other.dependencies
     }
 }
 
+impl Policy {
+    /// True if migrating to the `new` policy drops and creates the policy again since the
+    /// permissiveness and command of a policy cannot be altered
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.is_permissive != new.is_permissive || self.command != new.command
+    }
+}
+
 impl SqlObject for Policy {
     fn name(&self) -> &SchemaQualifiedName {
         &self.schema_qualified_name
@@ -91,11 +109,18 @@ impl SqlObject for Policy {
     }
 
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        println!("{:?}", self);
-        println!("{new:?}");
-        if self.is_permissive != new.is_permissive || self.command != new.command {
-            self.drop_statements(w)?;
-            self.create_statements(w)?;
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
             return Ok(());
         }
         write!(
@@ -115,13 +140,24 @@ impl SqlObject for Policy {
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP POLICY {} ON {};", self.name, self.owner_table_name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP POLICY {}{} ON {};",
+            context.if_exists(),
+            self.name,
+            self.owner_table_name
+        )?;
         Ok(())
     }
 }
 
 #[derive(Debug, PartialEq, sqlx::Type, strum::AsRefStr)]
+#[cfg_attr(test, derive(Default))]
 #[sqlx(type_name = "text")]
 pub enum PolicyCommand {
     #[strum(serialize = "SELECT")]
@@ -132,6 +168,102 @@ pub enum PolicyCommand {
     Update,
     #[strum(serialize = "DELETE")]
     Delete,
+    #[cfg_attr(test, default)]
     #[strum(serialize = "ALL")]
     All,
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::postgres::types::Oid;
+
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{Policy, PolicyCommand};
+
+    const SCHEMA: &str = "test_schema";
+    const TABLE: &str = "test_table";
+    const NAME: &str = "test_policy";
+    const ROLE: &str = "test_role";
+
+    fn create_policy(
+        is_permissive: bool,
+        command: PolicyCommand,
+        using_expression: Option<&str>,
+        check_expression: Option<&str>,
+    ) -> Policy {
+        Policy {
+            table_oid: Oid(1),
+            name: NAME.into(),
+            schema_qualified_name: SchemaQualifiedName::from(format!("{SCHEMA}.{TABLE}.{NAME}")),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            is_permissive,
+            applies_to: vec![ROLE.into()],
+            command,
+            check_expression: check_expression.map(String::from),
+            using_expression: using_expression.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_policy(true, PolicyCommand::Select, Some("owner = current_user"), None),
+        include_str!("../../test-files/sql/policy-create-case1.pgsql"),
+    )]
+    #[case(
+        create_policy(
+            false,
+            PolicyCommand::All,
+            Some("owner = current_user"),
+            Some("owner = current_user"),
+        ),
+        include_str!("../../test-files/sql/policy-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_policy_statement(
+        #[case] policy: Policy,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        policy.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_policy(true, PolicyCommand::Select, Some("owner = current_user"), None),
+        create_policy(true, PolicyCommand::Select, Some("owner = session_user"), None),
+        include_str!("../../test-files/sql/policy-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_policy(true, PolicyCommand::Select, Some("owner = current_user"), None),
+        create_policy(true, PolicyCommand::Update, Some("owner = current_user"), None),
+        include_str!("../../test-files/sql/policy-alter-case2.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_policy_statements(
+        #[case] old: Policy,
+        #[case] new: Policy,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_policy_statement() {
+        let policy = create_policy(true, PolicyCommand::Select, None, None);
+        let mut writable = String::new();
+
+        policy.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/policy-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use std::fmt::{Display, Formatter, Write};
+
+use serde::Deserialize;
+use sqlx::{query_as, PgPool};
+
+use crate::{write_join, PgDiffError};
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all publications found within the current database
+pub async fn get_publications(pool: &PgPool) -> Result<Vec<Publication>, PgDiffError> {
+    let publications_query = include_str!("./../../queries/publications.pgsql");
+    let publications = match fetch_with_retry(|| query_as(publications_query).fetch_all(pool)).await
+    {
+        Ok(inner) => inner,
+        Err(error) => {
+            println!("Could not load publications");
+            return Err(error.into());
+        },
+    };
+    Ok(publications)
+}
+
+/// Postgresql publication object used to configure logical replication. Subscriptions are not
+/// supported since they contain connection strings.
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct Publication {
+    /// Full name of the publication (never includes a schema name since publications reside
+    /// outside a schema)
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// True if the publication was created `FOR ALL TABLES`, including tables created later
+    pub(crate) is_all_tables: bool,
+    /// Tables explicitly published, ordered by name
+    #[sqlx(json)]
+    pub(crate) tables: Vec<PublicationTable>,
+    /// Schemas whose tables are all published (i.e. `TABLES IN SCHEMA`), ordered by name
+    pub(crate) schemas: Vec<String>,
+    /// DML operations published to subscribers (i.e. `insert`, `update`, `delete` and/or
+    /// `truncate`)
+    pub(crate) publish: Vec<String>,
+    /// True if changes to partitions are published as if they were made to the partitioned table
+    pub(crate) is_publish_via_partition_root: bool,
+    /// Dependencies of the publication. This is the tables and schemas published.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl Publication {
+    /// True if migrating to the `new` publication drops and creates the publication again (i.e.
+    /// publishing all tables is turned on or off)
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.is_all_tables != new.is_all_tables
+    }
+
+    /// Write the tables and schemas within the `FOR` clause of the publication as well as the
+    /// `ADD` and `SET` clauses of `ALTER PUBLICATION`
+    fn write_publication_objects<W>(
+        w: &mut W,
+        tables: &[&PublicationTable],
+        schemas: &[&String],
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write_join!(w, tables, ", ");
+        if !tables.is_empty() && !schemas.is_empty() {
+            w.write_str(", ")?;
+        }
+        write_join!(
+            w,
+            schemas.iter().map(|s| format!("TABLES IN SCHEMA {s}")),
+            ", "
+        );
+        Ok(())
+    }
+
+    /// Write the `SET` options of the publication
+    fn write_options<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "(publish = '{}', publish_via_partition_root = {})",
+            self.publish.join(", "),
+            self.is_publish_via_partition_root
+        )?;
+        Ok(())
+    }
+
+    /// Write the statements that drop and add the tables and schemas that differ between this
+    /// publication and the `new` publication. If the column list or row filter of a table
+    /// published by both changes, all objects are set at once since those details cannot be
+    /// altered in place.
+    fn alter_publication_objects<W: Write>(
+        &self,
+        new: &Self,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        let is_table_changed = self.tables.iter().any(|old_table| {
+            new.tables
+                .iter()
+                .any(|t| t.table_name == old_table.table_name && t != old_table)
+        });
+        if is_table_changed {
+            write!(w, "ALTER PUBLICATION {} SET ", self.name)?;
+            let tables: Vec<&PublicationTable> = new.tables.iter().collect();
+            let schemas: Vec<&String> = new.schemas.iter().collect();
+            Self::write_publication_objects(w, &tables, &schemas)?;
+            w.write_str(";\n")?;
+            return Ok(());
+        }
+
+        let dropped_tables: Vec<&PublicationTable> = self
+            .tables
+            .iter()
+            .filter(|old_table| {
+                !new.tables
+                    .iter()
+                    .any(|t| t.table_name == old_table.table_name)
+            })
+            .collect();
+        let dropped_schemas: Vec<&String> = self
+            .schemas
+            .iter()
+            .filter(|s| !new.schemas.contains(s))
+            .collect();
+        if !dropped_tables.is_empty() || !dropped_schemas.is_empty() {
+            write!(w, "ALTER PUBLICATION {} DROP ", self.name)?;
+            write_join!(
+                w,
+                dropped_tables
+                    .iter()
+                    .map(|t| format!("TABLE {}", t.table_name)),
+                ", "
+            );
+            if !dropped_tables.is_empty() && !dropped_schemas.is_empty() {
+                w.write_str(", ")?;
+            }
+            write_join!(
+                w,
+                dropped_schemas
+                    .iter()
+                    .map(|s| format!("TABLES IN SCHEMA {s}")),
+                ", "
+            );
+            w.write_str(";\n")?;
+        }
+
+        let added_tables: Vec<&PublicationTable> = new
+            .tables
+            .iter()
+            .filter(|new_table| {
+                !self
+                    .tables
+                    .iter()
+                    .any(|t| t.table_name == new_table.table_name)
+            })
+            .collect();
+        let added_schemas: Vec<&String> = new
+            .schemas
+            .iter()
+            .filter(|s| !self.schemas.contains(s))
+            .collect();
+        if !added_tables.is_empty() || !added_schemas.is_empty() {
+            write!(w, "ALTER PUBLICATION {} ADD ", self.name)?;
+            Self::write_publication_objects(w, &added_tables, &added_schemas)?;
+            w.write_str(";\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl SqlObject for Publication {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "PUBLICATION"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(w, "CREATE PUBLICATION {}", self.name)?;
+        if self.is_all_tables {
+            w.write_str("\nFOR ALL TABLES")?;
+        } else if !self.tables.is_empty() || !self.schemas.is_empty() {
+            w.write_str("\nFOR ")?;
+            let tables: Vec<&PublicationTable> = self.tables.iter().collect();
+            let schemas: Vec<&String> = self.schemas.iter().collect();
+            Self::write_publication_objects(w, &tables, &schemas)?;
+        }
+        w.write_str("\nWITH ")?;
+        self.write_options(w)?;
+        w.write_str(";\n")?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    /// Publications created `FOR ALL TABLES` cannot be changed to publish specific tables (and
+    /// vice versa) so the publication is recreated when that flag changes
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+            return Ok(());
+        }
+
+        self.alter_publication_objects(new, w)?;
+        if self.publish != new.publish
+            || self.is_publish_via_partition_root != new.is_publish_via_partition_root
+        {
+            write!(w, "ALTER PUBLICATION {} SET ", self.name)?;
+            new.write_options(w)?;
+            w.write_str(";\n")?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP PUBLICATION {}{};", context.if_exists(), self.name)?;
+        Ok(())
+    }
+}
+
+/// Table published by a [Publication]
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PublicationTable {
+    /// Full name of the published table
+    pub(crate) table_name: SchemaQualifiedName,
+    /// Columns published. [None] if all columns are published.
+    pub(crate) columns: Option<Vec<String>>,
+    /// Expression that rows must satisfy to be published as found by `pg_catalog.pg_get_expr`.
+    /// [None] if all rows are published.
+    pub(crate) row_filter: Option<String>,
+}
+
+impl Display for PublicationTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TABLE {}", self.table_name)?;
+        if let Some(columns) = &self.columns {
+            write!(f, " ({})", columns.join(", "))?;
+        }
+        if let Some(row_filter) = &self.row_filter {
+            write!(f, " WHERE ({row_filter})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{Publication, PublicationTable};
+
+    const SCHEMA: &str = "test_schema";
+    const OTHER_SCHEMA: &str = "other_test_schema";
+    const NAME: &str = "test_publication";
+    const TABLE: &str = "test_table";
+    const OTHER_TABLE: &str = "other_test_table";
+
+    fn create_publication_table(
+        table_name: &str,
+        columns: Option<&[&str]>,
+        row_filter: Option<&str>,
+    ) -> PublicationTable {
+        PublicationTable {
+            table_name: SchemaQualifiedName::new(SCHEMA, table_name),
+            columns: columns.map(|c| c.iter().map(|c| c.to_string()).collect()),
+            row_filter: row_filter.map(String::from),
+        }
+    }
+
+    fn create_publication(tables: Vec<PublicationTable>, schemas: &[&str]) -> Publication {
+        Publication {
+            name: SchemaQualifiedName::new("", NAME),
+            is_all_tables: false,
+            tables,
+            schemas: schemas.iter().map(|s| s.to_string()).collect(),
+            publish: vec![
+                "insert".into(),
+                "update".into(),
+                "delete".into(),
+                "truncate".into(),
+            ],
+            is_publish_via_partition_root: false,
+            dependencies: vec![],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        Publication {
+            is_all_tables: true,
+            ..create_publication(vec![], &[])
+        },
+        include_str!("../../test-files/sql/publication-create-case1.pgsql"),
+    )]
+    #[case(
+        Publication {
+            publish: vec!["insert".into(), "update".into()],
+            ..create_publication(
+                vec![
+                    create_publication_table(OTHER_TABLE, None, None),
+                    create_publication_table(TABLE, Some(&["id", "value"]), Some("(value > 5)")),
+                ],
+                &[],
+            )
+        },
+        include_str!("../../test-files/sql/publication-create-case2.pgsql"),
+    )]
+    #[case(
+        create_publication(vec![], &[OTHER_SCHEMA, SCHEMA]),
+        include_str!("../../test-files/sql/publication-create-case3.pgsql"),
+    )]
+    #[case(
+        create_publication(vec![], &[]),
+        include_str!("../../test-files/sql/publication-create-case4.pgsql"),
+    )]
+    fn create_statements_should_add_create_publication_statement(
+        #[case] publication: Publication,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        publication.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_publication(vec![create_publication_table(TABLE, None, None)], &[]),
+        create_publication(vec![create_publication_table(OTHER_TABLE, None, None)], &[SCHEMA]),
+        include_str!("../../test-files/sql/publication-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_publication(
+            vec![
+                create_publication_table(OTHER_TABLE, None, None),
+                create_publication_table(TABLE, None, None),
+            ],
+            &[],
+        ),
+        create_publication(
+            vec![
+                create_publication_table(OTHER_TABLE, None, None),
+                create_publication_table(TABLE, None, Some("(value > 5)")),
+            ],
+            &[],
+        ),
+        include_str!("../../test-files/sql/publication-alter-case2.pgsql"),
+    )]
+    #[case(
+        create_publication(vec![], &[SCHEMA]),
+        Publication {
+            publish: vec!["insert".into()],
+            is_publish_via_partition_root: true,
+            ..create_publication(vec![], &[SCHEMA])
+        },
+        include_str!("../../test-files/sql/publication-alter-case3.pgsql"),
+    )]
+    #[case(
+        create_publication(vec![create_publication_table(TABLE, None, None)], &[]),
+        Publication {
+            is_all_tables: true,
+            ..create_publication(vec![], &[])
+        },
+        include_str!("../../test-files/sql/publication-alter-case4.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_publication_statements(
+        #[case] old: Publication,
+        #[case] new: Publication,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_publication_statement() {
+        let publication = create_publication(vec![], &[]);
+        let mut writable = String::new();
+
+        publication.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/publication-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt::Write;
+
+use pg_query::protobuf::{node::Node, CreateRoleStmt, RoleStmtType};
+use sqlx::{query_as, PgPool};
+
+use crate::{write_join, PgDiffError};
+
+use super::{fetch_with_retry, quote_ident, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all non-superuser roles of the database cluster. Built-in roles (i.e. roles prefixed with
+/// `pg_`) are not included.
+pub async fn get_roles(pool: &PgPool) -> Result<Vec<Role>, PgDiffError> {
+    let roles_query = include_str!("./../../queries/roles.pgsql");
+    let roles = match fetch_with_retry(|| query_as(roles_query).fetch_all(pool)).await {
+        Ok(inner) => inner,
+        Err(error) => {
+            println!("Could not load roles");
+            return Err(error.into());
+        },
+    };
+    Ok(roles)
+}
+
+/// Postgresql role object. Roles exist at the cluster level so the name never includes a schema.
+///
+/// Passwords are never scripted or compared. Roles that require a password must have the password
+/// set outside of this tool.
+#[derive(Debug, Clone, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct Role {
+    /// Full name of the role (never includes a schema name)
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// True if the role can be used to log in (i.e. the role is a user)
+    pub(crate) can_login: bool,
+    /// True if the role inherits the privileges of the roles it's a member of
+    pub(crate) inherit: bool,
+    /// Names of the roles this role is a member of, in name order
+    pub(crate) member_of: Vec<String>,
+    /// Dependencies of the role. This is only ever populated with the non-superuser roles this
+    /// role is a member of.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl PartialEq for Role {
+    #[inline]
+    fn eq(&self, other: &Role) -> bool {
+        self.name == other.name
+            && self.can_login == other.can_login
+            && self.inherit == other.inherit
+            && self.member_of == other.member_of
+    }
+}
+
+impl Role {
+    /// Create a new [Role] from a `CREATE ROLE` (or `CREATE USER`/`CREATE GROUP`) statement found
+    /// within the source control files. Options other than `LOGIN`, `INHERIT` and `IN ROLE` are
+    /// ignored. Dependencies are not populated since the roles that are managed must be known
+    /// (see [Role::resolve_dependencies]). Role names are quoted the same way as scraped role
+    /// names (see [quote_ident]).
+    pub fn from_create_statement(create_role: &CreateRoleStmt) -> Self {
+        let mut role = Self {
+            name: SchemaQualifiedName::new("", &quote_ident(&create_role.role)),
+            can_login: create_role.stmt_type() == RoleStmtType::RolestmtUser,
+            inherit: true,
+            member_of: vec![],
+            dependencies: vec![],
+        };
+        for option in &create_role.options {
+            let Some(Node::DefElem(def_element)) = &option.node else {
+                continue;
+            };
+            let argument = def_element.arg.as_deref().and_then(|a| a.node.as_ref());
+            match (def_element.defname.as_str(), argument) {
+                ("canlogin", Some(Node::Boolean(value))) => role.can_login = value.boolval,
+                ("inherit", Some(Node::Boolean(value))) => role.inherit = value.boolval,
+                ("addroleto", Some(Node::List(roles))) => {
+                    role.member_of = roles
+                        .items
+                        .iter()
+                        .filter_map(|n| match &n.node {
+                            Some(Node::RoleSpec(role_spec)) => {
+                                Some(quote_ident(&role_spec.rolename))
+                            },
+                            _ => None,
+                        })
+                        .collect();
+                    role.member_of.sort();
+                },
+                _ => {},
+            }
+        }
+        role
+    }
+
+    /// Populate the dependencies of each role within `roles` using the roles it's a member of.
+    /// Only roles found within `roles` are included since other roles are not managed.
+    pub fn resolve_dependencies(roles: &mut [Role]) {
+        let names: Vec<SchemaQualifiedName> = roles.iter().map(|r| r.name.clone()).collect();
+        for role in roles.iter_mut() {
+            role.dependencies = role
+                .member_of
+                .iter()
+                .map(|r| SchemaQualifiedName::new("", r))
+                .filter(|r| names.contains(r))
+                .collect();
+        }
+    }
+
+    /// Write the role options (e.g. `LOGIN`) of this role
+    fn write_options<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "{}LOGIN {}INHERIT",
+            if self.can_login { "" } else { "NO" },
+            if self.inherit { "" } else { "NO" }
+        )?;
+        Ok(())
+    }
+}
+
+impl SqlObject for Role {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "ROLE"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(w, "CREATE ROLE {} WITH ", self.name)?;
+        self.write_options(w)?;
+        if !self.member_of.is_empty() {
+            let member_of = &self.member_of;
+            write_join!(w, " IN ROLE ", member_of, ", ", "");
+        }
+        w.write_str(";\n")?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        if self.can_login != new.can_login || self.inherit != new.inherit {
+            write!(w, "ALTER ROLE {} WITH ", self.name)?;
+            new.write_options(w)?;
+            w.write_str(";\n")?;
+        }
+        for role in new.member_of.iter().filter(|r| !self.member_of.contains(r)) {
+            writeln!(w, "GRANT {role} TO {};", self.name)?;
+        }
+        for role in self.member_of.iter().filter(|r| !new.member_of.contains(r)) {
+            writeln!(w, "REVOKE {role} FROM {};", self.name)?;
+        }
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP ROLE {}{};", context.if_exists(), self.name)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pg_query::NodeEnum;
+
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::Role;
+
+    const NAME: &str = "app_reader";
+
+    fn create_role(can_login: bool, inherit: bool, member_of: &[&str]) -> Role {
+        Role {
+            name: SchemaQualifiedName::new("", NAME),
+            can_login,
+            inherit,
+            member_of: member_of.iter().map(|r| r.to_string()).collect(),
+            dependencies: vec![],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_role(false, true, &[]),
+        include_str!("../../test-files/sql/role-create-case1.pgsql"),
+    )]
+    #[case(
+        create_role(true, false, &["app_base", "app_read"]),
+        include_str!("../../test-files/sql/role-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_role_statement(
+        #[case] role: Role,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        role.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_alter_options_and_memberships() {
+        let old = create_role(false, true, &["app_base", "app_write"]);
+        let new = create_role(true, true, &["app_base", "app_read"]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/role-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_role_statement() {
+        let role = create_role(false, true, &[]);
+        let mut writable = String::new();
+
+        role.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/role-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("CREATE ROLE app_reader;", create_role(false, true, &[]))]
+    #[case(
+        "CREATE ROLE app_reader WITH LOGIN NOINHERIT PASSWORD 'secret' IN ROLE app_read, app_base;",
+        create_role(true, false, &["app_base", "app_read"]),
+    )]
+    #[case("CREATE USER app_reader;", create_role(true, true, &[]))]
+    #[case(
+        "CREATE ROLE \"AppReader\" IN ROLE \"AppBase\";",
+        Role {
+            name: SchemaQualifiedName::new("", "\"AppReader\""),
+            ..create_role(false, true, &["\"AppBase\""])
+        },
+    )]
+    fn from_create_statement_should_parse_role_options(
+        #[case] statement: &str,
+        #[case] expected: Role,
+    ) {
+        let result = pg_query::parse(statement).unwrap();
+        let Some(NodeEnum::CreateRoleStmt(create_role)) = result
+            .protobuf
+            .stmts
+            .first()
+            .and_then(|s| s.stmt.as_ref())
+            .and_then(|n| n.node.as_ref())
+        else {
+            panic!("Statement should be a CREATE ROLE statement");
+        };
+
+        let role = Role::from_create_statement(create_role);
+
+        assert_eq!(expected, role);
+    }
+
+    #[test]
+    fn resolve_dependencies_should_only_include_managed_roles() {
+        let mut roles = vec![
+            create_role(false, true, &["app_base", "unmanaged"]),
+            Role {
+                name: SchemaQualifiedName::new("", "app_base"),
+                ..Default::default()
+            },
+        ];
+
+        Role::resolve_dependencies(&mut roles);
+
+        assert_eq!(
+            vec![SchemaQualifiedName::new("", "app_base")],
+            roles[0].dependencies
+        );
+        assert!(roles[1].dependencies.is_empty());
+    }
+}
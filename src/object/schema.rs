@@ -5,7 +5,7 @@ use sqlx::{query_as, FromRow, PgPool, Row};
 
 use crate::PgDiffError;
 
-use super::{SchemaQualifiedName, SqlObject};
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
 
 /// Fetch all schemas found within the current database (including the `public` schema).
 ///
@@ -13,7 +13,7 @@ use super::{SchemaQualifiedName, SqlObject};
 /// `^pg_temp`. These schemas always exist but should not be analyzed.  
 pub async fn get_schemas(pool: &PgPool) -> Result<Vec<Schema>, PgDiffError> {
     let schemas_query = include_str!("./../../queries/schemas.pgsql");
-    let schema_names = match query_as(schemas_query).fetch_all(pool).await {
+    let schema_names = match fetch_with_retry(|| query_as(schemas_query).fetch_all(pool)).await {
         Ok(inner) => inner,
         Err(error) => {
             println!("Could not load schemas");
@@ -25,6 +25,7 @@ pub async fn get_schemas(pool: &PgPool) -> Result<Vec<Schema>, PgDiffError> {
 
 /// Struct representing a schema SQL object
 #[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Default))]
 pub struct Schema {
     /// Name of the schema. Local part is always empty
     pub(crate) name: SchemaQualifiedName,
@@ -46,6 +47,24 @@ impl<'r> FromRow<'r, PgRow> for Schema {
     }
 }
 
+impl Schema {
+    /// Write a `DROP SCHEMA ... CASCADE` statement for this schema. This drops every object
+    /// contained within the schema as well. Includes `IF EXISTS` when the `context` is idempotent.
+    pub fn drop_cascade_statements<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP SCHEMA {}{} CASCADE;",
+            context.if_exists(),
+            self.name
+        )?;
+        Ok(())
+    }
+}
+
 impl SqlObject for Schema {
     fn name(&self) -> &SchemaQualifiedName {
         &self.name
@@ -68,13 +87,26 @@ impl SqlObject for Schema {
         Ok(())
     }
 
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "CREATE SCHEMA IF NOT EXISTS {} AUTHORIZATION {};",
+            self.name, self.owner
+        )?;
+        Ok(())
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
         writeln!(w, "ALTER SCHEMA {} OWNER TO {};", self.name, new.owner)?;
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP SCHEMA {};", self.name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP SCHEMA {}{};", context.if_exists(), self.name)?;
         Ok(())
     }
 
@@ -82,3 +114,74 @@ impl SqlObject for Schema {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::Schema;
+
+    const NAME: &str = "test_schema";
+    const OWNER: &str = "test_owner";
+    const OTHER_OWNER: &str = "other_test_owner";
+
+    fn create_schema(owner: &str) -> Schema {
+        Schema {
+            name: SchemaQualifiedName::from(NAME),
+            owner: owner.into(),
+        }
+    }
+
+    #[test]
+    fn create_statements_should_add_create_schema_statement() {
+        let schema = create_schema(OWNER);
+        let mut writable = String::new();
+
+        schema.create_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/schema-create.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_if_not_exists() {
+        let schema = create_schema(OWNER);
+        let mut writable = String::new();
+
+        schema.create_statements_idempotent(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/schema-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_add_alter_schema_owner_statement() {
+        let old = create_schema(OWNER);
+        let new = create_schema(OTHER_OWNER);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/schema-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_schema_statement() {
+        let schema = create_schema(OWNER);
+        let mut writable = String::new();
+
+        schema.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/schema-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -7,24 +7,28 @@ use sqlx::{query_as, FromRow, PgPool, Row};
 
 use crate::PgDiffError;
 
-use super::{SchemaQualifiedName, SqlObject};
+use super::{
+    fetch_with_retry, is_no_sequence_values, RenderContext, SchemaQualifiedName, SqlObject,
+};
 
 /// Fetch all sequences found within the schemas referenced. Ignores any index that is created when
 /// an identity column exists.
 pub async fn get_sequences(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Sequence>, PgDiffError> {
     let sequence_query = include_str!("./../../queries/sequences.pgsql");
-    let sequences = match query_as(sequence_query).bind(schemas).fetch_all(pool).await {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load sequences");
-            return Err(error.into());
-        },
-    };
+    let sequences =
+        match fetch_with_retry(|| query_as(sequence_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load sequences");
+                return Err(error.into());
+            },
+        };
     Ok(sequences)
 }
 
 /// Struct representing a SQL sequence object
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(test, derive(Default))]
 pub struct Sequence {
     /// Full name of the sequence
     pub(crate) name: SchemaQualifiedName,
@@ -40,6 +44,13 @@ pub struct Sequence {
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
 }
 
+impl PartialEq for Sequence {
+    #[inline]
+    fn eq(&self, other: &Sequence) -> bool {
+        self.matches(other, !is_no_sequence_values())
+    }
+}
+
 impl<'r> FromRow<'r, PgRow> for Sequence {
     fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
         let name: Json<SchemaQualifiedName> = row.try_get("name")?;
@@ -64,6 +75,47 @@ impl<'r> FromRow<'r, PgRow> for Sequence {
     }
 }
 
+impl Sequence {
+    /// True if this sequence is equal to the `other` sequence. The start value is only compared
+    /// when `include_values` is true.
+    fn matches(&self, other: &Sequence, include_values: bool) -> bool {
+        self.name == other.name
+            && self.data_type == other.data_type
+            && self.owner == other.owner
+            && self
+                .sequence_options
+                .matches(&other.sequence_options, include_values)
+            && self.dependencies == other.dependencies
+    }
+
+    /// Write the `CREATE` statement to the writable object. Includes the `IF NOT EXISTS` clause
+    /// when `if_not_exists` is true and the `START` option when `include_values` is true.
+    fn create_statement<W>(
+        &self,
+        w: &mut W,
+        if_not_exists: bool,
+        include_values: bool,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "CREATE SEQUENCE {}{} AS {} ",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.name,
+            self.data_type,
+        )?;
+        self.sequence_options.write_options(w, include_values)?;
+        if let Some(owner) = &self.owner {
+            writeln!(w, " {owner};")?;
+        } else {
+            w.write_str(" OWNED BY NONE;\n")?;
+        }
+        Ok(())
+    }
+}
+
 impl SqlObject for Sequence {
     fn name(&self) -> &SchemaQualifiedName {
         &self.name
@@ -78,21 +130,53 @@ impl SqlObject for Sequence {
     }
 
     fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        write!(
-            w,
-            "CREATE SEQUENCE {} AS {} {}",
-            self.name, self.data_type, self.sequence_options,
-        )?;
-        if let Some(owner) = &self.owner {
-            writeln!(w, " {owner};")?;
-        } else {
-            w.write_str(" OWNED BY NONE;\n")?;
+        self.create_statement(w, false, !is_no_sequence_values())
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, true, !is_no_sequence_values())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.write_alter_statements(new, w, !is_no_sequence_values())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP SEQUENCE {}{};", context.if_exists(), self.name)?;
+        Ok(())
+    }
+}
+
+impl Sequence {
+    /// Write the `ALTER SEQUENCE` statement required to migrate to the `new` sequence. Changes to
+    /// the start value are only included when `include_values` is true. Nothing is written if no
+    /// options have changed.
+    fn write_alter_statements<W: Write>(
+        &self,
+        new: &Self,
+        w: &mut W,
+        include_values: bool,
+    ) -> Result<(), PgDiffError> {
+        let mut options = String::new();
+        self.write_alter_options(new, &mut options, include_values)?;
+        if !options.is_empty() {
+            writeln!(w, "ALTER SEQUENCE {}{options};", self.name)?;
         }
         Ok(())
     }
 
-    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        write!(w, "ALTER SEQUENCE {}", self.name)?;
+    /// Write each option clause of the `ALTER SEQUENCE` statement that migrates to the `new`
+    /// sequence
+    fn write_alter_options<W: Write>(
+        &self,
+        new: &Self,
+        w: &mut W,
+        include_values: bool,
+    ) -> Result<(), PgDiffError> {
         if self.data_type != new.data_type {
             write!(w, " AS {}", new.data_type)?;
         }
@@ -105,7 +189,7 @@ impl SqlObject for Sequence {
         if self.sequence_options.max_value != new.sequence_options.max_value {
             write!(w, " MAXVALUE {}", new.sequence_options.max_value)?;
         }
-        if self.sequence_options.start_value != new.sequence_options.start_value {
+        if include_values && self.sequence_options.start_value != new.sequence_options.start_value {
             write!(w, " START WITH {}", new.sequence_options.start_value)?;
         }
         if self.sequence_options.cache != new.sequence_options.cache {
@@ -124,28 +208,23 @@ impl SqlObject for Sequence {
         }
         match (&self.owner, &new.owner) {
             (Some(old_owner), Some(new_owner)) if old_owner != new_owner => {
-                write!(w, " OWNED BY {new_owner}")?;
+                write!(w, " {new_owner}")?;
             },
             (Some(_), None) => {
                 w.write_str(" OWNED BY NONE")?;
             },
             (None, Some(new_owner)) => {
-                write!(w, " OWNED BY {new_owner}")?;
+                write!(w, " {new_owner}")?;
             },
             _ => {},
         }
-        w.write_str(";\n")?;
-        Ok(())
-    }
-
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP SEQUENCE {};", self.name)?;
         Ok(())
     }
 }
 
 /// Options that can be specified for a sequence
 #[derive(Debug, Deserialize, PartialEq)]
+#[cfg_attr(test, derive(Default))]
 pub struct SequenceOptions {
     /// Value to increment the sequence after value fetching. This value can be negative.
     pub(crate) increment: i64,
@@ -163,20 +242,41 @@ pub struct SequenceOptions {
 
 impl Display for SequenceOptions {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_options(f, true)
+    }
+}
+
+impl SequenceOptions {
+    /// True if these options are equal to the `other` options. The start value is only compared
+    /// when `include_values` is true.
+    fn matches(&self, other: &SequenceOptions, include_values: bool) -> bool {
+        self.increment == other.increment
+            && self.min_value == other.min_value
+            && self.max_value == other.max_value
+            && (!include_values || self.start_value == other.start_value)
+            && self.cache == other.cache
+            && self.is_cycle == other.is_cycle
+    }
+
+    /// Write the options as found within a `CREATE SEQUENCE` statement. The `START` option is
+    /// only included when `include_values` is true.
+    fn write_options<W: Write>(&self, w: &mut W, include_values: bool) -> std::fmt::Result {
         write!(
-            f,
-            "INCREMENT {} MINVALUE {} MAXVALUE {} START {} CACHE {} {} CYCLE",
-            self.increment,
-            self.min_value,
-            self.max_value,
-            self.start_value,
+            w,
+            "INCREMENT {} MINVALUE {} MAXVALUE {}",
+            self.increment, self.min_value, self.max_value,
+        )?;
+        if include_values {
+            write!(w, " START {}", self.start_value)?;
+        }
+        write!(
+            w,
+            " CACHE {} {} CYCLE",
             self.cache,
             if self.is_cycle { "" } else { "NO" }
         )
     }
-}
 
-impl SequenceOptions {
     pub fn alter_sequence<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
         write!(
             w,
@@ -206,3 +306,170 @@ impl Display for SequenceOwner {
         write!(f, "OWNED BY {}.{}", self.table_name, self.column_name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{Sequence, SequenceOptions, SequenceOwner};
+
+    const SCHEMA: &str = "test_schema";
+    const NAME: &str = "test_sequence";
+    const TABLE: &str = "test_table";
+    const COLUMN: &str = "test_col";
+
+    fn create_sequence(
+        sequence_options: SequenceOptions,
+        owner: Option<SequenceOwner>,
+    ) -> Sequence {
+        Sequence {
+            name: SchemaQualifiedName::new(SCHEMA, NAME),
+            data_type: "bigint".into(),
+            owner,
+            sequence_options,
+            dependencies: vec![],
+        }
+    }
+
+    fn default_options() -> SequenceOptions {
+        SequenceOptions {
+            increment: 1,
+            min_value: 1,
+            max_value: 9223372036854775807,
+            start_value: 1,
+            cache: 1,
+            is_cycle: false,
+        }
+    }
+
+    fn create_owner() -> SequenceOwner {
+        SequenceOwner {
+            table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            column_name: COLUMN.into(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_sequence(default_options(), None),
+        include_str!("../../test-files/sql/sequence-create-case1.pgsql"),
+    )]
+    #[case(
+        create_sequence(
+            SequenceOptions {
+                increment: 5,
+                cache: 10,
+                is_cycle: true,
+                ..default_options()
+            },
+            Some(create_owner()),
+        ),
+        include_str!("../../test-files/sql/sequence-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_sequence_statement(
+        #[case] sequence: Sequence,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        sequence.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_if_not_exists() {
+        let sequence = create_sequence(default_options(), None);
+        let mut writable = String::new();
+
+        sequence
+            .create_statements_idempotent(&mut writable)
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/sequence-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_sequence(default_options(), None),
+        create_sequence(
+            SequenceOptions {
+                increment: 5,
+                min_value: 10,
+                max_value: 1000,
+                start_value: 10,
+                cache: 10,
+                is_cycle: true,
+            },
+            Some(create_owner()),
+        ),
+        include_str!("../../test-files/sql/sequence-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_sequence(default_options(), Some(create_owner())),
+        create_sequence(default_options(), None),
+        include_str!("../../test-files/sql/sequence-alter-case2.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_sequence_statement(
+        #[case] old: Sequence,
+        #[case] new: Sequence,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn create_statement_should_omit_start_when_values_are_excluded() {
+        let sequence = create_sequence(default_options(), None);
+        let mut writable = String::new();
+
+        sequence
+            .create_statement(&mut writable, false, false)
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/sequence-create-no-values.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn sequences_should_not_differ_by_start_value_when_values_are_excluded() {
+        let old = create_sequence(default_options(), None);
+        let new = create_sequence(
+            SequenceOptions {
+                start_value: 1000,
+                ..default_options()
+            },
+            None,
+        );
+        let mut writable = String::new();
+
+        old.write_alter_statements(&new, &mut writable, false)
+            .unwrap();
+
+        assert!(old.matches(&new, false));
+        assert!(!old.matches(&new, true));
+        assert!(writable.is_empty());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_sequence_statement() {
+        let sequence = create_sequence(default_options(), None);
+        let mut writable = String::new();
+
+        sequence.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/sequence-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt::Write;
+
+use sqlx::postgres::types::Oid;
+use sqlx::{query_as, PgPool};
+
+use crate::{write_join, PgDiffError};
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all extended statistics objects associated with the tables specified (as table OID)
+pub async fn get_statistics(pool: &PgPool, tables: &[Oid]) -> Result<Vec<Statistics>, PgDiffError> {
+    let statistics_query = include_str!("./../../queries/statistics.pgsql");
+    let statistics =
+        match fetch_with_retry(|| query_as(statistics_query).bind(tables).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load statistics");
+                return Err(error.into());
+            },
+        };
+    Ok(statistics)
+}
+
+/// Struct representing a SQL extended statistics object
+#[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct Statistics {
+    /// Owner table's OID
+    pub(crate) table_oid: Oid,
+    /// Full name of the statistics object
+    #[sqlx(json)]
+    pub(crate) schema_qualified_name: SchemaQualifiedName,
+    /// Owner table's full name
+    #[sqlx(json)]
+    pub(crate) owner_table_name: SchemaQualifiedName,
+    /// Statistics kinds enabled for the object (i.e. `ndistinct`, `dependencies` and/or `mcv`).
+    /// Statistics of expressions are always built so they are never included.
+    pub(crate) kinds: Vec<String>,
+    /// Columns and expressions covered by the statistics object as found by
+    /// `pg_catalog.pg_get_statisticsobjdef_columns`
+    pub(crate) columns: String,
+    /// Dependencies of the statistics object. This is always the owner table.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl PartialEq for Statistics {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema_qualified_name == other.schema_qualified_name
+            && self.owner_table_name == other.owner_table_name
+            && self.kinds == other.kinds
+            && self.columns == other.columns
+    }
+}
+
+impl Statistics {
+    /// Write the `CREATE STATISTICS` statement, adding `IF NOT EXISTS` if `if_not_exists` is true
+    fn write_create_statement<W>(&self, w: &mut W, if_not_exists: bool) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "CREATE STATISTICS {}{}",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.schema_qualified_name
+        )?;
+        if !self.kinds.is_empty() {
+            write_join!(w, " (", self.kinds.iter(), ", ", ")");
+        }
+        writeln!(w, " ON {} FROM {};", self.columns, self.owner_table_name)?;
+        Ok(())
+    }
+}
+
+impl SqlObject for Statistics {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.schema_qualified_name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "STATISTICS"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.write_create_statement(w, false)
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.write_create_statement(w, true)
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    /// Statistics objects cannot have their definition altered so the object is always dropped
+    /// and created again
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        self.drop_statements_with(context, w)?;
+        new.create_statements_with(context, w)
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP STATISTICS {}{};",
+            context.if_exists(),
+            self.schema_qualified_name
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::postgres::types::Oid;
+
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::Statistics;
+
+    const SCHEMA: &str = "test_schema";
+    const TABLE: &str = "test_table";
+    const NAME: &str = "test_statistics";
+
+    fn create_statistics(kinds: &[&str], columns: &str) -> Statistics {
+        Statistics {
+            table_oid: Oid(1),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, NAME),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            kinds: kinds.iter().map(|k| k.to_string()).collect(),
+            columns: columns.into(),
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_statistics(&["ndistinct", "dependencies"], "col_a, col_b"),
+        include_str!("../../test-files/sql/statistics-create-case1.pgsql"),
+    )]
+    #[case(
+        create_statistics(&[], "(col_a + col_b)"),
+        include_str!("../../test-files/sql/statistics-create-case2.pgsql"),
+    )]
+    fn create_statements_should_add_create_statistics_statement(
+        #[case] statistics: Statistics,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        statistics.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_if_not_exists() {
+        let statistics = create_statistics(&["mcv"], "col_a, col_b");
+        let mut writable = String::new();
+
+        statistics
+            .create_statements_idempotent(&mut writable)
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/statistics-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_drop_and_create_statistics() {
+        let old = create_statistics(&["ndistinct"], "col_a, col_b");
+        let new = Statistics {
+            table_oid: Oid(2),
+            ..create_statistics(&["ndistinct", "dependencies"], "col_a, col_b")
+        };
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/statistics-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn eq_should_ignore_table_oid() {
+        let old = create_statistics(&["ndistinct"], "col_a, col_b");
+        let new = Statistics {
+            table_oid: Oid(2),
+            ..create_statistics(&["ndistinct"], "col_a, col_b")
+        };
+
+        assert_eq!(old, new);
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_statistics_statement() {
+        let statistics = create_statistics(&[], "col_a, col_b");
+        let mut writable = String::new();
+
+        statistics.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/statistics-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
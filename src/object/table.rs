@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter, Write};
 
+use pg_query::protobuf::a_const;
+use pg_query::protobuf::node::Node;
 use serde::Deserialize;
 use sqlx::postgres::types::Oid;
 use sqlx::postgres::PgRow;
@@ -8,43 +10,55 @@ use sqlx::{query_as, FromRow, PgPool, Row};
 
 use crate::{map_join_slice, write_join, PgDiffError};
 
+use super::function::get_functions_by_qualified_name;
 use super::sequence::SequenceOptions;
 use super::{
-    check_names_in_database, compare_tablespaces, Collation, SchemaQualifiedName, SqlObject,
-    StorageParameters, TableSpace,
+    check_names_in_database, compare_key_value_pairs, compare_tablespaces, expressions_match,
+    fetch_with_retry, is_built_in_name, optional_expressions_match, resolve_with_search_path,
+    write_acl_changes, AclGrant, Collation, Constraint, RenderContext, SchemaQualifiedName,
+    SqlObject, StorageParameters, TableSpace, PG_CATALOG_SCHEMA_NAME,
 };
 
 /// Fetch all tables that are found in the specified schemas.
 pub async fn get_tables(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Table>, PgDiffError> {
     let tables_query = include_str!("./../../queries/tables.pgsql");
-    let tables = match query_as(tables_query).bind(schemas).fetch_all(pool).await {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load tables");
-            return Err(error.into());
-        },
-    };
+    let tables =
+        match fetch_with_retry(|| query_as(tables_query).bind(schemas).fetch_all(pool)).await {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load tables");
+                return Err(error.into());
+            },
+        };
     Ok(tables)
 }
 
-/// Fetch all tables that could be associated with the provided qualified name
+/// Fetch all tables that could be associated with the provided qualified name. Unqualified names
+/// are searched for within the `search_path` if available.
 pub async fn get_table_by_qualified_name(
     pool: &PgPool,
     schema_qualified_name: &SchemaQualifiedName,
+    search_path: Option<&[String]>,
 ) -> Result<Vec<SchemaQualifiedName>, PgDiffError> {
     let tables_query = include_str!("./../../queries/dependency_tables.pgsql");
-    let tables = match check_names_in_database(pool, schema_qualified_name, tables_query).await {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load tables by qualified name");
-            return Err(error.into());
-        },
-    };
+    let tables =
+        match check_names_in_database(pool, schema_qualified_name, tables_query, search_path).await
+        {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load tables by qualified name");
+                return Err(error.into());
+            },
+        };
     Ok(tables)
 }
 
+/// Functions that accept the sequence they operate on as the first argument
+const SEQUENCE_FUNCTIONS: &[&str] = &["nextval", "currval", "setval"];
+
 /// Struct representing a SQL table
 #[derive(Debug)]
+#[cfg_attr(test, derive(Default))]
 pub struct Table {
     /// OID of the table
     pub(crate) oid: Oid,
@@ -65,6 +79,11 @@ pub struct Table {
     pub(crate) tablespace: Option<TableSpace>,
     /// Optional storage parameters for this table
     pub(crate) with: Option<StorageParameters>,
+    /// Role that owns this table. Table ownership is neither scripted nor compared but the owner
+    /// must exist for the table to be created as the owner.
+    pub(crate) owner: String,
+    /// Row identity information recorded for logical replication
+    pub(crate) replica_identity: ReplicaIdentity,
     /// Dependencies of this table
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
 }
@@ -80,6 +99,7 @@ impl PartialEq for Table {
             && self.partitioned_parent_table == other.partitioned_parent_table
             && self.tablespace == other.tablespace
             && self.with == other.with
+            && self.replica_identity == other.replica_identity
             && self.dependencies == other.dependencies
     }
 }
@@ -97,6 +117,8 @@ impl<'r> FromRow<'r, PgRow> for Table {
             row.try_get("partitioned_parent_table")?;
         let tablespace: Option<TableSpace> = row.try_get("tablespace")?;
         let with: Option<StorageParameters> = row.try_get("with")?;
+        let owner: String = row.try_get("owner")?;
+        let replica_identity: Json<ReplicaIdentity> = row.try_get("replica_identity")?;
         let dependencies: Json<Vec<SchemaQualifiedName>> = row.try_get("dependencies")?;
         Ok(Self {
             oid,
@@ -108,26 +130,169 @@ impl<'r> FromRow<'r, PgRow> for Table {
             partitioned_parent_table: partitioned_parent_table.map(|j| j.0),
             tablespace,
             with,
+            owner,
+            replica_identity: replica_identity.0,
             dependencies: dependencies.0,
         })
     }
 }
 
-impl SqlObject for Table {
-    fn name(&self) -> &SchemaQualifiedName {
-        &self.name
+impl Table {
+    /// Add the sequences and functions referenced by the default expressions of this table's
+    /// columns as dependencies. The catalog does not track these references as dependencies of the
+    /// table so, without this, a table could be created before the objects its defaults require.
+    ///
+    /// Unqualified names are resolved against the `search_path` (see
+    /// [Table::add_default_dependency]). Sequences within `owned_sequences` (i.e. sequences owned
+    /// by this table such as `serial` sequences) are skipped since those sequences already depend
+    /// on this table.
+    ///
+    /// ## Errors
+    /// If searching the database for the referenced objects fails
+    pub async fn extract_default_dependencies(
+        &mut self,
+        pool: &PgPool,
+        search_path: Option<&[String]>,
+        owned_sequences: &[&SchemaQualifiedName],
+    ) -> Result<(), PgDiffError> {
+        let (sequences, functions) = self.default_expression_references();
+        for sequence_name in sequences {
+            let sequences = get_table_by_qualified_name(pool, &sequence_name, search_path).await?;
+            self.add_default_dependency(&sequence_name, sequences, search_path, owned_sequences);
+        }
+        for function_name in functions {
+            let functions =
+                get_functions_by_qualified_name(pool, &function_name, search_path).await?;
+            self.add_default_dependency(&function_name, functions, search_path, owned_sequences);
+        }
+        Ok(())
     }
 
-    fn object_type_name(&self) -> &str {
-        "TABLE"
+    /// Names of the sequences (the first argument of [SEQUENCE_FUNCTIONS] calls) and functions
+    /// referenced by the default expressions of this table's columns. Built-in functions and
+    /// expressions that cannot be parsed are skipped.
+    fn default_expression_references(
+        &self,
+    ) -> (Vec<SchemaQualifiedName>, Vec<SchemaQualifiedName>) {
+        let mut sequences = vec![];
+        let mut functions = vec![];
+        for expression in self
+            .columns
+            .iter()
+            .filter_map(|c| c.default_expression.as_deref())
+        {
+            let Ok(result) = pg_query::parse(&format!("SELECT {expression}")) else {
+                continue;
+            };
+            for (node, ..) in result.protobuf.nodes() {
+                let pg_query::NodeRef::FuncCall(func_call) = node else {
+                    continue;
+                };
+                if let Some(sequence_name) = sequence_argument(func_call) {
+                    sequences.push(sequence_name);
+                }
+            }
+            functions.extend(
+                result
+                    .functions()
+                    .into_iter()
+                    .map(SchemaQualifiedName::from)
+                    .filter(|f| {
+                        f.schema_name != PG_CATALOG_SCHEMA_NAME
+                            && !(f.schema_name.is_empty()
+                                && (is_built_in_name(&f.local_name)
+                                    || SEQUENCE_FUNCTIONS.contains(&f.local_name.as_str())))
+                    }),
+            );
+        }
+        sequences.sort();
+        sequences.dedup();
+        functions.sort();
+        functions.dedup();
+        (sequences, functions)
     }
 
-    fn dependencies(&self) -> &[SchemaQualifiedName] {
-        &self.dependencies
+    /// Add the object found for the default expression reference `name` as a dependency. Only a
+    /// single matching object is added. Multiple objects found for an unqualified name are
+    /// narrowed using the `search_path` (if any), otherwise the name is skipped. Objects within
+    /// `pg_catalog`, objects within `owned_sequences` and existing dependencies are also skipped.
+    fn add_default_dependency(
+        &mut self,
+        name: &SchemaQualifiedName,
+        objects: Vec<SchemaQualifiedName>,
+        search_path: Option<&[String]>,
+        owned_sequences: &[&SchemaQualifiedName],
+    ) {
+        let objects = match search_path {
+            Some(search_path) if name.schema_name.is_empty() && objects.len() > 1 => {
+                resolve_with_search_path(objects, search_path)
+            },
+            _ => objects,
+        };
+        let [object] = &objects[..] else {
+            return;
+        };
+        if object.schema_name == PG_CATALOG_SCHEMA_NAME
+            || owned_sequences.contains(&object)
+            || self.dependencies.contains(object)
+        {
+            return;
+        }
+        self.dependencies.push(object.clone());
     }
 
-    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "CREATE TABLE {}", self.name)?;
+    /// True if constraints can be defined within the `CREATE TABLE` statement of this table (see
+    /// [Table::create_statement]). Partitions and tables without columns have no column list to
+    /// define the constraints within.
+    pub(crate) fn can_inline_constraints(&self) -> bool {
+        self.partitioned_parent_table.is_none() && !self.columns.is_empty()
+    }
+
+    /// True if migrating to the `new` table drops any of this table's columns
+    pub(crate) fn drops_columns(&self, new: &Table) -> bool {
+        !self.dropped_columns(new).is_empty()
+    }
+
+    /// True if migrating to the `new` table changes the data type of any of this table's columns
+    pub(crate) fn changes_column_types(&self, new: &Table) -> bool {
+        self.columns.iter().any(|c| {
+            new.columns
+                .iter()
+                .any(|n| n.name == c.name && n.data_type != c.data_type)
+        })
+    }
+
+    /// Names of this table's columns that are dropped when migrating to the `new` table
+    pub(crate) fn dropped_columns(&self, new: &Table) -> Vec<&str> {
+        self.columns
+            .iter()
+            .filter(|c| !new.columns.iter().any(|n| n.name == c.name))
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    /// Write the `CREATE` statement to the writable object with the `inline_constraints` defined
+    /// as table constraints after the columns. Includes the `IF NOT EXISTS` clause when
+    /// `if_not_exists` is true.
+    ///
+    /// The constraints must be constraints of this table that [Constraint::can_be_inlined] and
+    /// are ignored when the table [cannot inline constraints](Table::can_inline_constraints).
+    /// Privileges granted on the table's columns are written after the `CREATE` statement.
+    pub(crate) fn create_statement<W>(
+        &self,
+        w: &mut W,
+        if_not_exists: bool,
+        inline_constraints: &[&Constraint],
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(
+            w,
+            "CREATE TABLE {}{}",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.name
+        )?;
         if let Some(partitioned_parent_table) = &self.partitioned_parent_table {
             write!(w, "PARTITION OF {partitioned_parent_table}")?;
         } else if !self.columns.is_empty() {
@@ -138,7 +303,14 @@ impl SqlObject for Table {
                 ",\n    ",
                 w,
             )?;
+            for constraint in inline_constraints {
+                w.write_str(",\n    ")?;
+                constraint.write_inline_definition(w)?;
+            }
             w.write_str("\n)")?;
+        } else {
+            // Tables can have all columns dropped but the column list is still required
+            w.write_str("()")?;
         }
         match &self.partition_values {
             Some(partition_values) => {
@@ -167,26 +339,157 @@ impl SqlObject for Table {
             write!(w, "\nTABLESPACE {}", tablespace)?;
         }
         w.write_str(";\n")?;
+        // The index of an index replica identity does not exist yet so the identity is set once
+        // the index is created (see [Table::replica_identity_index])
+        if !matches!(
+            self.replica_identity,
+            ReplicaIdentity::Default | ReplicaIdentity::Index(_)
+        ) {
+            self.write_replica_identity(w, &self.replica_identity)?;
+        }
+        for column in &self.columns {
+            column.write_grant_changes(self, &[], w)?;
+        }
         Ok(())
     }
 
+    /// Write the `ALTER TABLE ... REPLICA IDENTITY` statement that sets the `replica_identity` of
+    /// this table
+    pub(crate) fn write_replica_identity<W>(
+        &self,
+        w: &mut W,
+        replica_identity: &ReplicaIdentity,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        writeln!(
+            w,
+            "ALTER TABLE {} REPLICA IDENTITY {replica_identity};",
+            self.name
+        )?;
+        Ok(())
+    }
+
+    /// Index used as the replica identity of this table, if any. The index must exist before the
+    /// identity can be set so the identity is never set by the table's create or alter
+    /// statements. Instead, the identity is set by a separate step that depends on the index (see
+    /// [Table::write_replica_identity]).
+    pub(crate) fn replica_identity_index(&self) -> Option<&SchemaQualifiedName> {
+        match &self.replica_identity {
+            ReplicaIdentity::Index(index) => Some(index),
+            _ => None,
+        }
+    }
+}
+
+/// Row identity information of a table written to the WAL for logical replication so updated and
+/// deleted rows can be identified
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum ReplicaIdentity {
+    /// Columns of the primary key, if any, are recorded
+    #[default]
+    Default,
+    /// No information is recorded
+    Nothing,
+    /// All columns are recorded
+    Full,
+    /// Columns of the unique index are recorded. The `REPLICA IDENTITY USING INDEX` statement is
+    /// written after the index exists, either by the primary key or unique constraint that
+    /// creates the index (see [crate::object::Constraint]) or by a separate step of the owner
+    /// table (see [Table::replica_identity_index]).
+    Index(SchemaQualifiedName),
+}
+
+impl Display for ReplicaIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => f.write_str("DEFAULT"),
+            Self::Nothing => f.write_str("NOTHING"),
+            Self::Full => f.write_str("FULL"),
+            Self::Index(index) => write!(f, "USING INDEX {}", index.local_name),
+        }
+    }
+}
+
+impl SqlObject for Table {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "TABLE"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, false, &[])
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, true, &[])
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_table(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        self.alter_table(new, context, w)
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP TABLE {}{};", context.if_exists(), self.name)?;
+        Ok(())
+    }
+}
+
+impl Table {
+    /// Write the statements that alter this table to the `new` table. If the `context` requests
+    /// safe `NOT NULL` changes, `NOT NULL` is added to existing columns using a validated check
+    /// constraint so the `SET NOT NULL` statement does not need to scan the table (see
+    /// [Column::write_safe_set_not_null]). If the `context` requests backfilling, `NULL` values of
+    /// columns becoming `NOT NULL` are set to the column's default first (see
+    /// [Column::alter_column]). If the `context` is idempotent, columns are added with
+    /// `IF NOT EXISTS` and dropped with `IF EXISTS`.
+    ///
+    /// Alterations that cannot be migrated do not stop the remaining alterations from being
+    /// written. The reason of each unsupported alteration is collected and returned as a single
+    /// error after every supported statement has been written.
+    ///
+    /// ## Errors
+    /// - if the partition key, partition values or parent partition table changes
+    /// - if a column cannot be altered (see [Column::alter_column])
+    pub(crate) fn alter_table<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        let mut unsupported: Vec<String> = vec![];
         match (&self.partition_key_def, &new.partition_key_def) {
             (Some(old_key), Some(new_key)) if old_key != new_key => {
-                return Err(PgDiffError::InvalidMigration {
-                    object_name: self.name.to_string(),
-                    reason: "Cannot update partition key definition".to_string(),
-                })
+                unsupported.push("Cannot update partition key definition".to_string());
             },
             _ => {},
         }
 
         match (&self.partition_values, &new.partition_values) {
             (Some(old_values), Some(new_values)) if old_values != new_values => {
-                return Err(PgDiffError::InvalidMigration {
-                    object_name: self.name.to_string(),
-                    reason: "Cannot update partition values".to_string(),
-                })
+                unsupported.push("Cannot update partition values".to_string());
             },
             _ => {},
         }
@@ -196,58 +499,90 @@ impl SqlObject for Table {
             &new.partitioned_parent_table,
         ) {
             (Some(old_key), Some(new_key)) if old_key != new_key => {
-                return Err(PgDiffError::InvalidMigration {
-                    object_name: self.name.to_string(),
-                    reason: "Cannot update parent partition table".to_string(),
-                })
+                unsupported.push("Cannot update parent partition table".to_string());
             },
             _ => {},
         }
 
-        if let Some(old_inherit) = &self.inherited_tables {
-            let new_inherited = new.inherited_tables.as_ref();
-            for remove_inherit in old_inherit
-                .iter()
-                .filter(|i| new_inherited.map(|o| o.contains(i)).unwrap_or(true))
-            {
-                writeln!(w, "ALTER TABLE {} NO INHERIT {remove_inherit};", self.name)?;
-            }
+        // No inherited tables is treated the same as an empty list of inherited tables
+        let old_inherited = self.inherited_tables.as_deref().unwrap_or_default();
+        let new_inherited = new.inherited_tables.as_deref().unwrap_or_default();
+        for remove_inherit in old_inherited.iter().filter(|i| !new_inherited.contains(i)) {
+            writeln!(w, "ALTER TABLE {} NO INHERIT {remove_inherit};", self.name)?;
         }
-        if let Some(new_inherit) = &new.inherited_tables {
-            let old_inherited = self.inherited_tables.as_ref();
-            for add_inherit in new_inherit
-                .iter()
-                .filter(|i| old_inherited.map(|o| o.contains(i)).unwrap_or(true))
-            {
-                writeln!(w, "ALTER TABLE {} INHERIT {add_inherit};", self.name)?;
-            }
+        for add_inherit in new_inherited.iter().filter(|i| !old_inherited.contains(i)) {
+            writeln!(w, "ALTER TABLE {} INHERIT {add_inherit};", self.name)?;
         }
 
         for column in &self.columns {
             if let Some(other) = new.columns.iter().find(|c| c.name == column.name) {
-                column.alter_column(other, self, w)?;
+                // A column that cannot be altered is left entirely to the manual change so none
+                // of its statements are written
+                let mut statements = String::new();
+                match column.alter_column(other, self, context, &mut statements) {
+                    Ok(()) => {
+                        w.write_str(&statements)?;
+                        other.write_grant_changes(self, &column.grants, w)?;
+                    },
+                    Err(PgDiffError::InvalidMigration { reason, .. }) => unsupported.push(reason),
+                    Err(error) => return Err(error),
+                }
             } else {
-                column.drop_column(self, w)?;
+                column.drop_column(self, context, w)?;
             };
         }
         for column in &new.columns {
             if !self.columns.iter().any(|c| c.name == column.name) {
-                column.add_column(self, w)?;
+                column.add_column(self, context, w)?;
+                column.write_grant_changes(self, &[], w)?;
             }
         }
 
+        if self.replica_identity != new.replica_identity && new.replica_identity_index().is_none() {
+            self.write_replica_identity(w, &new.replica_identity)?;
+        }
+        compare_key_value_pairs(w, self, &self.with, &new.with)?;
         compare_tablespaces(self, self.tablespace.as_ref(), new.tablespace.as_ref(), w)?;
-        Ok(())
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(PgDiffError::InvalidMigrations {
+                object_name: self.name.to_string(),
+                reasons: unsupported,
+            })
+        }
     }
+}
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP TABLE {};", self.name)?;
-        Ok(())
+/// Name of the sequence passed as the first argument to the `func_call` if the function is one
+/// of the [SEQUENCE_FUNCTIONS]. The argument must be a string literal (optionally cast to
+/// `regclass`) for the name to be found.
+fn sequence_argument(func_call: &pg_query::protobuf::FuncCall) -> Option<SchemaQualifiedName> {
+    let function_name = func_call.funcname.last()?;
+    let Some(Node::String(function_name)) = &function_name.node else {
+        return None;
+    };
+    if !SEQUENCE_FUNCTIONS.contains(&function_name.sval.as_str()) {
+        return None;
+    }
+    let mut argument = func_call.args.first()?.node.as_ref()?;
+    if let Node::TypeCast(type_cast) = argument {
+        argument = type_cast.arg.as_deref()?.node.as_ref()?;
+    }
+    let Node::AConst(constant) = argument else {
+        return None;
+    };
+    match &constant.val {
+        Some(a_const::Val::Sval(sequence_name)) => {
+            Some(SchemaQualifiedName::from(&sequence_name.sval))
+        },
+        _ => None,
     }
 }
 
 /// Struct representing a SQL table column
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize)]
+#[cfg_attr(test, derive(Default))]
 pub struct Column {
     /// Column name
     name: String,
@@ -267,8 +602,34 @@ pub struct Column {
     identity_column: Option<IdentityColumn>,
     /// Optional storage details for the column
     storage: Option<Storage>,
+    /// Default storage of the column's data type. The column's storage is only set explicitly
+    /// when it differs from this default.
+    default_storage: Option<Storage>,
     /// Compression option for the column
     compression: Compression,
+    /// Privileges granted on the column. The privilege type includes the column name (e.g.
+    /// `SELECT (id)`) so the privileges are granted and revoked on the owner table.
+    grants: Vec<AclGrant>,
+}
+
+impl PartialEq for Column {
+    #[inline]
+    fn eq(&self, other: &Column) -> bool {
+        self.name == other.name
+            && self.data_type == other.data_type
+            && self.size == other.size
+            && self.collation == other.collation
+            && self.is_non_null == other.is_non_null
+            && optional_expressions_match(
+                self.default_expression.as_deref(),
+                other.default_expression.as_deref(),
+            )
+            && self.generated_column == other.generated_column
+            && self.identity_column == other.identity_column
+            && self.storage == other.storage
+            && self.compression == other.compression
+            && self.grants == other.grants
+    }
 }
 
 impl Column {
@@ -310,24 +671,61 @@ impl Column {
         Ok(())
     }
 
-    /// Write an `ALTER TABLE {} ADD COLUMN` statement for this column to the writeable object
-    fn add_column<W: Write>(&self, table: &Table, w: &mut W) -> Result<(), PgDiffError> {
-        write!(w, "ALTER TABLE {} ADD COLUMN ", table.name)?;
+    /// Write the `REVOKE` and `GRANT` statements that change the `old` privileges of this column
+    /// into the current privileges of this column. Privileges of a dropped column are removed
+    /// with the column so this is only required for created, added and altered columns.
+    fn write_grant_changes<W: Write>(
+        &self,
+        table: &Table,
+        old: &[AclGrant],
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        write_acl_changes(w, old, &self.grants, "", &format!("TABLE {}", table.name))
+    }
+
+    /// Storage of the column if it is not the default storage of the column's data type
+    fn non_default_storage(&self) -> Option<&Storage> {
+        match (&self.storage, &self.default_storage) {
+            (Some(storage), Some(default_storage)) if storage != default_storage => Some(storage),
+            _ => None,
+        }
+    }
+
+    /// Write an `ALTER TABLE {} ADD COLUMN` statement for this column to the writeable object.
+    /// Storage and compression statements are only written when the column does not use the
+    /// defaults of the column's data type. Includes `IF NOT EXISTS` when the `context` is
+    /// idempotent.
+    fn add_column<W: Write>(
+        &self,
+        table: &Table,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "ALTER TABLE {} ADD COLUMN {}",
+            table.name,
+            if context.idempotent {
+                "IF NOT EXISTS "
+            } else {
+                ""
+            }
+        )?;
         self.field_definition(false, w)?;
         w.write_str(";\n")?;
-        if let Some(storage) = &self.storage {
+        if let Some(storage) = self.non_default_storage() {
             writeln!(
                 w,
-                "\nALTER TABLE {} ALTER COLUMN {} SET {};",
+                "ALTER TABLE {} ALTER COLUMN {} SET {};",
                 table.name,
                 self.name,
                 storage.as_ref()
             )?;
         }
-        if !self.compression.as_ref().is_empty() {
+        if self.compression != Compression::Default {
             writeln!(
                 w,
-                "\nALTER TABLE {} ALTER COLUMN {} SET {};",
+                "ALTER TABLE {} ALTER COLUMN {} SET {};",
                 table.name,
                 self.name,
                 self.compression.as_ref()
@@ -336,13 +734,73 @@ impl Column {
         Ok(())
     }
 
-    /// Write an `ALTER TABLE {} DROP COLUMN` statement for this column to the writeable object
-    fn drop_column<W: Write>(&self, table: &Table, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "\nALTER {} DROP COLUMN {};", table.name, self.name)?;
+    /// Write an `ALTER TABLE {} DROP COLUMN` statement for this column to the writeable object.
+    /// Includes `IF EXISTS` when the `context` is idempotent.
+    fn drop_column<W: Write>(
+        &self,
+        table: &Table,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "ALTER TABLE {} DROP COLUMN {}{};",
+            table.name,
+            context.if_exists(),
+            self.name
+        )?;
         Ok(())
     }
 
-    /// Write an `ALTER TABLE {} ALTER COLUMN` statement for this column to the writeable object
+    /// Name of the check constraint temporarily added by [Column::write_safe_set_not_null]
+    fn not_null_check_name(&self) -> String {
+        match self.name.strip_suffix('"') {
+            Some(quoted_name) => format!("{quoted_name}_not_null\""),
+            None => format!("{}_not_null", self.name),
+        }
+    }
+
+    /// Write the statements that set `NOT NULL` for this column without holding a lock for a full
+    /// table scan. A `NOT VALID` check constraint of the column is added and validated (which only
+    /// blocks writes), allowing `SET NOT NULL` to skip the table scan (Postgresql 12+). The check
+    /// constraint is dropped afterward.
+    fn write_safe_set_not_null<W: Write>(
+        &self,
+        table: &Table,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        let check_name = self.not_null_check_name();
+        writeln!(
+            w,
+            "ALTER TABLE {} ADD CONSTRAINT {check_name} CHECK ({} IS NOT NULL) NOT VALID;",
+            table.name, self.name
+        )?;
+        writeln!(
+            w,
+            "ALTER TABLE {} VALIDATE CONSTRAINT {check_name};",
+            table.name
+        )?;
+        writeln!(
+            w,
+            "ALTER TABLE {} ALTER COLUMN {} SET NOT NULL;",
+            table.name, self.name
+        )?;
+        writeln!(
+            w,
+            "ALTER TABLE {} DROP CONSTRAINT {check_name};",
+            table.name
+        )?;
+        Ok(())
+    }
+
+    /// Write an `ALTER TABLE {} ALTER COLUMN` statement for this column to the writeable object.
+    /// Nullability changes are always written after any default change. If the `context` requests
+    /// safe `NOT NULL` changes and the column becomes `NOT NULL`, the column is set as `NOT NULL`
+    /// using [Column::write_safe_set_not_null].
+    ///
+    /// When the column becomes `NOT NULL` and has a default, existing `NULL` values are updated
+    /// to the default before setting `NOT NULL` if the `context` requests backfilling. Otherwise,
+    /// a comment warns that setting `NOT NULL` fails if the column contains `NULL` values.
     ///
     /// ## Errors
     /// - if the data type of the column has changed between migrations
@@ -352,29 +810,22 @@ impl Column {
         &self,
         other: &Self,
         table: &Table,
+        context: &RenderContext,
         w: &mut W,
     ) -> Result<(), PgDiffError> {
         if self.data_type != other.data_type {
             return Err(PgDiffError::InvalidMigration {
                 object_name: table.name.to_string(),
-                reason: format!("Attempted to change the data type of a column which is currently not supported. Column = {}", self.name),
+                reason: format!(
+                    "column {} type {} -> {}",
+                    self.name, self.data_type, other.data_type
+                ),
             });
         }
-        if self.is_non_null != other.is_non_null {
-            writeln!(
-                w,
-                "ALTER TABLE {} ALTER COLUMN {} {};",
-                table.name,
-                self.name,
-                if self.is_non_null {
-                    "DROP NOT NULL"
-                } else {
-                    "SET NOT NULL"
-                }
-            )?;
-        }
         match (&self.default_expression, &other.default_expression) {
-            (Some(old_expression), Some(new_expression)) if old_expression != new_expression => {
+            (Some(old_expression), Some(new_expression))
+                if !expressions_match(old_expression, new_expression) =>
+            {
                 writeln!(
                     w,
                     "ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;",
@@ -402,6 +853,38 @@ impl Column {
             },
             _ => {},
         }
+        let is_set_not_null = !self.is_non_null && other.is_non_null;
+        if is_set_not_null && other.default_expression.is_some() {
+            if context.backfill_nulls {
+                writeln!(
+                    w,
+                    "UPDATE {} SET {} = DEFAULT WHERE {} IS NULL;",
+                    table.name, self.name, self.name
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "-- WARNING: SET NOT NULL fails if column {} of {} contains NULL values. \
+                    Existing NULL values are not replaced by the column default.",
+                    self.name, table.name
+                )?;
+            }
+        }
+        if is_set_not_null && context.safe_not_null {
+            self.write_safe_set_not_null(table, w)?;
+        } else if self.is_non_null != other.is_non_null {
+            writeln!(
+                w,
+                "ALTER TABLE {} ALTER COLUMN {} {};",
+                table.name,
+                self.name,
+                if self.is_non_null {
+                    "DROP NOT NULL"
+                } else {
+                    "SET NOT NULL"
+                }
+            )?;
+        }
         match (&self.generated_column, &other.generated_column) {
             (Some(old_expression), Some(new_expression)) if old_expression != new_expression => {
                 return Err(PgDiffError::InvalidMigration {
@@ -488,7 +971,7 @@ impl Column {
 }
 
 /// Column generation details
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize)]
 pub struct GeneratedColumn {
     /// Raw expression used to generate a column value
     expression: String,
@@ -496,6 +979,14 @@ pub struct GeneratedColumn {
     generation_type: GeneratedColumnType,
 }
 
+impl PartialEq for GeneratedColumn {
+    #[inline]
+    fn eq(&self, other: &GeneratedColumn) -> bool {
+        expressions_match(&self.expression, &other.expression)
+            && self.generation_type == other.generation_type
+    }
+}
+
 impl Display for GeneratedColumn {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -571,9 +1062,11 @@ pub enum Storage {
 
 /// Compression option variants for a column
 #[derive(Debug, Deserialize, PartialEq, strum::AsRefStr)]
+#[cfg_attr(test, derive(Default))]
 pub enum Compression {
     /// Default compression is used for a column. This should always be treated as empty since this
     /// should never display anything for columns not compressed.
+    #[cfg_attr(test, default)]
     #[serde(alias = "")]
     #[strum(serialize = "")]
     Default,
@@ -589,4 +1082,725 @@ pub enum Compression {
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use sqlx::postgres::types::Oid;
+
+    use crate::object::constraint::{Constraint, ConstraintTiming, ConstraintType};
+    use crate::object::sequence::SequenceOptions;
+    use crate::object::{
+        AclGrant, IndexParameters, RenderContext, SchemaQualifiedName, SqlObject, StorageParameters,
+    };
+    use crate::PgDiffError;
+
+    use super::{
+        Column, Compression, IdentityColumn, IdentityGeneration, ReplicaIdentity, Storage, Table,
+    };
+
+    const SCHEMA: &str = "test_schema";
+    const TABLE: &str = "test_table";
+    const PARENT_1: &str = "parent_table1";
+    const PARENT_2: &str = "parent_table2";
+    const PARENT_3: &str = "parent_table3";
+    const COLUMN_1: &str = "id";
+    const COLUMN_2: &str = "description";
+
+    fn create_table(inherited_tables: Option<Vec<&str>>) -> Table {
+        Table {
+            oid: Oid(1),
+            name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            columns: vec![],
+            partition_key_def: None,
+            partition_values: None,
+            inherited_tables: inherited_tables.map(|tables| {
+                tables
+                    .into_iter()
+                    .map(|t| SchemaQualifiedName::new(SCHEMA, t))
+                    .collect()
+            }),
+            partitioned_parent_table: None,
+            tablespace: None,
+            with: None,
+            owner: "postgres".into(),
+            replica_identity: ReplicaIdentity::Default,
+            dependencies: vec![],
+        }
+    }
+
+    fn create_column(
+        name: &str,
+        data_type: &str,
+        is_non_null: bool,
+        default_expression: Option<&str>,
+    ) -> Column {
+        Column {
+            name: name.into(),
+            data_type: data_type.into(),
+            size: -1,
+            is_non_null,
+            default_expression: default_expression.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    fn create_identity_column(name: &str, start_value: i64) -> Column {
+        Column {
+            identity_column: Some(IdentityColumn {
+                identity_generation: IdentityGeneration::Always,
+                sequence_options: SequenceOptions {
+                    increment: 1,
+                    min_value: 1,
+                    max_value: 9223372036854775807,
+                    start_value,
+                    cache: 1,
+                    is_cycle: false,
+                },
+            }),
+            ..create_column(name, "bigint", true, None)
+        }
+    }
+
+    fn column_grant(grantee: &str, privilege_type: &str, is_grantable: bool) -> AclGrant {
+        AclGrant {
+            grantee: grantee.into(),
+            privilege_type: privilege_type.into(),
+            is_grantable,
+        }
+    }
+
+    fn create_column_with_grants(name: &str, grants: Vec<AclGrant>) -> Column {
+        Column {
+            grants,
+            ..create_column(name, "bigint", false, None)
+        }
+    }
+
+    fn create_table_with_columns(columns: Vec<Column>) -> Table {
+        Table {
+            columns,
+            ..create_table(None)
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_table_with_columns(vec![
+            create_column(COLUMN_1, "bigint", true, None),
+            create_column(COLUMN_2, "text", false, Some("'none'::text")),
+        ]),
+        include_str!("../../test-files/sql/table-create-case1.pgsql"),
+    )]
+    #[case(
+        Table {
+            partition_key_def: Some("RANGE (id)".into()),
+            ..create_table_with_columns(vec![create_column(COLUMN_1, "bigint", true, None)])
+        },
+        include_str!("../../test-files/sql/table-create-case2.pgsql"),
+    )]
+    #[case(
+        Table {
+            partitioned_parent_table: Some(SchemaQualifiedName::new(SCHEMA, PARENT_1)),
+            partition_values: Some("FROM (1) TO (100)".into()),
+            ..create_table(None)
+        },
+        include_str!("../../test-files/sql/table-create-case3.pgsql"),
+    )]
+    #[case(
+        Table {
+            columns: vec![create_column(COLUMN_1, "bigint", true, None)],
+            ..create_table_with_storage_parameters(&["fillfactor=70", "autovacuum_enabled=false"])
+        },
+        include_str!("../../test-files/sql/table-create-case4.pgsql"),
+    )]
+    #[case(
+        Table {
+            columns: vec![create_column(COLUMN_1, "bigint", true, None)],
+            replica_identity: ReplicaIdentity::Full,
+            ..create_table(None)
+        },
+        include_str!("../../test-files/sql/table-create-case5.pgsql"),
+    )]
+    #[case(
+        create_table_with_columns(vec![
+            create_identity_column(COLUMN_1, 1),
+            create_identity_column("other_id", 10),
+        ]),
+        include_str!("../../test-files/sql/table-create-case6.pgsql"),
+    )]
+    #[case(
+        create_table(None),
+        include_str!("../../test-files/sql/table-create-case7.pgsql"),
+    )]
+    #[case(
+        create_table_with_columns(vec![create_column_with_grants(
+            COLUMN_1,
+            vec![column_grant("reader", "SELECT (id)", false)],
+        )]),
+        include_str!("../../test-files/sql/table-create-case8.pgsql"),
+    )]
+    fn create_statements_should_add_create_table_statement(
+        #[case] table: Table,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        table.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    fn create_table_with_storage_parameters(parameters: &[&str]) -> Table {
+        Table {
+            with: Some(StorageParameters::from(parameters)),
+            ..create_table(None)
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_table_with_storage_parameters(&["fillfactor=70"]),
+        create_table_with_storage_parameters(&["fillfactor=90"]),
+        "ALTER TABLE test_schema.test_table SET (fillfactor=90);",
+    )]
+    #[case(
+        create_table(None),
+        create_table_with_storage_parameters(&["autovacuum_enabled=false", "fillfactor=90"]),
+        "ALTER TABLE test_schema.test_table SET (autovacuum_enabled=false,fillfactor=90);",
+    )]
+    #[case(
+        create_table_with_storage_parameters(&["autovacuum_enabled=false", "fillfactor=90"]),
+        create_table_with_storage_parameters(&["fillfactor=90"]),
+        "ALTER TABLE test_schema.test_table RESET (autovacuum_enabled);",
+    )]
+    fn alter_statements_should_set_and_reset_storage_parameters(
+        #[case] old: Table,
+        #[case] new: Table,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement, writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_ignore_equivalent_numeric_storage_parameters() {
+        let old = create_table_with_storage_parameters(&["fillfactor=90"]);
+        let new = create_table_with_storage_parameters(&["fillfactor=90.0"]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(old, new);
+        assert!(writable.is_empty());
+    }
+
+    fn create_table_with_replica_identity(replica_identity: ReplicaIdentity) -> Table {
+        Table {
+            replica_identity,
+            ..create_table(None)
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        ReplicaIdentity::Default,
+        ReplicaIdentity::Full,
+        "ALTER TABLE test_schema.test_table REPLICA IDENTITY FULL;"
+    )]
+    #[case(
+        ReplicaIdentity::Index(SchemaQualifiedName::new(SCHEMA, "test_table_pkey")),
+        ReplicaIdentity::Default,
+        "ALTER TABLE test_schema.test_table REPLICA IDENTITY DEFAULT;"
+    )]
+    #[case(
+        ReplicaIdentity::Nothing,
+        ReplicaIdentity::Index(SchemaQualifiedName::new(SCHEMA, "test_table_pkey")),
+        ""
+    )]
+    fn alter_statements_should_set_replica_identity(
+        #[case] old_replica_identity: ReplicaIdentity,
+        #[case] new_replica_identity: ReplicaIdentity,
+        #[case] statement: &str,
+    ) {
+        let old = create_table_with_replica_identity(old_replica_identity);
+        let new = create_table_with_replica_identity(new_replica_identity);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement, writable.trim());
+    }
+
+    #[test]
+    fn write_replica_identity_should_use_index() {
+        let table = create_table(None);
+        let mut writable = String::new();
+
+        table
+            .write_replica_identity(
+                &mut writable,
+                &ReplicaIdentity::Index(SchemaQualifiedName::new(SCHEMA, "test_index")),
+            )
+            .unwrap();
+
+        assert_eq!(
+            "ALTER TABLE test_schema.test_table REPLICA IDENTITY USING INDEX test_index;",
+            writable.trim()
+        );
+    }
+
+    fn create_column_with_storage(
+        data_type: &str,
+        size: i32,
+        storage: Storage,
+        default_storage: Storage,
+        compression: Compression,
+    ) -> Column {
+        Column {
+            size,
+            storage: Some(storage),
+            default_storage: Some(default_storage),
+            compression,
+            ..create_column(COLUMN_2, data_type, false, None)
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_column_with_storage(
+            "integer",
+            4,
+            Storage::Plain,
+            Storage::Plain,
+            Compression::Default,
+        ),
+        include_str!("../../test-files/sql/table-add-column-plain.pgsql"),
+    )]
+    #[case(
+        create_column_with_storage(
+            "text",
+            -1,
+            Storage::Extended,
+            Storage::Extended,
+            Compression::Default,
+        ),
+        include_str!("../../test-files/sql/table-add-column-extended.pgsql"),
+    )]
+    #[case(
+        create_column_with_storage(
+            "bytea",
+            -1,
+            Storage::Extended,
+            Storage::Extended,
+            Compression::LZ4,
+        ),
+        include_str!("../../test-files/sql/table-add-column-compressed.pgsql"),
+    )]
+    #[case(
+        create_column_with_storage(
+            "text",
+            -1,
+            Storage::Main,
+            Storage::Extended,
+            Compression::Default,
+        ),
+        include_str!("../../test-files/sql/table-add-column-storage.pgsql"),
+    )]
+    fn alter_statements_should_only_set_storage_and_compression_of_added_column_when_not_default(
+        #[case] column: Column,
+        #[case] statement: &str,
+    ) {
+        let old = create_table_with_columns(vec![]);
+        let new = create_table_with_columns(vec![column]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn alter_statements_should_add_drop_column_statement() {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let new = create_table_with_columns(vec![]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-drop-column.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_table_should_guard_columns_when_idempotent() {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let new = create_table_with_columns(vec![create_column(COLUMN_2, "text", false, None)]);
+        let context = RenderContext {
+            idempotent: true,
+            ..Default::default()
+        };
+        let mut writable = String::new();
+
+        old.alter_table(&new, &context, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-alter-columns-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("nextval('s'::regclass)", &["s"], &[])]
+    #[case("nextval('test_schema.s'::regclass)", &["test_schema.s"], &[])]
+    #[case("test_schema.next_code()", &[], &["test_schema.next_code"])]
+    #[case("next_code(nextval('s'))", &["s"], &["next_code"])]
+    #[case("format('%s', 1)", &[], &[])]
+    #[case("'none'::text", &[], &[])]
+    fn default_expression_references_should_find_sequences_and_functions(
+        #[case] default_expression: &str,
+        #[case] expected_sequences: &[&str],
+        #[case] expected_functions: &[&str],
+    ) {
+        let table = create_table_with_columns(vec![create_column(
+            COLUMN_1,
+            "bigint",
+            true,
+            Some(default_expression),
+        )]);
+
+        let (sequences, functions) = table.default_expression_references();
+
+        let expected_sequences: Vec<SchemaQualifiedName> = expected_sequences
+            .iter()
+            .map(SchemaQualifiedName::from)
+            .collect();
+        let expected_functions: Vec<SchemaQualifiedName> = expected_functions
+            .iter()
+            .map(SchemaQualifiedName::from)
+            .collect();
+        assert_eq!(expected_sequences, sequences);
+        assert_eq!(expected_functions, functions);
+    }
+
+    #[test]
+    fn add_default_dependency_should_add_sequence_of_column_default() {
+        let mut table = create_table_with_columns(vec![create_column(
+            COLUMN_1,
+            "bigint",
+            true,
+            Some("nextval('s'::regclass)"),
+        )]);
+        let (sequences, _) = table.default_expression_references();
+        let sequence = SchemaQualifiedName::new(SCHEMA, "s");
+
+        table.add_default_dependency(&sequences[0], vec![sequence.clone()], None, &[]);
+
+        assert_eq!(vec![sequence], table.dependencies);
+    }
+
+    #[test]
+    fn add_default_dependency_should_skip_owned_sequences() {
+        let mut table = create_table(None);
+        let name = SchemaQualifiedName::new("", "test_table_id_seq");
+        let sequence = SchemaQualifiedName::new(SCHEMA, "test_table_id_seq");
+
+        table.add_default_dependency(&name, vec![sequence.clone()], None, &[&sequence]);
+
+        assert!(table.dependencies.is_empty());
+    }
+
+    #[test]
+    fn alter_statements_should_add_alter_column_statements() {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let new = create_table_with_columns(vec![
+            create_column(COLUMN_1, "bigint", true, Some("0")),
+            create_column(COLUMN_2, "text", false, None),
+        ]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-alter-columns.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_change_column_grants() {
+        let old = create_table_with_columns(vec![create_column_with_grants(
+            COLUMN_1,
+            vec![
+                column_grant("reader", "SELECT (id)", false),
+                column_grant("writer", "UPDATE (id)", false),
+            ],
+        )]);
+        let new = create_table_with_columns(vec![
+            create_column_with_grants(
+                COLUMN_1,
+                vec![
+                    column_grant("reader", "SELECT (id)", false),
+                    column_grant("writer", "UPDATE (id)", true),
+                ],
+            ),
+            create_column_with_grants(
+                COLUMN_2,
+                vec![column_grant("writer", "INSERT (description)", false)],
+            ),
+        ]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-alter-column-grants.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_table_should_set_default_before_safe_not_null_statements() {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let new = create_table_with_columns(vec![
+            create_column(COLUMN_1, "bigint", true, Some("0")),
+            create_column(COLUMN_2, "text", false, None),
+        ]);
+        let context = RenderContext {
+            safe_not_null: true,
+            ..Default::default()
+        };
+        let mut writable = String::new();
+
+        old.alter_table(&new, &context, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-alter-columns-safe-not-null.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_table_should_backfill_nulls_before_set_not_null() {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let new = create_table_with_columns(vec![
+            create_column(COLUMN_1, "bigint", true, Some("0")),
+            create_column(COLUMN_2, "text", false, None),
+        ]);
+        let context = RenderContext {
+            backfill_nulls: true,
+            ..Default::default()
+        };
+        let mut writable = String::new();
+
+        old.alter_table(&new, &context, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-alter-columns-backfill-nulls.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(false)]
+    #[case(true)]
+    fn alter_table_should_drop_not_null_directly(#[case] safe_not_null: bool) {
+        let old = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", true, None)]);
+        let new = create_table_with_columns(vec![create_column(COLUMN_1, "bigint", false, None)]);
+        let context = RenderContext {
+            safe_not_null,
+            ..Default::default()
+        };
+        let mut writable = String::new();
+
+        old.alter_table(&new, &context, &mut writable).unwrap();
+
+        assert_eq!(
+            "ALTER TABLE test_schema.test_table ALTER COLUMN id DROP NOT NULL;",
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_table_should_write_supported_statements_when_column_cannot_be_altered() {
+        let old = create_table_with_columns(vec![
+            create_column(COLUMN_1, "integer", true, None),
+            create_column(COLUMN_2, "text", true, None),
+        ]);
+        let new = create_table_with_columns(vec![
+            create_column(COLUMN_1, "bigint", false, None),
+            create_column(COLUMN_2, "text", false, None),
+        ]);
+        let mut writable = String::new();
+
+        let result = old.alter_table(&new, &RenderContext::default(), &mut writable);
+
+        assert_eq!(
+            "ALTER TABLE test_schema.test_table ALTER COLUMN description DROP NOT NULL;",
+            writable.trim()
+        );
+        let Err(PgDiffError::InvalidMigrations {
+            object_name,
+            reasons,
+        }) = result
+        else {
+            panic!("Expected unsupported alterations but got {result:?}");
+        };
+        assert_eq!("test_schema.test_table", object_name);
+        assert_eq!(
+            vec!["column id type integer -> bigint".to_string()],
+            reasons
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("id", "id_not_null")]
+    #[case("\"Id\"", "\"Id_not_null\"")]
+    fn not_null_check_name_should_suffix_column_name(#[case] name: &str, #[case] expected: &str) {
+        let column = create_column(name, "bigint", false, None);
+
+        assert_eq!(expected, column.not_null_check_name());
+    }
+
+    #[test]
+    fn alter_statements_should_ignore_default_with_explicit_cast() {
+        let old = create_table_with_columns(vec![create_column(
+            COLUMN_1,
+            "text",
+            true,
+            Some("'active'::text"),
+        )]);
+        let new = create_table_with_columns(vec![create_column(
+            COLUMN_1,
+            "text",
+            true,
+            Some("'active'"),
+        )]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(old, new);
+        assert!(writable.is_empty());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_table_statement() {
+        let table = create_table(None);
+        let mut writable = String::new();
+
+        table.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_add_inherit_and_no_inherit_statements() {
+        let old = create_table(Some(vec![PARENT_1, PARENT_2]));
+        let new = create_table(Some(vec![PARENT_2, PARENT_3]));
+        let statement = include_str!("../../test-files/sql/table-alter-inherit-case1.pgsql");
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(statement.trim(), writeable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(create_table(Some(vec![])), create_table(None))]
+    #[case(create_table(None), create_table(Some(vec![])))]
+    #[case(create_table(Some(vec![PARENT_1])), create_table(Some(vec![PARENT_1])))]
+    fn alter_statements_should_not_add_inherit_statements_when_inherited_tables_unchanged(
+        #[case] old: Table,
+        #[case] new: Table,
+    ) {
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert!(writeable.is_empty(), "{writeable}");
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_if_not_exists() {
+        let table = create_table(Some(vec![PARENT_1]));
+        let mut writable = String::new();
+
+        table.create_statements_idempotent(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    fn create_table_constraint(name: &str, constraint_type: ConstraintType) -> Constraint {
+        Constraint {
+            table_oid: Oid(1),
+            owner_table_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            name: name.into(),
+            schema_qualified_name: SchemaQualifiedName::new(SCHEMA, name),
+            constraint_type,
+            timing: ConstraintTiming::NotDeferrable,
+            is_validated: true,
+            is_enforced: true,
+            is_replica_identity: false,
+            dependencies: vec![SchemaQualifiedName::new(SCHEMA, TABLE)],
+        }
+    }
+
+    #[test]
+    fn create_statement_should_define_inline_constraints_after_columns() {
+        let table = create_table_with_columns(vec![
+            create_column(COLUMN_1, "integer", true, None),
+            create_column(COLUMN_2, "text", false, None),
+        ]);
+        let primary_key = create_table_constraint(
+            "test_table_pkey",
+            ConstraintType::PrimaryKey {
+                columns: vec![COLUMN_1.into()],
+                index_parameters: IndexParameters::default(),
+            },
+        );
+        let check = create_table_constraint(
+            "test_table_description_check",
+            ConstraintType::Check {
+                columns: vec![COLUMN_2.into()],
+                expression: "description <> ''".into(),
+                is_inheritable: true,
+            },
+        );
+        let mut writable = String::new();
+
+        table
+            .create_statement(&mut writable, false, &[&primary_key, &check])
+            .unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/table-create-inline-constraints.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(create_table(None), false)]
+    #[case(create_table_with_columns(vec![create_column(COLUMN_1, "integer", true, None)]), true)]
+    #[case(
+        Table {
+            partitioned_parent_table: Some(SchemaQualifiedName::new(SCHEMA, PARENT_1)),
+            ..create_table_with_columns(vec![create_column(COLUMN_1, "integer", true, None)])
+        },
+        false
+    )]
+    fn can_inline_constraints_should_require_column_list(
+        #[case] table: Table,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, table.can_inline_constraints());
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt::Write;
+
+use crate::PgDiffError;
+
+use super::{RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Stub of a tablespace referenced by a table, index or constraint. Tablespaces are cluster-wide
+/// objects tied to a directory of the server so they are never scraped or compared. Stubs only
+/// exist so that scripts can be replayed against a fresh cluster (see
+/// [super::ScriptOptions::emit_tablespaces]).
+#[derive(Debug, PartialEq)]
+pub struct TablespaceStub {
+    /// Name of the tablespace (never includes a schema name since tablespaces reside outside a
+    /// database)
+    pub(crate) name: SchemaQualifiedName,
+}
+
+impl SqlObject for TablespaceStub {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "TABLESPACE"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &[]
+    }
+
+    /// The location of the tablespace is not known so a placeholder directory is used
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        w.write_str(
+            "-- Placeholder location. Replace with an empty directory on the server that is owned \
+             by the postgres user\n",
+        )?;
+        writeln!(
+            w,
+            "CREATE TABLESPACE {} LOCATION '/path/to/{}';",
+            self.name,
+            self.name.local_name.trim_matches('"').replace('\'', "''")
+        )?;
+        Ok(())
+    }
+
+    /// `CREATE TABLESPACE` cannot run within a transaction block (including a `DO` block) and does
+    /// not support `IF NOT EXISTS` so the statement cannot be guarded
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statements(w)
+    }
+
+    fn alter_statements<W: Write>(&self, _: &Self, _: &mut W) -> Result<(), PgDiffError> {
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP TABLESPACE {}{};", context.if_exists(), self.name)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::TablespaceStub;
+
+    #[test]
+    fn create_statements_should_add_create_tablespace_with_placeholder_location() {
+        let tablespace = TablespaceStub {
+            name: SchemaQualifiedName::new("", "test_tablespace"),
+        };
+        let mut writable = String::new();
+
+        tablespace.create_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/tablespace-stub-create.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
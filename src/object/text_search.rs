@@ -0,0 +1,468 @@
+use std::fmt::Write;
+
+use serde::Deserialize;
+use sqlx::{query_as, PgPool};
+
+use crate::{write_join, PgDiffError};
+
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
+
+/// Fetch all text search dictionaries found within the specified schemas
+pub async fn get_text_search_dictionaries(
+    pool: &PgPool,
+    schemas: &[&str],
+) -> Result<Vec<TextSearchDictionary>, PgDiffError> {
+    let dictionaries_query = include_str!("./../../queries/text_search_dictionaries.pgsql");
+    let dictionaries =
+        match fetch_with_retry(|| query_as(dictionaries_query).bind(schemas).fetch_all(pool)).await
+        {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load text search dictionaries");
+                return Err(error.into());
+            },
+        };
+    Ok(dictionaries)
+}
+
+/// Fetch all text search configurations found within the specified schemas
+pub async fn get_text_search_configurations(
+    pool: &PgPool,
+    schemas: &[&str],
+) -> Result<Vec<TextSearchConfiguration>, PgDiffError> {
+    let configurations_query = include_str!("./../../queries/text_search_configurations.pgsql");
+    let configurations =
+        match fetch_with_retry(|| query_as(configurations_query).bind(schemas).fetch_all(pool))
+            .await
+        {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load text search configurations");
+                return Err(error.into());
+            },
+        };
+    Ok(configurations)
+}
+
+/// Postgresql text search dictionary object
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct TextSearchDictionary {
+    /// Full name of the dictionary
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Full name of the template the dictionary is created from
+    #[sqlx(json)]
+    pub(crate) template: SchemaQualifiedName,
+    /// Template specific options of the dictionary as a comma separated list of `key = value`
+    /// pairs. [None] if no options are specified.
+    pub(crate) options: Option<String>,
+    /// Dependencies of the dictionary. This is the schema owning the dictionary and the extension
+    /// providing the template (if any).
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+impl TextSearchDictionary {
+    /// True if migrating to the `new` dictionary drops and creates the dictionary again since the
+    /// template of a dictionary cannot be altered
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.template != new.template
+    }
+
+    /// Split the dictionary options into `(key, value)` pairs. Commas found within quoted values
+    /// are not treated as separators.
+    fn option_pairs(&self) -> Vec<(&str, &str)> {
+        let Some(options) = &self.options else {
+            return vec![];
+        };
+        let mut pairs = vec![];
+        let mut in_quotes = false;
+        let mut start = 0;
+        for (i, c) in options.char_indices() {
+            match c {
+                '\'' => in_quotes = !in_quotes,
+                ',' if !in_quotes => {
+                    pairs.push(&options[start..i]);
+                    start = i + 1;
+                },
+                _ => {},
+            }
+        }
+        pairs.push(&options[start..]);
+        pairs
+            .into_iter()
+            .filter(|pair| !pair.trim().is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (pair.trim(), ""),
+            })
+            .collect()
+    }
+}
+
+impl SqlObject for TextSearchDictionary {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "TEXT SEARCH DICTIONARY"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        write!(
+            w,
+            "CREATE TEXT SEARCH DICTIONARY {} (\n    TEMPLATE = {}",
+            self.name, self.template
+        )?;
+        for (key, value) in self.option_pairs() {
+            write!(w, ",\n    {key} = {value}")?;
+        }
+        w.write_str("\n);\n")?;
+        Ok(())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+            return Ok(());
+        }
+
+        let old_options = self.option_pairs();
+        let new_options = new.option_pairs();
+        let mut changes = vec![];
+        for (key, value) in &new_options {
+            if !old_options.contains(&(key, value)) {
+                changes.push(format!("{key} = {value}"));
+            }
+        }
+        for (key, _) in &old_options {
+            if !new_options.iter().any(|(new_key, _)| new_key == key) {
+                changes.push(key.to_string());
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.write_alter_prefix(w)?;
+        write_join!(w, " (\n    ", changes, ",\n    ", "\n);\n");
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP TEXT SEARCH DICTIONARY {}{};",
+            context.if_exists(),
+            self.name
+        )?;
+        Ok(())
+    }
+}
+
+/// Postgresql text search configuration object
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
+pub struct TextSearchConfiguration {
+    /// Full name of the configuration
+    #[sqlx(json)]
+    pub(crate) name: SchemaQualifiedName,
+    /// Full name of the parser used by the configuration
+    #[sqlx(json)]
+    pub(crate) parser: SchemaQualifiedName,
+    /// Token type mappings of the configuration, ordered by the parser's token type id
+    #[sqlx(json)]
+    pub(crate) mappings: Vec<TextSearchMapping>,
+    /// Dependencies of the configuration. This is the schema owning the configuration, the
+    /// extension providing the parser (if any) and all non-builtin dictionaries mapped.
+    #[sqlx(json)]
+    pub(crate) dependencies: Vec<SchemaQualifiedName>,
+}
+
+/// Mapping of a parser token type to the dictionaries consulted (in order) for the token type
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct TextSearchMapping {
+    /// Alias of the token type as defined by the configuration's parser
+    pub(crate) token_type: String,
+    /// Dictionaries consulted for the token type in order of precedence
+    pub(crate) dictionaries: Vec<SchemaQualifiedName>,
+}
+
+impl TextSearchConfiguration {
+    /// True if migrating to the `new` configuration drops and creates the configuration again
+    /// since the parser of a configuration cannot be altered
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        self.parser != new.parser
+    }
+
+    /// Write `ALTER TEXT SEARCH CONFIGURATION` statements performing the `action` (i.e. `ADD` or
+    /// `ALTER`) for each of the `mappings`. Token types that map to the same dictionaries are
+    /// combined into a single statement.
+    fn write_mappings<'m, W, I>(
+        &self,
+        w: &mut W,
+        action: &str,
+        mappings: I,
+    ) -> Result<(), PgDiffError>
+    where
+        W: Write,
+        I: Iterator<Item = &'m TextSearchMapping>,
+    {
+        let mut groups: Vec<(&[SchemaQualifiedName], Vec<&str>)> = vec![];
+        for mapping in mappings {
+            match groups
+                .iter_mut()
+                .find(|(dictionaries, _)| *dictionaries == mapping.dictionaries.as_slice())
+            {
+                Some((_, token_types)) => token_types.push(&mapping.token_type),
+                None => groups.push((&mapping.dictionaries, vec![&mapping.token_type])),
+            }
+        }
+        for (dictionaries, token_types) in groups {
+            self.write_alter_prefix(w)?;
+            write!(w, " {action} MAPPING FOR ")?;
+            write_join!(w, token_types, ", ");
+            w.write_str(" WITH ")?;
+            write_join!(w, dictionaries, ", ");
+            w.write_str(";\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl SqlObject for TextSearchConfiguration {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "TEXT SEARCH CONFIGURATION"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
+
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "CREATE TEXT SEARCH CONFIGURATION {} (PARSER = {});",
+            self.name, self.parser
+        )?;
+        self.write_mappings(w, "ADD", self.mappings.iter())
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
+            return Ok(());
+        }
+
+        let dropped_token_types: Vec<&str> = self
+            .mappings
+            .iter()
+            .filter(|m| !new.mappings.iter().any(|n| n.token_type == m.token_type))
+            .map(|m| m.token_type.as_str())
+            .collect();
+        if !dropped_token_types.is_empty() {
+            self.write_alter_prefix(w)?;
+            w.write_str(" DROP MAPPING FOR ")?;
+            write_join!(w, dropped_token_types, ", ");
+            w.write_str(";\n")?;
+        }
+        self.write_mappings(
+            w,
+            "ALTER",
+            new.mappings.iter().filter(|n| {
+                self.mappings
+                    .iter()
+                    .any(|m| m.token_type == n.token_type && m.dictionaries != n.dictionaries)
+            }),
+        )?;
+        self.write_mappings(
+            w,
+            "ADD",
+            new.mappings
+                .iter()
+                .filter(|n| !self.mappings.iter().any(|m| m.token_type == n.token_type)),
+        )?;
+        Ok(())
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(
+            w,
+            "DROP TEXT SEARCH CONFIGURATION {}{};",
+            context.if_exists(),
+            self.name
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{TextSearchConfiguration, TextSearchDictionary, TextSearchMapping};
+
+    const SCHEMA: &str = "test_schema";
+    const DICTIONARY_NAME: &str = "test_dictionary";
+    const CONFIGURATION_NAME: &str = "test_configuration";
+
+    fn create_dictionary(template: &str, options: Option<&str>) -> TextSearchDictionary {
+        TextSearchDictionary {
+            name: SchemaQualifiedName::new(SCHEMA, DICTIONARY_NAME),
+            template: SchemaQualifiedName::new("pg_catalog", template),
+            options: options.map(String::from),
+            dependencies: vec![],
+        }
+    }
+
+    fn create_mapping(token_type: &str, dictionaries: &[&str]) -> TextSearchMapping {
+        TextSearchMapping {
+            token_type: token_type.into(),
+            dictionaries: dictionaries.iter().map(SchemaQualifiedName::from).collect(),
+        }
+    }
+
+    fn create_configuration(mappings: Vec<TextSearchMapping>) -> TextSearchConfiguration {
+        TextSearchConfiguration {
+            name: SchemaQualifiedName::new(SCHEMA, CONFIGURATION_NAME),
+            parser: SchemaQualifiedName::new("pg_catalog", "\"default\""),
+            mappings,
+            dependencies: vec![],
+        }
+    }
+
+    #[test]
+    fn create_statements_should_add_create_dictionary_statement() {
+        let dictionary = create_dictionary(
+            "snowball",
+            Some("language = 'english', stopwords = 'english'"),
+        );
+        let mut writeable = String::new();
+
+        dictionary.create_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/text-search-dictionary-create.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_dictionary("snowball", Some("language = 'english', stopwords = 'english'")),
+        create_dictionary("snowball", Some("language = 'english', accept = false")),
+        include_str!("../../test-files/sql/text-search-dictionary-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_dictionary("snowball", Some("language = 'english'")),
+        create_dictionary("simple", Some("stopwords = 'english'")),
+        include_str!("../../test-files/sql/text-search-dictionary-alter-case2.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_dictionary_statements(
+        #[case] old: TextSearchDictionary,
+        #[case] new: TextSearchDictionary,
+        #[case] statement: &str,
+    ) {
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(statement.trim(), writeable.trim());
+    }
+
+    #[test]
+    fn create_statements_should_add_create_configuration_statement_with_mappings() {
+        let configuration = create_configuration(vec![
+            create_mapping(
+                "asciiword",
+                &["test_schema.test_dictionary", "english_stem"],
+            ),
+            create_mapping("word", &["test_schema.test_dictionary", "english_stem"]),
+            create_mapping("numword", &["simple"]),
+        ]);
+        let mut writeable = String::new();
+
+        configuration.create_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/text-search-configuration-create.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_add_alter_configuration_mapping_statements() {
+        let old = create_configuration(vec![
+            create_mapping("asciiword", &["english_stem"]),
+            create_mapping("word", &["english_stem"]),
+            create_mapping("numword", &["simple"]),
+        ]);
+        let new = create_configuration(vec![
+            create_mapping(
+                "asciiword",
+                &["test_schema.test_dictionary", "english_stem"],
+            ),
+            create_mapping("word", &["english_stem"]),
+            create_mapping("email", &["simple"]),
+        ]);
+        let mut writeable = String::new();
+
+        old.alter_statements(&new, &mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/text-search-configuration-alter.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_configuration_statement() {
+        let configuration = create_configuration(vec![]);
+        let mut writeable = String::new();
+
+        configuration.drop_statements(&mut writeable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/text-search-configuration-drop.pgsql").trim(),
+            writeable.trim()
+        );
+    }
+}
@@ -6,27 +6,26 @@ use sqlx::{query_as, PgPool};
 
 use crate::{write_join, PgDiffError};
 
-use super::{SchemaQualifiedName, SqlObject};
+use super::{fetch_with_retry, RenderContext, SchemaQualifiedName, SqlObject};
 
 /// Fetch all triggers associated with the objects referenced (by OID)
 pub async fn get_triggers(pool: &PgPool, object_oids: &[Oid]) -> Result<Vec<Trigger>, PgDiffError> {
     let triggers_query = include_str!("./../../queries/triggers.pgsql");
-    let triggers = match query_as(triggers_query)
-        .bind(object_oids)
-        .fetch_all(pool)
-        .await
-    {
-        Ok(inner) => inner,
-        Err(error) => {
-            println!("Could not load triggers");
-            return Err(error.into());
-        },
-    };
+    let triggers =
+        match fetch_with_retry(|| query_as(triggers_query).bind(object_oids).fetch_all(pool)).await
+        {
+            Ok(inner) => inner,
+            Err(error) => {
+                println!("Could not load triggers");
+                return Err(error.into());
+            },
+        };
     Ok(triggers)
 }
 
 /// Struct representing a SQL trigger object
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Trigger {
     /// Owner object OID
     pub(crate) owner_oid: Oid,
@@ -58,7 +57,10 @@ pub struct Trigger {
     /// stored in the database as `bytea` so it's present here as raw bytes. To access this
     /// information as text use [Trigger::write_function_arguments].
     pub(crate) function_args: Option<Vec<u8>>,
-    /// Dependencies of the trigger. This is always the table and trigger function
+    /// Firing state of the trigger. The state is not migrated. It is only used to restore the
+    /// state of triggers disabled while a migration plan runs.
+    pub(crate) enabled: TriggerEnabled,
+    /// Dependencies of the trigger. This is always the owner table/view and trigger function
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
 }
@@ -110,26 +112,23 @@ impl Trigger {
         }
         Ok(())
     }
-}
-
-impl SqlObject for Trigger {
-    fn name(&self) -> &SchemaQualifiedName {
-        &self.schema_qualified_name
-    }
 
-    fn object_type_name(&self) -> &str {
-        "TRIGGER"
-    }
-
-    fn dependencies(&self) -> &[SchemaQualifiedName] {
-        &self.dependencies
-    }
-
-    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        write!(w, "CREATE TRIGGER {} {} ", self.name, self.timing.as_ref())?;
+    /// Write the `CREATE` statement to the writable object. Uses `CREATE OR REPLACE` when
+    /// `or_replace` is true.
+    fn create_statement<W>(&self, w: &mut W, or_replace: bool) -> Result<(), PgDiffError>
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "CREATE {}TRIGGER {} {} ",
+            if or_replace { "OR REPLACE " } else { "" },
+            self.name,
+            self.timing.as_ref()
+        )?;
         write_join!(w, self.events.iter(), " OR ");
         write!(w, "\nON {}", self.owner_object_name)?;
-        if self.old_name.is_some() || self.old_name.is_some() {
+        if self.old_name.is_some() || self.new_name.is_some() {
             w.write_str("\nREFERENCING")?;
         }
         if let Some(old_table) = &self.old_name {
@@ -155,17 +154,54 @@ impl SqlObject for Trigger {
         w.write_str(");\n")?;
         Ok(())
     }
+}
+
+impl SqlObject for Trigger {
+    fn name(&self) -> &SchemaQualifiedName {
+        &self.schema_qualified_name
+    }
+
+    fn object_type_name(&self) -> &str {
+        "TRIGGER"
+    }
+
+    fn dependencies(&self) -> &[SchemaQualifiedName] {
+        &self.dependencies
+    }
 
-    fn alter_statements<W: Write>(&self, _: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        self.drop_statements(w)?;
-        self.create_statements(w)
+    fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, false)
+    }
+
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        self.create_statement(w, true)
+    }
+
+    fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
+        self.alter_statements_with(new, &RenderContext::default(), w)
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        self.drop_statements_with(context, w)?;
+        new.create_statements_with(context, w)
+    }
+
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
         writeln!(
             w,
-            "DROP TRIGGER {} ON {};",
-            self.name, self.owner_object_name
+            "DROP TRIGGER {}{} ON {};",
+            context.if_exists(),
+            self.name,
+            self.owner_object_name
         )?;
         Ok(())
     }
@@ -173,9 +209,11 @@ impl SqlObject for Trigger {
 
 /// Trigger timing variants
 #[derive(Debug, PartialEq, strum::AsRefStr, sqlx::Type)]
+#[cfg_attr(test, derive(Default))]
 #[sqlx(type_name = "text")]
 pub enum TriggerTiming {
     /// Trigger executes before the actual operation
+    #[cfg_attr(test, default)]
     #[sqlx(rename = "before")]
     #[strum(serialize = "BEFORE")]
     Before,
@@ -189,6 +227,30 @@ pub enum TriggerTiming {
     InsteadOf,
 }
 
+/// Firing state of a trigger with respect to the `session_replication_role`
+#[derive(Debug, PartialEq, strum::AsRefStr, sqlx::Type)]
+#[cfg_attr(test, derive(Default))]
+#[sqlx(type_name = "text")]
+pub enum TriggerEnabled {
+    /// Trigger fires in origin and local modes (i.e. the default state)
+    #[cfg_attr(test, default)]
+    #[sqlx(rename = "O")]
+    #[strum(serialize = "ENABLE")]
+    Origin,
+    /// Trigger never fires
+    #[sqlx(rename = "D")]
+    #[strum(serialize = "DISABLE")]
+    Disabled,
+    /// Trigger only fires in replica mode
+    #[sqlx(rename = "R")]
+    #[strum(serialize = "ENABLE REPLICA")]
+    Replica,
+    /// Trigger fires in every mode
+    #[sqlx(rename = "A")]
+    #[strum(serialize = "ENABLE ALWAYS")]
+    Always,
+}
+
 /// Event that is tracked for the trigger
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(tag = "type")]
@@ -224,3 +286,117 @@ impl Display for TriggerEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::postgres::types::Oid;
+
+    use crate::object::{SchemaQualifiedName, SqlObject};
+
+    use super::{Trigger, TriggerEvent, TriggerTiming};
+
+    const SCHEMA: &str = "test_schema";
+    const TABLE: &str = "test_table";
+    const NAME: &str = "test_trigger";
+    const FUNCTION: &str = "test_trigger_function";
+
+    fn create_trigger(timing: TriggerTiming, events: Vec<TriggerEvent>) -> Trigger {
+        Trigger {
+            owner_oid: Oid(1),
+            name: NAME.into(),
+            schema_qualified_name: SchemaQualifiedName::from(format!("{SCHEMA}.{TABLE}.{NAME}")),
+            owner_object_name: SchemaQualifiedName::new(SCHEMA, TABLE),
+            timing,
+            events,
+            is_row_level: true,
+            function_name: SchemaQualifiedName::new(SCHEMA, FUNCTION),
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_trigger(TriggerTiming::After, vec![TriggerEvent::Insert, TriggerEvent::Delete]),
+        include_str!("../../test-files/sql/trigger-create-case1.pgsql"),
+    )]
+    #[case(
+        Trigger {
+            when_expression: Some("(new.test_col IS NOT NULL)".into()),
+            function_args: Some(b"arg1\0arg2\0".to_vec()),
+            ..create_trigger(
+                TriggerTiming::Before,
+                vec![TriggerEvent::Update { columns: Some(vec!["test_col".into()]) }],
+            )
+        },
+        include_str!("../../test-files/sql/trigger-create-case2.pgsql"),
+    )]
+    #[case(
+        Trigger {
+            is_row_level: false,
+            new_name: Some("new_table".into()),
+            ..create_trigger(TriggerTiming::After, vec![TriggerEvent::Insert])
+        },
+        include_str!("../../test-files/sql/trigger-create-case3.pgsql"),
+    )]
+    #[case(
+        Trigger {
+            schema_qualified_name: SchemaQualifiedName::from(format!("{SCHEMA}.test_view.{NAME}")),
+            owner_object_name: SchemaQualifiedName::new(SCHEMA, "test_view"),
+            ..create_trigger(TriggerTiming::InsteadOf, vec![TriggerEvent::Insert])
+        },
+        include_str!("../../test-files/sql/trigger-create-case4.pgsql"),
+    )]
+    fn create_statements_should_add_create_trigger_statement(
+        #[case] trigger: Trigger,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        trigger.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_or_replace() {
+        let trigger = create_trigger(
+            TriggerTiming::After,
+            vec![TriggerEvent::Insert, TriggerEvent::Delete],
+        );
+        let mut writable = String::new();
+
+        trigger.create_statements_idempotent(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/trigger-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn alter_statements_should_recreate_trigger() {
+        let old = create_trigger(TriggerTiming::After, vec![TriggerEvent::Insert]);
+        let new = create_trigger(TriggerTiming::Before, vec![TriggerEvent::Truncate]);
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/trigger-alter.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_trigger_statement() {
+        let trigger = create_trigger(TriggerTiming::After, vec![TriggerEvent::Insert]);
+        let mut writable = String::new();
+
+        trigger.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/trigger-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -5,13 +5,13 @@ use sqlx::{query_as, PgPool};
 
 use crate::{write_join, PgDiffError};
 
-use super::{Collation, SchemaQualifiedName, SqlObject};
+use super::{fetch_with_retry, Collation, RenderContext, SchemaQualifiedName, SqlObject};
 
 /// Fetch all UDT types found within the specified schemas. This includes composites, enums and
 /// range types.
 pub async fn get_udts(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Udt>, PgDiffError> {
     let udts_query = include_str!("./../../queries/udts.pgsql");
-    let udts = match query_as(udts_query).bind(schemas).fetch_all(pool).await {
+    let udts = match fetch_with_retry(|| query_as(udts_query).bind(schemas).fetch_all(pool)).await {
         Ok(inner) => inner,
         Err(error) => {
             println!("Could not load udts");
@@ -24,6 +24,7 @@ pub async fn get_udts(pool: &PgPool, schemas: &[&str]) -> Result<Vec<Udt>, PgDif
 /// Struct representing a Postgres UDT. This encapsulates all UDT types supported by this
 /// application.
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct Udt {
     #[sqlx(json)]
     pub(crate) name: SchemaQualifiedName,
@@ -55,11 +56,17 @@ impl SqlObject for Udt {
 
     fn create_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
         match &self.udt_type {
+            UdtType::Enum { labels } if labels.is_empty() => {
+                writeln!(w, "CREATE TYPE {} AS ENUM ();", self.name)?;
+            },
             UdtType::Enum { labels } => {
                 write!(w, "CREATE TYPE {} AS ENUM (\n    '", self.name)?;
                 write_join!(w, labels, "',\n    '");
                 w.write_str("'\n);\n")?;
             },
+            UdtType::Composite { attributes } if attributes.is_empty() => {
+                writeln!(w, "CREATE TYPE {} AS ();", self.name)?;
+            },
             UdtType::Composite { attributes } => {
                 write!(w, "CREATE TYPE {} AS (\n    ", self.name)?;
                 write_join!(w, attributes, ",\n    ");
@@ -176,7 +183,7 @@ impl SqlObject for Udt {
                         self.name, attribute.name, attribute.data_type,
                     )?;
                     if let Some(collation) = &attribute.collation {
-                        write!(w, " COLLATE {collation}")?;
+                        write!(w, " {collation}")?;
                     }
                     w.write_str(";\n")?;
                 }
@@ -189,7 +196,7 @@ impl SqlObject for Udt {
                     subtype: new_subtype,
                 },
             ) => {
-                if existing_subtype == new_subtype {
+                if existing_subtype != new_subtype {
                     return Err(PgDiffError::InvalidMigration {
                         object_name: self.name.to_string(),
                         reason: format!(
@@ -265,7 +272,7 @@ impl SqlObject for Udt {
                                     .find(|n| n.name == o.name),
                             )
                         })
-                        .filter(|(o, n)| n.map(|n| n.expression == o.expression).unwrap_or(true))
+                        .filter(|(o, n)| n.map(|n| n.expression != o.expression).unwrap_or(true))
                     {
                         writeln!(
                             w,
@@ -275,7 +282,7 @@ impl SqlObject for Udt {
                         if let Some(new) = new {
                             writeln!(
                                 w,
-                                "ALTER DOMAIN {} ADD CONSTRAINT {} CHECK({});",
+                                "ALTER DOMAIN {} ADD CONSTRAINT {} {};",
                                 self.name, new.name, new.expression
                             )?;
                         }
@@ -288,7 +295,7 @@ impl SqlObject for Udt {
                     }) {
                         writeln!(
                             w,
-                            "ALTER DOMAIN {} ADD CONSTRAINT {} CHECK({});",
+                            "ALTER DOMAIN {} ADD CONSTRAINT {} {};",
                             self.name, new.name, new.expression
                         )?;
                     }
@@ -305,14 +312,19 @@ impl SqlObject for Udt {
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP TYPE {};", self.name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP TYPE {}{};", context.if_exists(), self.name)?;
         Ok(())
     }
 }
 
 /// UDT type variants
 #[derive(Debug, Deserialize, PartialEq, strum::AsRefStr)]
+#[cfg_attr(test, derive(Default))]
 #[serde(tag = "type")]
 pub enum UdtType {
     /// Enum type containing 1 or more labels to constraint the field value
@@ -333,6 +345,7 @@ pub enum UdtType {
         is_not_null: bool,
         checks: Option<Vec<DomainCheckConstraint>>,
     },
+    #[cfg_attr(test, default)]
     #[strum(serialize = "base")]
     Base,
     #[strum(serialize = "pseudo")]
@@ -391,3 +404,188 @@ impl Display for DomainCheckConstraint {
         write!(f, "CONSTRAINT {} {}", self.name, self.expression)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::object::{Collation, SchemaQualifiedName, SqlObject};
+
+    use super::{CompositeField, DomainCheckConstraint, Udt, UdtType};
+
+    const SCHEMA: &str = "test_schema";
+    const NAME: &str = "test_type";
+
+    fn create_udt(udt_type: UdtType) -> Udt {
+        Udt {
+            name: SchemaQualifiedName::new(SCHEMA, NAME),
+            udt_type,
+            dependencies: vec![],
+        }
+    }
+
+    fn create_enum(labels: Vec<&str>) -> Udt {
+        create_udt(UdtType::Enum {
+            labels: labels.into_iter().map(String::from).collect(),
+        })
+    }
+
+    fn create_field(name: &str, data_type: &str, collation: Option<&str>) -> CompositeField {
+        CompositeField {
+            name: name.into(),
+            data_type: data_type.into(),
+            size: -1,
+            collation: collation.map(|c| Collation(c.into())),
+            is_base_type: true,
+        }
+    }
+
+    fn create_check(name: &str, expression: &str) -> DomainCheckConstraint {
+        DomainCheckConstraint {
+            name: name.into(),
+            expression: expression.into(),
+        }
+    }
+
+    fn create_domain(
+        default: Option<&str>,
+        is_not_null: bool,
+        checks: Option<Vec<DomainCheckConstraint>>,
+    ) -> Udt {
+        create_udt(UdtType::Domain {
+            data_type: "text".into(),
+            collation: None,
+            default: default.map(String::from),
+            is_not_null,
+            checks,
+        })
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_enum(vec!["first", "second"]),
+        include_str!("../../test-files/sql/udt-create-case1.pgsql"),
+    )]
+    #[case(
+        create_udt(UdtType::Composite {
+            attributes: vec![
+                create_field("id", "bigint", None),
+                create_field("label", "text", Some("\"C\"")),
+            ],
+        }),
+        include_str!("../../test-files/sql/udt-create-case2.pgsql"),
+    )]
+    #[case(
+        create_udt(UdtType::Range { subtype: "numeric".into() }),
+        include_str!("../../test-files/sql/udt-create-case3.pgsql"),
+    )]
+    #[case(
+        create_domain(
+            Some("'none'::text"),
+            true,
+            Some(vec![create_check("not_empty", "CHECK (VALUE <> ''::text)")]),
+        ),
+        include_str!("../../test-files/sql/udt-create-case4.pgsql"),
+    )]
+    #[case(create_enum(vec![]), "CREATE TYPE test_schema.test_type AS ENUM ();")]
+    #[case(
+        create_udt(UdtType::Composite { attributes: vec![] }),
+        "CREATE TYPE test_schema.test_type AS ();",
+    )]
+    fn create_statements_should_add_create_type_statement(
+        #[case] udt: Udt,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        udt.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_enum(vec!["first"]),
+        create_enum(vec!["first", "second"]),
+        include_str!("../../test-files/sql/udt-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_udt(UdtType::Composite {
+            attributes: vec![create_field("id", "bigint", None)],
+        }),
+        create_udt(UdtType::Composite {
+            attributes: vec![
+                create_field("id", "bigint", None),
+                create_field("label", "text", Some("\"C\"")),
+            ],
+        }),
+        include_str!("../../test-files/sql/udt-alter-case2.pgsql"),
+    )]
+    #[case(
+        create_domain(
+            None,
+            false,
+            Some(vec![
+                create_check("not_empty", "CHECK (VALUE <> ''::text)"),
+                create_check("short", "CHECK (length(VALUE) < 10)"),
+            ]),
+        ),
+        create_domain(
+            Some("'none'::text"),
+            true,
+            Some(vec![
+                create_check("not_empty", "CHECK (VALUE <> ''::text)"),
+                create_check("short", "CHECK (length(VALUE) < 20)"),
+            ]),
+        ),
+        include_str!("../../test-files/sql/udt-alter-case3.pgsql"),
+    )]
+    #[case(
+        create_enum(vec![]),
+        create_enum(vec!["first"]),
+        "ALTER TYPE test_schema.test_type ADD VALUE 'first';",
+    )]
+    fn alter_statements_should_add_alter_type_statements(
+        #[case] old: Udt,
+        #[case] new: Udt,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_enum(vec!["first", "second"]),
+        create_enum(vec!["first"]),
+    )]
+    #[case(
+        create_udt(UdtType::Range { subtype: "numeric".into() }),
+        create_udt(UdtType::Range { subtype: "integer".into() }),
+    )]
+    #[case(
+        create_enum(vec!["first"]),
+        create_udt(UdtType::Range { subtype: "numeric".into() }),
+    )]
+    fn alter_statements_should_fail_for_invalid_migration(#[case] old: Udt, #[case] new: Udt) {
+        let mut writable = String::new();
+
+        let result = old.alter_statements(&new, &mut writable);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_type_statement() {
+        let udt = create_enum(vec!["first"]);
+        let mut writable = String::new();
+
+        udt.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/udt-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}
@@ -1,16 +1,21 @@
 use std::fmt::{Display, Formatter, Write};
 
+use pg_query::protobuf::node::Node;
 use sqlx::postgres::types::Oid;
 use sqlx::{query_as, PgPool};
 
 use crate::{impl_type_for_kvp_wrapper, write_join, PgDiffError};
 
-use super::{compare_key_value_pairs, KeyValuePairs, SchemaQualifiedName, SqlObject};
+use super::{
+    compare_key_value_pairs, fetch_with_retry, sources_match, ColumnDependency, KeyValuePairs,
+    RenderContext, SchemaQualifiedName, SqlObject,
+};
 
 /// Fetch all views found within the specified schemas
 pub async fn get_views(pool: &PgPool, schemas: &[&str]) -> Result<Vec<View>, PgDiffError> {
     let views_query = include_str!("./../../queries/views.pgsql");
-    let views = match query_as(views_query).bind(schemas).fetch_all(pool).await {
+    let views = match fetch_with_retry(|| query_as(views_query).bind(schemas).fetch_all(pool)).await
+    {
         Ok(inner) => inner,
         Err(error) => {
             println!("Could not load views");
@@ -21,6 +26,7 @@ pub async fn get_views(pool: &PgPool, schemas: &[&str]) -> Result<Vec<View>, PgD
 }
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Default))]
 pub struct ViewOptions(KeyValuePairs);
 
 impl_type_for_kvp_wrapper!(ViewOptions);
@@ -33,7 +39,7 @@ impl Display for ViewOptions {
         write_join!(
             f,
             "WITH(",
-            self.0.iter(),
+            self.0.sorted().into_iter(),
             |w, (key, value)| write!(w, "{key}={value}"),
             ",",
             ")"
@@ -42,8 +48,22 @@ impl Display for ViewOptions {
     }
 }
 
+/// Check option of an automatically updatable view. Rows inserted or updated through the view
+/// must satisfy the view's condition.
+#[derive(Debug, PartialEq, sqlx::Type, strum::AsRefStr)]
+#[sqlx(type_name = "text")]
+pub enum ViewCheckOption {
+    /// Only the condition of the view itself is checked
+    #[strum(serialize = "LOCAL")]
+    Local,
+    /// Conditions of the view and all underlining views are checked
+    #[strum(serialize = "CASCADED")]
+    Cascaded,
+}
+
 /// Struct representing a SQL view
 #[derive(Debug, sqlx::FromRow)]
+#[cfg_attr(test, derive(Default))]
 pub struct View {
     /// View OID
     pub(crate) oid: Oid,
@@ -52,13 +72,24 @@ pub struct View {
     pub(crate) name: SchemaQualifiedName,
     /// Columns specified for the view
     pub(crate) columns: Option<Vec<String>>,
+    /// Data types of the view's columns, in the same order as [View::columns]. Only used to check
+    /// if the view can be replaced so this is not included in equality checks.
+    pub(crate) column_types: Option<Vec<String>>,
     /// Query representing the view result
     pub(crate) query: String,
-    /// View options supplied. All items are key value pairs separated by `=`
+    /// View options supplied (e.g. `security_barrier` or `security_invoker`). All items are key
+    /// value pairs separated by `=`. The check option is not included (see
+    /// [View::check_option]).
     pub(crate) options: Option<ViewOptions>,
+    /// `WITH [ CASCADED | LOCAL ] CHECK OPTION` clause of the view, if any
+    pub(crate) check_option: Option<ViewCheckOption>,
     /// Dependencies of the view
     #[sqlx(json)]
     pub(crate) dependencies: Vec<SchemaQualifiedName>,
+    /// Columns of tables and views referenced by the view. [None] if the referenced columns are
+    /// unknown.
+    #[sqlx(json)]
+    pub(crate) column_dependencies: Option<Vec<ColumnDependency>>,
 }
 
 impl PartialEq for View {
@@ -66,9 +97,116 @@ impl PartialEq for View {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && self.columns == other.columns
-            && self.query == other.query
+            && sources_match(&self.query, &other.query)
             && self.options == other.options
+            && self.check_option == other.check_option
+    }
+}
+
+impl View {
+    /// True if this view references any of the `columns` of the `relation`. When the referenced
+    /// columns are unknown, the view is assumed to reference every column of its dependencies.
+    pub(crate) fn references_columns(
+        &self,
+        relation: &SchemaQualifiedName,
+        columns: &[&str],
+    ) -> bool {
+        match &self.column_dependencies {
+            Some(column_dependencies) => column_dependencies
+                .iter()
+                .any(|d| &d.relation == relation && columns.contains(&d.column.as_str())),
+            None => !columns.is_empty() && self.dependencies.contains(relation),
+        }
+    }
+
+    /// True if the `new` version of this view cannot be reached by altering or replacing the
+    /// existing view so the view must be dropped and created again. This is the case when the
+    /// query changes in any way other than renaming or appending output columns.
+    ///
+    /// When this is true, views that depend on this view must also be dropped before this view
+    /// and created again afterward.
+    pub(crate) fn requires_recreation(&self, new: &Self) -> bool {
+        (!sources_match(&self.query, &new.query) || self.columns != new.columns)
+            && !self.only_appends_columns(new)
+            && self.column_renames(new).is_none()
+    }
+
+    /// True if the `new` view contains all the existing columns (in the same order and with the
+    /// same data types) with extra columns added to the end. Postgresql allows
+    /// `CREATE OR REPLACE VIEW` in this case. When the column types of either view are unknown,
+    /// the view is assumed to require recreation.
+    fn only_appends_columns(&self, new: &Self) -> bool {
+        match (
+            &self.columns,
+            &new.columns,
+            &self.column_types,
+            &new.column_types,
+        ) {
+            (Some(old_columns), Some(new_columns), Some(old_types), Some(new_types)) => {
+                new_columns.len() > old_columns.len()
+                    && new_columns.starts_with(old_columns)
+                    && new_types.starts_with(old_types)
+            },
+            _ => false,
+        }
+    }
+
+    /// Find the output columns renamed in the `new` view as pairs of old and new column names.
+    ///
+    /// Returns [None] if the views do not differ only by column names. That includes the case
+    /// where a new column name matches another existing column since renames would conflict.
+    fn column_renames<'v>(&'v self, new: &'v Self) -> Option<Vec<(&'v str, &'v str)>> {
+        let (Some(old_columns), Some(new_columns)) = (&self.columns, &new.columns) else {
+            return None;
+        };
+        if old_columns == new_columns || old_columns.len() != new_columns.len() {
+            return None;
+        }
+        let renames: Vec<(&str, &str)> = old_columns
+            .iter()
+            .zip(new_columns)
+            .filter(|(old_column, new_column)| old_column != new_column)
+            .map(|(old_column, new_column)| (old_column.as_str(), new_column.as_str()))
+            .collect();
+        if renames
+            .iter()
+            .any(|(_, new_column)| old_columns.iter().any(|c| c == new_column))
+        {
+            return None;
+        }
+        let old_query = query_without_column_aliases(&self.query)?;
+        let new_query = query_without_column_aliases(&new.query)?;
+        if old_query != new_query {
+            return None;
+        }
+        Some(renames)
+    }
+}
+
+/// Parse the view `query` and remove the aliases of the output columns. Renaming a view column
+/// updates the alias within the view definition so this allows comparing view queries while
+/// ignoring column names.
+///
+/// Returns [None] if the query cannot be parsed or is not a simple `SELECT` statement.
+fn query_without_column_aliases(query: &str) -> Option<String> {
+    let mut result = pg_query::parse(query).ok()?;
+    let [statement] = result.protobuf.stmts.as_mut_slice() else {
+        return None;
+    };
+    let Some(Node::SelectStmt(select)) =
+        statement.stmt.as_deref_mut().and_then(|s| s.node.as_mut())
+    else {
+        return None;
+    };
+    if select.target_list.is_empty() {
+        return None;
+    }
+    for target in select.target_list.iter_mut() {
+        if let Some(Node::ResTarget(res_target)) = target.node.as_mut() {
+            res_target.name = String::new();
+        }
     }
+    result.deparse().ok()
 }
 
 impl SqlObject for View {
@@ -92,25 +230,288 @@ impl SqlObject for View {
         if let Some(options) = &self.options {
             write!(w, "{options}")?;
         }
-        writeln!(w, " AS\n{}", self.query)?;
+        let Some(check_option) = &self.check_option else {
+            writeln!(w, " AS\n{}", self.query)?;
+            return Ok(());
+        };
+        let query = self.query.trim_end().trim_end_matches(';');
+        writeln!(
+            w,
+            " AS\n{query}\nWITH {} CHECK OPTION;",
+            check_option.as_ref()
+        )?;
         Ok(())
     }
 
+    fn create_statements_idempotent<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
+        // Views are always created using `CREATE OR REPLACE`
+        self.create_statements(w)
+    }
+
     fn alter_statements<W: Write>(&self, new: &Self, w: &mut W) -> Result<(), PgDiffError> {
-        if self.query != new.query || self.columns != new.columns {
-            self.drop_statements(w)?;
-            self.create_statements(w)?;
+        self.alter_statements_with(new, &RenderContext::default(), w)
+    }
+
+    fn alter_statements_with<W: Write>(
+        &self,
+        new: &Self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        if self.requires_recreation(new) {
+            self.drop_statements_with(context, w)?;
+            new.create_statements_with(context, w)?;
             return Ok(());
         }
-        compare_key_value_pairs(w, self, &self.options, &new.options, false)?;
+        if self.only_appends_columns(new) {
+            // Replacing the view also replaces the view's options
+            new.create_statements(w)?;
+            return Ok(());
+        }
+        for (old_column, new_column) in self.column_renames(new).unwrap_or_default() {
+            writeln!(
+                w,
+                "ALTER VIEW {} RENAME COLUMN {old_column} TO {new_column};",
+                self.name
+            )?;
+        }
+        if self.check_option != new.check_option {
+            // The check option can only be changed by replacing the view, which also replaces the
+            // view's options
+            new.create_statements(w)?;
+            return Ok(());
+        }
+        compare_key_value_pairs(w, self, &self.options, &new.options)?;
         Ok(())
     }
 
-    fn drop_statements<W: Write>(&self, w: &mut W) -> Result<(), PgDiffError> {
-        writeln!(w, "DROP VIEW {};", self.name)?;
+    fn drop_statements_with<W: Write>(
+        &self,
+        context: &RenderContext,
+        w: &mut W,
+    ) -> Result<(), PgDiffError> {
+        writeln!(w, "DROP VIEW {}{};", context.if_exists(), self.name)?;
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use sqlx::postgres::types::Oid;
+
+    use crate::object::{ColumnDependency, SchemaQualifiedName, SqlObject};
+
+    use super::{View, ViewCheckOption, ViewOptions};
+
+    const SCHEMA: &str = "test_schema";
+    const NAME: &str = "test_view";
+    const QUERY: &str = "SELECT 1 AS test_col;";
+    const OTHER_QUERY: &str = "SELECT 2 AS test_col;";
+    const OPTION_1: &str = "security_barrier=true";
+    const OPTION_2: &str = "security_barrier=false";
+    const RENAMED_QUERY: &str = "SELECT 1 AS other_col;";
+    const APPENDED_QUERY: &str = "SELECT 1 AS test_col, 2 AS other_col;";
+    const OTHER_RENAMED_QUERY: &str = "SELECT 2 AS other_col;";
+
+    fn create_view(columns: Option<Vec<&str>>, query: &str, options: Option<Vec<&str>>) -> View {
+        View {
+            oid: Oid(1),
+            name: SchemaQualifiedName::new(SCHEMA, NAME),
+            column_types: columns
+                .as_ref()
+                .map(|c| c.iter().map(|_| String::from("integer")).collect()),
+            columns: columns.map(|c| c.into_iter().map(String::from).collect()),
+            query: query.into(),
+            options: options.map(|o| ViewOptions::from(o.as_slice())),
+            check_option: None,
+            dependencies: vec![],
+            column_dependencies: None,
+        }
+    }
+
+    fn create_view_with_check_option(check_option: ViewCheckOption) -> View {
+        View {
+            check_option: Some(check_option),
+            ..create_view(None, "SELECT t.id FROM test_schema.test_table t;", None)
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_view(None, QUERY, None),
+        include_str!("../../test-files/sql/view-create-case1.pgsql"),
+    )]
+    #[case(
+        create_view(Some(vec!["test_col"]), QUERY, Some(vec![OPTION_1])),
+        include_str!("../../test-files/sql/view-create-case2.pgsql"),
+    )]
+    #[case(
+        create_view_with_check_option(ViewCheckOption::Cascaded),
+        include_str!("../../test-files/sql/view-create-case3.pgsql"),
+    )]
+    fn create_statements_should_add_create_view_statement(
+        #[case] view: View,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        view.create_statements(&mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn create_statements_idempotent_should_add_or_replace() {
+        let view = create_view(None, QUERY, None);
+        let mut writable = String::new();
+
+        view.create_statements_idempotent(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/view-create-idempotent.pgsql").trim(),
+            writable.trim()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        create_view(None, QUERY, None),
+        create_view(None, OTHER_QUERY, None),
+        include_str!("../../test-files/sql/view-alter-case1.pgsql"),
+    )]
+    #[case(
+        create_view(None, QUERY, Some(vec![OPTION_1])),
+        create_view(None, QUERY, Some(vec![OPTION_2])),
+        include_str!("../../test-files/sql/view-alter-case2.pgsql"),
+    )]
+    #[case(
+        create_view(None, QUERY, Some(vec![OPTION_1])),
+        create_view(None, QUERY, None),
+        include_str!("../../test-files/sql/view-alter-case3.pgsql"),
+    )]
+    #[case(
+        create_view(Some(vec!["test_col"]), QUERY, None),
+        create_view(Some(vec!["other_col"]), RENAMED_QUERY, None),
+        include_str!("../../test-files/sql/view-alter-case4.pgsql"),
+    )]
+    #[case(
+        create_view(Some(vec!["test_col"]), QUERY, Some(vec![OPTION_1])),
+        create_view(Some(vec!["test_col", "other_col"]), APPENDED_QUERY, None),
+        include_str!("../../test-files/sql/view-alter-case5.pgsql"),
+    )]
+    #[case(
+        create_view(Some(vec!["test_col"]), QUERY, None),
+        create_view(Some(vec!["other_col"]), OTHER_RENAMED_QUERY, None),
+        include_str!("../../test-files/sql/view-alter-case6.pgsql"),
+    )]
+    #[case(
+        create_view(None, QUERY, None),
+        create_view(None, QUERY, Some(vec!["security_invoker=true"])),
+        include_str!("../../test-files/sql/view-alter-case7.pgsql"),
+    )]
+    #[case(
+        create_view_with_check_option(ViewCheckOption::Cascaded),
+        create_view_with_check_option(ViewCheckOption::Local),
+        include_str!("../../test-files/sql/view-alter-case8.pgsql"),
+    )]
+    fn alter_statements_should_add_alter_view_statements(
+        #[case] old: View,
+        #[case] new: View,
+        #[case] statement: &str,
+    ) {
+        let mut writable = String::new();
+
+        old.alter_statements(&new, &mut writable).unwrap();
+
+        assert_eq!(statement.trim(), writable.trim());
+    }
+
+    #[test]
+    fn eq_should_ignore_query_formatting() {
+        let old = create_view(
+            None,
+            " SELECT t.id,\n    t.name\n   FROM test_schema.test_table t\n  WHERE t.active;",
+            None,
+        );
+        let new = create_view(
+            None,
+            "select t.id, t.name from test_schema.test_table t where t.active;",
+            None,
+        );
+
+        assert_eq!(old, new);
+        assert!(!old.requires_recreation(&new));
+    }
+
+    #[rstest::rstest]
+    #[case(create_view(Some(vec!["test_col"]), OTHER_QUERY, None), true)]
+    #[case(create_view(Some(vec!["test_col"]), QUERY, Some(vec![OPTION_2])), false)]
+    #[case(create_view(Some(vec!["other_col"]), RENAMED_QUERY, None), false)]
+    #[case(create_view(Some(vec!["test_col", "other_col"]), APPENDED_QUERY, None), false)]
+    #[case(
+        View {
+            column_types: Some(vec!["text".into(), "integer".into()]),
+            ..create_view(Some(vec!["test_col", "other_col"]), APPENDED_QUERY, None)
+        },
+        true,
+    )]
+    #[case(
+        View {
+            column_types: None,
+            ..create_view(Some(vec!["test_col", "other_col"]), APPENDED_QUERY, None)
+        },
+        true,
+    )]
+    #[case(create_view(Some(vec!["other_col"]), OTHER_RENAMED_QUERY, None), true)]
+    #[case(create_view(Some(vec!["other_col", "test_col"]), "SELECT 1 AS other_col, 2 AS test_col;", None), true)]
+    fn requires_recreation_should_be_true_when_view_cannot_be_altered(
+        #[case] new: View,
+        #[case] expected: bool,
+    ) {
+        let old = create_view(Some(vec!["test_col"]), QUERY, None);
+
+        assert_eq!(expected, old.requires_recreation(&new));
+    }
+
+    #[rstest::rstest]
+    #[case(Some(vec!["id"]), &["id"], true)]
+    #[case(Some(vec!["id"]), &["name"], false)]
+    #[case(Some(vec![]), &["id"], false)]
+    #[case(None, &["name"], true)]
+    #[case(None, &[], false)]
+    fn references_columns_should_match_columns_or_fall_back_to_dependencies(
+        #[case] columns: Option<Vec<&str>>,
+        #[case] dropped_columns: &[&str],
+        #[case] expected: bool,
+    ) {
+        let table = SchemaQualifiedName::new(SCHEMA, "test_table");
+        let view = View {
+            dependencies: vec![table.clone()],
+            column_dependencies: columns.map(|columns| {
+                columns
+                    .into_iter()
+                    .map(|column| ColumnDependency {
+                        relation: table.clone(),
+                        column: column.into(),
+                    })
+                    .collect()
+            }),
+            ..create_view(None, QUERY, None)
+        };
+
+        assert_eq!(expected, view.references_columns(&table, dropped_columns));
+    }
+
+    #[test]
+    fn drop_statements_should_add_drop_view_statement() {
+        let view = create_view(None, QUERY, None);
+        let mut writable = String::new();
+
+        view.drop_statements(&mut writable).unwrap();
+
+        assert_eq!(
+            include_str!("../../test-files/sql/view-drop.pgsql").trim(),
+            writable.trim()
+        );
+    }
+}